@@ -0,0 +1,19 @@
+//! The synthetic table the benchmarks parse.
+
+/// Integer, float and factor columns in turn - deterministic, no rng needed.
+pub fn synthetic_table(n_cols: usize, n_rows: usize) -> String {
+    let mut tsv = (0..n_cols).map(|c| format!("col{c}")).collect::<Vec<_>>().join("\t");
+    tsv.push('\n');
+    for r in 0..n_rows {
+        let row: Vec<String> = (0..n_cols)
+            .map(|c| match c % 3 {
+                0 => ((r * 7 + c) % 1000).to_string(),
+                1 => format!("{:.3}", (r * 13 + c) as f64 / 97.0),
+                _ => format!("level{}", (r + c) % 12),
+            })
+            .collect();
+        tsv.push_str(&row.join("\t"));
+        tsv.push('\n');
+    }
+    tsv
+}
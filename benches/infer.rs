@@ -0,0 +1,40 @@
+//! Parse a synthetic meta table and report the time it takes.
+//!
+//! Compare the sequential and the parallel column type inference:
+//!
+//! ```text
+//! cargo bench --bench infer
+//! cargo bench --bench infer --features rayon
+//! ```
+//!
+//! The table size defaults to 150 columns x 500k rows and can be changed with
+//! the `BENCH_COLS` and `BENCH_ROWS` environment variables.
+
+use std::io::Cursor;
+use std::time::Instant;
+use rust_data_table::{ParseOptions, SurvivalData};
+
+mod common;
+use common::synthetic_table;
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let n_cols = env_or("BENCH_COLS", 150);
+    let n_rows = env_or("BENCH_ROWS", 500_000);
+    let parallel = if cfg!(feature = "rayon") { "parallel (rayon)" } else { "sequential" };
+
+    let tsv = synthetic_table(n_cols, n_rows);
+    let start = Instant::now();
+    let data = SurvivalData::from_reader_with_options(Cursor::new(tsv.as_bytes()), &ParseOptions::default())
+        .expect("synthetic table parses");
+    let elapsed = start.elapsed();
+
+    println!(
+        "{n_cols} x {n_rows} table, {parallel} inference: {:.2?} ({} columns)",
+        elapsed,
+        data.headers.len()
+    );
+}
@@ -0,0 +1,49 @@
+//! Parse a large table file with the buffered reader and through a memory map and report
+//! both times:
+//!
+//! ```text
+//! cargo bench --bench mmap --features mmap
+//! ```
+//!
+//! The file is written to a temporary directory once and read `BENCH_RUNS` (default 3) times
+//! each way, so the later runs find it in the page cache like repeated processing does. The
+//! table size defaults to 40 columns x 1M rows and can be changed with the `BENCH_COLS` and
+//! `BENCH_ROWS` environment variables.
+
+use std::time::{Duration, Instant};
+use rust_data_table::{FactorMap, ParseOptions, SurvivalData};
+
+mod common;
+use common::synthetic_table;
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let n_cols = env_or("BENCH_COLS", 40);
+    let n_rows = env_or("BENCH_ROWS", 1_000_000);
+    let runs = env_or("BENCH_RUNS", 3).max(1);
+    let dir = tempfile::tempdir().expect("temporary directory");
+    let path = dir.path().join("large.tsv");
+    std::fs::write(&path, synthetic_table(n_cols, n_rows)).expect("synthetic table is written");
+    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    println!("{n_cols} x {n_rows} table, {:.1} MB", size as f64 / 1e6);
+
+    let options = ParseOptions::default();
+    let factors = FactorMap::default();
+    let mut best = [Duration::MAX; 2];
+    for _ in 0..runs {
+        let start = Instant::now();
+        let buffered = SurvivalData::from_file_with_factors(&path, &options, &factors).expect("table parses");
+        best[0] = best[0].min(start.elapsed());
+
+        let start = Instant::now();
+        let mapped = SurvivalData::from_file_mmap(&path, &options).expect("table parses");
+        best[1] = best[1].min(start.elapsed());
+        assert_eq!(buffered.columns, mapped.columns, "both readers give the same table");
+    }
+    for (label, elapsed) in ["buffered reader", "memory map"].iter().zip(best) {
+        println!("{label:>15}: {elapsed:.2?} (best of {runs}, {:.0} MB/s)", size as f64 / 1e6 / elapsed.as_secs_f64());
+    }
+}
@@ -0,0 +1,45 @@
+//! Parse tables of growing width and report the time per cell - it should stay about
+//! the same, i.e. the parse time grows linearly with the number of columns:
+//!
+//! ```text
+//! cargo bench --bench wide
+//! ```
+//!
+//! The widths default to 1000, 2000, 4000 and 8000 columns of 10k rows each; change them
+//! with `BENCH_WIDTHS` (comma-separated) and `BENCH_ROWS`.
+
+use std::io::Cursor;
+use std::time::Instant;
+use rust_data_table::{ColumnSelection, ParseOptions, SurvivalData};
+
+mod common;
+use common::synthetic_table;
+
+fn main() {
+    let n_rows = std::env::var("BENCH_ROWS").ok().and_then(|v| v.parse().ok()).unwrap_or(10_000);
+    let widths: Vec<usize> = std::env::var("BENCH_WIDTHS")
+        .unwrap_or_else(|_| "1000,2000,4000,8000".to_string())
+        .split(',')
+        .filter_map(|w| w.trim().parse().ok())
+        .collect();
+
+    for n_cols in widths {
+        let tsv = synthetic_table(n_cols, n_rows);
+        // every other column listed: the selection is looked up once per column
+        let excluded = (0..n_cols).step_by(2).map(|c| format!("col{c}")).collect();
+        for (label, options) in [
+            ("all columns", ParseOptions::default()),
+            ("half excluded", ParseOptions { columns: ColumnSelection::Exclude(excluded), ..Default::default() }),
+        ] {
+            let start = Instant::now();
+            let data = SurvivalData::from_reader_with_options(Cursor::new(tsv.as_bytes()), &options)
+                .expect("synthetic table parses");
+            let elapsed = start.elapsed();
+            let cells = data.headers.len() * n_rows;
+            println!(
+                "{n_cols:>6} x {n_rows} table, {label}: {elapsed:.2?} ({:.1} ns per cell)",
+                elapsed.as_nanos() as f64 / cells as f64
+            );
+        }
+    }
+}
@@ -0,0 +1,31 @@
+#![no_main]
+//! Arbitrary bytes into the in-memory parser: `from_reader_with_options()` and what is done
+//! with a table right after it must return errors, never panic. The first byte picks the
+//! parser settings, the rest is the file.
+use std::io::Cursor;
+use libfuzzer_sys::fuzz_target;
+use rust_data_table::{JsonCells, ParseOptions, SurvivalData, TextEncoding};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, table)) = data.split_first() else { return };
+    let flag = |bit: u8| flags >> bit & 1 == 1;
+    let options = ParseOptions {
+        delimiter: if flag(0) { b',' } else { b'\t' },
+        detect_dates: flag(1),
+        max_levels: flag(2).then_some(2),
+        comment: flag(3).then_some(b'#'),
+        skip_bad_rows: flag(4),
+        transpose: flag(5),
+        json_cells: if flag(6) { JsonCells::Flatten } else { JsonCells::Keep },
+        encoding: if flag(7) { TextEncoding::Windows1252 } else { TextEncoding::Utf8 },
+        ..Default::default()
+    };
+    if let Ok(data) = SurvivalData::from_reader_with_options(Cursor::new(table), &options) {
+        let _ = data.compute_factors();
+        let _ = data.write_ndjson(&mut std::io::sink());
+        let _ = data.json_schema();
+        for name in data.column_names() {
+            let _ = data.column_data(name);
+        }
+    }
+});
@@ -1,9 +1,22 @@
 // generate_json.rs
 
 use std::collections::HashSet;
-use std::path::PathBuf;
-use clap::{Arg, Command};
-use rust_data_table::SurvivalData;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::Context;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use indicatif::{ProgressBar, ProgressStyle};
+use rust_data_table::{BinOptions, ColumnSelection, Delimiter, DropReason, DuplicateColumns, FactorCache, FactorSpec, JsonCells, LevelCase, MergeStrategy, NumberFormat, Palette, ParseOptions, Progress, Reservoir, SampleCheck, Separator, SurvivalData, TableSpec, TextEncoding, TopKOrder, TrailingDelimiter};
+use rust_data_table::lint::LintOptions;
+use rust_data_table::sniff::sniff;
+use rust_data_table::vocabulary::read_vocabulary;
+use rust_data_table::compress::Compression;
+use rust_data_table::column::schema_table;
+use rust_data_table::input::{is_gzip_magic, is_url, open_input, read_stdin};
+use rust_data_table::options::{parse_na_values, parse_renames, split_column_arg, unescape_delimiter};
+use rust_data_table::factor_map::{read_level_codes, read_level_labels, ColumnOrder, FactorMap, FactorsFileFormat, JsonLayout, Provenance, FACTORS_FILE_HELP};
 
 fn main() -> anyhow::Result<()> {
     let matches = Command::new("generate_json")
@@ -15,15 +28,191 @@ fn main() -> anyhow::Result<()> {
         )
         .after_help(
 r"EXAMPLES:
-  # prepare the factors.json file for tsv: 
+  # write the factors file pbmc3k/meta.factors.json (JSON unless --format or a .tsv --factors-file says otherwise)
   generate_json pbmc3k/meta.tsv
 
-  # Specify comma as delimiter (for CSV files)
+  # The delimiter is detected - specify it if detection is ambiguous
   generate_json data/meta.csv --delimiter ,
 
+  # Only the columns the app needs end up in the factors file and the --ndjson table
+  generate_json data/meta.tsv --include cluster,sample,nCount_RNA
+
+  # Friendlier names for R-mangled columns (the factors file keeps the original_name)
+  generate_json data/meta.tsv --rename percent.mt=mito_pct,nCount_RNA=counts
+
+  # Whitespace aligned output of a scientific tool, or a '::' separated export
+  generate_json results/fit.txt --delimiter-regex '\s+'
+  generate_json data/genes.txt --delimiter '::'
+
   # Mark specific columns as categorical (numeric but treated as factors)
   generate_json data/meta.tsv --categorical cluster,sex,condition
 
+  # 'dose' is a number even with entries like '<0.5' (they become missing), 'id' stays as written
+  generate_json data/meta.tsv --as-numeric dose --as-string id
+
+  # Declare an ordered factor; unlisted values go to the level 'other'
+  generate_json data/meta.tsv --ordered stage:low,medium,high --ordered-other other
+
+  # Collapse the clusters with less than 5 cells into the level 'rare'
+  generate_json data/meta.tsv --merge-rare cluster:5 --rare-label rare
+
+  # A tractable legend: the 15 largest clusters, all others in 'Other'
+  generate_json data/meta.tsv --top-k cluster:15
+
+  # 'control' becomes level 0 of 'condition' (like R's relevel())
+  generate_json data/meta.tsv --reference condition:control
+
+  # Keep the column setup in version control instead of on the command line
+  generate_json data/meta.tsv --spec meta.spec.json
+
+  # The same codes for the clusters in every version of the data - new clusters get new codes
+  generate_json data/meta.tsv --codes cluster_codes.json
+
+  # Show cluster 0 as 'Naive CD4' without touching the data
+  generate_json data/meta.tsv --categorical cluster --labels labels.json
+
+  # One table from per sample files with the same header - 'source_file' records the file of each row
+  generate_json s1/meta.tsv s2/meta.tsv s3/meta.tsv --factors-file all.factors.json
+  generate_json s*/meta.tsv --union-columns --factors-file all.factors.json
+
+  # Read from a pipe - the factors file has to be named
+  zcat meta.tsv.gz | generate_json - --factors-file meta.factors.json
+
+  # European lab values like 1.234,56 are floats, not factors
+  generate_json data/clinical.csv --delimiter ';' --thousands-separator .
+
+  # A Windows-1252 / Latin-1 export with accented level names
+  generate_json data/clinical.csv --encoding latin1
+
+  # Skip '#' provenance lines before the header
+  generate_json data/meta.tsv --comment '#'
+
+  # Zip codes and sample ids like 007 stay factors and keep their zeros
+  generate_json data/meta.tsv --preserve-leading-zeros
+
+  # CI check: fail if a column mixes numbers and text instead of guessing a factor
+  generate_json data/meta.tsv --strict --dry-run
+
+  # One missing count per column - empty cells and NA are not told apart
+  generate_json data/meta.tsv --collapse-missing
+
+  # Clinical sentinels: -999 and 9999 are missing everywhere, -1 only in age
+  generate_json data/clinical.tsv --na-values -999,9999,age:-1
+
+  # In a cron job: only warnings and errors, no progress bar
+  generate_json data/meta.tsv --quiet
+
+  # Samples as columns, variables as rows
+  generate_json data/transposed.tsv --transpose
+  generate_json data/export.tsv --header-row 2
+
+  # Drop truncated rows (reported as a count) instead of failing
+  generate_json data/meta.tsv --skip-bad-rows
+
+  # Summarize a table too large for memory
+  generate_json data/huge_meta.tsv.gz --streaming
+
+  # A quick prototype on the first million rows - the rest of the file is not read
+  generate_json data/huge_meta.tsv.gz --streaming --limit 1000000
+
+  # List the numeric columns first, then the factors (input order within each group)
+  generate_json data/meta.tsv --order type
+
+  # Download a shared example table (built with --features net) - -d streams it into the parser
+  generate_json https://example.com/meta.tsv -d '\t'
+
+  # Fail fast in a pipeline if the input lacks a column
+  generate_json data/meta.tsv --assert-columns time,event,cluster
+
+  # Changed the --categorical columns? Overwrite the existing factors file
+  generate_json data/meta.tsv --categorical cluster,sex --force
+
+  # Also write the table as one JSON object per row (for JS stream readers)
+  generate_json data/meta.tsv --ndjson meta.ndjson
+
+  # A JSON Schema of those rows for typed frontend bindings
+  generate_json data/meta.tsv --ndjson meta.ndjson --emit-schema meta.schema.json
+  generate_json data/meta.tsv --split-output meta_columns
+  generate_json data/meta.tsv --compact
+  generate_json data/meta.tsv --id-column barcode
+
+  # The same factor setup in R: source('factors.R') after df <- read.delim('data/meta.tsv')
+  generate_json data/meta.tsv --emit-r factors.R
+
+  # Preview: 10000 random rows (the same ones on every run), all factor levels kept
+  generate_json data/meta.tsv --ndjson preview.ndjson --subsample 10000 --seed 42
+
+  # A balanced scene: at most 500 random cells per cluster, all cells of the smaller ones
+  generate_json data/meta.tsv --ndjson balanced.ndjson --balance cluster:500 --seed 42
+
+  # Colors for the app: tab10 for nominal factors, viridis over the levels of ordered ones
+  generate_json data/meta.tsv --palette tab10,viridis
+
+  # Also write the factors as a spreadsheet friendly table (meta.factors.json and meta.factors.tsv)
+  generate_json data/meta.tsv --format both
+
+  # A zstd compressed factors file (meta.factors.json.zst) - from_json() reads it as it is
+  generate_json data/meta.tsv --compress zstd --compress-level 19
+
+  # On a shared node: at most 4 threads (built with --features rayon)
+  generate_json data/meta.tsv --threads 4
+
+  # Leave out dead columns (a single value or entirely missing)
+  generate_json data/meta.tsv --drop-constant
+
+  # Leave out only the columns without a single value (typed all_missing otherwise)
+  generate_json data/meta.tsv --drop-all-missing
+
+  # ... and the annotations fewer than 10% of the rows have
+  generate_json data/meta.tsv --drop-constant --min-coverage 0.1
+
+  # Only Cell Ontology labels in cell_type - fail on anything else
+  generate_json data/meta.tsv --vocab cell_type:cl_labels.txt --strict
+
+  # Quartiles of percent.mt and deciles of nCount_RNA as ordered factors
+  generate_json data/meta.tsv --bin percent.mt:4,nCount_RNA:10
+
+  # ... next to the numbers (percent.mt_bin)
+  generate_json data/meta.tsv --bin percent.mt:4 --bin-keep
+
+  # Add the columns of a later annotation to the existing factors file
+  generate_json data/phase.tsv -f data/meta.factors.json --append
+
+  # Byte identical factors files for a test suite: no _meta (input path, time, options)
+  generate_json data/meta.tsv --no-provenance
+
+  # Factor codes for R: the first level is 1
+  generate_json data/meta.tsv --one-based-codes
+
+  # One column per key of the JSON objects in column qc (qc.x, qc.y, ...)
+  generate_json data/meta.tsv --json-cells flatten
+
+  # Re-runs on an unchanged file come from the cache - parse again anyway, or skip the cache
+  generate_json data/meta.tsv --force --refresh
+  generate_json data/meta.tsv --force --no-cache
+
+  # Check the detected column types first - nothing is written
+  generate_json data/meta.tsv --categorical cluster --dry-run
+
+  # Find 'treated' next to 'treated ' (and 'Treated' with --lint-case) - --trim writes the factors after all
+  generate_json data/meta.tsv --lint --lint-case
+
+  # Does a new export still match the reviewed factors file? (exits 1 on drift)
+  generate_json validate data/meta_v2.tsv data/meta.factors.json
+
+  # What changed in the factor definitions of a re-export? (exits 1 on changes)
+  generate_json diff old.factors.json meta.factors.json
+
+  # One factor vocabulary for two samples of an atlas (all levels, new codes)
+  generate_json merge s1.factors.json s2.factors.json -o atlas.factors.json --strategy union
+
+  # A typed look at the first 20 (or last 5) rows - factors as level(code)
+  generate_json head data/meta.tsv -n 20 --codes
+  generate_json tail data/meta.tsv -n 5 --categorical cluster
+
+  # Handed an unknown file? Encoding, delimiter, line endings, rows and column types
+  generate_json sniff export.csv
+
 NOTES:
   • The generated JSON file contains factor information 
     for the not numerical and categorical metadata,
@@ -31,18 +220,147 @@ NOTES:
   • Afterwards you can modify the json file to 
     define exactly the factor setup you want/need."
         )
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("validate")
+                .about("Check a table against an existing factors file: missing or new columns, new factor levels and changed types")
+                .arg(Arg::new("data").help("Table to check (TSV or CSV)").required(true).index(1))
+                .arg(Arg::new("factors").help("The factors file (JSON or .tsv) it should match").required(true).index(2))
+                .arg(
+                    Arg::new("delimiter")
+                        .short('d')
+                        .long("delimiter")
+                        .help("Field delimiter: '\\t', ',', ';', '|' or 'auto'")
+                        .default_value("auto"),
+                )
+                .arg(
+                    Arg::new("encoding")
+                        .long("encoding")
+                        .help("Character encoding of the input: utf-8, latin1 or windows-1252")
+                        .default_value("utf-8"),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare two factors files: added or removed columns and levels, reordered levels and type changes")
+                .arg(Arg::new("old").help("The reference factors file (JSON or .tsv)").required(true).index(1))
+                .arg(Arg::new("new").help("The factors file to compare with it").required(true).index(2)),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Merge two factors files into a shared vocabulary with new level codes")
+                .arg(Arg::new("first").help("The factors file whose level order comes first (JSON or .tsv)").required(true).index(1))
+                .arg(Arg::new("second").help("The factors file merged into it").required(true).index(2))
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("The merged factors file (JSON or .tsv)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("strategy")
+                        .long("strategy")
+                        .help("union (all levels), intersection (shared levels and columns) or strict (any difference is an error)")
+                        .default_value("union"),
+                ),
+        )
+        .subcommand(preview_command("head", "Print the first rows of a table with the inferred column types"))
+        .subcommand(preview_command("tail", "Print the last rows of a table with the inferred column types"))
+        .subcommand(
+            Command::new("sniff")
+                .about("Describe a table file: compression, encoding, BOM, line endings, delimiter candidates, rows, columns and their types")
+                .arg(Arg::new("data").help("The file to look at").required(true).index(1)),
+        )
         .arg(
             Arg::new("input")
-                .help("Input metadata file (TSV or CSV)")
+                .help("Input metadata file(s) (TSV or CSV) - '-' reads stdin, an http(s):// URL is downloaded (feature net); several files are concatenated")
                 .required(true)
+                .num_args(1..)
                 .index(1),
         )
+        .arg(
+            Arg::new("union_columns")
+                .long("union-columns")
+                .help("Several inputs: fill the columns a file does not have with missing values instead of failing")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("drop_constant")
+                .long("drop-constant")
+                .help("Leave out the columns with a single value (or none) - they are marked 'constant' otherwise")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("drop_all_missing")
+                .long("drop-all-missing")
+                .help("Leave out the columns without a single value - they are typed 'all_missing' otherwise")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min_coverage")
+                .long("min-coverage")
+                .help("Leave out the columns where fewer than this fraction of the rows has a value, e.g. 0.1 (see --missing-tokens)")
+                .value_parser(parse_fraction)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("bin")
+                .long("bin")
+                .help("Comma-separated <column>:<bins>, e.g. percent.mt:4 - turn the numeric column into an ordered factor of quantile bins ([0,0.5), ...)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("bin_keep")
+                .long("bin-keep")
+                .help("Keep the --bin columns and add the factor after each as <column>_bin")
+                .requires("bin")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("delimiter")
                 .short('d')
                 .long("delimiter")
-                .help("Field delimiter (default: tab '\\t')")
-                .default_value("\\t"),
+                .help("Field delimiter: '\\t', ',', ';', '|', a text like '::' or 'auto' (default: detected from the first lines). The escapes \\t, \\n, \\r and \\\\ are resolved")
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("decimal_separator")
+                .long("decimal-separator")
+                .help("Decimal separator of the numbers: '.' or ',' (default: both are accepted)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("thousands_separator")
+                .long("thousands-separator")
+                .help("Thousands separator of the numbers, e.g. '.' for 1.234,56 (the decimal separator then defaults to the other one)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("delimiter_regex")
+                .long("delimiter-regex")
+                .help("Split the rows at matches of this regex instead, e.g. '\\s+' for aligned columns (no capture groups)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .help("Comma-separated list of the columns to read - all others are skipped")
+                .num_args(1)
+                .conflicts_with("exclude"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .help("Comma-separated list of columns to skip")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("rename")
+                .long("rename")
+                .help("Rename header columns, e.g. 'percent.mt=mito_pct,nCount_RNA=counts' - other options may use either name")
+                .num_args(1),
         )
         .arg(
             Arg::new("categorical")
@@ -52,23 +370,549 @@ NOTES:
                 .num_args(1)
                 .required(false),
         )
+        .arg(
+            Arg::new("assert_columns")
+                .long("assert-columns")
+                .help("Comma-separated columns the input must have (any type) - fail before parsing, listing the missing ones")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("vocab")
+                .long("vocab")
+                .help("Comma-separated <column>:<file> - warn about values of the column that are not in the file (one allowed value per line); an error with --strict")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("as_numeric")
+                .long("as-numeric")
+                .help("Comma-separated list of columns read as numbers - values that are no number become missing")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("as_string")
+                .long("as-string")
+                .help("Comma-separated list of columns kept as free text, exactly as written")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("ordered")
+                .long("ordered")
+                .help("Ordered factor with explicit level order, e.g. 'stage:low,medium,high' (repeatable)")
+                .action(ArgAction::Append)
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("reference")
+                .long("reference")
+                .help("Baseline level (index 0) of a factor, e.g. 'condition:control' (repeatable)")
+                .action(ArgAction::Append)
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("merge_rare")
+                .long("merge-rare")
+                .help("Merge the levels with fewer rows than the threshold into one level, e.g. 'condition:5' (repeatable)")
+                .action(ArgAction::Append)
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("top_k")
+                .long("top-k")
+                .help("Keep only the K most frequent levels and merge the others into one level, e.g. 'cluster:15' (repeatable)")
+                .action(ArgAction::Append)
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("top_k_order")
+                .long("top-k-order")
+                .help("Order of the levels --top-k keeps: frequency (most frequent first) or original")
+                .default_value("frequency"),
+        )
+        .arg(
+            Arg::new("rare_label")
+                .long("rare-label")
+                .help("The level collecting the merged levels of --merge-rare and --top-k")
+                .default_value("Other"),
+        )
+        .arg(
+            Arg::new("ordered_other")
+                .long("ordered-other")
+                .help("Trailing level for values missing from an --ordered list (default: error)")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("missing_tokens")
+                .long("missing-tokens")
+                .help("Comma-separated cell values meaning 'missing' (default: NA,NaN,,NULL,None)")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("na_values")
+                .long("na-values")
+                .help("Comma-separated numbers meaning 'missing', for all columns or one as <column>:<number>, e.g. -999,9999,age:-1")
+                .num_args(1)
+                .allow_hyphen_values(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new("true_tokens")
+                .long("true-tokens")
+                .help("Comma-separated values meaning 'true' in boolean columns (default: TRUE,yes,1)")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("false_tokens")
+                .long("false-tokens")
+                .help("Comma-separated values meaning 'false' in boolean columns (default: FALSE,no,0)")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("max_levels")
+                .long("max-levels")
+                .help("Text columns with more distinct values are free text, not factors (0: no limit)")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("200"),
+        )
+        .arg(
+            Arg::new("sample")
+                .short('n')
+                .long("sample")
+                .help("Infer the column types from the first N rows only")
+                .value_parser(clap::value_parser!(usize))
+                .required(false),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .help("Number of threads for the type inference (1: sequential; only with the rayon feature) [default: RAYON_NUM_THREADS or all cores]")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("order")
+                .long("order")
+                .help("Order of the column entries in the factors file: as in the 'input', 'alpha'betical or by 'type' (numbers first, then factors)")
+                .value_parser(["input", "alpha", "type"])
+                .default_value("input"),
+        )
+        .arg(
+            Arg::new("sample_check")
+                .long("sample-check")
+                .help("Rows after the --sample: 'widen' the type, report an 'error' or 'skip' the check")
+                .value_parser(["widen", "error", "skip"])
+                .default_value("widen"),
+        )
+        .arg(
+            Arg::new("level_case")
+                .long("level-case")
+                .help("Normalize factor values: 'keep' them, or 'lower'/'upper' case them (CD8 = cd8)")
+                .value_parser(["keep", "lower", "upper"])
+                .default_value("keep"),
+        )
+        .arg(
+            Arg::new("sort_levels")
+                .long("sort-levels")
+                .help("Sort the inferred factor levels (numbers by value, text alphabetically) instead of first appearance")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dates")
+                .long("dates")
+                .help("Detect date columns (ISO-8601 like 2023-05-14 first, then e.g. 14.05.2023)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .help("Character encoding of the input: utf-8, latin1 or windows-1252")
+                .default_value("utf-8"),
+        )
+        .arg(
+            Arg::new("comment")
+                .long("comment")
+                .help("Skip the lines starting with this character, e.g. '#' (a '#' inside a field is kept)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("header_row")
+                .long("header-row")
+                .help("The number of title lines above the header (comment and blank lines not counted) - they are dropped")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("preserve_leading_zeros")
+                .long("preserve-leading-zeros")
+                .help("Numeric looking columns with values like 007 (zip codes, ids) are factors, not numbers")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("collapse_missing")
+                .long("collapse-missing")
+                .help("Report one missing count per column - not split into empty cells and NA (missing_kinds)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Columns mixing numbers and text are an error listing them, not a factor (unless declared) - and so are --vocab violations")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json_cells")
+                .long("json-cells")
+                .help("Columns of JSON objects ({\"x\":1,\"y\":2}): 'keep' them as json (no factor levels) or 'flatten' them into col.x, col.y")
+                .value_parser(["off", "keep", "flatten"])
+                .default_value("off"),
+        )
+        .arg(
+            Arg::new("one_based_codes")
+                .long("one-based-codes")
+                .help("Number the factor levels 1, 2, ... like R instead of 0, 1, ... - in the factors file and every other output")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("transpose")
+                .long("transpose")
+                .help("The file has the variables as rows: transpose it before the types are inferred - its first column becomes the header")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("streaming"),
+        )
+        .arg(
+            Arg::new("skip_bad_rows")
+                .long("skip-bad-rows")
+                .help("Drop rows with a different number of fields than the header (default: report the line and fail)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("duplicate_columns")
+                .long("duplicate-columns")
+                .help("Repeated column names: 'rename' them (name.1, name.2, ...) or report an 'error'")
+                .value_parser(["rename", "error"])
+                .default_value("rename"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .help("Read only the first N data rows (after --header-row, comments and skipped bad rows) - the factors describe only them")
+                .value_parser(clap::value_parser!(usize))
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("trailing_delimiter")
+                .long("trailing-delimiter")
+                .help("Lines ending with a delimiter: 'strip' the empty last field or 'keep' it as a column (V<n>) if the header has it too")
+                .value_parser(["strip", "keep"])
+                .default_value("strip"),
+        )
+        .arg(
+            Arg::new("labels")
+                .long("labels")
+                .help("JSON file with display labels per level, e.g. {\"cluster\": {\"0\": \"Naive CD4\"}}")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("streaming")
+                .long("streaming")
+                .help("Read the file twice without keeping its rows in memory (for very large tables)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help("Print the inferred column types and exit without writing any file")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("lint")
+                .long("lint")
+                .help("Warn about factor levels differing only by surrounding whitespace ('treated' and 'treated ') and exit without writing any file")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("lint_case")
+                .long("lint-case")
+                .help("--lint, also warning about levels differing only by case ('Treated' and 'treated' - --level-case folds them)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("trim")
+                .long("trim")
+                .help("With --lint: write the factors file after the warnings, with the surrounding whitespace trimmed off the levels")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .short('F')
+                .long("force")
+                .help("Regenerate the factors file even if it already exists")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .help("Add the columns to the existing factors file (after its columns) instead of regenerating it - a column it has already is an error")
+                .conflicts_with("force")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("overwrite_columns")
+                .long("overwrite-columns")
+                .help("With --append: replace the columns the factors file has already")
+                .requires("append")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_provenance")
+                .long("no-provenance")
+                .help("Leave the _meta object (input path, tool version, time, options) out of the factors file - for byte identical files")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_cache")
+                .long("no-cache")
+                .help("Neither read nor store the factors in the cache (~/.cache/rust_data_table, keyed on the input files and the options)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("refresh")
+                .long("refresh")
+                .help("Parse the input even if the cache has its factors - and update the cache entry")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("no_cache"),
+        )
+        .arg(
+            Arg::new("spec")
+                .long("spec")
+                .help("JSON file with per column settings: dtype, categorical, levels, ordered, reference and labels")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("codes")
+                .long("codes")
+                .help("JSON file with fixed codes per level, e.g. {\"cluster\": {\"T\": 0, \"B\": 1}} - the columns are factors, new levels get the codes above the largest")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("ndjson")
+                .long("ndjson")
+                .help("Also write the table as newline delimited JSON (one object per row) to this file")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("emit_schema")
+                .long("emit-schema")
+                .help("Also write a JSON Schema (draft-07) of the --ndjson rows to this file: column types, factor levels as enum, null where values are missing")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("emit_r")
+                .long("emit-r")
+                .help("Also write R code recreating the factors (levels, labels, ordered) of a data frame 'df' to this file")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("id_column")
+                .long("id-column")
+                .help("Mark this column as the row identifier in the factors file (\"id\": true) - fails if a row has no value or a value is there twice")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("compact")
+                .long("compact")
+                .help("Write the JSON files (factors, --split-output, --emit-schema) without indentation and line breaks - smaller and faster to parse")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .help("Compress the factors file: none or zstd (default: zstd for a .zst file name, the default name gets the .zst added)")
+                .value_parser(["none", "zstd"])
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("compress_level")
+                .long("compress-level")
+                .help("The zstd level of --compress zstd or a .zst factors file: 1 (fast) to 22 (small), default 3")
+                .value_parser(clap::value_parser!(i32))
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("split_output")
+                .long("split-output")
+                .help("Also write one JSON per column (type, summary and levels) into this directory, with an index.json mapping the column names to their file names")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("subsample")
+                .long("subsample")
+                .help("Write only this many random rows to the --ndjson file (the factors file still describes all rows)")
+                .value_parser(clap::value_parser!(usize))
+                .requires("ndjson")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("balance")
+                .long("balance")
+                .help("Write at most N random rows per level of a factor to the --ndjson file, e.g. 'cluster:500'")
+                .requires("ndjson")
+                .conflicts_with_all(["subsample", "streaming"])
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Random seed of --subsample and --balance - the same seed gives the same rows")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0")
+                .num_args(1),
+        )
         .arg(
             Arg::new("factors_file")
                 .short('f')
                 .long("factors-file")
-                .help("Optional path for factor definitions output")
+                .help("Optional path for factor definitions output (required when reading stdin)")
                 .required(false)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("palette")
+                .long("palette")
+                .help("Add a hex color per factor level: a qualitative (tab10, tab20, set2) scheme for nominal and a \
+                       sequential (viridis, magma) one for ordered factors, e.g. 'tab10,viridis'")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Layout of the factors file: json, tsv (one row per level) or both (.json and .tsv siblings) [default: from the --factors-file extension, else json]")
+                .value_parser(["json", "tsv", "both"])
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Only print warnings and errors")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .global(true),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Also print debug details (forced columns, the type of every column)")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         .get_matches();
 
-    let input_path = PathBuf::from(matches.get_one::<String>("input").unwrap());
-    let delimiter_str = matches.get_one::<String>("delimiter").unwrap();
-    let delimiter = match delimiter_str.as_str() {
-        "\\t" | "tab" => b'\t',
-        "," | "comma" => b',',
-        other if other.len() == 1 => other.as_bytes()[0],
-        _ => anyhow::bail!("Invalid delimiter '{}'", delimiter_str),
+    let level = match (matches.get_flag("quiet"), matches.get_flag("verbose")) {
+        (true, _) => log::LevelFilter::Warn,
+        (_, true) => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Info,
+    };
+    StatusLogger::init(level)?;
+
+    if let Some(matches) = matches.subcommand_matches("validate") {
+        return validate(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("merge") {
+        return merge(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("diff") {
+        return diff(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("head") {
+        return preview(matches, false);
+    }
+    if let Some(matches) = matches.subcommand_matches("tail") {
+        return preview(matches, true);
+    }
+    if let Some(matches) = matches.subcommand_matches("sniff") {
+        let data_path = matches.get_one::<String>("data").unwrap();
+        let report = sniff(data_path).with_context(|| format!("Failed to read {:?}", data_path))?;
+        std::io::stdout().lock().write_all(report.to_string().as_bytes())?;
+        return Ok(());
+    }
+
+    let inputs: Vec<PathBuf> = matches.get_many::<String>("input").unwrap().map(PathBuf::from).collect();
+    let input_path = inputs[0].clone();
+    let url = input_path.to_str().filter(|input| is_url(input)).map(str::to_string);
+    if inputs.len() > 1 {
+        if inputs.iter().any(|i| i.as_os_str() == "-") {
+            anyhow::bail!("stdin ('-') can only be read on its own, not with other input files");
+        }
+        if inputs.iter().any(|i| i.to_str().is_some_and(is_url)) {
+            anyhow::bail!("A URL can only be read on its own, not with other input files");
+        }
+        if matches.get_flag("streaming") {
+            anyhow::bail!("--streaming reads a single file - several inputs are concatenated in memory");
+        }
+    }
+    if let Some(url) = &url {
+        if !cfg!(feature = "net") {
+            anyhow::bail!("Reading {} needs the 'net' feature - build generate_json with --features net", url);
+        }
+        if matches.get_flag("streaming") {
+            anyhow::bail!("--streaming reads the input twice and needs a file, not a URL");
+        }
+    }
+    if matches.get_flag("streaming") && matches.contains_id("bin") {
+        anyhow::bail!("--bin needs the values in memory and cannot be used with --streaming");
+    }
+    let delimiter_arg = matches.get_one::<String>("delimiter").unwrap();
+    // a download is streamed into the parser - unless the delimiter detection or the lint read it first
+    let read_twice = matches.get_one::<String>("delimiter_regex").is_none() && matches!(Delimiter::parse(delimiter_arg), Ok(Delimiter::Auto))
+        || matches.get_flag("lint")
+        || matches.get_flag("lint_case");
+    // stdin can only be read once - keep it for the delimiter detection and the parser
+    let buffered = if input_path.as_os_str() == "-" {
+        if matches.get_flag("streaming") {
+            anyhow::bail!("--streaming reads the input twice and needs a file, not stdin");
+        }
+        Some(read_stdin()?)
+    } else if let Some(url) = url.as_deref().filter(|_| read_twice) {
+        Some(download(url)?)
+    } else {
+        None
+    };
+    let single_byte = |arg: &str| match matches.get_one::<String>(arg).map(|c| c.as_bytes()) {
+        Some(&[byte]) => Ok(Some(byte)),
+        Some(_) => Err(anyhow::anyhow!("--{} has to be a single character", arg.replace('_', "-"))),
+        None => Ok(None),
+    };
+    let comment = single_byte("comment")?;
+    let header_row = *matches.get_one::<usize>("header_row").unwrap();
+    // a delimiter the csv reader cannot split at is handled line by line
+    let separator = match matches.get_one::<String>("delimiter_regex") {
+        Some(pattern) => Some(Separator::regex(pattern)?),
+        None if Delimiter::parse(delimiter_arg).is_err() => Some(Separator::text(&unescape_delimiter(delimiter_arg)?)?),
+        None => None,
+    };
+    let delimiter = match Delimiter::parse(delimiter_arg) {
+        // not used by the split rows
+        _ if separator.is_some() => b'\t',
+        Err(err) => return Err(err.into()),
+        Ok(Delimiter::Auto) => {
+            let detected = match &buffered {
+                Some(data) => Delimiter::Auto.detect_skipping(&data[..], comment, header_row)?,
+                None => Delimiter::Auto.resolve_skipping(&input_path, comment, header_row)?,
+            };
+            log::info!("🔍 Detected delimiter '{}'", (detected as char).escape_default());
+            detected
+        }
+        Ok(Delimiter::Byte(delimiter)) => delimiter,
     };
 
     // Categorical columns (optional)
@@ -77,48 +921,700 @@ NOTES:
         .map(|s| s.split(',').map(|v| v.trim().to_string()).collect())
         .unwrap_or_default();
 
+    let mut options = ParseOptions::new(delimiter, categorical_cols.clone());
+    options.separator = separator;
+    let thousands = single_byte("thousands_separator")?;
+    // 1.234,5 or 1,234.5 - the thousands separator tells which one is meant
+    options.number_format = match (single_byte("decimal_separator")?, thousands) {
+        (Some(decimal), thousands) => NumberFormat::new(decimal, thousands)?,
+        (None, Some(b'.')) => NumberFormat::new(b',', thousands)?,
+        (None, Some(_)) => NumberFormat::new(b'.', thousands)?,
+        (None, None) => NumberFormat::default(),
+    };
+    let column_list = |arg: &str| -> Option<Vec<String>> { Some(matches.get_one::<String>(arg)?.split(',').map(|v| v.trim().to_string()).collect()) };
+    options.as_numeric = column_list("as_numeric").unwrap_or_default().into_iter().collect();
+    options.as_string = column_list("as_string").unwrap_or_default().into_iter().collect();
+    options.required_columns = column_list("assert_columns").unwrap_or_default();
+    if let Some(columns) = column_list("include") {
+        options.columns = ColumnSelection::Include(columns);
+    } else if let Some(columns) = column_list("exclude") {
+        options.columns = ColumnSelection::Exclude(columns);
+    }
+    if let Some(renames) = matches.get_one::<String>("rename") {
+        options.renames = parse_renames(renames)?;
+    }
+    if let Some(tokens) = matches.get_one::<String>("missing_tokens") {
+        options.missing_tokens = tokens.split(',').map(|t| t.trim().to_string()).collect();
+    }
+    if let Some(values) = matches.get_one::<String>("na_values") {
+        (options.na_values, options.column_na_values) = parse_na_values(values)?;
+    }
+    if let Some(tokens) = matches.get_one::<String>("true_tokens") {
+        options.true_tokens = tokens.split(',').map(|t| t.trim().to_string()).collect();
+    }
+    if let Some(tokens) = matches.get_one::<String>("false_tokens") {
+        options.false_tokens = tokens.split(',').map(|t| t.trim().to_string()).collect();
+    }
+    options.max_levels = match *matches.get_one::<usize>("max_levels").unwrap() {
+        0 => None,
+        max => Some(max),
+    };
+    options.sample_rows = matches.get_one::<usize>("sample").copied();
+    options.threads = matches.get_one::<usize>("threads").copied();
+    options.sample_check = match matches.get_one::<String>("sample_check").unwrap().as_str() {
+        "error" => SampleCheck::Error,
+        "skip" => SampleCheck::Skip,
+        _ => SampleCheck::Widen,
+    };
+    options.detect_dates = matches.get_flag("dates");
+    options.sort_levels = matches.get_flag("sort_levels");
+    options.encoding = TextEncoding::parse(matches.get_one::<String>("encoding").unwrap())?;
+    options.comment = comment;
+    options.header_row = header_row;
+    options.skip_bad_rows = matches.get_flag("skip_bad_rows");
+    options.transpose = matches.get_flag("transpose");
+    options.preserve_leading_zeros = matches.get_flag("preserve_leading_zeros");
+    options.strict = matches.get_flag("strict");
+    options.collapse_missing = matches.get_flag("collapse_missing");
+    options.one_based_codes = matches.get_flag("one_based_codes");
+    options.json_cells = JsonCells::parse(matches.get_one::<String>("json_cells").unwrap())?;
+    options.level_case = LevelCase::parse(matches.get_one::<String>("level_case").unwrap())?;
+    if matches.get_one::<String>("duplicate_columns").unwrap() == "error" {
+        options.duplicate_columns = DuplicateColumns::Error;
+    }
+    options.max_rows = matches.get_one::<usize>("limit").copied();
+    options.trailing_delimiter = TrailingDelimiter::parse(matches.get_one::<String>("trailing_delimiter").unwrap())?;
+    let ordered_other = matches.get_one::<String>("ordered_other");
+    for arg in matches.get_many::<String>("ordered").unwrap_or_default() {
+        let (column, mut spec) = FactorSpec::parse_ordered(arg)?;
+        spec.other = ordered_other.cloned();
+        options.factor_specs.insert(column, spec);
+    }
+    let rare_label = matches.get_one::<String>("rare_label").unwrap();
+    for arg in matches.get_many::<String>("merge_rare").unwrap_or_default() {
+        let (column, min_count) = split_column_arg(arg)?;
+        let min_count: usize = min_count
+            .parse()
+            .with_context(|| format!("--merge-rare {}: the threshold must be a number of rows", arg))?;
+        let spec = options.factor_specs.entry(column.to_string()).or_default();
+        spec.merge_rare = Some(min_count);
+        spec.rare_label = Some(rare_label.clone());
+    }
+    let top_k_order = TopKOrder::parse(matches.get_one::<String>("top_k_order").unwrap())?;
+    for arg in matches.get_many::<String>("top_k").unwrap_or_default() {
+        let (column, k) = split_column_arg(arg)?;
+        let k: usize = k
+            .parse()
+            .with_context(|| format!("--top-k {}: K must be a number of levels", arg))?;
+        let spec = options.factor_specs.entry(column.to_string()).or_default();
+        spec.top_k = Some(k);
+        spec.top_k_order = top_k_order;
+        spec.rare_label = Some(rare_label.clone());
+    }
+    for arg in matches.get_many::<String>("reference").unwrap_or_default() {
+        let (column, level) = split_column_arg(arg)?;
+        options.factor_specs.entry(column.to_string()).or_default().reference = Some(level.to_string());
+    }
+    // the spec file has the last word for the columns it lists
+    let spec = match matches.get_one::<String>("spec") {
+        Some(path) => TableSpec::from_json(path)?,
+        None => TableSpec::default(),
+    };
+    spec.apply(&mut options)?;
+    if let Some(path) = matches.get_one::<String>("codes") {
+        options.level_codes = read_level_codes(path)?;
+    }
+
     // Factors file (optional)
     let factors_file = matches
         .get_one::<String>("factors_file")
         .map(PathBuf::from)
         .unwrap_or_else(|| {
-            let mut p = input_path.clone();
+            // a download is named after the file at the end of its URL, in the working directory
+            let mut p = url.as_deref().map_or_else(|| input_path.clone(), |url| PathBuf::from(url_file_name(url)));
             // Remove existing extension (.tsv, .csv, etc.)
             p.set_extension(""); 
             // Append the new suffix
-            let mut new_name = p.file_name()
+            let new_name = p.file_name()
                 .and_then(|n| n.to_str())
                 .map(|n| format!("{}.factors.json", n))
                 .unwrap_or_else(|| "factors.json".to_string());
             p.set_file_name(new_name);
             p
         });
+    let layout = if matches.get_flag("compact") { JsonLayout::Compact } else { JsonLayout::Pretty };
+    let factors_files = match matches.get_one::<String>("format").map(String::as_str) {
+        None => vec![factors_file],
+        Some("both") => vec![factors_file.with_extension("json"), factors_file.with_extension("tsv")],
+        Some(format) if matches.get_one::<String>("factors_file").is_none() => vec![factors_file.with_extension(format)],
+        Some(format) => {
+            let wanted = if format == "tsv" { FactorsFileFormat::Tsv } else { FactorsFileFormat::Json };
+            if FactorsFileFormat::from_path(&factors_file) != wanted {
+                anyhow::bail!("--factors-file {:?} does not match --format {} - use a .{} file name", factors_file, format, format);
+            }
+            vec![factors_file]
+        }
+    };
+    let compression = matches.get_one::<String>("compress").map(|c| Compression::parse(c)).transpose()?;
+    let factors_files: Vec<PathBuf> = match compression {
+        Some(Compression::Zstd(_)) if matches.get_one::<String>("factors_file").is_none() => {
+            factors_files.into_iter().map(|f| PathBuf::from(format!("{}.zst", f.display()))).collect()
+        }
+        _ => factors_files,
+    };
+    let compressions = factors_files
+        .iter()
+        .map(|file| {
+            let compression = compression.unwrap_or_else(|| Compression::from_path(file));
+            match matches.get_one::<i32>("compress_level") {
+                Some(&level) => compression.with_level(level),
+                None => Ok(compression),
+            }
+        })
+        .collect::<Result<Vec<Compression>, _>>()?;
 
-    if factors_file.exists(){
-        println!("factors file already exists - no need to run this.");
+    // one bar per table - several inputs are read without
+    let bar = if inputs.len() > 1 || matches.get_flag("quiet") {
+        ProgressBar::hidden()
+    } else {
+        let (bar, progress) = progress_bar(&input_path, buffered.is_some() || url.is_some())?;
+        options.progress = Some(progress);
+        bar
+    };
+    let union_columns = matches.get_flag("union_columns");
+    let lint = (matches.get_flag("lint") || matches.get_flag("lint_case")).then_some(LintOptions { case: matches.get_flag("lint_case") });
+    // without --trim the lint only reports
+    let lint_only = lint.is_some() && !matches.get_flag("trim");
+
+    if matches.get_flag("dry_run") || lint_only {
+        let data = if inputs.len() > 1 {
+            SurvivalData::from_files(&inputs, &options, union_columns)
+        } else if let Some(data) = &buffered {
+            SurvivalData::from_reader_with_options(&data[..], &options)
+        } else if let Some(url) = &url {
+            url_table(url, &options)
+        } else if matches.get_flag("streaming") {
+            SurvivalData::from_file_streaming(&input_path, &options)
+        } else {
+            SurvivalData::from_file_with_factors(&input_path, &options, &FactorMap::default())
+        }.with_context(|| read_context(&inputs, &input_path))?;
+        bar.finish_and_clear();
+        if let Some(lint) = &lint {
+            report_lints(&data, &inputs, buffered.as_deref(), &options, lint)?;
+        }
+        if matches.get_flag("dry_run") {
+            print!("{}", schema_table(&data.columns));
+        }
+        return Ok(());
+    }
+
+    if input_path.as_os_str() == "-" && matches.get_one::<String>("factors_file").is_none() {
+        anyhow::bail!("Reading stdin ('-'): there is no file name to derive the factors file from - please give --factors-file");
+    }
+    if inputs.len() > 1 && matches.get_one::<String>("factors_file").is_none() {
+        anyhow::bail!("Several input files: there is no single file name to derive the factors file from - please give --factors-file");
+    }
+
+    let append = matches.get_flag("append");
+    if let Some(existing) = factors_files.iter().find(|f| f.exists())
+        && !matches.get_flag("force")
+        && !append
+    {
+        log::info!("factors file {:?} already exists - nothing to do. Use --force to regenerate it.", existing);
+        return Ok(());
+    }
+    for input in &inputs {
+        log::info!("📄 Input file: {:?}", input);
+    }
+    for factors_file in &factors_files {
+        log::info!("📘 Factors file: {:?}", factors_file);
+    }
+    log::debug!("Categorical cols: {:?}", categorical_cols);
+
+    // an unchanged input read with the same options gives the same factors - unless its rows are written
+    // or checked (--vocab, --lint) too; --id-column is checked before a run is stored, so a hit has passed it
+    let writes_table = ["ndjson", "emit_schema"].iter().any(|arg| matches.get_one::<String>(arg).is_some());
+    let checks_rows = lint.is_some() || matches.get_one::<String>("vocab").is_some();
+    let cache = match FactorCache::user_default() {
+        Some(cache) if !matches.get_flag("no_cache") && buffered.is_none() && url.is_none() && !writes_table && !checks_rows && !append => {
+            let mut files = inputs.clone();
+            files.extend(["spec", "labels", "codes"].iter().filter_map(|arg| matches.get_one::<String>(arg)).map(PathBuf::from));
+            let settings: Vec<String> = std::env::args()
+                .skip(1)
+                .filter(|arg| !["--refresh", "--no-cache", "--force", "-F"].contains(&arg.as_str()))
+                .collect();
+            let key = FactorCache::key(&files, &settings)?;
+            Some((cache, key))
+        }
+        _ => None,
+    };
+    if let Some((cache, key)) = &cache
+        && !matches.get_flag("refresh")
+        && let Some(mut factor_map) = cache.get(key)
+    {
+        bar.finish_and_clear();
+        log::info!("⚡ Input and options are unchanged - factors from the cache {:?} (--refresh parses again)", cache.path(key));
+        // the factors are the cached ones, the run is this one
+        if let Some(meta) = factor_map.meta.as_mut() {
+            let now = Provenance::now(&inputs);
+            meta.inputs = now.inputs;
+            meta.generated = now.generated;
+            meta.command = std::env::args().collect();
+        }
+        for (factors_file, &compression) in factors_files.iter().zip(&compressions) {
+            factor_map.write_compressed(factors_file, layout, compression)
+                .with_context(|| format!("Failed to write the factors file {:?}", factors_file))?;
+            log::info!("✅ Factors successfully written to {:?}", factors_file);
+        }
+        write_map_outputs(&matches, &factor_map, layout)?;
+        log::info!("{}", FACTORS_FILE_HELP);
         return Ok(());
     }
-    println!("📄 Input file: {:?}", input_path);
-    println!("📘 Factors file: {:?}", factors_file);
-    println!("Categorical cols: {:?}", categorical_cols);
 
     std::panic::set_hook(Box::new(|info| {
         eprintln!("💥 Internal panic: {}", info);
     }));
 
-    match SurvivalData::from_file(
-        &input_path,
-        delimiter,
-        categorical_cols,
-        &factors_file
-    ) {
-        Ok(_) => println!("This is strange - this should actually fail here!"),
-        Err(e) => {
-            println!("The hoefully expected error from the lib: {:?}",e);
-            ()
-        },
+    // errors propagate: the process exits non-zero and prints the cause
+    // regenerate from scratch - an existing (forced) factors file must not shape the new one
+    let mut data = if inputs.len() > 1 {
+        SurvivalData::from_files(&inputs, &options, union_columns)
+    } else if let Some(data) = &buffered {
+        SurvivalData::from_reader_with_options(&data[..], &options)
+    } else if let Some(url) = &url {
+        url_table(url, &options)
+    } else if matches.get_flag("streaming") {
+        SurvivalData::from_file_streaming(&input_path, &options)
+    } else {
+        SurvivalData::from_file_with_factors(&input_path, &options, &FactorMap::default())
+    }.with_context(|| read_context(&inputs, &input_path))?;
+    bar.finish_and_clear();
+    if let Some(lint) = &lint {
+        report_lints(&data, &inputs, buffered.as_deref(), &options, lint)?;
+    }
+
+    if let Some(&min_coverage) = matches.get_one::<f64>("min_coverage") {
+        let dropped = data.drop_sparse_columns(min_coverage);
+        if !dropped.is_empty() {
+            let listed: Vec<String> = dropped.iter().map(|(name, coverage)| format!("{} ({:.1}%)", name, coverage * 100.0)).collect();
+            log::info!("🗑️ Dropped {} column(s) with less than {}% values: {}", dropped.len(), min_coverage * 100.0, listed.join(", "));
+        }
+    }
+    if matches.get_flag("drop_all_missing") {
+        let dropped = data.drop_all_missing_columns();
+        if !dropped.is_empty() {
+            log::info!("🗑️ Dropped {} column(s) without any value: {}", dropped.len(), dropped.join(", "));
+        }
+    }
+    if matches.get_flag("drop_constant") {
+        let dropped = data.drop_constant_columns();
+        if !dropped.is_empty() {
+            log::info!("🗑️ Dropped {} constant column(s): {}", dropped.len(), dropped.join(", "));
+        }
+    } else {
+        let constant: Vec<&str> = data.columns.iter().filter(|c| c.constant).map(|c| c.name.as_str()).collect();
+        if !constant.is_empty() {
+            log::warn!("Constant column(s): {} (--drop-constant leaves them out)", constant.join(", "));
+        }
+    }
+
+    if let Some(arg) = matches.get_one::<String>("bin") {
+        let bin = BinOptions { keep: matches.get_flag("bin_keep") };
+        for definition in arg.split(',') {
+            let (column, bins) = split_column_arg(definition)?;
+            let bins: usize = bins
+                .parse()
+                .with_context(|| format!("--bin {}: the number of bins must be a whole number", definition))?;
+            let binned = data.bin_quantiles_with(column, bins, &bin)?;
+            log::info!("📊 Binned '{}' into {} quantile bins as '{}'", column, data.factors[&binned].get_levels().len(), binned);
+        }
+    }
+
+    if let Some(arg) = matches.get_one::<String>("vocab") {
+        let mut violations = Vec::new();
+        for definition in arg.split(',') {
+            let (column, file) = split_column_arg(definition)?;
+            if data.column_index(column).is_none() {
+                anyhow::bail!("--vocab {}: the table has no column '{}'", definition, column);
+            }
+            let allowed = read_vocabulary(file).with_context(|| format!("Failed to read the vocabulary {:?}", file))?;
+            let unknown = data.check_vocabulary(column, &allowed);
+            if !unknown.is_empty() {
+                log::warn!("Column '{}': {} value(s) not in {:?}: {}", column, unknown.len(), file, unknown.join(", "));
+                violations.push(column);
+            }
+        }
+        if options.strict && !violations.is_empty() {
+            anyhow::bail!("--strict: values outside the vocabulary in {}", violations.join(", "));
+        }
+    }
+
+    data.relabel_levels(&spec.labels())?;
+    if let Some(labels) = matches.get_one::<String>("labels") {
+        data.relabel_levels(&read_level_labels(labels)?)?;
+    }
+
+    if let Some(id_column) = matches.get_one::<String>("id_column") {
+        data.set_id_column(id_column)?;
+    } else {
+        let candidates = data.candidate_id_columns();
+        if !candidates.is_empty() {
+            log::debug!("Possible row identifiers: {} (--id-column marks one)", candidates.join(", "));
+        }
+    }
+    let mut factor_map = data.compute_factors();
+    factor_map.reorder(ColumnOrder::parse(matches.get_one::<String>("order").unwrap())?);
+    if let Some(palette) = matches.get_one::<String>("palette") {
+        let (nominal, ordered) = Palette::parse_pair(palette)?;
+        factor_map.apply_palette(nominal, ordered);
+    }
+    if !matches.get_flag("no_provenance") {
+        let mut meta = Provenance::now(&inputs);
+        meta.delimiter = match (&options.separator, matches.get_one::<String>("delimiter_regex")) {
+            (Some(_), Some(pattern)) => pattern.clone(),
+            (Some(_), None) => delimiter_arg.clone(),
+            (None, _) => (delimiter as char).to_string(),
+        };
+        meta.categorical = categorical_cols.iter().cloned().collect();
+        meta.categorical.sort();
+        meta.command = std::env::args().collect();
+        meta.max_rows = data.parse_report().limit;
+        factor_map.meta = Some(meta);
+    }
+    for (factors_file, &compression) in factors_files.iter().zip(&compressions) {
+        if append && factors_file.exists() {
+            let mut existing = FactorMap::read(factors_file)
+                .with_context(|| format!("Failed to read the factors file {:?} to append to", factors_file))?;
+            existing.append(factor_map.clone(), matches.get_flag("overwrite_columns"))
+                .with_context(|| format!("--append to {:?} (--overwrite-columns replaces existing columns)", factors_file))?;
+            existing.write_compressed(factors_file, layout, compression)
+                .with_context(|| format!("Failed to write the factors file {:?}", factors_file))?;
+            log::info!("✅ {} column(s) appended to {:?}", factor_map.columns.len(), factors_file);
+            continue;
+        }
+        factor_map.write_compressed(factors_file, layout, compression)
+            .with_context(|| format!("Failed to write the factors file {:?}", factors_file))?;
+    }
+    if let Some((cache, key)) = &cache
+        && let Err(err) = cache.put(key, &factor_map)
+    {
+        log::warn!("Could not cache the factors in {:?}: {}", cache.dir(), err);
+    }
+    write_map_outputs(&matches, &factor_map, layout)?;
+
+    if let Some(ndjson) = matches.get_one::<String>("ndjson") {
+        let file = File::create(ndjson).with_context(|| format!("Failed to create {:?}", ndjson))?;
+        let mut writer = BufWriter::new(file);
+        let seed = *matches.get_one::<u64>("seed").unwrap();
+        if let Some(arg) = matches.get_one::<String>("balance") {
+            let (column, per_level) = split_column_arg(arg)?;
+            let per_level: usize = per_level
+                .parse()
+                .with_context(|| format!("--balance {}: the cap must be a number of rows", arg))?;
+            data.downsample_balanced(column, per_level, seed)?.write_ndjson(&mut writer)?;
+        } else {
+            match (matches.get_one::<usize>("subsample"), matches.get_flag("streaming")) {
+                (Some(&n), true) => {
+                    let mut reservoir = Reservoir::new(n, seed);
+                    data.stream_rows(&input_path, &options, |row| {
+                        reservoir.push(row.to_vec());
+                        Ok(())
+                    })?;
+                    for row in reservoir.into_sorted() {
+                        data.write_ndjson_row(&mut writer, &row)?;
+                    }
+                }
+                (Some(&n), false) => data.sample_rows(n, seed).write_ndjson(&mut writer)?,
+                (None, true) => {
+                    data.stream_rows(&input_path, &options, |row| data.write_ndjson_row(&mut writer, row))?;
+                }
+                (None, false) => data.write_ndjson(&mut writer)?,
+            }
+        }
+        writer.flush()?;
+        log::info!("✅ Rows written as NDJSON to {:?}", ndjson);
+    }
+    if let Some(schema) = matches.get_one::<String>("emit_schema") {
+        layout.write_file(schema, &data.json_schema()).with_context(|| format!("Failed to write {:?}", schema))?;
+        log::info!("✅ JSON Schema written to {:?}", schema);
     }
 
-    println!("✅ JSON successfully written to {:?}", factors_file);
+    for factors_file in &factors_files {
+        log::info!("✅ Factors successfully written to {:?}", factors_file);
+    }
+    let report = data.parse_report();
+    if let Some(limit) = report.limit {
+        log::warn!("Only the first {} rows were read (--limit) - the factor levels and summaries describe them, not the whole file", limit);
+    }
+    // the --header-row titles are dropped on request
+    match report.rows_dropped == report.dropped(DropReason::BeforeHeader) {
+        true => log::info!("📊 Rows: {}", report),
+        false => log::warn!("Rows: {}", report),
+    }
+    log::info!("{}", FACTORS_FILE_HELP);
+    Ok(())
+}
+
+/// The context of a failed read - combined files name the one at fault in the error itself.
+fn read_context(inputs: &[PathBuf], input_path: &Path) -> String {
+    match inputs.len() {
+        1 => format!("Failed to read {:?}", input_path),
+        n => format!("Failed to combine the {} input files", n),
+    }
+}
+
+/// The files derived from the factor map: `--split-output` and `--emit-r`, if given.
+fn write_map_outputs(matches: &ArgMatches, factor_map: &FactorMap, layout: JsonLayout) -> anyhow::Result<()> {
+    if let Some(dir) = matches.get_one::<String>("split_output") {
+        let index = factor_map.write_split(dir, layout)
+            .with_context(|| format!("Failed to write the column files to {:?}", dir))?;
+        log::info!("✅ {} column file(s) and their index written to {:?}", index.columns.len(), dir);
+    }
+    if let Some(r_file) = matches.get_one::<String>("emit_r") {
+        factor_map.write_r_source(r_file, "df").with_context(|| format!("Failed to write {:?}", r_file))?;
+        log::info!("✅ R factor definitions written to {:?}", r_file);
+    }
+    Ok(())
+}
+
+/// The file name at the end of a URL - `meta.tsv` of `https://example.com/data/meta.tsv?raw=1`.
+fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    path.split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').find(|segment| !segment.is_empty()))
+        .unwrap_or("download")
+}
+
+/// Download all of `url` - for the delimiter detection and the lint, which read the table twice.
+#[cfg(feature = "net")]
+fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    log::info!("🌐 Downloading {}", url);
+    Ok(rust_data_table::input::read_url(url)?)
+}
+
+#[cfg(not(feature = "net"))]
+fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("Reading {} needs the 'net' feature", url)
+}
+
+/// Parse the table at `url` while it is downloaded.
+#[cfg(feature = "net")]
+fn url_table(url: &str, options: &ParseOptions) -> rust_data_table::error::Result<SurvivalData> {
+    log::info!("🌐 Streaming {}", url);
+    SurvivalData::from_url_with_options(url, options)
+}
+
+#[cfg(not(feature = "net"))]
+fn url_table(url: &str, _options: &ParseOptions) -> rust_data_table::error::Result<SurvivalData> {
+    Err(rust_data_table::DataTableError::InvalidArgument(format!("Reading {} needs the 'net' feature", url)))
+}
+
+/// Warn about the `--lint` findings in every input table.
+fn report_lints(data: &SurvivalData, inputs: &[PathBuf], buffered: Option<&[u8]>, options: &ParseOptions, lint: &LintOptions) -> anyhow::Result<()> {
+    let mut found = 0;
+    for input in inputs {
+        let lints = match buffered {
+            Some(bytes) => data.lint_levels(bytes, options, lint)?,
+            None => data.lint_levels(open_input(input)?, options, lint)?,
+        };
+        for finding in &lints {
+            match inputs.len() {
+                1 => log::warn!("{}", finding),
+                _ => log::warn!("{:?}: {}", input, finding),
+            }
+        }
+        found += lints.len();
+    }
+    match found {
+        0 => log::info!("✅ No factor levels differing only by whitespace{}", if lint.case { " or case" } else { "" }),
+        n => log::warn!("{} level conflict(s) - --trim writes the factors file with the levels trimmed{}", n, if lint.case { ", --level-case folds the case" } else { "" }),
+    }
+    Ok(())
+}
+
+/// A number from 0 to 1 (`--min-coverage`).
+fn parse_fraction(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err(format!("'{}' is no fraction from 0 to 1", arg)),
+    }
+}
+
+/// The status output of the tool and the library (`log` records of both crates): info and
+/// debug on stdout, warnings and errors on stderr. On a terminal the messages keep their emoji;
+/// piped into a file or another program they get a plain level prefix instead.
+struct StatusLogger {
+    level: log::LevelFilter,
+    interactive: bool,
+}
+
+impl StatusLogger {
+    fn init(level: log::LevelFilter) -> anyhow::Result<()> {
+        let interactive = std::io::stdout().is_terminal();
+        log::set_boxed_logger(Box::new(StatusLogger { level, interactive }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl log::Log for StatusLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let ours = ["rust_data_table", "generate_json"].iter().any(|c| metadata.target().starts_with(c));
+        ours && metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        let line = match (record.level(), self.interactive) {
+            (log::Level::Error, true) => format!("❌ {}", message),
+            (log::Level::Warn, true) => format!("⚠️ {}", message),
+            (_, true) => message,
+            (log::Level::Info, false) => without_emoji(&message).to_string(),
+            (log::Level::Warn, false) => format!("warning: {}", without_emoji(&message)),
+            (level, false) => format!("{}: {}", level.as_str().to_lowercase(), without_emoji(&message)),
+        };
+        match record.level() {
+            log::Level::Error | log::Level::Warn => eprintln!("{}", line),
+            _ => println!("{}", line),
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// A message without its leading symbol (`✅ written` -> `written`).
+fn without_emoji(message: &str) -> &str {
+    message.trim_start_matches(|c: char| c.is_whitespace() || (!c.is_ascii() && !c.is_alphanumeric()))
+}
+
+/// A bar over the file size - or a spinner counting lines if the size of the table is
+/// unknown (stdin, gzip). Neither is drawn if stderr is no terminal.
+fn progress_bar(input: &Path, stdin: bool) -> anyhow::Result<(ProgressBar, Progress)> {
+    let mut magic = [0_u8; 2];
+    let gzip = !stdin && File::open(input)?.read(&mut magic)? == 2 && is_gzip_magic(&magic);
+    if stdin || gzip {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(ProgressStyle::with_template("{spinner} {human_pos} lines read ({elapsed})")?);
+        bar.enable_steady_tick(Duration::from_millis(120));
+        let lines = bar.clone();
+        return Ok((bar, Progress::lines(move |n| lines.set_position(n))));
+    }
+    let bar = ProgressBar::new(std::fs::metadata(input)?.len());
+    bar.set_style(ProgressStyle::with_template("[{bar:40}] {bytes}/{total_bytes} ({eta} left)")?.progress_chars("=> "));
+    let bytes = bar.clone();
+    Ok((bar, Progress::bytes(move |n| bytes.set_position(n))))
+}
+
+/// The `validate` subcommand - fails if the table drifted from the factors file.
+fn validate(matches: &ArgMatches) -> anyhow::Result<()> {
+    let data_path = PathBuf::from(matches.get_one::<String>("data").unwrap());
+    let factors_path = PathBuf::from(matches.get_one::<String>("factors").unwrap());
+    let map = FactorMap::read(&factors_path)?;
+    let delimiter = Delimiter::parse(matches.get_one::<String>("delimiter").unwrap())?.resolve(&data_path)?;
+    let mut options = ParseOptions::new(delimiter, HashSet::new());
+    options.encoding = TextEncoding::parse(matches.get_one::<String>("encoding").unwrap())?;
+
+    let drift = map.validate_file(&data_path, &options)?;
+    if drift.is_empty() {
+        log::info!("✅ {:?} matches {:?}", data_path, factors_path);
+        return Ok(());
+    }
+    for problem in &drift {
+        log::error!("{}", problem);
+    }
+    anyhow::bail!("{:?} does not match {:?}: {} problem(s)", data_path, factors_path, drift.len())
+}
+
+/// The `merge` subcommand - writes the combined factors file.
+fn merge(matches: &ArgMatches) -> anyhow::Result<()> {
+    let first = PathBuf::from(matches.get_one::<String>("first").unwrap());
+    let second = PathBuf::from(matches.get_one::<String>("second").unwrap());
+    let output = PathBuf::from(matches.get_one::<String>("output").unwrap());
+    let strategy = MergeStrategy::parse(matches.get_one::<String>("strategy").unwrap())?;
+    let merged = FactorMap::read(&first)?.merge(&FactorMap::read(&second)?, strategy)?;
+    merged.write(&output).with_context(|| format!("Failed to write the factors file {:?}", output))?;
+    log::info!("✅ {:?} and {:?} merged into {:?} ({} factors)", first, second, output, merged.len());
+    Ok(())
+}
+
+/// The `diff` subcommand - fails if the factor definitions changed.
+fn diff(matches: &ArgMatches) -> anyhow::Result<()> {
+    let old_path = PathBuf::from(matches.get_one::<String>("old").unwrap());
+    let new_path = PathBuf::from(matches.get_one::<String>("new").unwrap());
+    let diff = FactorMap::read(&old_path)?.diff(&FactorMap::read(&new_path)?);
+    if diff.is_empty() {
+        log::info!("✅ {:?} and {:?} define the same factors", old_path, new_path);
+        return Ok(());
+    }
+    for change in &diff.changes {
+        log::error!("{}", change);
+    }
+    anyhow::bail!("{:?} differs from {:?}: {} change(s)", new_path, old_path, diff.len())
+}
+
+/// The `head` and `tail` subcommands - the same arguments, other rows.
+fn preview_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(Arg::new("data").help("Table to show (TSV or CSV)").required(true).index(1))
+        .arg(
+            Arg::new("rows")
+                .short('n')
+                .long("rows")
+                .help("Number of rows to print")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("codes")
+                .long("codes")
+                .help("Show factor values as level(code)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("categorical")
+                .long("categorical")
+                .help("Comma-separated list of columns to treat as categorical"),
+        )
+        .arg(
+            Arg::new("delimiter")
+                .short('d')
+                .long("delimiter")
+                .help("Field delimiter: '\\t', ',', ';', '|' or 'auto'")
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .help("Character encoding of the input: utf-8, latin1 or windows-1252")
+                .default_value("utf-8"),
+        )
+}
+
+/// The `head` (or with `tail` the `tail`) subcommand - prints the typed rows to stdout.
+fn preview(matches: &ArgMatches, tail: bool) -> anyhow::Result<()> {
+    let data_path = PathBuf::from(matches.get_one::<String>("data").unwrap());
+    let delimiter = Delimiter::parse(matches.get_one::<String>("delimiter").unwrap())?.resolve(&data_path)?;
+    let categorical_cols: HashSet<String> = matches
+        .get_one::<String>("categorical")
+        .map(|s| s.split(',').map(|v| v.trim().to_string()).collect())
+        .unwrap_or_default();
+    let mut options = ParseOptions::new(delimiter, categorical_cols);
+    options.encoding = TextEncoding::parse(matches.get_one::<String>("encoding").unwrap())?;
+
+    let data = SurvivalData::from_file_with_factors(&data_path, &options, &FactorMap::default())?;
+    let rows = *matches.get_one::<usize>("rows").unwrap();
+    let codes = matches.get_flag("codes");
+    let table = match tail {
+        true => data.tail_table(rows, codes),
+        false => data.head_table(rows, codes),
+    };
+    std::io::stdout().lock().write_all(table.as_bytes())?;
     Ok(())
-}
\ No newline at end of file
+}
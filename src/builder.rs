@@ -0,0 +1,410 @@
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use crate::column::Dtype;
+use crate::error::Result;
+use crate::factor::FactorSpec;
+use crate::factor_map::{FactorMap, LevelCodes};
+use crate::input::Progress;
+use crate::options::{ColumnSelection, Delimiter, DuplicateColumns, JsonCells, LevelCase, ParseOptions, SampleCheck, Separator, TextEncoding, TrailingDelimiter};
+use crate::spec::TableSpec;
+use crate::SurvivalData;
+
+/// Chainable configuration of the parser - new settings do not change any signature.
+///
+/// # Example
+/// ```
+/// use std::io::Cursor;
+/// use rust_data_table::SurvivalDataBuilder;
+/// let data = SurvivalDataBuilder::new()
+///     .delimiter(b',')
+///     .categorical(["cluster"])
+///     .max_levels(200)
+///     .missing_tokens(&["NA", ""])
+///     .build_from_reader(Cursor::new("cluster,age\n1,42\n2,NA\n"))
+///     .unwrap();
+/// assert!(data.factors.contains_key("cluster"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SurvivalDataBuilder {
+    options: ParseOptions,
+    delimiter: Delimiter,
+    factors_file: Option<PathBuf>,
+}
+
+impl Default for SurvivalDataBuilder {
+    fn default() -> Self {
+        SurvivalDataBuilder {
+            options: ParseOptions::default(),
+            delimiter: Delimiter::Byte(b'\t'),
+            factors_file: None,
+        }
+    }
+}
+
+impl SurvivalDataBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Field delimiter (default: tab).
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = Delimiter::Byte(delimiter);
+        self
+    }
+
+    /// Detect the delimiter from the first lines of the file (see `sniff_delimiter()`).
+    /// Readers are parsed with the tab delimiter.
+    pub fn detect_delimiter(mut self) -> Self {
+        self.delimiter = Delimiter::Auto;
+        self
+    }
+
+    /// Split the rows at a text like `::` or a regex like `\s+` instead of a single byte delimiter.
+    pub fn separator(mut self, separator: Separator) -> Self {
+        self.options.separator = Some(separator);
+        self
+    }
+
+    /// Columns that are always treated as factors.
+    pub fn categorical<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.categorical.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Read these columns as numbers - values that are no number become missing.
+    pub fn as_numeric<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.as_numeric.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Keep these columns as free text, exactly as written.
+    pub fn as_string<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.as_string.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Read only these columns - the others are skipped.
+    pub fn include<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.columns = ColumnSelection::Include(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Skip these columns.
+    pub fn exclude<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.columns = ColumnSelection::Exclude(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Rename the header column `original` to `new` - the other settings may use either name.
+    pub fn rename(mut self, original: &str, new: &str) -> Self {
+        self.options.renames.push((original.to_string(), new.to_string()));
+        self
+    }
+
+    /// A user supplied factor setup (e.g. `FactorSpec::ordered()`) for one column.
+    pub fn factor_spec(mut self, column: &str, spec: FactorSpec) -> Self {
+        self.options.factor_specs.insert(column.to_string(), spec);
+        self
+    }
+
+    /// Fixed codes for the levels of factor columns (`ParseOptions::level_codes`), e.g. from
+    /// `factor_map::read_level_codes()`.
+    pub fn level_codes(mut self, codes: LevelCodes) -> Self {
+        self.options.level_codes.extend(codes);
+        self
+    }
+
+    /// Make `level` the baseline (index 0) of the factor `column` - it has to be one of its levels.
+    pub fn reference(mut self, column: &str, level: &str) -> Self {
+        self.options
+            .factor_specs
+            .entry(column.to_string())
+            .or_default()
+            .reference = Some(level.to_string());
+        self
+    }
+
+    /// Merge the levels of `column` with fewer than `min_count` rows into `label` (e.g. `Other`).
+    pub fn merge_rare(mut self, column: &str, min_count: usize, label: &str) -> Self {
+        let spec = self.options.factor_specs.entry(column.to_string()).or_default();
+        spec.merge_rare = Some(min_count);
+        spec.rare_label = Some(label.to_string());
+        self
+    }
+
+    /// Keep the `k` most frequent levels of `column` and merge the others into `label` (see `Factor::top_k()`).
+    pub fn top_k(mut self, column: &str, k: usize, label: &str) -> Self {
+        let spec = self.options.factor_specs.entry(column.to_string()).or_default();
+        spec.top_k = Some(k);
+        spec.rare_label = Some(label.to_string());
+        self
+    }
+
+    /// Inferred text columns with more distinct values become free text.
+    pub fn max_levels(mut self, max_levels: usize) -> Self {
+        self.options.max_levels = Some(max_levels);
+        self
+    }
+
+    /// Never demote text columns to free text.
+    pub fn unlimited_levels(mut self) -> Self {
+        self.options.max_levels = None;
+        self
+    }
+
+    /// Normalize factor values before they become levels (e.g. `LevelCase::Lower`).
+    pub fn level_case(mut self, case: LevelCase) -> Self {
+        self.options.level_case = case;
+        self
+    }
+
+    /// Drop rows with the wrong number of fields (reported as a count) instead of failing.
+    pub fn skip_bad_rows(mut self) -> Self {
+        self.options.skip_bad_rows = true;
+        self
+    }
+
+    /// Sort the levels of inferred factors instead of keeping the order of first appearance.
+    pub fn sort_levels(mut self) -> Self {
+        self.options.sort_levels = true;
+        self
+    }
+
+    /// Number the factor levels 1, 2, ... like R (see `ParseOptions::one_based_codes`).
+    pub fn one_based_codes(mut self) -> Self {
+        self.options.one_based_codes = true;
+        self
+    }
+
+    /// Keep the columns of JSON objects as `Json` or flatten them (see `ParseOptions::json_cells`).
+    pub fn json_cells(mut self, json_cells: JsonCells) -> Self {
+        self.options.json_cells = json_cells;
+        self
+    }
+
+    /// Use at most `threads` threads for the type inference (`rayon` feature, 1: sequential).
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = Some(threads);
+        self
+    }
+
+    /// The character encoding of the input (default: UTF-8).
+    pub fn encoding(mut self, encoding: TextEncoding) -> Self {
+        self.options.encoding = encoding;
+        self
+    }
+
+    /// Report the parser's progress, e.g. `Progress::bytes(|read| bar.set_position(read))`.
+    pub fn progress(mut self, progress: Progress) -> Self {
+        self.options.progress = Some(progress);
+        self
+    }
+
+    /// Declare the type of `column` instead of inferring it - a value that does not fit is an error.
+    pub fn dtype(mut self, column: &str, dtype: Dtype) -> Self {
+        self.options.dtypes.insert(column.to_string(), dtype);
+        self
+    }
+
+    /// Read the settings of a spec file (see `TableSpec::apply()`); its labels are not part
+    /// of the parser settings - apply them with `relabel_levels(&spec.labels())`.
+    pub fn spec(mut self, spec: &TableSpec) -> Result<Self> {
+        spec.apply(&mut self.options)?;
+        Ok(self)
+    }
+
+    /// Skip the lines starting with `comment` (e.g. `b'#'`) - also while detecting the delimiter.
+    pub fn comment(mut self, comment: u8) -> Self {
+        self.options.comment = Some(comment);
+        self
+    }
+
+    /// The header is the line after `lines` title lines (see `ParseOptions::header_row`).
+    pub fn header_row(mut self, lines: usize) -> Self {
+        self.options.header_row = lines;
+        self
+    }
+
+    /// Read only the first `rows` rows (see `ParseOptions::max_rows`).
+    pub fn max_rows(mut self, rows: usize) -> Self {
+        self.options.max_rows = Some(rows);
+        self
+    }
+
+    /// Fail unless the header has all of `columns` (see `ParseOptions::required_columns`).
+    pub fn require_columns(mut self, columns: &[&str]) -> Self {
+        self.options.required_columns.extend(columns.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// Keep the empty last column of lines ending with a delimiter instead of dropping it.
+    pub fn keep_trailing_delimiter(mut self) -> Self {
+        self.options.trailing_delimiter = TrailingDelimiter::Keep;
+        self
+    }
+
+    /// Count empty and `NA` cells as one kind of missing (`ParseOptions::collapse_missing`).
+    pub fn collapse_missing(mut self) -> Self {
+        self.options.collapse_missing = true;
+        self
+    }
+
+    /// Fail for inferred columns mixing numbers and text instead of reading them as factors.
+    pub fn strict(mut self) -> Self {
+        self.options.strict = true;
+        self
+    }
+
+    /// Keep numeric looking columns with values like `007` as factors (identifiers, zip codes).
+    pub fn preserve_leading_zeros(mut self) -> Self {
+        self.options.preserve_leading_zeros = true;
+        self
+    }
+
+    /// Detect date columns (see `DATE_FORMATS`) instead of reading them as factors.
+    pub fn detect_dates(mut self) -> Self {
+        self.options.detect_dates = true;
+        self
+    }
+
+    /// Cells meaning "no value" (replaces the default list).
+    pub fn missing_tokens<S: AsRef<str>>(mut self, tokens: &[S]) -> Self {
+        self.options.missing_tokens = tokens.iter().map(|t| t.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Read a file with the variables as rows - its first column becomes the header.
+    pub fn transpose(mut self) -> Self {
+        self.options.transpose = true;
+        self
+    }
+
+    /// Numbers meaning "no value" in every column, e.g. `-999` (see `ParseOptions::na_values`).
+    pub fn na_values(mut self, values: &[f64]) -> Self {
+        self.options.na_values = values.to_vec();
+        self
+    }
+
+    /// Numbers meaning "no value" in `column` only, e.g. `-1` for `age`.
+    pub fn column_na_values(mut self, column: &str, values: &[f64]) -> Self {
+        self.options.column_na_values.insert(column.to_string(), values.to_vec());
+        self
+    }
+
+    /// The tokens of boolean columns (replace the default lists).
+    pub fn boolean_tokens<S: AsRef<str>>(mut self, true_tokens: &[S], false_tokens: &[S]) -> Self {
+        self.options.true_tokens = true_tokens.iter().map(|t| t.as_ref().to_string()).collect();
+        self.options.false_tokens = false_tokens.iter().map(|t| t.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Infer the column types from the first `rows` rows only; `check` decides about the rest.
+    pub fn sample_rows(mut self, rows: usize, check: SampleCheck) -> Self {
+        self.options.sample_rows = Some(rows);
+        self.options.sample_check = check;
+        self
+    }
+
+    /// How repeated column names in the header are handled (default: renamed).
+    pub fn duplicate_columns(mut self, mode: DuplicateColumns) -> Self {
+        self.options.duplicate_columns = mode;
+        self
+    }
+
+    /// Apply this factors file if it exists (see `SurvivalData::from_file_with_options()`).
+    pub fn factors_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.factors_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// The parser settings collected so far.
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Read a (possibly gzip-compressed) file.
+    pub fn build_from_file<P: AsRef<Path> + std::fmt::Debug>(&self, path: P) -> Result<SurvivalData> {
+        let mut options = self.options.clone();
+        if options.separator.is_none() {
+            options.delimiter = self.delimiter.resolve_with_comment(&path, options.comment)?;
+        }
+        match &self.factors_file {
+            Some(factors_file) => SurvivalData::from_file_with_options(path, &options, factors_file),
+            None => SurvivalData::from_file_with_factors(path, &options, &FactorMap::default()),
+        }
+    }
+
+    /// Parse a table from any buffered reader - a factors file is applied if set and existing.
+    pub fn build_from_reader<R: BufRead>(&self, reader: R) -> Result<SurvivalData> {
+        let mut options = self.options.clone();
+        if let Delimiter::Byte(delimiter) = self.delimiter {
+            options.delimiter = delimiter;
+        }
+        match &self.factors_file {
+            Some(factors_file) if factors_file.exists() => {
+                SurvivalData::from_reader_with_factors(reader, &options, &FactorMap::read(factors_file)?)
+            }
+            _ => SurvivalData::from_reader_with_options(reader, &options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_builder() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.csv");
+        std::fs::write(&path, "cluster;age;stage\n1;42;low\n2;-;high\n")?;
+
+        let data = SurvivalDataBuilder::new()
+            .detect_delimiter()
+            .categorical(["cluster"])
+            .missing_tokens(&["-"])
+            .factor_spec("stage", FactorSpec::ordered(vec!["low".into(), "high".into()]))
+            .build_from_file(&path)?;
+        assert_eq!(data.headers, vec!["cluster", "age", "stage"]);
+        assert_eq!(data.delimiter(), b';', "the detected delimiter");
+        assert_eq!(data.header(), ["cluster", "age", "stage"]);
+        assert!(data.factors.contains_key("cluster"));
+        assert_eq!(data.column("age").unwrap().missing, 1);
+        assert!(data.factors["stage"].ordered);
+
+        // the factors file is picked up
+        let factors = dir.path().join("meta.factors.json");
+        data.write_factors(&factors)?;
+        let again = SurvivalDataBuilder::new()
+            .delimiter(b';')
+            .factors_file(&factors)
+            .build_from_file(&path)?;
+        assert!(again.factors.contains_key("cluster"), "declared by the factors file");
+
+        let selected = SurvivalDataBuilder::new().delimiter(b';').include(["age"]).build_from_file(&path)?;
+        assert_eq!(selected.column_names(), ["age"]);
+        assert_eq!(selected.header(), ["cluster", "age", "stage"], "the file header");
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::error::{DataTableError, Result};
+use crate::factor_map::{FactorMap, SCHEMA_VERSION};
+
+/// A directory of factor maps keyed by their input (`<key>.json`) - re-running on an
+/// unchanged file with the same settings reads the map instead of parsing the table again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactorCache {
+    dir: PathBuf,
+}
+
+impl FactorCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        FactorCache { dir: dir.into() }
+    }
+
+    /// The cache below `$XDG_CACHE_HOME` or `~/.cache` (`rust_data_table`) - `None` if neither is set.
+    pub fn user_default() -> Option<Self> {
+        let base = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME").filter(|dir| !dir.is_empty())?).join(".cache"),
+        };
+        Some(FactorCache::new(base.join("rust_data_table")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The key of a run: every input file by path, size and modification time (the content is
+    /// not read) plus the `settings` that shape the result, e.g. the command line options.
+    /// The crate version and `SCHEMA_VERSION` are part of it, so an update starts fresh.
+    pub fn key<P: AsRef<Path>, S: AsRef<str>>(files: &[P], settings: &[S]) -> Result<String> {
+        let mut hash = Fnv1a::default();
+        hash.write(env!("CARGO_PKG_VERSION").as_bytes());
+        hash.write(SCHEMA_VERSION.as_bytes());
+        for file in files {
+            let file = file.as_ref();
+            let metadata = fs::metadata(file).map_err(|e| DataTableError::io(file, e))?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            let path = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+            hash.write(path.to_string_lossy().as_bytes());
+            hash.write(&metadata.len().to_le_bytes());
+            hash.write(&modified.as_nanos().to_le_bytes());
+        }
+        for setting in settings {
+            hash.write(setting.as_ref().as_bytes());
+        }
+        Ok(format!("{:016x}", hash.0))
+    }
+
+    /// The file of an entry.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The cached map - `None` if there is none (or it cannot be read any more).
+    pub fn get(&self, key: &str) -> Option<FactorMap> {
+        let path = self.path(key);
+        if !path.exists() {
+            return None;
+        }
+        FactorMap::from_json(path).ok()
+    }
+
+    /// Store the map of `key` - an older entry is replaced.
+    pub fn put(&self, key: &str, map: &FactorMap) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(|e| DataTableError::io(&self.dir, e))?;
+        map.write_json(self.path(key))
+    }
+}
+
+/// 64 bit FNV-1a - stable across Rust versions and platforms, unlike `DefaultHasher`.
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    /// Hash `bytes` followed by a separator - `ab`,`c` and `a`,`bc` differ.
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter().chain(&[0xff]) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use crate::SurvivalData;
+
+    #[test]
+    fn test_factor_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        fs::write(&input, "cell\tcluster\nA\tx\nB\ty\n")?;
+        let cache = FactorCache::new(dir.path().join("cache"));
+
+        let key = FactorCache::key(&[&input], &["--categorical", "cluster"])?;
+        assert_eq!(key.len(), 16);
+        assert_eq!(key, FactorCache::key(&[&input], &["--categorical", "cluster"])?, "the same run");
+        assert_ne!(key, FactorCache::key(&[&input], &["--categorical", "cell"])?, "other options");
+        assert_ne!(key, FactorCache::key(&[&input], &["--categorical", "clu", "ster"])?);
+        assert!(cache.get(&key).is_none());
+
+        let map = SurvivalData::from_reader(Cursor::new(fs::read(&input)?), b'\t', HashSet::new())?.compute_factors();
+        cache.put(&key, &map)?;
+        assert_eq!(cache.get(&key), Some(map));
+
+        // a changed file is another entry
+        fs::write(&input, "cell\tcluster\nA\tx\nB\ty\nC\tz\n")?;
+        assert_ne!(key, FactorCache::key(&[&input], &["--categorical", "cluster"])?);
+
+        // a broken entry is a miss
+        fs::write(cache.path(&key), "{")?;
+        assert!(cache.get(&key).is_none());
+        assert!(FactorCache::key(&[dir.path().join("missing.tsv")], &[] as &[&str]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use crate::column::{Dtype, NumericSummary};
+use crate::error::{bail, Result};
+use crate::factor::Factor;
+use crate::infer::parse_float;
+use crate::SurvivalData;
+
+impl SurvivalData {
+    /// Turn the factor (or free text) column `column` into numbers without reading the file
+    /// again: every level is parsed as a number (`Integer` if all are whole numbers, `Float`
+    /// otherwise), levels that are no number become missing. One-hot columns of the factor
+    /// are removed. Returns the number of values that became missing.
+    pub fn as_numeric(&mut self, column: &str) -> Result<usize> {
+        let Some(index) = self.column_index(column) else {
+            bail!(Column, "Column '{}' not found", column);
+        };
+        let Some(factor) = self.factors.remove(column) else {
+            bail!(Column, "Column '{}' is not a factor column, but {}", column, self.columns[index].dtype);
+        };
+        let mut lost = 0;
+        let values: Vec<f64> = self
+            .numeric_data
+            .column(index)
+            .iter()
+            .map(|&code| match factor.level_at(code) {
+                None => f64::NAN,
+                Some(level) => parse_float(level).unwrap_or_else(|| {
+                    lost += 1;
+                    f64::NAN
+                }),
+            })
+            .collect();
+        if lost > 0 {
+            log::warn!("Column '{column}': {lost} value(s) are no number -> missing");
+        }
+        self.numeric_data.column_mut(index).iter_mut().zip(&values).for_each(|(cell, &value)| *cell = value);
+        let whole = values.iter().filter(|v| !v.is_nan()).all(|v| v.fract() == 0.0 && v.abs() < i64::MAX as f64);
+        let info = &mut self.columns[index];
+        info.dtype = if whole { Dtype::Integer } else { Dtype::Float };
+        info.missing = values.iter().filter(|v| v.is_nan()).count();
+        if let Some(kinds) = &mut info.missing_kinds {
+            kinds.na += lost;
+        }
+        info.cardinality = None;
+        info.summary = Some(NumericSummary::from_values(&values));
+        if factor.one_hot {
+            self.drop_headers(&factor.all_column_names().into_iter().collect::<HashSet<_>>());
+        }
+        self.mark_constant();
+        Ok(lost)
+    }
+
+    /// Turn the numeric column `column` into a factor: its distinct values are the levels, in
+    /// order of first appearance, written like the parser writes numbers (`1.0` is `1`).
+    pub fn as_factor(&mut self, column: &str) -> Result<()> {
+        let Some(index) = self.column_index(column) else {
+            bail!(Column, "Column '{}' not found", column);
+        };
+        let dtype = self.columns[index].dtype;
+        if !dtype.is_numeric() || self.factors.contains_key(column) {
+            bail!(Column, "Column '{}' must be numeric to become a factor, but it is {}", column, dtype);
+        }
+        let mut factor = Factor::new(column, false).declared();
+        let codes: Vec<f64> = self
+            .numeric_data
+            .column(index)
+            .iter()
+            .map(|&value| factor.count(&if value.is_nan() { String::new() } else { value.to_string() }))
+            .collect();
+        self.numeric_data.column_mut(index).iter_mut().zip(codes).for_each(|(cell, code)| *cell = code);
+        let info = &mut self.columns[index];
+        info.dtype = Dtype::Factor;
+        info.cardinality = Some(factor.get_levels().len());
+        info.summary = None;
+        self.factors.insert(column.to_string(), factor);
+        self.mark_constant();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_as_numeric_as_factor() -> Result<()> {
+        let tsv = "dose\tgroup\tscore\n1\tA\t0.5\n2.5\tB\t1\nhigh\tA\tNA\n2.5\tNA\t1\n";
+        let options = ParseOptions { categorical: ["dose".to_string()].into(), ..Default::default() };
+        let mut data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column("dose").unwrap().dtype, Dtype::Factor);
+
+        assert_eq!(data.as_numeric("dose")?, 1, "'high' is no number");
+        let dose = data.column("dose").unwrap();
+        assert_eq!((dose.dtype, dose.missing, dose.cardinality), (Dtype::Float, 1, None));
+        assert_eq!(dose.summary.as_ref().unwrap().max, Some(2.5));
+        assert!(!data.factors.contains_key("dose"));
+        assert_eq!(data.as_vec_f64("dose")[..2], [1.0, 2.5]);
+
+        data.as_factor("score")?;
+        let score = data.column("score").unwrap();
+        assert_eq!((score.dtype, score.cardinality, score.missing), (Dtype::Factor, Some(2), 1));
+        assert_eq!(data.factors["score"].get_levels(), ["0.5", "1"]);
+        assert_eq!(data.column_data("score"), Some(crate::ColumnData::Factor {
+            codes: vec![Some(0), Some(1), None, Some(1)],
+            levels: vec!["0.5".into(), "1".into()],
+        }));
+        // and back
+        data.factors.get_mut("score").unwrap().relabel(&[("0.5".to_string(), "half".to_string())].into())?;
+        assert_eq!(data.as_numeric("score")?, 0, "the level is parsed, not the label");
+        assert_eq!(data.column("score").unwrap().dtype, Dtype::Float);
+        assert_eq!(data.as_vec_f64("score")[..2], [0.5, 1.0]);
+
+        let err = data.as_factor("group").unwrap_err();
+        assert!(err.to_string().contains("must be numeric"), "{err}");
+        assert!(data.as_numeric("missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_numeric_one_hot() -> Result<()> {
+        let tsv = "stage\tn\n1\t5\n2\t6\n1\t7\n";
+        let options = ParseOptions { categorical: ["stage".to_string()].into(), ..Default::default() };
+        let mut data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        data.factors.get_mut("stage").unwrap().one_hot = true;
+        let map = data.compute_factors();
+        let mut data = SurvivalData::from_reader_with_factors(Cursor::new(tsv), &ParseOptions::default(), &map)?;
+        assert_eq!(data.headers, vec!["stage", "stage_1", "stage_2", "n"]);
+
+        data.as_numeric("stage")?;
+        assert_eq!(data.headers, vec!["stage", "n"]);
+        assert_eq!(data.column("stage").unwrap().dtype, Dtype::Integer);
+        assert_eq!(data.as_vec_f64("n"), vec![5.0, 6.0, 7.0]);
+        Ok(())
+    }
+}
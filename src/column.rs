@@ -0,0 +1,272 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+/// The detected type of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dtype {
+    /// Every non-missing value parses as an `i64` (no decimal point).
+    Integer,
+    /// Every non-missing value parses as a floating point number.
+    Float,
+    /// Every non-missing value is one of the boolean tokens (`TRUE`/`FALSE`, `yes`/`no`, `1`/`0`).
+    /// Stored as 1.0 / 0.0.
+    Boolean,
+    /// Every non-missing value is a date (or date and time) in one of `DATE_FORMATS`
+    /// (only with `detect_dates`). Stored as seconds since 1970-01-01, written as ISO-8601.
+    Date,
+    /// Anything else - stored as a `Factor`.
+    Factor,
+    /// A text column with more distinct values than `max_levels` (e.g. barcodes).
+    /// Stored like a factor, but its levels are not part of the factors JSON.
+    FreeText,
+    /// A column of JSON objects (see `ParseOptions::json_cells`) - stored like free text.
+    Json,
+    /// An inferred column without a single value - no factor levels, no summary.
+    /// Stored as missing values.
+    AllMissing,
+}
+
+impl Dtype {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Dtype::Integer | Dtype::Float)
+    }
+}
+
+/// The name used in the JSON (`free_text`).
+impl fmt::Display for Dtype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Dtype::Integer => "integer",
+            Dtype::Float => "float",
+            Dtype::Boolean => "boolean",
+            Dtype::Date => "date",
+            Dtype::Factor => "factor",
+            Dtype::FreeText => "free_text",
+            Dtype::Json => "json",
+            Dtype::AllMissing => "all_missing",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the missing cells of a column were written - see `Column::missing_kinds`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingKinds {
+    /// Literally empty cells (often "not applicable")
+    pub empty: usize,
+    /// Cells with a missing token like `NA`, an `na_values` sentinel or a value an `as_numeric`
+    /// column could not read (often "measured but unknown")
+    pub na: usize,
+}
+
+/// Metadata describing one column of a parsed table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    pub dtype: Dtype,
+    /// Number of missing cells found while parsing
+    #[serde(default)]
+    pub missing: usize,
+    /// The `missing` cells split into empty and `NA` ones - not with `ParseOptions::collapse_missing`
+    /// and not for columns without missing cells
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_kinds: Option<MissingKinds>,
+    /// Number of distinct values of factor and free text columns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cardinality: Option<usize>,
+    /// Statistics of a numeric column (min, max, mean and quartiles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<NumericSummary>,
+    /// The `strftime` format a date column was read with (e.g. `%d.%m.%Y`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Every non-missing value is the same - or the column is entirely missing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub constant: bool,
+    /// The column identifies the rows (`SurvivalData::set_id_column()`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub id: bool,
+    /// The name in the file header of a renamed column (`ParseOptions::renames`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_name: Option<String>,
+}
+
+impl Column {
+    pub fn new(name: &str, dtype: Dtype) -> Self {
+        Column {
+            name: name.to_string(),
+            dtype,
+            missing: 0,
+            missing_kinds: None,
+            cardinality: None,
+            summary: None,
+            format: None,
+            constant: false,
+            id: false,
+            original_name: None,
+        }
+    }
+}
+
+/// A plain text table of the columns: name, dtype, cardinality (factors only) and missing count.
+pub fn schema_table(columns: &[Column]) -> String {
+    let width = columns.iter().map(|c| c.name.chars().count()).chain([6]).max().unwrap_or(6);
+    let mut table = format!("{:<width$}  {:<9}  {:>11}  {:>7}\n", "column", "dtype", "cardinality", "missing");
+    for column in columns {
+        let cardinality = column.cardinality.map_or("-".to_string(), |c| c.to_string());
+        table.push_str(&format!(
+            "{:<width$}  {:<9}  {:>11}  {:>7}\n",
+            column.name,
+            column.dtype.to_string(),
+            cardinality,
+            column.missing
+        ));
+    }
+    table
+}
+
+/// Statistics of a numeric column - missing values are excluded.
+/// All values are `None` (`null` in JSON) for a column without any value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NumericSummary {
+    pub count: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    /// First quartile, median and third quartile (linear interpolation between
+    /// the closest ranks). Not available from the streaming reader.
+    #[serde(default)]
+    pub q1: Option<f64>,
+    #[serde(default)]
+    pub median: Option<f64>,
+    #[serde(default)]
+    pub q3: Option<f64>,
+}
+
+impl NumericSummary {
+    /// Summarize a column; NaN (missing) values are skipped.
+    pub fn from_values(values: &[f64]) -> Self {
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+        sorted.sort_by(f64::total_cmp);
+        let mut ret = NumericSummary::default();
+        for &value in &sorted {
+            ret.push(value);
+        }
+        // all values at hand: the compensated mean is exact to the last digit or two
+        ret.mean = mean(&sorted);
+        ret.q1 = quantile(&sorted, 0.25);
+        ret.median = quantile(&sorted, 0.5);
+        ret.q3 = quantile(&sorted, 0.75);
+        ret
+    }
+
+    /// Add one value - constant memory, so it works while streaming.
+    /// The quartiles are not updated; the mean is a running mean (Welford), which never sums
+    /// the values and so cannot overflow.
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        let mean = self.mean.unwrap_or(0.0);
+        let n = self.count as f64;
+        self.mean = Some(match value - mean {
+            delta if delta.is_finite() => mean + delta / n,
+            // values of opposite sign near f64::MAX
+            _ => mean - mean / n + value / n,
+        });
+    }
+}
+
+/// The mean of `values` (`None` without any): a compensated (Neumaier) sum, so many
+/// large counts lose no digits. Sums beyond the f64 range are taken over `value / n` instead.
+pub(crate) fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let n = values.len() as f64;
+    let sum = compensated_sum(values.iter().copied());
+    Some(match sum.is_finite() {
+        true => sum / n,
+        false => compensated_sum(values.iter().map(|v| v / n)),
+    })
+}
+
+fn compensated_sum(values: impl Iterator<Item = f64>) -> f64 {
+    let (mut sum, mut compensation) = (0.0_f64, 0.0_f64);
+    for value in values {
+        let next = sum + value;
+        compensation += if sum.abs() >= value.abs() { (sum - next) + value } else { (value - next) + sum };
+        sum = next;
+    }
+    sum + compensation
+}
+
+/// Quantile `q` of sorted values (the default method of R and numpy).
+pub(crate) fn quantile(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lower, upper) = (pos.floor() as usize, pos.ceil() as usize);
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - lower as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_table() {
+        let mut cluster = Column::new("cluster", Dtype::Factor);
+        cluster.cardinality = Some(12);
+        let mut age = Column::new("age", Dtype::Integer);
+        age.missing = 3;
+        let table = schema_table(&[cluster, age]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "column   dtype      cardinality  missing");
+        assert_eq!(lines[1], "cluster  factor              12        0");
+        assert_eq!(lines[2], "age      integer              -        3");
+    }
+
+    #[test]
+    fn test_numeric_summary() {
+        let summary = NumericSummary::from_values(&[4.0, f64::NAN, 1.0, 3.0, 2.0, 5.0]);
+        assert_eq!(summary.count, 5);
+        assert_eq!((summary.min, summary.max, summary.mean), (Some(1.0), Some(5.0), Some(3.0)));
+        assert_eq!((summary.q1, summary.median, summary.q3), (Some(2.0), Some(3.0), Some(4.0)));
+        assert_eq!(NumericSummary::from_values(&[1.0, 2.0]).median, Some(1.5));
+
+        let empty = NumericSummary::from_values(&[f64::NAN, f64::NAN]);
+        assert_eq!(empty.count, 0);
+        let json = serde_json::to_value(&empty).unwrap();
+        for key in ["min", "max", "mean", "q1", "median", "q3"] {
+            assert!(json[key].is_null(), "{key} must be null, not NaN");
+        }
+    }
+
+    #[test]
+    fn test_mean_of_large_values() {
+        // counts near i64::MAX (all exact in f64), the reference summed exactly in i128
+        let values: Vec<i64> = (0..200_000_i64).map(|i| (1_i64 << 62) + ((i * 7919) % 100_003) * 1024 - 3 * (i % 2) * (1 << 40)).collect();
+        let exact: i128 = values.iter().map(|&v| v as i128).sum();
+        let reference = exact as f64 / values.len() as f64;
+        let floats: Vec<f64> = values.iter().map(|&v| v as f64).collect();
+        let summary = NumericSummary::from_values(&floats);
+        assert_eq!(summary.count, 200_000);
+        assert!((summary.mean.unwrap() - reference).abs() <= reference * 1e-15, "{:?} vs {reference}", summary.mean);
+
+        let mut streamed = NumericSummary::default();
+        floats.iter().for_each(|&v| streamed.push(v));
+        assert!((streamed.mean.unwrap() - reference).abs() <= reference * 1e-12, "{:?} vs {reference}", streamed.mean);
+
+        // no overflow to infinity
+        assert_eq!(NumericSummary::from_values(&[f64::MAX, f64::MAX]).mean, Some(f64::MAX));
+        let mut extreme = NumericSummary::default();
+        [f64::MAX, -f64::MAX, f64::MAX].iter().for_each(|&v| extreme.push(v));
+        assert!(extreme.mean.unwrap().is_finite());
+        assert_eq!(NumericSummary::from_values(&[f64::MAX, -f64::MAX]).mean, Some(0.0));
+        // many small values next to a large one: the compensated sum keeps them
+        assert_eq!(mean(&[1e16, 1.0, 1.0, -1e16]), Some(0.5));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime};
+use crate::column::Dtype;
+use crate::SurvivalData;
+
+/// The values of one column with their type - missing values are `None`
+/// (see `SurvivalData::column_data()`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    /// A float column
+    Numeric(Vec<Option<f64>>),
+    /// An integer column - also the 0/1 indicators of a one-hot factor
+    Integer(Vec<Option<i64>>),
+    /// A factor: per row the position of its level in `levels`. The positions are not the
+    /// numeric codes of the factors file, which may start at 1 or have gaps.
+    Factor { codes: Vec<Option<u32>>, levels: Vec<String> },
+    Bool(Vec<Option<bool>>),
+    /// A date column (midnight for dates without a time)
+    Date(Vec<Option<NaiveDateTime>>),
+    /// A free text or JSON column, as written
+    Text(Vec<Option<String>>),
+}
+
+impl ColumnData {
+    /// The number of rows.
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Numeric(values) => values.len(),
+            ColumnData::Integer(values) => values.len(),
+            ColumnData::Factor { codes, .. } => codes.len(),
+            ColumnData::Bool(values) => values.len(),
+            ColumnData::Date(values) => values.len(),
+            ColumnData::Text(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SurvivalData {
+    /// The values of column `name`, typed by its `Dtype` - `None` for an unknown column.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::{ColumnData, SurvivalData};
+    /// let tsv = "cluster\tnCount\nT\t120\nB\tNA\n";
+    /// let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+    /// match data.column_data("nCount") {
+    ///     Some(ColumnData::Integer(counts)) => assert_eq!(counts, vec![Some(120), None]),
+    ///     other => panic!("{other:?}"),
+    /// }
+    /// ```
+    pub fn column_data(&self, name: &str) -> Option<ColumnData> {
+        let index = self.column_index(name)?;
+        let dtype = self.column_at(index)?.dtype;
+        let values = self.numeric_data.column(index);
+        let present = values.iter().map(|v| (!v.is_nan()).then_some(*v));
+        Some(match (dtype, self.factors.get(name)) {
+            (Dtype::Factor, Some(factor)) => {
+                let levels = factor.get_levels().to_vec();
+                let position: HashMap<&str, u32> = levels.iter().enumerate().map(|(i, l)| (l.as_str(), i as u32)).collect();
+                let codes = values.iter().map(|&v| factor.level_at(v).and_then(|l| position.get(l).copied())).collect();
+                ColumnData::Factor { codes, levels }
+            }
+            (Dtype::FreeText | Dtype::Json, Some(factor)) => {
+                ColumnData::Text(values.iter().map(|&v| factor.level_at(v).map(str::to_string)).collect())
+            }
+            (Dtype::Integer, _) => ColumnData::Integer(present.map(|v| v.map(|v| v as i64)).collect()),
+            (Dtype::Boolean, _) => ColumnData::Bool(present.map(|v| v.map(|v| v != 0.0)).collect()),
+            (Dtype::Date, _) => ColumnData::Date(
+                present
+                    .map(|v| v.and_then(|v| DateTime::from_timestamp(v as i64, 0)).map(|d| d.naive_utc()))
+                    .collect(),
+            ),
+            _ => ColumnData::Numeric(present.collect()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_column_data() -> crate::error::Result<()> {
+        let tsv = "cell\tgroup\tdose\tn\tflag\tday\nA\tx\t1.5\t3\tyes\t2023-05-14\nB\tNA\tNA\tNA\tno\tNA\nC\ty\t2\t1\tNA\t2023-05-15\n";
+        let options = ParseOptions { detect_dates: true, max_levels: Some(2), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+
+        assert_eq!(data.column_data("cell"), Some(ColumnData::Text(vec![Some("A".into()), Some("B".into()), Some("C".into())])));
+        assert_eq!(
+            data.column_data("group"),
+            Some(ColumnData::Factor { codes: vec![Some(0), None, Some(1)], levels: vec!["x".into(), "y".into()] })
+        );
+        assert_eq!(data.column_data("dose"), Some(ColumnData::Numeric(vec![Some(1.5), None, Some(2.0)])));
+        assert_eq!(data.column_data("n"), Some(ColumnData::Integer(vec![Some(3), None, Some(1)])));
+        assert_eq!(data.column_data("flag"), Some(ColumnData::Bool(vec![Some(true), Some(false), None])));
+        let Some(ColumnData::Date(days)) = data.column_data("day") else { panic!("no date column") };
+        assert_eq!(days[0].unwrap().to_string(), "2023-05-14 00:00:00");
+        assert_eq!((days[1], days.len()), (None, 3));
+        assert!(data.column_data("missing").is_none());
+
+        // positions, not codes: one-based codes still index the levels from 0
+        let options = ParseOptions { one_based_codes: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let Some(ColumnData::Factor { codes, .. }) = data.column_data("group") else { panic!("no factor") };
+        assert_eq!(codes, vec![Some(0), None, Some(1)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use crate::error::{bail, DataTableError, Result};
+
+/// The level `Compression::Zstd` uses unless it is told otherwise - zstd's own default.
+pub const DEFAULT_ZSTD_LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+/// How output files are written - see `JsonLayout::write_compressed()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain text
+    #[default]
+    None,
+    /// A zstd stream at the level (1 to 22, higher is smaller and slower) - `from_json()`
+    /// and the table readers decompress it transparently
+    Zstd(i32),
+}
+
+impl Compression {
+    /// `none` or `zstd` (at `DEFAULT_ZSTD_LEVEL`).
+    pub fn parse(arg: &str) -> Result<Self> {
+        Ok(match arg.to_ascii_lowercase().as_str() {
+            "none" => Compression::None,
+            "zstd" | "zst" => Compression::Zstd(DEFAULT_ZSTD_LEVEL),
+            _ => bail!(InvalidArgument, "Invalid compression '{}' - expected none or zstd", arg),
+        })
+    }
+
+    /// Files ending in `.zst` are zstd compressed, any other file is plain text.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match has_zst_extension(path) {
+            true => Compression::Zstd(DEFAULT_ZSTD_LEVEL),
+            false => Compression::None,
+        }
+    }
+
+    /// The same compression at `level` - an error for `None` and for levels zstd does not have.
+    pub fn with_level(self, level: i32) -> Result<Self> {
+        let max = *zstd::compression_level_range().end();
+        match self {
+            Compression::None => bail!(InvalidArgument, "A compression level needs zstd compression"),
+            Compression::Zstd(_) if !(1..=max).contains(&level) => {
+                bail!(InvalidArgument, "Invalid zstd compression level {} - expected 1 to {}", level, max)
+            }
+            Compression::Zstd(_) => Ok(Compression::Zstd(level)),
+        }
+    }
+
+    /// Create (or replace) the file `path`, compressing what is written to it.
+    /// `OutputFile::finish()` must be called to complete the file.
+    pub(crate) fn create<P: AsRef<Path>>(self, path: P) -> Result<OutputFile> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| DataTableError::io(path, e))?;
+        let writer = BufWriter::new(file);
+        Ok(match self {
+            Compression::None => OutputFile::Plain(writer),
+            Compression::Zstd(level) => OutputFile::Zstd(zstd::Encoder::new(writer, level).map_err(|e| DataTableError::io(path, e))?),
+        })
+    }
+}
+
+/// Does the file name end in `.zst`?
+pub(crate) fn has_zst_extension<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("zst"))
+}
+
+/// A new output file - plain or compressed (see `Compression::create()`).
+pub(crate) enum OutputFile {
+    Plain(BufWriter<File>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputFile {
+    /// Write the end of the compressed stream and flush everything to the file.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(mut writer) => writer.flush(),
+            OutputFile::Zstd(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(writer) => writer.write(buf),
+            OutputFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(writer) => writer.flush(),
+            OutputFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{is_zstd_magic, open_input};
+    use std::io::Read;
+
+    #[test]
+    fn test_compression() -> Result<()> {
+        assert_eq!(Compression::parse("ZSTD")?, Compression::Zstd(DEFAULT_ZSTD_LEVEL));
+        assert_eq!(Compression::parse("none")?, Compression::None);
+        assert!(Compression::parse("gzip").is_err());
+        assert_eq!(Compression::from_path("factors.json.zst"), Compression::Zstd(DEFAULT_ZSTD_LEVEL));
+        assert_eq!(Compression::from_path("factors.json"), Compression::None);
+        assert_eq!(Compression::parse("zstd")?.with_level(19)?, Compression::Zstd(19));
+        assert_eq!(Compression::parse("zstd")?.with_level(23).unwrap_err().to_string(), "Invalid zstd compression level 23 - expected 1 to 22");
+        assert!(Compression::None.with_level(3).is_err());
+
+        let dir = tempfile::tempdir()?;
+        let text = "column\tlevel\n".repeat(100);
+        for (name, compression) in [("plain.tsv", Compression::None), ("packed.tsv", Compression::Zstd(19))] {
+            let path = dir.path().join(name);
+            let mut file = compression.create(&path)?;
+            file.write_all(text.as_bytes())?;
+            file.finish()?;
+            let bytes = std::fs::read(&path)?;
+            assert_eq!(is_zstd_magic(&bytes), compression != Compression::None);
+            let mut read = String::new();
+            open_input(&path)?.read_to_string(&mut read)?;
+            assert_eq!(read, text, "{name} round trips");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+use crate::column::mean;
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+/// Pairwise Pearson correlations of numeric columns (see `SurvivalData::correlation_matrix()`).
+/// An entry is `None` (`null` in JSON) if a column of the pair has fewer than two values in
+/// the rows both have, or no variance in them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CorrMatrix {
+    pub columns: Vec<String>,
+    /// `values[i][j]` is the correlation of `columns[i]` and `columns[j]` - symmetric
+    pub values: Vec<Vec<Option<f64>>>,
+    /// The rows both columns have a value in
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl CorrMatrix {
+    /// The correlation of columns `a` and `b` - `None` for an unknown column or an undefined entry.
+    pub fn get(&self, a: &str, b: &str) -> Option<f64> {
+        let i = self.columns.iter().position(|c| c == a)?;
+        let j = self.columns.iter().position(|c| c == b)?;
+        self.values[i][j]
+    }
+}
+
+/// Pearson correlation over the rows where both values are present.
+fn pearson(x: &[f64], y: &[f64]) -> (Option<f64>, usize) {
+    let pairs: Vec<(f64, f64)> = x.iter().zip(y).filter(|(a, b)| !a.is_nan() && !b.is_nan()).map(|(&a, &b)| (a, b)).collect();
+    let n = pairs.len();
+    if n < 2 {
+        return (None, n);
+    }
+    let (xs, ys): (Vec<f64>, Vec<f64>) = pairs.iter().copied().unzip();
+    let (mean_x, mean_y) = (mean(&xs).unwrap_or(0.0), mean(&ys).unwrap_or(0.0));
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (a, b) in pairs {
+        sxy += (a - mean_x) * (b - mean_y);
+        sxx += (a - mean_x) * (a - mean_x);
+        syy += (b - mean_y) * (b - mean_y);
+    }
+    if sxx == 0.0 || syy == 0.0 {
+        return (None, n);
+    }
+    // rounding can push a perfect correlation just past 1
+    (Some((sxy / (sxx * syy).sqrt()).clamp(-1.0, 1.0)), n)
+}
+
+impl SurvivalData {
+    /// Pearson correlation of every pair of the numeric columns `columns` - all numeric columns
+    /// in table order for `None`. Missing values are handled pairwise: each pair uses the rows
+    /// where both columns have a value.
+    pub fn correlation_matrix(&self, columns: Option<&[&str]>) -> Result<CorrMatrix> {
+        let names: Vec<String> = match columns {
+            Some(columns) => {
+                for &name in columns {
+                    match self.column(name) {
+                        None => bail!(Column, "Column '{}' not found", name),
+                        Some(c) if !c.dtype.is_numeric() || self.factors.contains_key(name) => {
+                            bail!(Column, "Correlation column '{}' must be numeric, but it is {:?}", name, c.dtype)
+                        }
+                        Some(_) => {}
+                    }
+                }
+                columns.iter().map(|name| name.to_string()).collect()
+            }
+            None => self
+                .columns
+                .iter()
+                .filter(|c| c.dtype.is_numeric() && !self.factors.contains_key(&c.name))
+                .map(|c| c.name.clone())
+                .collect(),
+        };
+        let data: Vec<Vec<f64>> = names.iter().map(|name| self.as_vec_f64(name)).collect();
+        let n = names.len();
+        let mut values = vec![vec![None; n]; n];
+        let mut counts = vec![vec![0; n]; n];
+        for i in 0..n {
+            for j in i..n {
+                let (r, count) = pearson(&data[i], &data[j]);
+                values[i][j] = r;
+                values[j][i] = r;
+                counts[i][j] = count;
+                counts[j][i] = count;
+            }
+        }
+        Ok(CorrMatrix { columns: names, values, counts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_correlation_matrix() -> Result<()> {
+        let tsv = "cell\tnCount\tnFeature\tflat\tdrop\nA\t1\t2\t5\t4\nB\t2\t4\t5\t3\nC\t3\t6\t5\tNA\nD\tNA\t8\t5\t1\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let matrix = data.correlation_matrix(None)?;
+        assert_eq!(matrix.columns, vec!["nCount", "nFeature", "flat", "drop"]);
+        assert!((matrix.get("nCount", "nFeature").unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(matrix.counts[0][1], 3, "the row without nCount is left out");
+        assert!((matrix.get("nCount", "drop").unwrap() + 1.0).abs() < 1e-12);
+        assert_eq!(matrix.counts[0][3], 2);
+        assert_eq!(matrix.get("flat", "nCount"), None, "no variance");
+        assert_eq!(matrix.get("flat", "flat"), None);
+        assert_eq!(matrix.get("drop", "drop"), Some(1.0));
+        assert_eq!(matrix.values[1][3], matrix.values[3][1]);
+
+        let json = serde_json::to_value(&matrix).unwrap();
+        assert_eq!(json["values"][2][0], serde_json::Value::Null);
+
+        let matrix = data.correlation_matrix(Some(&["nFeature", "drop"]))?;
+        assert_eq!(matrix.columns, vec!["nFeature", "drop"]);
+        assert!(matrix.get("nFeature", "drop").unwrap() < -0.9);
+
+        let err = data.correlation_matrix(Some(&["cell"])).unwrap_err();
+        assert!(err.to_string().contains("must be numeric"), "{err}");
+        assert!(data.correlation_matrix(Some(&["missing"])).is_err());
+        Ok(())
+    }
+}
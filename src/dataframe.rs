@@ -0,0 +1,77 @@
+use polars::prelude::{Categories, Column as PlColumn, DataFrame, DataType, NamedFrom, PolarsResult, Series};
+use crate::column::Dtype;
+use crate::SurvivalData;
+
+impl SurvivalData {
+    /// Convert the table into a polars `DataFrame` - in header order.
+    ///
+    /// Integer columns become `Int64`, float columns `Float64`, booleans `Boolean`
+    /// and factor / free text columns `Categorical` with their labels. Date columns stay
+    /// `Float64` seconds since 1970-01-01.
+    /// Missing values are nulls.
+    pub fn to_polars(&self) -> PolarsResult<DataFrame> {
+        let columns = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                let values = self.numeric_data.column(id);
+                let dtype = self.columns.get(id).map_or(Dtype::Float, |c| c.dtype);
+                let series = match (dtype, self.factors.get(name)) {
+                    (Dtype::Factor | Dtype::FreeText | Dtype::Json, Some(factor)) => {
+                        let labels: Vec<Option<String>> = values
+                            .iter()
+                            .map(|v| (!v.is_nan()).then(|| factor.get_string(*v)))
+                            .collect();
+                        Series::new(name.into(), labels)
+                            .cast(&DataType::from_categories(Categories::global()))?
+                    }
+                    (Dtype::Integer, _) => {
+                        let ints: Vec<Option<i64>> =
+                            values.iter().map(|v| (!v.is_nan()).then_some(*v as i64)).collect();
+                        Series::new(name.into(), ints)
+                    }
+                    (Dtype::Boolean, _) => {
+                        let bools: Vec<Option<bool>> =
+                            values.iter().map(|v| (!v.is_nan()).then_some(*v != 0.0)).collect();
+                        Series::new(name.into(), bools)
+                    }
+                    _ => {
+                        let floats: Vec<Option<f64>> =
+                            values.iter().map(|v| (!v.is_nan()).then_some(*v)).collect();
+                        Series::new(name.into(), floats)
+                    }
+                };
+                Ok(PlColumn::from(series))
+            })
+            .collect::<PolarsResult<Vec<_>>>()?;
+        DataFrame::new(self.n_rows(), columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_to_polars() {
+        let tsv = "cluster\tn\tscore\tdoublet\nB cell\t3\t0.5\tTRUE\nT cell\tNA\t1.5\tFALSE\nNA\t5\tNA\tNA\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+        let df = data.to_polars().unwrap();
+
+        let names: Vec<&str> = df.get_column_names().iter().map(|n| n.as_str()).collect();
+        assert_eq!(names, vec!["cluster", "n", "score", "doublet"], "header order is kept");
+        let dtypes: Vec<DataType> = df.dtypes();
+        assert!(matches!(dtypes[0], DataType::Categorical(_, _)));
+        assert_eq!(dtypes[1..], [DataType::Int64, DataType::Float64, DataType::Boolean]);
+        for column in df.columns() {
+            assert_eq!(column.null_count(), 1, "missing values are nulls in '{}'", column.name());
+        }
+        let n = df.column("n").unwrap().i64().unwrap();
+        assert_eq!(n.get(0), Some(3));
+        let cluster = df.column("cluster").unwrap().cast(&DataType::String).unwrap();
+        assert_eq!(cluster.str().unwrap().get(1), Some("T cell"));
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::fmt;
+use serde::Serialize;
+use crate::column::Dtype;
+use crate::factor_map::FactorMap;
+
+/// One difference between two versions of a factors file (see `FactorMap::diff()`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    /// A column only the new map has
+    ColumnAdded { column: String },
+    /// A column only the old map has
+    ColumnRemoved { column: String },
+    /// The column has another type
+    DtypeChanged { column: String, old: Dtype, new: Dtype },
+    /// A factor only the new map has (the column itself did not change)
+    FactorAdded { column: String, levels: Vec<String> },
+    /// A factor only the old map has (the column itself did not change)
+    FactorRemoved { column: String },
+    /// Levels only the new factor has
+    LevelsAdded { column: String, levels: Vec<String> },
+    /// Levels only the old factor has
+    LevelsRemoved { column: String, levels: Vec<String> },
+    /// The levels both factors have are in another order
+    LevelsReordered { column: String, old: Vec<String>, new: Vec<String> },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::ColumnAdded { column } => write!(f, "column '{}' was added", column),
+            Change::ColumnRemoved { column } => write!(f, "column '{}' was removed", column),
+            Change::DtypeChanged { column, old, new } => write!(f, "column '{}' changed from {} to {}", column, old, new),
+            Change::FactorAdded { column, levels } => {
+                write!(f, "factor '{}' was added with level(s): {}", column, levels.join(", "))
+            }
+            Change::FactorRemoved { column } => write!(f, "factor '{}' was removed", column),
+            Change::LevelsAdded { column, levels } => {
+                write!(f, "factor '{}' has new level(s): {}", column, levels.join(", "))
+            }
+            Change::LevelsRemoved { column, levels } => {
+                write!(f, "factor '{}' lost level(s): {}", column, levels.join(", "))
+            }
+            Change::LevelsReordered { column, old, new } => {
+                write!(f, "factor '{}' has reordered levels: {} -> {}", column, old.join(", "), new.join(", "))
+            }
+        }
+    }
+}
+
+/// All changes from one factors file to another - empty if they define the same table.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FactorDiff {
+    pub changes: Vec<Change>,
+}
+
+impl FactorDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+/// One change per line.
+impl fmt::Display for FactorDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+impl FactorMap {
+    /// What changed from this map to `other`: added and removed columns, type changes and
+    /// per factor the added, removed and reordered levels.
+    ///
+    /// Columns are only compared if both maps list them (older files hold just the factors).
+    /// A factor that comes or goes with its column or a type change is not reported again.
+    pub fn diff(&self, other: &FactorMap) -> FactorDiff {
+        let mut changes = Vec::new();
+        // columns with a column level change
+        let mut explained: HashSet<&str> = HashSet::new();
+        if !self.columns.is_empty() && !other.columns.is_empty() {
+            for old in &self.columns {
+                match other.column(&old.name) {
+                    None => changes.push(Change::ColumnRemoved { column: old.name.clone() }),
+                    Some(new) if new.dtype != old.dtype => changes.push(Change::DtypeChanged {
+                        column: old.name.clone(),
+                        old: old.dtype,
+                        new: new.dtype,
+                    }),
+                    Some(_) => continue,
+                }
+                explained.insert(&old.name);
+            }
+            for new in &other.columns {
+                if self.column(&new.name).is_none() {
+                    changes.push(Change::ColumnAdded { column: new.name.clone() });
+                    explained.insert(&new.name);
+                }
+            }
+        }
+
+        for old in &self.factors {
+            let Some(new) = other.get(&old.column) else {
+                if !explained.contains(old.column.as_str()) {
+                    changes.push(Change::FactorRemoved { column: old.column.clone() });
+                }
+                continue;
+            };
+            let column = &old.column;
+            let added: Vec<String> = new.levels.iter().filter(|l| !old.levels.contains(l)).cloned().collect();
+            let removed: Vec<String> = old.levels.iter().filter(|l| !new.levels.contains(l)).cloned().collect();
+            let old_order: Vec<String> = old.levels.iter().filter(|l| new.levels.contains(l)).cloned().collect();
+            let new_order: Vec<String> = new.levels.iter().filter(|l| old.levels.contains(l)).cloned().collect();
+            if !added.is_empty() {
+                changes.push(Change::LevelsAdded { column: column.clone(), levels: added });
+            }
+            if !removed.is_empty() {
+                changes.push(Change::LevelsRemoved { column: column.clone(), levels: removed });
+            }
+            if old_order != new_order {
+                changes.push(Change::LevelsReordered { column: column.clone(), old: old_order, new: new_order });
+            }
+        }
+        for new in &other.factors {
+            if self.get(&new.column).is_none() && !explained.contains(new.column.as_str()) {
+                changes.push(Change::FactorAdded { column: new.column.clone(), levels: new.levels.clone() });
+            }
+        }
+        FactorDiff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use crate::SurvivalData;
+
+    fn factors(tsv: &str) -> FactorMap {
+        SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap().compute_factors()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = factors("cell\tcluster\tgrade\tage\nA\tx\tlow\t40\nB\ty\thigh\t52\nC\tz\tlow\t60\n");
+        assert!(old.diff(&old).is_empty());
+
+        let new = factors("cluster\tgrade\tage\tbatch\ny\thigh\tNA\tb1\nw\tlow\told\tb1\nx\tlow\t3\tb2\n");
+        let diff = old.diff(&new);
+        assert_eq!(diff.changes, vec![
+            Change::ColumnRemoved { column: "cell".into() },
+            Change::DtypeChanged { column: "age".into(), old: Dtype::Integer, new: Dtype::Factor },
+            Change::ColumnAdded { column: "batch".into() },
+            Change::LevelsAdded { column: "cluster".into(), levels: vec!["w".into()] },
+            Change::LevelsRemoved { column: "cluster".into(), levels: vec!["z".into()] },
+            Change::LevelsReordered { column: "cluster".into(), old: vec!["x".into(), "y".into()], new: vec!["y".into(), "x".into()] },
+            Change::LevelsReordered { column: "grade".into(), old: vec!["low".into(), "high".into()], new: vec!["high".into(), "low".into()] },
+        ]);
+        assert_eq!(diff.changes[5].to_string(), "factor 'cluster' has reordered levels: x, y -> y, x");
+        assert_eq!(diff.to_string().lines().count(), 7);
+
+        // without the columns only the factors are compared
+        let legacy = FactorMap::new(old.factors.clone());
+        let mut fewer = FactorMap::new(new.factors.clone());
+        fewer.factors.retain(|f| f.column != "grade");
+        let diff = legacy.diff(&fewer);
+        assert!(diff.changes.contains(&Change::FactorRemoved { column: "grade".into() }), "{diff}");
+        assert!(diff.changes.iter().any(|c| matches!(c, Change::FactorAdded { column, .. } if column == "age")), "{diff}");
+    }
+}
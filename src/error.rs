@@ -0,0 +1,132 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Everything that can go wrong in this library.
+///
+/// The messages are meant for end users; match on the variant to react to a specific problem.
+#[derive(Debug, Error)]
+pub enum DataTableError {
+    /// An input, factors or labels file does not exist
+    #[error("File not found: {0:?}")]
+    FileNotFound(PathBuf),
+    /// A file exists but could not be read or written
+    #[error("Failed to access {path:?}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    /// Reading or writing a stream failed
+    #[error(transparent)]
+    Stream(#[from] io::Error),
+    /// The input has no header line (an empty or blank file)
+    #[error("input is empty")]
+    EmptyInput,
+    /// A data row has a different number of fields than the header
+    #[error("line {line} has {found} fields, expected {expected}")]
+    InconsistentRow { line: u64, found: usize, expected: usize },
+    /// Columns named in the parser settings (`categorical`, factor specs, dtypes, include/exclude) are not in the header
+    #[error("Unknown column(s) in the parser settings: {message}")]
+    UnknownColumns { columns: Vec<String>, message: String },
+    /// Columns the input must have (`ParseOptions::required_columns`) are not in the header
+    #[error("Missing required column(s): {message}")]
+    MissingColumns { columns: Vec<String>, message: String },
+    /// A column a method needs does not exist or has the wrong type
+    #[error("{0}")]
+    Column(String),
+    /// The delimiter is invalid or cannot be detected
+    #[error("{0}")]
+    BadDelimiter(String),
+    /// A factors file has an unsupported or invalid `schema_version`
+    #[error("{0}")]
+    Schema(String),
+    /// A value does not fit the table setup (declared levels, sampled types, ...)
+    #[error("{0}")]
+    InvalidData(String),
+    /// A parser setting or command line value is invalid
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// A malformed CSV/TSV file
+    #[error("Invalid table: {0}")]
+    Csv(csv::Error),
+    /// A factors or labels file is no valid JSON of the expected layout
+    #[error("Invalid JSON{}: {source}", path.as_ref().map(|p| format!(" in {:?}", p)).unwrap_or_default())]
+    Json { path: Option<PathBuf>, source: serde_json::Error },
+    /// `source` happened in the file at `path` - one of several read together
+    #[error("{path:?}: {source}")]
+    InFile { path: PathBuf, source: Box<DataTableError> },
+    /// Writing a Parquet file failed
+    #[cfg(feature = "parquet")]
+    #[error("Parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    /// Reading an `.h5ad` file failed
+    #[cfg(feature = "hdf5")]
+    #[error("HDF5: {0}")]
+    Hdf5(#[from] hdf5::Error),
+    /// Downloading a table failed (feature `net`)
+    #[cfg(feature = "net")]
+    #[error("HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// The result type of this library.
+pub type Result<T, E = DataTableError> = std::result::Result<T, E>;
+
+impl DataTableError {
+    /// An io error of the file at `path` - a missing file becomes `FileNotFound`.
+    pub fn io<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        if source.kind() == io::ErrorKind::NotFound {
+            DataTableError::FileNotFound(path)
+        } else {
+            DataTableError::Io { path, source }
+        }
+    }
+}
+
+/// Rows with the wrong number of fields get their own variant.
+impl From<csv::Error> for DataTableError {
+    fn from(err: csv::Error) -> Self {
+        if let csv::ErrorKind::UnequalLengths { pos, expected_len, len } = err.kind() {
+            return DataTableError::InconsistentRow {
+                line: pos.as_ref().map_or(0, |p| p.line()),
+                found: *len as usize,
+                expected: *expected_len as usize,
+            };
+        }
+        if let csv::ErrorKind::Io(_) = err.kind() {
+            match err.into_kind() {
+                csv::ErrorKind::Io(err) => return DataTableError::Stream(err),
+                _ => unreachable!("checked above"),
+            }
+        }
+        DataTableError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for DataTableError {
+    fn from(source: serde_json::Error) -> Self {
+        DataTableError::Json { path: None, source }
+    }
+}
+
+/// `return Err(DataTableError::<variant>(format!(...)))` - like `anyhow::bail!`.
+macro_rules! bail {
+    ($variant:ident, $($arg:tt)*) => {
+        return Err($crate::error::DataTableError::$variant(format!($($arg)*)))
+    };
+}
+pub(crate) use bail;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_errors() {
+        let mut rdr = csv::ReaderBuilder::new().from_reader("a,b\n1,2\n3\n".as_bytes());
+        let err: DataTableError = rdr.records().find_map(|r| r.err()).unwrap().into();
+        assert!(matches!(err, DataTableError::InconsistentRow { line: 3, found: 1, expected: 2 }), "{err:?}");
+        assert_eq!(err.to_string(), "line 3 has 1 fields, expected 2");
+
+        let missing = DataTableError::io("nope.tsv", io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(missing, DataTableError::FileNotFound(_)));
+    }
+}
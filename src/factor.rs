@@ -1,19 +1,162 @@
-use std::collections::{HashMap, HashSet};
-use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::error::{bail, Result};
 use serde::{Serialize, Deserialize};
-use serde_json;
 use std::fmt;
 use ordered_float::OrderedFloat;
 use ndarray::Array2;
+use crate::options::split_column_arg;
 
 /// Only save labels in JSON
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FactorJson {
     pub column: String,
     pub levels: Vec<String>,
     pub numeric: Option<Vec<f64>>,
     pub matching:Option<Vec<String>>, 
     pub one_hot: bool, // NEW
+    /// The level order is meaningful (`low < medium < high`)
+    #[serde(default)]
+    pub ordered: bool,
+    /// Display labels aligned with `levels` - the levels themselves keep matching the data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    /// Number of rows per level (aligned with `levels`) - counted while parsing, ignored when read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counts: Option<Vec<usize>>,
+    /// Number of rows without a value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing: Option<usize>,
+    /// Original level -> the level it was merged into (see `Factor::merge_rare()`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged: Option<BTreeMap<String, String>>,
+    /// Hex colors aligned with `levels` (see `FactorMap::apply_palette()`) - empty for a level without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<Vec<String>>,
+    /// Whether the factor was declared or inferred - a factors file without it declares its factors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<FactorSource>,
+    /// Ascending edges of a binned numeric column, one more than there are levels (see `SurvivalData::bin_quantiles()`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bins: Option<Vec<f64>>,
+}
+
+/// Why a column is a factor (`source` in the factors file).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FactorSource {
+    /// Named by the user - `--categorical`, a `--spec` or factors file, a conversion like `as_factor()`
+    Declared,
+    /// Text values the type inference made a factor
+    #[default]
+    Inferred,
+}
+
+/// User supplied setup of one factor column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FactorSpec {
+    /// The levels are ordered (R's `ordered = TRUE`)
+    pub ordered: bool,
+    /// Explicit level order - values outside of it are an error unless `other` is set
+    pub levels: Vec<String>,
+    /// Trailing level collecting all values not listed in `levels`
+    pub other: Option<String>,
+    /// Baseline level moved to index 0 (R's `relevel()`)
+    pub reference: Option<String>,
+    /// Levels with fewer rows are merged into `rare_label` (see `Factor::merge_rare()`)
+    pub merge_rare: Option<usize>,
+    /// Only this many of the most frequent levels are kept, the others go to `rare_label` (see `Factor::top_k()`)
+    pub top_k: Option<usize>,
+    /// The order of the levels `top_k` keeps
+    pub top_k_order: TopKOrder,
+    /// The level collecting the rare levels (default: `DEFAULT_RARE_LABEL`)
+    pub rare_label: Option<String>,
+}
+
+/// The level order after `Factor::top_k()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TopKOrder {
+    /// The most frequent level first - ties keep their order
+    #[default]
+    Frequency,
+    /// The kept levels stay in the order they had
+    Original,
+}
+
+impl TopKOrder {
+    /// `frequency` or `original`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        match arg.to_ascii_lowercase().as_str() {
+            "frequency" => Ok(TopKOrder::Frequency),
+            "original" => Ok(TopKOrder::Original),
+            _ => bail!(InvalidArgument, "Unknown level order '{}' (frequency or original)", arg),
+        }
+    }
+}
+
+/// The level rare levels are merged into unless configured otherwise.
+pub const DEFAULT_RARE_LABEL: &str = "Other";
+
+impl FactorSpec {
+
+    /// An ordered factor with exactly these levels.
+    pub fn ordered(levels: Vec<String>) -> Self {
+        FactorSpec {
+            ordered: true,
+            levels,
+            ..Default::default()
+        }
+    }
+
+    /// A factor with this baseline level (see `Factor::relevel()`).
+    pub fn reference(level: &str) -> Self {
+        FactorSpec {
+            reference: Some(level.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Parse a command line definition like `stage:low,medium,high`.
+    pub fn parse_ordered(arg: &str) -> Result<(String, Self)> {
+        let (column, levels) = split_column_arg(arg)?;
+        let levels: Vec<String> = levels
+            .split(',')
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        Ok((column.to_string(), Self::ordered(levels)))
+    }
+
+    /// Merge the levels with fewer than `min_count` rows (see `Factor::merge_rare()`).
+    pub fn merge_rare(min_count: usize) -> Self {
+        FactorSpec {
+            merge_rare: Some(min_count),
+            ..Default::default()
+        }
+    }
+
+    /// Keep the `k` most frequent levels only (see `Factor::top_k()`).
+    pub fn top_k(k: usize) -> Self {
+        FactorSpec {
+            top_k: Some(k),
+            ..Default::default()
+        }
+    }
+
+    /// Map a raw value onto the declared levels.
+    /// Missing values pass through, unknown values go to `other` or are an error.
+    pub fn resolve<'a>(&'a self, column: &str, value: &'a str) -> Result<&'a str> {
+        if self.levels.is_empty() || self.levels.iter().any(|l| l == value) {
+            return Ok(value);
+        }
+        match &self.other {
+            Some(other) => Ok(other),
+            None => bail!(
+                InvalidData,
+                "Value '{}' in column '{}' is not one of the declared levels {:?}",
+                value, column, self.levels
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +167,16 @@ pub struct Factor {
     index_to_level: HashMap<OrderedFloat<f64>, String>, // <-- instead of a indices vector
     matching:Option<Vec<String>>, // this could match to multiple column names. Like SNP or something
     pub one_hot: bool, // NEW
+    pub ordered: bool,
+    labels: HashMap<String, String>, // display label by level - unmapped levels show as they are
+    colors: HashMap<String, String>, // hex color by level, from the factors file
+    counts: HashMap<String, usize>, // rows per level, filled by count()
+    missing: usize, // rows without a value, filled by count()
+    merged: BTreeMap<String, String>, // merged away level -> the level holding its rows now
+    first_code: f64, // code of the first level - 0, or 1 for R style codes (see starting_at())
+    bins: Vec<f64>, // bin edges of a binned numeric column (see bin_edges())
+    pub source: FactorSource, // declared by the user or inferred from the values
+    pinned: bool, // codes fixed by a codes file - never renumbered (see pin_codes())
 }
 
 
@@ -31,6 +184,7 @@ impl fmt::Display for Factor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Factor '{}':", self.column_name)?;
         writeln!(f, "  One-hot: {}", self.one_hot)?;
+        writeln!(f, "  Ordered: {}", self.ordered)?;
         writeln!(f, "  Levels: {:?}", self.levels)?;
         writeln!(f, "  Matching: {:?}", self.matching)?;
         writeln!(f, "  All columns: {:?}", self.all_column_names())
@@ -49,7 +203,42 @@ impl Factor {
             index_to_level: HashMap::new(),
             matching: None,
             one_hot,
+            ordered: false,
+            labels: HashMap::new(),
+            colors: HashMap::new(),
+            counts: HashMap::new(),
+            missing: 0,
+            merged: BTreeMap::new(),
+            first_code: 0.0,
+            bins: Vec::new(),
+            source: FactorSource::Inferred,
+            pinned: false,
+        }
+    }
+
+    /// Mark the factor as declared by the user rather than inferred (see `FactorSource`).
+    pub fn declared(mut self) -> Self {
+        self.source = FactorSource::Declared;
+        self
+    }
+
+    /// Number the levels from `first_code` on (`1.0` for R's 1-based factor codes)
+    /// instead of 0. Existing levels are renumbered.
+    pub fn starting_at(mut self, first_code: f64) -> Self {
+        self.first_code = first_code;
+        self.renumber();
+        self
+    }
+
+    /// Create a Factor following a user supplied `FactorSpec`:
+    /// the declared levels are registered up front - in exactly that order.
+    pub fn from_spec(column_name: &str, spec: &FactorSpec) -> Self {
+        let mut factor = Self::new(column_name, false).declared();
+        factor.ordered = spec.ordered;
+        for level in &spec.levels {
+            factor.level_to_index(level);
         }
+        factor
     }
 
     /*
@@ -109,12 +298,19 @@ impl Factor {
     }
 
 
-    /// Subset factor with **extreme verbosity**.
+    /// The factor restricted to the levels observed in column `col_id` of `data`.
+    /// The levels keep their order and codes, so a subset never reorders the factors JSON.
     pub fn subset(&self, data: &Array2<f64>, col_id: usize) -> Self {
         //println!("➡️ Starting subset for factor '{}', column id {}", self.column_name, col_id);
 
         let mut new_factor = Self::new(&self.column_name, self.one_hot);
         new_factor.matching = self.matching.clone();
+        new_factor.ordered = self.ordered;
+        new_factor.first_code = self.first_code;
+        new_factor.labels = self.labels.clone();
+        new_factor.colors = self.colors.clone();
+        new_factor.merged = self.merged.clone();
+        new_factor.pinned = self.pinned;
 
         /*println!("    Original factor levels: {:?}", self.levels);
         println!("    Original level_to_index: {:?}", self.level_to_index);
@@ -125,6 +321,7 @@ impl Factor {
 
             if val.is_nan() {
                 //println!("    ❌ Skipping NaN value");
+                new_factor.missing += 1;
                 continue;
             }
 
@@ -139,13 +336,19 @@ impl Factor {
                 ),
             };
 
+            *new_factor.counts.entry(lvl.clone()).or_default() += 1;
             if !new_factor.index_to_level.contains_key(&OrderedFloat(val)) {
                 //println!("    ➕ Adding new level '{}' with numeric {}", lvl, val);
-                new_factor.levels.push(lvl.clone());
                 new_factor.level_to_index.insert(lvl.clone(), val);
                 new_factor.index_to_level.insert(OrderedFloat(val), lvl.clone());
             } 
         }
+        new_factor.levels = self
+            .levels
+            .iter()
+            .filter(|l| new_factor.level_to_index.contains_key(*l))
+            .cloned()
+            .collect();
 
         /*println!("🏁 Subset complete!");
         println!("    New factor levels: {:?}", new_factor.levels);
@@ -195,7 +398,8 @@ impl Factor {
     /// Special cases:
     /// - Empty strings or "NA" → return NaN.
     pub fn level_to_index(&mut self, value: &str) -> f64 {
-        let trimmed = value.trim();
+        let trimmed = self.level_of(value.trim()).to_string();
+        let trimmed = trimmed.as_str();
 
         // Handle missing values
         if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("NA") {
@@ -205,7 +409,7 @@ impl Factor {
         // For one-hot encoding, presence means 1.0 — but we still must register the level
         if self.one_hot {
             if !self.level_to_index.contains_key(trimmed) {
-                let new_idx = self.levels.len() as f64;
+                let new_idx = self.next_code();
                 self.levels.push(trimmed.to_string());
                 self.level_to_index.insert(trimmed.to_string(), new_idx);
                 self.index_to_level.insert(OrderedFloat(new_idx), trimmed.to_string());
@@ -217,7 +421,7 @@ impl Factor {
         if let Some(&idx) = self.level_to_index.get(trimmed) {
             idx
         } else {
-            let new_idx = self.levels.len() as f64;
+            let new_idx = self.next_code();
             self.levels.push(trimmed.to_string());
             self.level_to_index.insert(trimmed.to_string(), new_idx);
             self.index_to_level.insert(OrderedFloat(new_idx), trimmed.to_string());
@@ -251,12 +455,34 @@ impl Factor {
 
 
     pub fn get_f64( &self, trimmed: &str ) ->f64  {
-       *self.level_to_index.get(trimmed).unwrap_or( &f64::NAN )
+       *self.level_to_index.get(self.level_of(trimmed)).unwrap_or( &f64::NAN )
+    }
+
+    /// The level a value is stored as - its own or the one it was merged into.
+    pub fn level_of<'a>(&'a self, value: &'a str) -> &'a str {
+        self.merged.get(value).map_or(value, |l| l.as_str())
     }
 
     pub fn get_levels(&self) -> &[String] {
         &self.levels
     }
+    /// The level behind a numeric code - borrowed, `None` for missing values and unknown codes.
+    pub fn level_at(&self, value: f64) -> Option<&str> {
+        self.index_to_level.get(&OrderedFloat(value)).map(|level| level.as_str())
+    }
+
+    /// The integer code of `level` (a merged level gives the code of the level it went to) -
+    /// `None` for an unknown level or a code that is no `u32`. A hash lookup like `level_of_code()`.
+    pub fn code_of(&self, level: &str) -> Option<u32> {
+        let code = *self.level_to_index.get(self.level_of(level))?;
+        (code.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&code)).then_some(code as u32)
+    }
+
+    /// The level of an integer code - `None` for an unknown code.
+    pub fn level_of_code(&self, code: u32) -> Option<&str> {
+        self.level_at(code as f64)
+    }
+
     pub fn get_string(&self, value:f64 ) -> String {
         match self.index_to_level.get( &OrderedFloat(value)){
             Some(string) => string.to_string(),
@@ -286,6 +512,15 @@ impl Factor {
         }
 
 
+        let labels = match &def.labels {
+            Some(labels) => def.levels.iter().cloned().zip(labels.iter().cloned()).filter(|(l, d)| l != d).collect(),
+            None => HashMap::new(),
+        };
+        let colors = match &def.colors {
+            Some(colors) => def.levels.iter().cloned().zip(colors.iter().cloned()).filter(|(_, c)| !c.is_empty()).collect(),
+            None => HashMap::new(),
+        };
+
         Factor {
             column_name: def.column.to_string(),
             levels: def.levels.clone(),
@@ -293,6 +528,16 @@ impl Factor {
             index_to_level,
             matching: def.matching.clone(),
             one_hot: def.one_hot,
+            ordered: def.ordered,
+            labels,
+            colors,
+            counts: HashMap::new(),
+            missing: 0,
+            merged: def.merged.clone().unwrap_or_default(),
+            first_code: 0.0,
+            bins: def.bins.clone().unwrap_or_default(),
+            source: def.source.unwrap_or(FactorSource::Declared),
+            pinned: false,
         }
     }
 
@@ -316,8 +561,281 @@ impl Factor {
             numeric: Some(numeric),
             matching: self.matching.clone(),
             one_hot: self.one_hot,
+            ordered: self.ordered,
+            labels: (!self.labels.is_empty())
+                .then(|| self.levels.iter().map(|l| self.label(l).to_string()).collect()),
+            counts: self.is_counted()
+                .then(|| self.levels.iter().map(|l| self.count_of(l)).collect()),
+            missing: self.is_counted().then_some(self.missing),
+            merged: (!self.merged.is_empty()).then(|| self.merged.clone()),
+            colors: (!self.colors.is_empty())
+                .then(|| self.levels.iter().map(|l| self.colors.get(l).cloned().unwrap_or_default()).collect()),
+            source: Some(self.source),
+            bins: (!self.bins.is_empty()).then(|| self.bins.clone()),
+        }
+    }
+
+    /// Register the level of one row (like `level_to_index()`) and count it.
+    /// Missing values are counted separately.
+    pub fn count(&mut self, value: &str) -> f64 {
+        let code = self.level_to_index(value);
+        if code.is_nan() {
+            self.missing += 1;
+        } else {
+            let level = self.level_of(value.trim()).to_string();
+            *self.counts.entry(level).or_default() += 1;
+        }
+        code
+    }
+
+    /// The edges of the bins the levels stand for, if the factor is a binned numeric column
+    /// (`SurvivalData::bin_quantiles()`) - level `i` holds the values from `edges[i]` on.
+    pub fn bin_edges(&self) -> Option<&[f64]> {
+        (!self.bins.is_empty()).then_some(self.bins.as_slice())
+    }
+
+    pub(crate) fn set_bin_edges(&mut self, edges: Vec<f64>) {
+        self.bins = edges;
+    }
+
+    /// Number of counted rows of a level.
+    pub fn count_of(&self, level: &str) -> usize {
+        self.counts.get(level).copied().unwrap_or(0)
+    }
+
+    /// Number of counted rows without a value.
+    pub fn missing_count(&self) -> usize {
+        self.missing
+    }
+
+    /// Have any rows been counted?
+    fn is_counted(&self) -> bool {
+        self.missing > 0 || !self.counts.is_empty()
+    }
+
+    /// The display label of a level (the level itself unless relabeled).
+    pub fn label<'a>(&'a self, level: &'a str) -> &'a str {
+        self.labels.get(level).map_or(level, |l| l.as_str())
+    }
+
+    /// Give levels a display label (`"0"` -> `"Naive CD4"`) - the levels and codes stay as they are.
+    /// Levels missing from `labels` keep their name; two levels ending up with the
+    /// same label are an error.
+    pub fn relabel(&mut self, labels: &HashMap<String, String>) -> Result<()> {
+        for level in labels.keys().filter(|l| !self.level_to_index.contains_key(*l)) {
+            log::warn!("Factor '{}' has no level '{}' to relabel", self.column_name, level);
+        }
+        let mut new_labels = self.labels.clone();
+        for (level, label) in labels.iter().filter(|(l, _)| self.level_to_index.contains_key(*l)) {
+            new_labels.insert(level.clone(), label.clone());
+        }
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for level in &self.levels {
+            let label = new_labels.get(level).map_or(level.as_str(), |l| l.as_str());
+            if let Some(other) = seen.insert(label, level) {
+                bail!(
+                    InvalidArgument,
+                    "Relabeling factor '{}': the levels '{}' and '{}' would both be labeled '{}'",
+                    self.column_name, other, level, label
+                );
+            }
+        }
+        self.labels = new_labels;
+        Ok(())
+    }
+    /// Make `reference` the baseline level (R's `relevel()`): it moves to index 0 while
+    /// the other levels keep their relative order. The codes are renumbered 0, 1, ...
+    /// in the new level order, so data encoded before has to be encoded again.
+    pub fn relevel(&mut self, reference: &str) -> Result<()> {
+        let Some(pos) = self.levels.iter().position(|l| l == reference) else {
+            bail!(
+                InvalidArgument,
+                "Reference level '{}' is not a level of factor '{}' (levels: {:?})",
+                reference, self.column_name, self.levels
+            );
+        };
+        let level = self.levels.remove(pos);
+        self.levels.insert(0, level);
+        self.renumber();
+        Ok(())
+    }
+
+    /// Collapse every level with fewer than `min_count` counted rows into the level `label`
+    /// (appended as last level unless it exists). The codes are renumbered like in `relevel()`;
+    /// the original -> merged mapping is kept, so later values of a merged level still end up
+    /// in `label` and the factors JSON records what was merged.
+    /// Returns the number of merged levels.
+    pub fn merge_rare(&mut self, min_count: usize, label: &str) -> usize {
+        let rare: Vec<String> = self
+            .levels
+            .iter()
+            .filter(|l| *l != label && self.count_of(l) < min_count)
+            .cloned()
+            .collect();
+        self.merge_into(rare, label)
+    }
+
+    /// Keep the `k` most frequent levels and collapse all others into `label` like
+    /// `merge_rare()` - its count is the sum of theirs and `merged` records them. With
+    /// `TopKOrder::Frequency` the kept levels are sorted by their count, most frequent first
+    /// (ties keep their order), and `label` goes last; ordered factors keep their order.
+    /// A level named `label` is no candidate, it collects the others.
+    /// Returns the number of merged levels.
+    pub fn top_k(&mut self, k: usize, label: &str, order: TopKOrder) -> usize {
+        let mut ranked: Vec<String> = self.levels.iter().filter(|l| *l != label).cloned().collect();
+        // a stable sort - equally frequent levels stay in level order
+        ranked.sort_by_key(|l| std::cmp::Reverse(self.count_of(l)));
+        let collapsed = ranked.split_off(k.min(ranked.len()));
+        let merged = self.merge_into(collapsed, label);
+        if order == TopKOrder::Frequency && !self.ordered {
+            let has_label = self.levels.iter().any(|l| l == label);
+            self.levels = ranked;
+            if has_label {
+                self.levels.push(label.to_string());
+            }
+            self.renumber();
+        }
+        merged
+    }
+
+    /// Merge the levels `rare` into `label` - see `merge_rare()`.
+    fn merge_into(&mut self, rare: Vec<String>, label: &str) -> usize {
+        if rare.is_empty() {
+            return 0;
+        }
+        let mut merged_count = 0;
+        for level in &rare {
+            merged_count += self.counts.remove(level).unwrap_or(0);
+            self.labels.remove(level);
+            self.merged.insert(level.clone(), label.to_string());
+        }
+        // levels merged earlier now point to the new target as well
+        for target in self.merged.values_mut() {
+            if rare.contains(target) {
+                *target = label.to_string();
+            }
+        }
+        // a pinned label gets a code above all, the merged ones included
+        let code = self.next_code();
+        self.levels.retain(|l| !rare.contains(l));
+        if !self.levels.iter().any(|l| l == label) {
+            match self.pinned {
+                true => self.insert_level(label.to_string(), code),
+                false => self.levels.push(label.to_string()),
+            }
+        }
+        *self.counts.entry(label.to_string()).or_default() += merged_count;
+        self.renumber();
+        rare.len()
+    }
+
+    /// Record that several spellings (`CD8`, `cd8`) were normalized to one `level`:
+    /// their rows are counted together and the spellings end up in `merged`.
+    pub fn note_spellings<S: AsRef<str>>(&mut self, level: &str, spellings: &[S]) {
+        let spellings: Vec<&str> = spellings.iter().map(|s| s.as_ref()).collect();
+        log::info!("Note: {:?} in column '{}' are the same level '{}'", spellings, self.column_name, level);
+        for spelling in spellings.into_iter().filter(|s| *s != level) {
+            self.merged.insert(spelling.to_string(), level.to_string());
+        }
+    }
+
+    /// Apply the parts of a `FactorSpec` that need the counted rows:
+    /// merge the rare levels first, then keep the top k, then move the reference level to index 0.
+    pub fn apply_counted_spec(&mut self, spec: &FactorSpec) -> Result<()> {
+        let label = spec.rare_label.as_deref().unwrap_or(DEFAULT_RARE_LABEL);
+        if let Some(min_count) = spec.merge_rare {
+            let merged = self.merge_rare(min_count, label);
+            if merged > 0 {
+                log::info!("Merged {} level(s) of '{}' with fewer than {} rows into '{}'", merged, self.column_name, min_count, label);
+            }
+        }
+        if let Some(k) = spec.top_k {
+            let merged = self.top_k(k, label, spec.top_k_order);
+            if merged > 0 {
+                log::info!("Merged {} level(s) of '{}' outside the {} most frequent into '{}'", merged, self.column_name, k, label);
+            }
+        }
+        if let Some(reference) = &spec.reference {
+            self.relevel(reference)?;
+        }
+        Ok(())
+    }
+
+    /// Sort the levels - numerically if all of them are numbers, alphabetically otherwise -
+    /// and renumber the codes like in `relevel()`.
+    pub fn sort_levels(&mut self) {
+        let numbers: Option<Vec<f64>> = self.levels.iter().map(|l| l.parse::<f64>().ok()).collect();
+        match numbers {
+            Some(numbers) => {
+                let mut sorted: Vec<(f64, String)> = numbers.into_iter().zip(self.levels.drain(..)).collect();
+                sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+                self.levels = sorted.into_iter().map(|(_, level)| level).collect();
+            }
+            None => self.levels.sort(),
+        }
+        self.renumber();
+    }
+
+    /// Give the levels of `codes` these codes for good (`ParseOptions::level_codes`): they become
+    /// the first levels, in code order, followed by the levels the factor has already. Every level
+    /// added later gets the code above the largest one, so no code is ever reused, and reordering
+    /// the levels (`relevel()`, `sort_levels()`) keeps the codes. The codes have to be unique
+    /// (see `factor_map::check_level_codes()`).
+    pub fn pin_codes(&mut self, codes: &BTreeMap<String, u32>) {
+        let mut fixed: Vec<(&String, u32)> = codes.iter().map(|(level, &code)| (level, code)).collect();
+        fixed.sort_by_key(|&(_, code)| code);
+        let others: Vec<String> = self.levels.iter().filter(|level| !codes.contains_key(*level)).cloned().collect();
+        self.levels.clear();
+        self.level_to_index.clear();
+        self.index_to_level.clear();
+        self.pinned = true;
+        for (level, code) in fixed {
+            self.insert_level(level.clone(), code as f64);
+        }
+        for level in others {
+            let code = self.next_code();
+            self.insert_level(level, code);
+        }
+    }
+
+    fn insert_level(&mut self, level: String, code: f64) {
+        self.level_to_index.insert(level.clone(), code);
+        self.index_to_level.insert(OrderedFloat(code), level.clone());
+        self.levels.push(level);
+    }
+
+    /// Codes 0, 1, ... (or from `first_code` on) in level order. Pinned codes stay - only
+    /// those of removed levels go.
+    fn renumber(&mut self) {
+        if self.pinned {
+            let levels: HashSet<&String> = self.levels.iter().collect();
+            self.level_to_index.retain(|level, _| levels.contains(level));
+            self.index_to_level.retain(|_, level| levels.contains(level));
+            return;
+        }
+        self.level_to_index.clear();
+        self.index_to_level.clear();
+        for (i, level) in self.levels.iter().enumerate() {
+            let code = self.first_code + i as f64;
+            self.level_to_index.insert(level.clone(), code);
+            self.index_to_level.insert(OrderedFloat(code), level.clone());
         }
     }
+
+    /// The code of a new level: the next one in line - or, if the codes came from a
+    /// factors file and that one is taken, one above the largest code. Pinned codes
+    /// (`pin_codes()`) are always followed by one above the largest.
+    fn next_code(&self) -> f64 {
+        let code = self.first_code + self.levels.len() as f64;
+        if self.pinned {
+            return self.index_to_level.keys().map(|c| c.0 + 1.0).fold(self.first_code, f64::max);
+        }
+        if !self.index_to_level.contains_key(&OrderedFloat(code)) {
+            return code;
+        }
+        self.index_to_level.keys().map(|c| c.0).fold(code, f64::max) + 1.0
+    }
+
     /// Modify numeric values for levels in this factor.
     ///
     /// # Arguments
@@ -328,7 +846,7 @@ impl Factor {
             Some(levels) => {
                 // Modify only the specified levels
                 for (lvl, &val) in levels.iter().zip(numeric_values.iter()) {
-                    if self.levels.contains(&lvl) {
+                    if self.levels.contains(lvl) {
                         self.level_to_index.insert(lvl.clone(), val);
                         self.index_to_level.insert(OrderedFloat(val), lvl.clone());
                     } else {
@@ -374,7 +892,7 @@ mod tests {
         // --- Modify subset of levels ---
         let levels_to_change = vec!["Red".to_string(), "Green".to_string()];
         let new_values = vec![10.0, 30.0];
-        factor.modify_levels(&new_values, Some(&levels_to_change));
+        factor.modify_levels(&new_values, Some(&levels_to_change)).unwrap();
 
         assert_eq!(*factor.level_to_index.get("Red").unwrap(), 10.0);
         assert_eq!(*factor.level_to_index.get("Green").unwrap(), 30.0);
@@ -382,7 +900,7 @@ mod tests {
 
         // --- Overwrite all levels ---
         let all_new_values = vec![100.0, 200.0, 300.0];
-        factor.modify_levels(&all_new_values, None);
+        factor.modify_levels(&all_new_values, None).unwrap();
 
         assert_eq!(*factor.level_to_index.get("Red").unwrap(), 100.0);
         assert_eq!(*factor.level_to_index.get("Blue").unwrap(), 200.0);
@@ -391,13 +909,154 @@ mod tests {
         // --- Test panic for non-existing level ---
         
         assert!( 
-            factor.modify_levels(&[1.0], Some(&vec!["Yellow".to_string()])).is_err(), 
+            factor.modify_levels(&[1.0], Some(&["Yellow".to_string()])).is_err(), 
             "Should panic when modifying non-existing level" 
         );
         
         assert!(factor.modify_levels(&[1.0, 2.0], None).is_err(), "Should panic for length mismatch");
     }
 
+    #[test]
+    fn test_level_counts() {
+        let mut factor = Factor::new("cluster", false);
+        for value in ["B", "A", "B", "NA", "", "B"] {
+            factor.count(value);
+        }
+        assert_eq!((factor.count_of("B"), factor.count_of("A"), factor.missing_count()), (3, 1, 2));
+        let json = factor.as_json("cluster");
+        assert_eq!(json.levels, ["B", "A"]);
+        assert_eq!(json.counts, Some(vec![3, 1]));
+        assert_eq!(json.missing, Some(2));
+
+        // counts follow their level when the order changes
+        factor.relevel("A").unwrap();
+        assert_eq!(factor.as_json("cluster").counts, Some(vec![1, 3]));
+        assert_eq!(Factor::new("empty", false).as_json("empty").counts, None);
+    }
+
+    #[test]
+    fn test_merge_rare() {
+        let mut factor = Factor::new("condition", false);
+        for value in ["a", "b", "a", "c", "a", "b", "d"] {
+            factor.count(value);
+        }
+        assert_eq!(factor.merge_rare(2, "Other"), 2);
+        assert_eq!(factor.get_levels(), ["a", "b", "Other"]);
+        assert_eq!(factor.count_of("Other"), 2);
+        assert_eq!((factor.get_f64("c"), factor.get_f64("d"), factor.get_f64("Other")), (2.0, 2.0, 2.0));
+        assert_eq!(factor.level_to_index("d"), 2.0, "a merged level is not registered again");
+        let json = factor.as_json("condition");
+        assert_eq!(json.counts, Some(vec![3, 2, 2]));
+        let merged = json.merged.clone().unwrap();
+        assert_eq!((merged["c"].as_str(), merged["d"].as_str()), ("Other", "Other"));
+
+        // the mapping survives the factors file
+        let back = Factor::from_def(&json);
+        assert_eq!(back.get_f64("c"), 2.0);
+        assert_eq!(factor.merge_rare(1, "Other"), 0, "nothing left to merge");
+    }
+
+    #[test]
+    fn test_top_k() {
+        let counted = |ordered: bool| {
+            let mut factor = Factor::new("cluster", false);
+            factor.ordered = ordered;
+            for value in ["a", "b", "c", "c", "d", "c", "b", "e", "d", "d", "d"] {
+                factor.count(value);
+            }
+            factor
+        };
+        let mut factor = counted(false);
+        assert_eq!(factor.top_k(2, "Other", TopKOrder::Frequency), 3);
+        assert_eq!(factor.get_levels(), ["d", "c", "Other"]);
+        assert_eq!(factor.as_json("cluster").counts, Some(vec![4, 3, 4]), "Other holds the collapsed rows");
+        assert_eq!((factor.get_f64("d"), factor.get_f64("a"), factor.get_f64("e")), (0.0, 2.0, 2.0));
+        let merged = factor.as_json("cluster").merged.unwrap();
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["a", "b", "e"]);
+
+        let mut factor = counted(false);
+        assert_eq!(factor.top_k(3, "Other", TopKOrder::Original), 2);
+        assert_eq!(factor.get_levels(), ["b", "c", "d", "Other"], "b wins the tie with a and e");
+
+        // nothing to collapse, but sorted; ordered factors keep their order
+        let mut factor = counted(false);
+        assert_eq!(factor.top_k(10, "Other", TopKOrder::Frequency), 0);
+        assert_eq!(factor.get_levels(), ["d", "c", "b", "a", "e"]);
+        let mut factor = counted(true);
+        factor.top_k(2, "Other", TopKOrder::Frequency);
+        assert_eq!(factor.get_levels(), ["c", "d", "Other"]);
+
+        assert_eq!(TopKOrder::parse("Original").unwrap(), TopKOrder::Original);
+        assert!(TopKOrder::parse("count").is_err());
+    }
+
+    #[test]
+    fn test_code_lookup() {
+        let mut factor = Factor::new("cluster", false).starting_at(1.0);
+        for value in ["T", "B", "NK", "T"] {
+            factor.count(value);
+        }
+        assert_eq!((factor.code_of("T"), factor.code_of("NK"), factor.code_of("DC")), (Some(1), Some(3), None));
+        assert_eq!((factor.level_of_code(2), factor.level_of_code(0)), (Some("B"), None));
+        factor.merge_rare(2, "Other");
+        assert_eq!(factor.code_of("NK"), factor.code_of("Other"), "a merged level has the code of its new level");
+        for level in factor.get_levels() {
+            assert_eq!(factor.level_of_code(factor.code_of(level).unwrap()), Some(level.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_relevel() {
+        let mut factor = Factor::new("condition", false);
+        for value in ["treated", "sham", "control", "treated"] {
+            let _ = factor.push(value);
+        }
+        factor.relevel("control").unwrap();
+        assert_eq!(factor.get_levels(), ["control", "treated", "sham"]);
+        assert_eq!(factor.get_f64("control"), 0.0);
+        assert_eq!(factor.get_f64("sham"), 2.0);
+        assert_eq!(factor.get_string(1.0), "treated");
+
+        let err = factor.relevel("placebo").unwrap_err();
+        assert!(err.to_string().contains("Reference level 'placebo' is not a level of factor 'condition'"), "{err}");
+    }
+
+    #[test]
+    fn test_one_based_codes() {
+        let mut factor = Factor::new("condition", false).starting_at(1.0);
+        for value in ["treated", "sham", "control"] {
+            let _ = factor.push(value);
+        }
+        assert_eq!(factor.get_f64("treated"), 1.0);
+        assert_eq!(factor.get_string(3.0), "control");
+        factor.relevel("control").unwrap();
+        assert_eq!((factor.get_f64("control"), factor.get_f64("sham")), (1.0, 3.0), "renumbered from 1");
+
+        // 1-based codes from a factors file: a new level must not take the code of 'b'
+        let def = FactorJson { numeric: Some(vec![1.0, 2.0]), ..Factor::new("x", false).as_json("x") };
+        let mut factor = Factor::from_def(&FactorJson { levels: vec!["a".into(), "b".into()], ..def });
+        assert_eq!(factor.level_to_index("c"), 3.0);
+        assert_eq!(factor.get_string(2.0), "b");
+    }
+
+    #[test]
+    fn test_sort_levels() {
+        let mut factor = Factor::new("cluster", false);
+        for value in ["10", "2", "1", "2"] {
+            let _ = factor.push(value);
+        }
+        factor.sort_levels();
+        assert_eq!(factor.get_levels(), ["1", "2", "10"], "numbers by value");
+        assert_eq!(factor.get_f64("10"), 2.0);
+
+        let mut factor = Factor::new("cell_type", false);
+        for value in ["T cell", "B cell", "NK"] {
+            let _ = factor.push(value);
+        }
+        factor.sort_levels();
+        assert_eq!(factor.get_levels(), ["B cell", "NK", "T cell"]);
+    }
+
     #[test]
     fn test_push_one_hot() {
         let mut f = Factor::new("Color", false);
@@ -479,7 +1138,9 @@ mod tests {
 
         // --- Step 4: Assertions ---
         let expected_levels = vec!["Red".to_string(), "Green".to_string() ];
-        assert_eq!(new_factor.levels, expected_levels, "Levels should preserve first appearance order");
+        assert_eq!(new_factor.levels, expected_levels, "Levels should preserve the original order");
+        let green_first = array![[2.0], [0.0]];
+        assert_eq!(factor.subset(&green_first, 0).levels, expected_levels, "not the order of the subset rows");
 
         let mut expected_level_to_index: HashMap<String, f64> = HashMap::new();
         expected_level_to_index.insert("Red".into(), 0.0);
@@ -500,4 +1161,22 @@ mod tests {
         println!("Subset factor: {:?}", new_factor);
     }
 
+    #[test]
+    fn test_ordered_spec() {
+        let (column, spec) = FactorSpec::parse_ordered("stage: low, medium ,high").unwrap();
+        assert_eq!(column, "stage");
+        assert!(spec.ordered);
+        assert_eq!(spec.levels, vec!["low", "medium", "high"]);
+
+        let factor = Factor::from_spec(&column, &spec);
+        assert_eq!(factor.get_levels(), ["low", "medium", "high"]);
+        assert_eq!(factor.get_f64("high"), 2.0);
+        assert!(factor.as_json("stage").ordered);
+
+        assert_eq!(spec.resolve("stage", "medium").unwrap(), "medium");
+        assert!(spec.resolve("stage", "extreme").is_err());
+        let spec = FactorSpec { other: Some("other".into()), ..spec };
+        assert_eq!(spec.resolve("stage", "extreme").unwrap(), "other");
+    }
+
 }
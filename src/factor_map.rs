@@ -0,0 +1,897 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::DateTime;
+use crate::compress::{has_zst_extension, Compression};
+use crate::error::{bail, DataTableError, Result};
+use crate::input::open_input;
+use serde::{Serialize, Deserialize};
+use crate::factor::{Factor, FactorJson, FactorSource};
+use crate::column::{Column, Dtype};
+
+/// Explanation printed after a new factors file has been written.
+pub const FACTORS_FILE_HELP: &str = r#"
+Please review and update the factors file so that it accurately reflects the logic in the data.
+
+The factors file is a JSON object (layout version 1.19 in 'schema_version'):
+  '_meta'    where it came from - inputs, tool, generated (time), delimiter, categorical columns and command
+  'n_rows'   the number of data rows
+  'columns'  every column with its 'dtype' - integer, float, boolean, date, factor, free_text, json
+             or all_missing (no value at all) - its 'missing' count and, by type, 'cardinality',
+             'summary' (min, max, mean, quartiles), date 'format' or 'constant'
+  'factors'  the factor definitions: levels, their codes ('numeric'), counts and settings
+
+For example:
+
+{
+  "schema_version": "1.19",
+  "_meta": { "inputs": ["meta.tsv"], "tool": "rust_data_table", "generated": "2024-03-01T12:00:00Z" },
+  "n_rows": 120,
+  "columns": [
+    { "name": "status2", "dtype": "factor", "missing": 0, "cardinality": 2 }
+  ],
+  "factors": [
+    {
+      "column": "status2",
+      "levels": ["1", "0"],
+      "numeric": [0.0, 1.0],
+      "matching": null,
+      "one_hot": false,
+      "ordered": false,
+      "counts": [31, 89],
+      "missing": 0,
+      "source": "inferred"
+    }
+  ]
+}
+
+In this example, there is an error: the numeric values do not match the actual data. They should be [1.0, 0.0].
+Once corrected, the factor will work as expected - the next run keeps the levels and codes of this file.
+
+The one_hot option expands a factor into one 0.0/1.0 column per level. This is particularly useful when
+the levels have no inherent numeric order or relationship:
+
+    {
+      "column": "tp53_mutation_type",
+      "levels": ["Missense", "Nonsense", "Frameshift", "Splice_site", "Silent"],
+      "numeric": [0.0, 1.0, 2.0, 3.0, 4.0],
+      "matching": null,
+      "one_hot": true
+    }
+
+This factor is categorical with no inherent order, so it's a good candidate for one-hot encoding in a model.
+Set 'ordered' for levels with an order (low < medium < high); 'labels', 'colors', 'merged' and 'bins' are
+optional and written where they apply.
+"#;
+
+/// Display labels per column and level, e.g. `{"cluster": {"0": "Naive CD4"}}`.
+pub type LevelLabels = HashMap<String, HashMap<String, String>>;
+
+/// Read a level relabeling file (see `SurvivalData::relabel_levels()`).
+pub fn read_level_labels<P: AsRef<Path>>(path: P) -> Result<LevelLabels> {
+    let path = path.as_ref();
+    // the layout is {"column": {"level": "label"}}
+    serde_json::from_reader(open_input(path)?)
+        .map_err(|source| DataTableError::Json { path: Some(path.to_path_buf()), source })
+}
+
+/// Fixed integer codes per column and level, e.g. `{"cluster": {"T": 0, "B": 1, "NK": 5}}`.
+pub type LevelCodes = HashMap<String, BTreeMap<String, u32>>;
+
+/// Read a level codes file (see `ParseOptions::level_codes`) - a code given to two levels
+/// of a column is an error.
+pub fn read_level_codes<P: AsRef<Path>>(path: P) -> Result<LevelCodes> {
+    let path = path.as_ref();
+    // the layout is {"column": {"level": code}}
+    let codes: LevelCodes = serde_json::from_reader(open_input(path)?)
+        .map_err(|source| DataTableError::Json { path: Some(path.to_path_buf()), source })?;
+    check_level_codes(&codes).map_err(|e| DataTableError::InvalidData(format!("{} in {:?}", e, path)))?;
+    Ok(codes)
+}
+
+/// Every code of a column belongs to one level only. The error names the first collision
+/// (columns in alphabetical order).
+pub fn check_level_codes(codes: &LevelCodes) -> Result<()> {
+    let mut columns: Vec<&String> = codes.keys().collect();
+    columns.sort();
+    for column in columns {
+        let mut taken: BTreeMap<u32, &String> = BTreeMap::new();
+        for (level, &code) in &codes[column] {
+            if let Some(other) = taken.insert(code, level) {
+                bail!(InvalidData, "Column '{}': the levels '{}' and '{}' both have the code {}", column, other, level, code);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version of the factors JSON layout.
+///
+/// Bump the minor number for backwards compatible additions (new optional fields)
+/// and the major number whenever existing fields change their meaning.
+pub const SCHEMA_VERSION: &str = "1.19";
+
+/// The column types and factor definitions of a table - the in-memory form of the factors JSON file.
+///
+/// Entries are kept in column order, so writing the same map twice gives identical files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactorMap {
+    /// Layout version of the JSON (see `SCHEMA_VERSION`)
+    pub schema_version: String,
+    /// Where the file comes from - input, tool and options (`_meta` in the JSON)
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Provenance>,
+    /// Number of data rows of the table the map was computed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n_rows: Option<usize>,
+    /// Detected type of every column
+    #[serde(default)]
+    pub columns: Vec<Column>,
+    pub factors: Vec<FactorJson>,
+}
+
+impl Default for FactorMap {
+    fn default() -> Self {
+        FactorMap {
+            schema_version: SCHEMA_VERSION.to_string(),
+            meta: None,
+            columns: Vec::new(),
+            factors: Vec::new(),
+            n_rows: None,
+        }
+    }
+}
+
+/// The origin of a factors file: which input, which tool version, when and with which options.
+/// Written as the `_meta` object unless it is left out for byte identical files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The input file(s) - absolute if they could be resolved, `-` for stdin
+    pub inputs: Vec<String>,
+    /// `rust_data_table 0.1.0`
+    pub tool: String,
+    /// The time the map was computed (UTC, `2024-03-01T12:00:00Z`)
+    pub generated: String,
+    /// The field delimiter of the input
+    #[serde(default)]
+    pub delimiter: String,
+    /// The columns forced to be factors
+    #[serde(default)]
+    pub categorical: Vec<String>,
+    /// The complete command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// Set if only the first this many rows were read (`ParseOptions::max_rows`) - the
+    /// levels, counts and summaries describe them, not the whole input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<usize>,
+}
+
+impl Provenance {
+    /// The provenance of a map computed from `inputs` right now - the options are left empty.
+    pub fn now<P: AsRef<Path>>(inputs: &[P]) -> Self {
+        let inputs = inputs
+            .iter()
+            .map(|input| {
+                let input = input.as_ref();
+                std::fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf()).display().to_string()
+            })
+            .collect();
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        let generated = DateTime::from_timestamp(seconds, 0)
+            .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .unwrap_or_default();
+        Provenance {
+            inputs,
+            tool: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            generated,
+            ..Default::default()
+        }
+    }
+}
+
+/// The order of the column and factor entries in the JSON (see `FactorMap::reorder()`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnOrder {
+    /// As in the input header
+    #[default]
+    Input,
+    /// Alphabetical by name
+    Alpha,
+    /// Numbers, booleans, dates, factors and free text - in input order within each group
+    Type,
+}
+
+impl ColumnOrder {
+    /// Parse a command line value: `input`, `alpha` or `type`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        Ok(match arg {
+            "input" => ColumnOrder::Input,
+            "alpha" => ColumnOrder::Alpha,
+            "type" => ColumnOrder::Type,
+            _ => bail!(InvalidArgument, "Invalid column order '{}' - expected input, alpha or type", arg),
+        })
+    }
+}
+
+/// The group of a dtype in `ColumnOrder::Type`.
+fn type_rank(dtype: Dtype) -> u8 {
+    match dtype {
+        Dtype::Integer | Dtype::Float => 0,
+        Dtype::Boolean => 1,
+        Dtype::Date => 2,
+        Dtype::Factor => 3,
+        Dtype::FreeText => 4,
+        Dtype::Json => 5,
+        Dtype::AllMissing => 6,
+    }
+}
+
+/// The layout of a factors file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FactorsFileFormat {
+    /// The pretty printed JSON (`write_json()`)
+    #[default]
+    Json,
+    /// A flat table with one row per factor level (`write_tsv()`) - easy to edit in a spreadsheet
+    Tsv,
+}
+
+impl FactorsFileFormat {
+    /// `.tsv` and `.txt` files are tables, anything else is JSON - a `.zst` behind it does not count.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let path = match has_zst_extension(path) {
+            true => Path::new(path.file_stem().unwrap_or_default()),
+            false => path,
+        };
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("txt") => FactorsFileFormat::Tsv,
+            _ => FactorsFileFormat::Json,
+        }
+    }
+}
+
+/// How JSON files are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonLayout {
+    /// Indented, one value per line - easy to read and hand edit
+    #[default]
+    Pretty,
+    /// Without any whitespace - smaller and faster to parse, e.g. for factors with many levels
+    Compact,
+}
+
+impl JsonLayout {
+    /// Write `value` as JSON into a new file `path` (replacing an existing one) -
+    /// zstd compressed if the path ends in `.zst`.
+    pub fn write_file<P: AsRef<Path>, T: Serialize>(self, path: P, value: &T) -> Result<()> {
+        self.write_compressed(&path, value, Compression::from_path(&path))
+    }
+
+    /// `write_file()` with the `compression` given instead of the one the extension says.
+    pub fn write_compressed<P: AsRef<Path>, T: Serialize>(self, path: P, value: &T, compression: Compression) -> Result<()> {
+        let mut writer = compression.create(&path)?;
+        match self {
+            JsonLayout::Pretty => serde_json::to_writer_pretty(&mut writer, value)?,
+            JsonLayout::Compact => serde_json::to_writer(&mut writer, value)?,
+        }
+        writer.finish().map_err(|e| DataTableError::io(&path, e))?;
+        Ok(())
+    }
+}
+
+/// One row of the TSV factors file: a column without levels or one level of a factor.
+#[derive(Debug, Serialize, Deserialize)]
+struct TsvRow {
+    column: String,
+    dtype: Dtype,
+    level: Option<String>,
+    numeric: Option<f64>,
+    label: Option<String>,
+    color: Option<String>,
+    count: Option<usize>,
+    ordered: Option<bool>,
+    one_hot: Option<bool>,
+    #[serde(default)]
+    source: Option<FactorSource>,
+}
+
+/// One row of an R factor levels table (see `FactorMap::from_r_tsv()`).
+#[derive(Debug, Deserialize)]
+struct RTsvRow {
+    column: String,
+    level: String,
+    code: String,
+}
+
+/// Split a `major.minor` version string.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// A file can be read if it has our major version and is not newer than us.
+fn check_schema_version(version: &str) -> Result<()> {
+    let ours = parse_version(SCHEMA_VERSION).expect("SCHEMA_VERSION is 'major.minor'");
+    match parse_version(version) {
+        Some((major, minor)) if major == ours.0 && minor <= ours.1 => Ok(()),
+        Some(_) => bail!(
+            Schema,
+            "Unsupported factors file schema_version '{}' - this version of the library reads schema_version {}.x up to {}",
+            version, ours.0, SCHEMA_VERSION
+        ),
+        None => bail!(Schema, "Invalid factors file schema_version '{}' - expected 'major.minor'", version),
+    }
+}
+
+impl FactorMap {
+
+    pub fn new(factors: Vec<FactorJson>) -> Self {
+        FactorMap { factors, ..Default::default() }
+    }
+
+    /// The metadata of one column.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Look up the definition of one column.
+    pub fn get(&self, column: &str) -> Option<&FactorJson> {
+        self.factors.iter().find(|f| f.column == column)
+    }
+
+    pub fn len(&self) -> usize {
+        self.factors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FactorJson> {
+        self.factors.iter()
+    }
+
+    /// Build the working `Factor` objects keyed by column name.
+    pub fn to_factors(&self) -> HashMap<String, Factor> {
+        self.factors
+            .iter()
+            .map(|def| (def.column.clone(), Factor::from_def(def)))
+            .collect()
+    }
+
+    /// Read a factors JSON file as written by `write_json()` (or the `generate_json` tool).
+    /// Hand edited level orders and labels are kept exactly as they are in the file.
+    ///
+    /// The `schema_version` must be compatible with `SCHEMA_VERSION`; a bare list of
+    /// factors (the layout before versioning) is still accepted. Compressed files
+    /// (zstd or gzip) are decompressed.
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = open_input(path)?;
+        let json_error = |source| DataTableError::Json { path: Some(path.to_path_buf()), source };
+        let value: serde_json::Value = serde_json::from_reader(reader).map_err(json_error)?;
+        Self::from_value(value).map_err(|err| match err {
+            DataTableError::Json { path: None, source } => json_error(source),
+            err => err,
+        })
+    }
+
+    /// Build the map from already parsed JSON - see `from_json()`.
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        if value.is_array() {
+            return Ok(FactorMap::new(serde_json::from_value(value)?));
+        }
+        match value.get("schema_version") {
+            Some(serde_json::Value::String(version)) => check_schema_version(version)?,
+            Some(other) => bail!(Schema, "schema_version must be a string like \"{}\", found {}", SCHEMA_VERSION, other),
+            None => bail!(Schema, "Missing \"schema_version\" field (expected \"{}\")", SCHEMA_VERSION),
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Sort the `columns` (and the `factors` along with them) - a sort is stable,
+    /// so the input order breaks ties. One-hot columns stay with their factor in the `Type` order.
+    pub fn reorder(&mut self, order: ColumnOrder) {
+        match order {
+            ColumnOrder::Input => return,
+            ColumnOrder::Alpha => self.columns.sort_by(|a, b| a.name.cmp(&b.name)),
+            ColumnOrder::Type => {
+                let parent: HashMap<String, Dtype> = self
+                    .to_factors()
+                    .values()
+                    .filter(|f| f.one_hot)
+                    .flat_map(|f| f.all_column_names())
+                    .map(|name| (name, Dtype::Factor))
+                    .collect();
+                self.columns.sort_by_key(|c| type_rank(parent.get(&c.name).copied().unwrap_or(c.dtype)));
+            }
+        }
+        let position: HashMap<&str, usize> = self.columns.iter().enumerate().map(|(i, c)| (c.name.as_str(), i)).collect();
+        // factors without a column entry (a legacy file) go last
+        self.factors.sort_by_key(|f| position.get(f.column.as_str()).copied().unwrap_or(usize::MAX));
+    }
+
+    /// Read a factors file in the layout its extension says (see `FactorsFileFormat::from_path()`).
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        match FactorsFileFormat::from_path(&path) {
+            FactorsFileFormat::Json => Self::from_json(path),
+            FactorsFileFormat::Tsv => Self::from_tsv(path),
+        }
+    }
+
+    /// Write the map in the layout the extension of `path` says.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write_with(path, JsonLayout::Pretty)
+    }
+
+    /// `write()` with the JSON files in `layout` - tables are not affected.
+    pub fn write_with<P: AsRef<Path>>(&self, path: P, layout: JsonLayout) -> Result<()> {
+        self.write_compressed(&path, layout, Compression::from_path(&path))
+    }
+
+    /// `write_with()` with the `compression` given instead of the one the extension says.
+    pub fn write_compressed<P: AsRef<Path>>(&self, path: P, layout: JsonLayout, compression: Compression) -> Result<()> {
+        match FactorsFileFormat::from_path(&path) {
+            FactorsFileFormat::Json => layout.write_compressed(path, self, compression),
+            FactorsFileFormat::Tsv => self.write_tsv_compressed(path, compression),
+        }
+    }
+
+    /// Write the map as a tab separated table with the columns
+    /// `column dtype level numeric label color count ordered one_hot`:
+    /// one row per factor level and one row (without a level) per other column.
+    ///
+    /// `from_tsv()` reads it back. The table keeps the column types and the factor
+    /// definitions - the column statistics, `missing` counts and merged levels are only in the JSON.
+    pub fn write_tsv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write_tsv_compressed(&path, Compression::from_path(&path))
+    }
+
+    fn write_tsv_compressed<P: AsRef<Path>>(&self, path: P, compression: Compression) -> Result<()> {
+        let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_writer(compression.create(&path)?);
+        let plain = |column: &str, dtype| TsvRow {
+            column: column.to_string(),
+            dtype,
+            level: None,
+            numeric: None,
+            label: None,
+            color: None,
+            count: None,
+            ordered: None,
+            one_hot: None,
+            source: None,
+        };
+        let mut written = Vec::new();
+        let columns = self.columns.iter().map(|c| (c.name.as_str(), c.dtype));
+        // factors without a column entry (a legacy file) go last
+        let legacy = self.factors.iter().filter(|f| self.column(&f.column).is_none()).map(|f| (f.column.as_str(), Dtype::Factor));
+        for (name, dtype) in columns.chain(legacy) {
+            let Some(def) = self.get(name).filter(|_| !written.contains(&name)) else {
+                writer.serialize(plain(name, dtype))?;
+                continue;
+            };
+            written.push(name);
+            let factor = |i: usize| TsvRow {
+                level: def.levels.get(i).cloned(),
+                numeric: def.numeric.as_ref().and_then(|n| n.get(i).copied()),
+                label: def.labels.as_ref().and_then(|l| l.get(i).cloned()),
+                color: def.colors.as_ref().and_then(|c| c.get(i).cloned()).filter(|c| !c.is_empty()),
+                count: def.counts.as_ref().and_then(|c| c.get(i).copied()),
+                ordered: Some(def.ordered),
+                one_hot: Some(def.one_hot),
+                source: def.source,
+                ..plain(name, dtype)
+            };
+            // a factor without levels still gets its row
+            for i in 0..def.levels.len().max(1) {
+                writer.serialize(factor(i))?;
+            }
+        }
+        let file = writer.into_inner().map_err(|e| DataTableError::io(&path, e.into_error()))?;
+        file.finish().map_err(|e| DataTableError::io(&path, e))?;
+        Ok(())
+    }
+
+    /// Read a factors table as written by `write_tsv()`. The rows of a factor have to be
+    /// next to each other; its levels are in row order and `ordered`/`one_hot`/`source` come from its first row.
+    pub fn from_tsv<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(open_input(path)?);
+        let mut map = FactorMap::default();
+        let mut previous: Option<String> = None;
+        for (i, row) in reader.deserialize::<TsvRow>().enumerate() {
+            let row = row?;
+            let factor = matches!(row.dtype, Dtype::Factor | Dtype::FreeText) && (row.level.is_some() || row.ordered.is_some());
+            if previous.as_deref() != Some(row.column.as_str()) {
+                if map.column(&row.column).is_some() {
+                    bail!(InvalidData, "{:?} line {}: the rows of column '{}' are not next to each other", path, i + 2, row.column);
+                }
+                map.columns.push(Column::new(&row.column, row.dtype));
+                if factor {
+                    map.factors.push(FactorJson {
+                        column: row.column.clone(),
+                        levels: Vec::new(),
+                        numeric: Some(Vec::new()),
+                        matching: None,
+                        one_hot: row.one_hot.unwrap_or(false),
+                        ordered: row.ordered.unwrap_or(false),
+                        labels: Some(Vec::new()),
+                        counts: Some(Vec::new()),
+                        missing: None,
+                        merged: None,
+                        colors: Some(Vec::new()),
+                        bins: None,
+                        source: row.source,
+                    });
+                }
+                previous = Some(row.column.clone());
+            }
+            let Some(level) = row.level else { continue };
+            let Some(def) = map.factors.last_mut().filter(|f| f.column == row.column) else {
+                bail!(InvalidData, "{:?} line {}: column '{}' is {} and has no levels", path, i + 2, row.column, row.dtype);
+            };
+            if def.levels.contains(&level) {
+                bail!(InvalidData, "{:?} line {}: level '{}' of '{}' is listed twice", path, i + 2, level, row.column);
+            }
+            // a blank numeric code is the position of the level
+            def.numeric.get_or_insert_default().push(row.numeric.unwrap_or(def.levels.len() as f64));
+            def.labels.get_or_insert_default().push(row.label.unwrap_or_else(|| level.clone()));
+            def.colors.get_or_insert_default().push(row.color.unwrap_or_default());
+            if let (Some(counts), Some(count)) = (&mut def.counts, row.count) {
+                counts.push(count);
+            } else {
+                def.counts = None;
+            }
+            def.levels.push(level);
+        }
+        for def in &mut map.factors {
+            if def.labels.as_ref().is_some_and(|labels| labels.iter().eq(&def.levels)) {
+                def.labels = None;
+            }
+            if def.colors.as_ref().is_some_and(|colors| colors.iter().all(String::is_empty)) {
+                def.colors = None;
+            }
+            let levels = def.levels.len();
+            if let Some(column) = map.columns.iter_mut().find(|c| c.name == def.column) {
+                column.cardinality = Some(levels);
+            }
+        }
+        Ok(map)
+    }
+
+    /// Read the factor levels table of an R pipeline: a TSV with the columns `column`, `level`
+    /// and `code`, one row per level. The rows of a column need not be next to each other; its
+    /// levels are ordered by code, and the codes (usually 1..n) are kept as the numeric values.
+    /// Duplicate codes or levels and gaps between the codes of a column are errors.
+    pub fn from_r_tsv<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(open_input(path)?);
+        // per column in the order of first appearance: (code, level, line)
+        type Levels = Vec<(i64, String, usize)>;
+        let mut columns: Vec<(String, Levels)> = Vec::new();
+        let mut position: HashMap<String, usize> = HashMap::new();
+        for (i, row) in reader.deserialize::<RTsvRow>().enumerate() {
+            let row = row?;
+            let Ok(code) = row.code.trim().parse::<i64>() else {
+                bail!(InvalidData, "{:?} line {}: the code '{}' of level '{}' is not a whole number", path, i + 2, row.code, row.level);
+            };
+            let id = *position.entry(row.column.clone()).or_insert_with(|| {
+                columns.push((row.column, Vec::new()));
+                columns.len() - 1
+            });
+            columns[id].1.push((code, row.level, i + 2));
+        }
+        let mut map = FactorMap::default();
+        for (name, mut levels) in columns {
+            levels.sort_by_key(|(code, _, _)| *code);
+            for pair in levels.windows(2) {
+                let ((previous, _, _), (code, level, line)) = (&pair[0], &pair[1]);
+                if code == previous {
+                    bail!(InvalidData, "{:?} line {}: code {} of column '{}' is used twice", path, line, code, name);
+                }
+                if *code != previous + 1 {
+                    bail!(InvalidData, "{:?} line {}: the codes of column '{}' jump from {} to {} at level '{}'", path, line, name, previous, code, level);
+                }
+            }
+            let mut seen = std::collections::HashSet::new();
+            for (_, level, line) in &levels {
+                if !seen.insert(level) {
+                    bail!(InvalidData, "{:?} line {}: level '{}' of '{}' is listed twice", path, line, level, name);
+                }
+            }
+            let mut column = Column::new(&name, Dtype::Factor);
+            column.cardinality = Some(levels.len());
+            map.columns.push(column);
+            map.factors.push(FactorJson {
+                column: name,
+                numeric: Some(levels.iter().map(|(code, _, _)| *code as f64).collect()),
+                levels: levels.into_iter().map(|(_, level, _)| level).collect(),
+                matching: None,
+                one_hot: false,
+                ordered: false,
+                labels: None,
+                counts: None,
+                missing: None,
+                merged: None,
+                colors: None,
+                bins: None,
+                source: None,
+            });
+        }
+        Ok(map)
+    }
+
+    /// Write the map as pretty printed JSON (easy to hand edit).
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        JsonLayout::Pretty.write_file(path, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_compact_json() -> Result<()> {
+        let mut cluster = Factor::new("cluster", false);
+        for i in 0..500 {
+            let _ = cluster.push(&format!("cell_{i}"));
+        }
+        let map = FactorMap::new(vec![cluster.as_json("cluster")]);
+        let dir = tempdir()?;
+        let (pretty, compact) = (dir.path().join("pretty.json"), dir.path().join("compact.json"));
+        map.write_with(&pretty, JsonLayout::Pretty)?;
+        map.write_with(&compact, JsonLayout::Compact)?;
+        let compact_text = std::fs::read_to_string(&compact)?;
+        assert!(!compact_text.contains('\n') && !compact_text.contains(": "));
+        assert!(compact_text.len() * 2 < std::fs::metadata(&pretty)?.len() as usize, "less than half the size");
+        assert_eq!(FactorMap::read(&compact)?, FactorMap::read(&pretty)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zstd_factors_file() -> Result<()> {
+        let mut cluster = Factor::new("cluster", false);
+        for i in 0..500 {
+            let _ = cluster.push(&format!("cell_{i}"));
+        }
+        let map = FactorMap::new(vec![cluster.as_json("cluster")]);
+        let dir = tempdir()?;
+        let plain = dir.path().join("factors.json");
+        map.write_json(&plain)?;
+        let size = |path: &Path| std::fs::metadata(path).map(|m| m.len());
+
+        // the extension says zstd - for JSON and tables
+        let json = dir.path().join("factors.json.zst");
+        map.write_with(&json, JsonLayout::Pretty)?;
+        assert!(crate::input::is_zstd_magic(&std::fs::read(&json)?));
+        assert!(size(&json)? * 5 < size(&plain)?, "much smaller than the plain file");
+        assert_eq!(FactorMap::read(&json)?, map, "lossless");
+        let table = dir.path().join("factors.tsv.zst");
+        assert_eq!(FactorsFileFormat::from_path(&table), FactorsFileFormat::Tsv);
+        map.write(&table)?;
+        assert_eq!(FactorMap::read(&table)?.factors, map.factors);
+
+        // asked for: a plain name is sniffed by its magic bytes
+        let packed = dir.path().join("packed.json");
+        map.write_compressed(&packed, JsonLayout::Compact, Compression::Zstd(19))?;
+        assert_eq!(FactorMap::from_json(&packed)?, map);
+        Ok(())
+    }
+
+    #[test]
+    fn test_factor_map_json_round_trip() {
+        let mut color = Factor::new("Color", false);
+        let _ = color.push("Red");
+        let _ = color.push("Blue");
+        let map = FactorMap::new(vec![color.as_json("Color")]);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("factors.json");
+        map.write_json(&path).unwrap();
+        let back = FactorMap::from_json(&path).unwrap();
+
+        assert_eq!(back, map);
+        assert_eq!(back.get("Color").unwrap().levels, vec!["Red", "Blue"]);
+        assert!(back.get("Shape").is_none());
+        assert_eq!(back.to_factors()["Color"].get_f64("Blue"), 1.0);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, "cell\n").unwrap();
+        let mut meta = Provenance::now(&[&input]);
+        assert_eq!(meta.inputs, [std::fs::canonicalize(&input).unwrap().display().to_string()]);
+        assert!(meta.tool.starts_with("rust_data_table "), "{}", meta.tool);
+        assert!(chrono::NaiveDateTime::parse_from_str(&meta.generated, "%Y-%m-%dT%H:%M:%SZ").is_ok(), "{}", meta.generated);
+        meta.delimiter = "\t".into();
+        meta.categorical = vec!["cluster".into()];
+
+        let plain = FactorMap::default();
+        let json = serde_json::to_string(&plain).unwrap();
+        assert!(!json.contains("_meta"), "{json}");
+        let map = FactorMap { meta: Some(meta), ..Default::default() };
+        let json = serde_json::to_value(&map).unwrap();
+        assert_eq!(json["_meta"]["delimiter"], "\t");
+        assert!(json["_meta"].get("command").is_none(), "no command line given");
+
+        let path = dir.path().join("factors.json");
+        map.write_json(&path).unwrap();
+        assert_eq!(FactorMap::from_json(&path).unwrap(), map);
+        assert!(plain.diff(&map).is_empty(), "the origin is no change");
+        assert_eq!(Provenance::now(&["-"]).inputs, ["-"]);
+    }
+
+    #[test]
+    fn test_factor_map_tsv_round_trip() {
+        let tsv = "cell type\tage\tstage\nB cell\t40\tlow\nT, CD4\t52\thigh\nB cell\tNA\t\n";
+        let mut data = crate::SurvivalData::from_reader(std::io::Cursor::new(tsv), b'\t', Default::default()).unwrap();
+        data.relabel_levels(&[("cell type".to_string(), [("B cell".to_string(), "B".to_string())].into())].into()).unwrap();
+        let mut map = data.compute_factors();
+        map.factors[1].one_hot = true;
+        map.factors[1].ordered = true;
+        map.apply_palette(crate::Palette::Set2, crate::Palette::Viridis);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("meta.factors.tsv");
+        assert_eq!(FactorsFileFormat::from_path(&path), FactorsFileFormat::Tsv);
+        assert_eq!(FactorsFileFormat::from_path("meta.factors.json"), FactorsFileFormat::Json);
+        map.write(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("column\tdtype\tlevel\tnumeric\tlabel\tcolor\tcount\tordered\tone_hot\tsource\ncell type\tfactor\tB cell\t0.0\tB\t#66c2a5\t2\tfalse\tfalse\tinferred\n"), "{text}");
+        assert!(text.contains("\nage\tinteger\t\t\t\t\t\t\t\t\n"), "{text}");
+
+        let back = FactorMap::read(&path).unwrap();
+        let without_missing = |map: &FactorMap| map.factors.iter().map(|f| FactorJson { missing: None, ..f.clone() }).collect::<Vec<_>>();
+        assert_eq!(without_missing(&back), without_missing(&map));
+        let types = |map: &FactorMap| map.columns.iter().map(|c| (c.name.clone(), c.dtype, c.cardinality)).collect::<Vec<_>>();
+        assert_eq!(types(&back), types(&map));
+
+        std::fs::write(&path, "column\tdtype\tlevel\tnumeric\tlabel\tcolor\tcount\tordered\tone_hot\ns\tfactor\ta\t\t\t\t\t\t\nn\tinteger\t\t\t\t\t\t\t\ns\tfactor\tb\t\t\t\t\t\t\n").unwrap();
+        let err = FactorMap::from_tsv(&path).unwrap_err();
+        assert!(err.to_string().ends_with("line 4: the rows of column 's' are not next to each other"), "{err}");
+    }
+
+    #[test]
+    fn test_from_r_tsv() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("levels.tsv");
+        std::fs::write(&path, "column\tlevel\tcode\nstage\thigh\t2\nsex\tm\t1\nstage\tlow\t1\nsex\tf\t2\nstage\tmax\t3\n").unwrap();
+        let map = FactorMap::from_r_tsv(&path).unwrap();
+        let names: Vec<&str> = map.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["stage", "sex"]);
+        assert_eq!(map.column("stage").unwrap().cardinality, Some(3));
+        let stage = map.get("stage").unwrap();
+        assert_eq!(stage.levels, vec!["low", "high", "max"]);
+        assert_eq!(stage.numeric, Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(map.get("sex").unwrap().levels, vec!["m", "f"]);
+
+        let error = |text: &str| {
+            std::fs::write(&path, text).unwrap();
+            FactorMap::from_r_tsv(&path).unwrap_err().to_string()
+        };
+        let err = error("column\tlevel\tcode\ns\ta\t1\ns\tb\t3\n");
+        assert!(err.ends_with("line 3: the codes of column 's' jump from 1 to 3 at level 'b'"), "{err}");
+        let err = error("column\tlevel\tcode\ns\ta\t1\nt\tx\t1\ns\tb\t1\n");
+        assert!(err.ends_with("line 4: code 1 of column 's' is used twice"), "{err}");
+        let err = error("column\tlevel\tcode\ns\ta\t1\ns\ta\t2\n");
+        assert!(err.ends_with("line 3: level 'a' of 's' is listed twice"), "{err}");
+        let err = error("column\tlevel\tcode\ns\ta\tone\n");
+        assert!(err.ends_with("line 2: the code 'one' of level 'a' is not a whole number"), "{err}");
+    }
+
+    #[test]
+    fn test_read_legacy_factor_list() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("factors.json");
+        std::fs::write(&path, r#"[{"column":"sex","levels":["m","f"],"numeric":[0.0,1.0],"matching":null,"one_hot":false}]"#).unwrap();
+
+        let map = FactorMap::from_json(&path).unwrap();
+        assert!(map.columns.is_empty());
+        assert_eq!(map.get("sex").unwrap().levels, vec!["m", "f"]);
+    }
+
+    #[test]
+    fn test_reorder() {
+        let mut stage = Factor::new("stage", true);
+        let _ = stage.push("low");
+        let sex = Factor::new("sex", false);
+        let mut map = FactorMap::new(vec![sex.as_json("sex"), stage.as_json("stage")]);
+        map.columns = vec![
+            Column::new("sex", Dtype::Factor),
+            Column::new("age", Dtype::Integer),
+            Column::new("stage", Dtype::Factor),
+            Column::new("stage_low", Dtype::Integer),
+            Column::new("alive", Dtype::Boolean),
+            Column::new("bmi", Dtype::Float),
+        ];
+        let names = |map: &FactorMap| map.columns.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+
+        let mut typed = map.clone();
+        typed.reorder(ColumnOrder::Type);
+        assert_eq!(names(&typed), ["age", "bmi", "alive", "sex", "stage", "stage_low"]);
+        let mut alpha = map.clone();
+        alpha.reorder(ColumnOrder::Alpha);
+        assert_eq!(names(&alpha), ["age", "alive", "bmi", "sex", "stage", "stage_low"]);
+        alpha.factors.reverse();
+        alpha.reorder(ColumnOrder::Alpha);
+        assert_eq!(alpha.factors.iter().map(|f| f.column.as_str()).collect::<Vec<_>>(), ["sex", "stage"]);
+        let mut input = map.clone();
+        input.reorder(ColumnOrder::Input);
+        assert_eq!(input, map);
+        assert!(ColumnOrder::parse("size").is_err());
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let json = serde_json::to_value(FactorMap::default()).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert!(FactorMap::from_value(json).is_ok());
+        // the example of the help is a factors file of this version
+        let example = &FACTORS_FILE_HELP[FACTORS_FILE_HELP.find("\n{\n").unwrap()..FACTORS_FILE_HELP.find("\n}\n").unwrap() + 2];
+        let example = FactorMap::from_value(serde_json::from_str(example).unwrap()).unwrap();
+        assert_eq!((example.schema_version.as_str(), example.factors.len()), (SCHEMA_VERSION, 1));
+
+        let read = |v: &str| FactorMap::from_value(serde_json::from_str(v).unwrap());
+        assert!(read(r#"{"schema_version":"1.0","factors":[]}"#).is_ok());
+        let err = read(r#"{"schema_version":"2.0","factors":[]}"#).unwrap_err();
+        assert!(err.to_string().contains("Unsupported factors file schema_version '2.0'"), "{err}");
+        let err = read(r#"{"factors":[]}"#).unwrap_err();
+        assert!(err.to_string().contains("Missing \"schema_version\""), "{err}");
+        assert!(read(r#"{"schema_version":1,"factors":[]}"#).is_err());
+        assert!(read(r#"{"schema_version":"x","factors":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_level_codes() -> Result<()> {
+        use std::io::Cursor;
+        use crate::{ParseOptions, SurvivalData};
+        let dir = tempdir()?;
+        let path = dir.path().join("codes.json");
+        std::fs::write(&path, r#"{"cluster": {"T": 3, "B": 0, "retired": 7}}"#)?;
+        let codes = read_level_codes(&path)?;
+
+        // NK is new - it gets the code above the largest one, the retired 7 is not reused
+        let tsv = "cell\tcluster\nA\tNK\nB\tT\nC\tNA\nD\tB\nE\tDC\n";
+        let options = ParseOptions { level_codes: codes, sort_levels: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let cluster = &data.factors["cluster"];
+        assert_eq!(cluster.get_levels(), ["B", "T", "retired", "NK", "DC"]);
+        assert_eq!(data.as_vec_f64("cluster")[..2], [8.0, 3.0]);
+        assert_eq!(cluster.get_f64("DC"), 9.0);
+        assert_eq!(cluster.source, FactorSource::Declared);
+
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.compute_factors().get("cluster"), data.compute_factors().get("cluster"));
+
+        // merging rare levels keeps the pinned codes, the label gets a new one
+        let tsv = "cell\tcluster\nA\tT\nB\tNK\nC\tB\nD\tT\nE\tDC\nF\tB\n";
+        let mut options = ParseOptions { level_codes: [("cluster".to_string(), [("T".to_string(), 3), ("B".to_string(), 0)].into())].into(), ..Default::default() };
+        options.factor_specs.insert("cluster".into(), crate::FactorSpec::merge_rare(2));
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        for data in [SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?, streamed] {
+            let cluster = &data.factors["cluster"];
+            assert_eq!(cluster.get_levels(), ["B", "T", "Other"]);
+            assert_eq!((cluster.get_f64("T"), cluster.get_f64("Other"), cluster.get_f64("NK")), (3.0, 6.0, 6.0));
+            let json = data.compute_factors();
+            assert_eq!(json.get("cluster").unwrap().numeric, Some(vec![0.0, 3.0, 6.0]));
+        }
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.as_vec_f64("cluster"), [3.0, 6.0, 0.0, 3.0, 6.0, 0.0]);
+
+        std::fs::write(&path, r#"{"cluster": {"T": 1, "B": 1}}"#)?;
+        let err = read_level_codes(&path).unwrap_err().to_string();
+        assert!(err.starts_with("Column 'cluster': the levels 'B' and 'T' both have the code 1 in "), "{err}");
+        let unknown = ParseOptions { level_codes: [("clutser".to_string(), BTreeMap::new())].into(), ..Default::default() };
+        assert!(SurvivalData::from_reader_with_options(Cursor::new(tsv), &unknown).is_err());
+        Ok(())
+    }
+}
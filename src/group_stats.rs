@@ -0,0 +1,86 @@
+use serde::Serialize;
+use crate::column::NumericSummary;
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+/// Summary of a numeric column within one level of a factor (see `SurvivalData::group_stats()`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupStat {
+    pub level: String,
+    /// Rows of this level having a value
+    pub count: usize,
+    /// `None` if the level has no value
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl SurvivalData {
+    /// Count, mean, min and max of the numeric column `value` per level of the factor `by`
+    /// (e.g. the mean `percent.mt` per `cluster`) - in level order, levels without rows included.
+    /// Rows missing either value are left out.
+    pub fn group_stats(&self, by: &str, value: &str) -> Result<Vec<GroupStat>> {
+        let Some(factor) = self.factors.get(by) else {
+            bail!(Column, "Group column '{}' is not a factor", by);
+        };
+        if self.column(by).is_none() {
+            bail!(Column, "Group column '{}' not found", by);
+        }
+        match self.column(value) {
+            None => bail!(Column, "Value column '{}' not found", value),
+            Some(column) if !column.dtype.is_numeric() || self.factors.contains_key(value) => {
+                bail!(Column, "Value column '{}' must be numeric, but it is {:?}", value, column.dtype)
+            }
+            Some(_) => {}
+        }
+        let levels = factor.get_levels();
+        let codes: Vec<f64> = levels.iter().map(|level| factor.get_f64(level)).collect();
+        let mut summaries = vec![NumericSummary::default(); levels.len()];
+        for (group, value) in self.as_vec_f64(by).into_iter().zip(self.as_vec_f64(value)) {
+            if value.is_nan() {
+                continue;
+            }
+            if let Some(id) = codes.iter().position(|&code| code == group) {
+                summaries[id].push(value);
+            }
+        }
+        Ok(levels
+            .iter()
+            .zip(summaries)
+            .map(|(level, summary)| GroupStat {
+                level: level.clone(),
+                count: summary.count,
+                mean: summary.mean,
+                min: summary.min,
+                max: summary.max,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DataTableError;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_group_stats() -> Result<()> {
+        let tsv = "cluster\tpercent.mt\tnCount\nT\t1.5\t10\nB\t3\t20\nT\t2.5\tNA\nNA\t9\t5\nT\tNA\t7\nNK\tNA\t1\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let stats = data.group_stats("cluster", "percent.mt")?;
+        assert_eq!(stats[0], GroupStat { level: "T".into(), count: 2, mean: Some(2.0), min: Some(1.5), max: Some(2.5) });
+        assert_eq!(stats[1], GroupStat { level: "B".into(), count: 1, mean: Some(3.0), min: Some(3.0), max: Some(3.0) });
+        assert_eq!(stats[2], GroupStat { level: "NK".into(), count: 0, mean: None, min: None, max: None }, "no value");
+        assert_eq!(data.group_stats("cluster", "nCount")?[0].count, 2);
+
+        let err = data.group_stats("nCount", "percent.mt").unwrap_err();
+        assert!(matches!(err, DataTableError::Column(_)), "{err}");
+        let err = data.group_stats("cluster", "cluster").unwrap_err();
+        assert!(err.to_string().contains("must be numeric"), "{err}");
+        assert!(data.group_stats("cluster", "missing").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::Path;
+use crate::error::{bail, DataTableError, Result};
+use hdf5::types::{TypeDescriptor, VarLenAscii, VarLenUnicode};
+use hdf5::{Dataset, Group};
+use ndarray::Array2;
+use crate::column::{Column, Dtype, NumericSummary};
+use crate::factor::{Factor, FactorSpec};
+use crate::options::DEFAULT_MAX_LEVELS;
+use crate::SurvivalData;
+
+/// One decoded `/obs` column.
+enum ObsColumn {
+    Numeric(Dtype, Vec<f64>),
+    Boolean(Vec<f64>),
+    /// The factor and the codes of all rows (NaN: missing)
+    Factor(Factor, Vec<f64>),
+}
+
+/// The `encoding-type` attribute of an AnnData element (empty if there is none).
+fn encoding_type(group: &Group) -> String {
+    group
+        .attr("encoding-type")
+        .and_then(|a| a.read_scalar::<VarLenUnicode>())
+        .map(|s| s.as_str().to_string())
+        .unwrap_or_default()
+}
+
+/// Read a string array - variable length UTF-8 or ASCII.
+fn read_strings(ds: &Dataset) -> Result<Vec<String>> {
+    Ok(match ds.dtype()?.to_descriptor()? {
+        TypeDescriptor::VarLenUnicode => ds.read_raw::<VarLenUnicode>()?.iter().map(|s| s.as_str().to_string()).collect(),
+        TypeDescriptor::VarLenAscii => ds.read_raw::<VarLenAscii>()?.iter().map(|s| s.as_str().to_string()).collect(),
+        other => bail!(InvalidData, "Expected a string dataset, found {:?}", other),
+    })
+}
+
+/// Category labels - AnnData also stores numeric categories (e.g. cluster ids).
+fn read_categories(ds: &Dataset) -> Result<Vec<String>> {
+    match ds.dtype()?.to_descriptor()? {
+        TypeDescriptor::Integer(_) | TypeDescriptor::Unsigned(_) | TypeDescriptor::Float(_) => {
+            Ok(ds.read_raw::<f64>()?.iter().map(|v| v.to_string()).collect())
+        }
+        _ => read_strings(ds),
+    }
+}
+
+/// A categorical: `codes` index into `categories`, -1 is missing.
+fn read_categorical(name: &str, group: &Group) -> Result<ObsColumn> {
+    let categories = read_categories(&group.dataset("categories")?)?;
+    let ordered = group.attr("ordered").and_then(|a| a.read_scalar::<bool>()).unwrap_or(false);
+    let spec = FactorSpec { ordered, levels: categories, ..Default::default() };
+    let mut factor = Factor::from_spec(name, &spec);
+    let codes = group
+        .dataset("codes")?
+        .read_raw::<i64>()?
+        .into_iter()
+        .map(|c| match c {
+            c if c < 0 => Ok(factor.count("")),
+            c => match spec.levels.get(c as usize) {
+                Some(level) => Ok(factor.count(level)),
+                None => bail!(InvalidData, "obs column '{}': code {} has no category", name, c),
+            },
+        })
+        .collect::<Result<_>>()?;
+    Ok(ObsColumn::Factor(factor, codes))
+}
+
+/// `nullable-integer` / `nullable-boolean`: `values` plus a `mask` that is true for missing values.
+fn read_nullable(group: &Group, boolean: bool) -> Result<ObsColumn> {
+    let mask = group.dataset("mask")?.read_raw::<bool>()?;
+    let values: Vec<f64> = if boolean {
+        group.dataset("values")?.read_raw::<bool>()?.into_iter().map(|b| if b { 1.0 } else { 0.0 }).collect()
+    } else {
+        group.dataset("values")?.read_raw::<f64>()?
+    };
+    let values = values.into_iter().zip(mask).map(|(v, m)| if m { f64::NAN } else { v }).collect();
+    Ok(if boolean { ObsColumn::Boolean(values) } else { ObsColumn::Numeric(Dtype::Integer, values) })
+}
+
+/// A plain dataset: numbers, booleans or strings (strings become a factor in order of appearance).
+fn read_dataset(name: &str, ds: &Dataset) -> Result<ObsColumn> {
+    Ok(match ds.dtype()?.to_descriptor()? {
+        TypeDescriptor::Integer(_) | TypeDescriptor::Unsigned(_) => ObsColumn::Numeric(Dtype::Integer, ds.read_raw::<f64>()?),
+        TypeDescriptor::Float(_) => ObsColumn::Numeric(Dtype::Float, ds.read_raw::<f64>()?),
+        TypeDescriptor::Boolean => {
+            ObsColumn::Boolean(ds.read_raw::<bool>()?.into_iter().map(|b| if b { 1.0 } else { 0.0 }).collect())
+        }
+        _ => {
+            let mut factor = Factor::new(name, false);
+            let codes = read_strings(ds)?.iter().map(|s| factor.count(s)).collect();
+            ObsColumn::Factor(factor, codes)
+        }
+    })
+}
+
+impl SurvivalData {
+    /// Read the `/obs` table of an AnnData `.h5ad` file - no `meta.tsv` export needed.
+    ///
+    /// Categoricals keep their category order (and `ordered` flag) as factors, numeric columns
+    /// become integer / float columns and boolean columns `Boolean`. Plain string columns are
+    /// factors or - with more than `DEFAULT_MAX_LEVELS` values - free text.
+    /// The obs index is the first column, called `barcode` like in a TSV export.
+    pub fn from_h5ad<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(DataTableError::FileNotFound(path.to_path_buf()));
+        }
+        let file = hdf5::File::open(path)?;
+        let Ok(obs) = file.group("obs") else {
+            bail!(InvalidData, "{:?} has no /obs group", path);
+        };
+
+        let index = obs
+            .attr("_index")
+            .and_then(|a| a.read_scalar::<VarLenUnicode>())
+            .map_or("_index".to_string(), |s| s.as_str().to_string());
+        let names: Vec<String> = obs
+            .attr("column-order")
+            .and_then(|a| a.read_raw::<VarLenUnicode>())
+            .map(|names| names.iter().map(|n| n.as_str().to_string()).collect())
+            .unwrap_or_default();
+
+        let mut obs_columns = vec![("barcode".to_string(), read_dataset("barcode", &obs.dataset(&index)?)?)];
+        for name in names {
+            let column = match obs.group(&name) {
+                Ok(group) => match encoding_type(&group).as_str() {
+                    "categorical" => read_categorical(&name, &group)?,
+                    "nullable-integer" => read_nullable(&group, false)?,
+                    "nullable-boolean" => read_nullable(&group, true)?,
+                    other => bail!(InvalidData, "obs column '{}' has the unsupported encoding '{}'", name, other),
+                },
+                Err(_) => read_dataset(&name, &obs.dataset(&name)?)?,
+            };
+            obs_columns.push((name, column));
+        }
+
+        let mut ret = SurvivalData::default();
+        let mut headers = Vec::with_capacity(obs_columns.len());
+        let mut columns = Vec::with_capacity(obs_columns.len());
+        let mut values: Vec<Vec<f64>> = Vec::with_capacity(obs_columns.len());
+        for (name, obs_column) in obs_columns {
+            let (column, encoded) = match obs_column {
+                ObsColumn::Numeric(dtype, encoded) => {
+                    let mut column = Column::new(&name, dtype);
+                    column.summary = Some(NumericSummary::from_values(&encoded));
+                    (column, encoded)
+                }
+                ObsColumn::Boolean(encoded) => (Column::new(&name, Dtype::Boolean), encoded),
+                ObsColumn::Factor(factor, encoded) => {
+                    let cardinality = factor.get_levels().len();
+                    let dtype = if cardinality > DEFAULT_MAX_LEVELS { Dtype::FreeText } else { Dtype::Factor };
+                    let mut column = Column::new(&name, dtype);
+                    column.cardinality = Some(cardinality);
+                    ret.factors.insert(name.clone(), factor);
+                    (column, encoded)
+                }
+            };
+            let mut column = column;
+            column.missing = encoded.iter().filter(|v| v.is_nan()).count();
+            headers.push(name);
+            columns.push(column);
+            values.push(encoded);
+        }
+
+        let n_rows = values.first().map_or(0, |v| v.len());
+        if let Some((name, _)) = headers.iter().zip(&values).find(|(_, v)| v.len() != n_rows) {
+            bail!(InvalidData, "obs column '{}' does not have {} rows", name, n_rows);
+        }
+        ret.numeric_data = Array2::from_shape_fn((n_rows, values.len()), |(i, j)| values[j][i]);
+        ret.set_columns(headers, columns);
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn strings(values: &[&str]) -> Vec<VarLenUnicode> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_from_h5ad() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("pbmc.h5ad");
+        {
+            let file = hdf5::File::create(&path)?;
+            let obs = file.create_group("obs")?;
+            obs.new_attr::<VarLenUnicode>().create("_index")?.write_scalar(&"_index".parse::<VarLenUnicode>()?)?;
+            obs.new_attr_builder().with_data(&strings(&["cluster", "n_genes", "doublet"])).create("column-order")?;
+            obs.new_dataset_builder().with_data(&strings(&["AAAC", "AAAG", "AACT"])).create("_index")?;
+            let cluster = obs.create_group("cluster")?;
+            cluster.new_attr::<VarLenUnicode>().create("encoding-type")?.write_scalar(&"categorical".parse::<VarLenUnicode>()?)?;
+            cluster.new_attr::<bool>().create("ordered")?.write_scalar(&false)?;
+            cluster.new_dataset_builder().with_data(&strings(&["T cell", "B cell"])).create("categories")?;
+            cluster.new_dataset_builder().with_data(&[1_i8, 0, -1]).create("codes")?;
+            obs.new_dataset_builder().with_data(&[812_i32, 1020, 640]).create("n_genes")?;
+            obs.new_dataset_builder().with_data(&[false, true, false]).create("doublet")?;
+        }
+
+        let data = SurvivalData::from_h5ad(&path)?;
+        assert_eq!(data.column_names(), ["barcode", "cluster", "n_genes", "doublet"]);
+        assert_eq!(data.factors["cluster"].get_levels(), ["T cell", "B cell"], "category order is kept");
+        let cluster = data.as_vec_string("cluster").unwrap();
+        assert_eq!(cluster[..2], ["B cell".to_string(), "T cell".to_string()]);
+        assert_eq!(data.column("cluster").unwrap().missing, 1);
+        assert_eq!(data.column("n_genes").unwrap().dtype, Dtype::Integer);
+        assert_eq!(data.column("doublet").unwrap().dtype, Dtype::Boolean);
+        assert_eq!(data.as_vec_f64("doublet"), vec![0.0, 1.0, 0.0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+use ndarray::Array2;
+use serde::Serialize;
+use crate::column::{quantile, Column, Dtype};
+use crate::error::{bail, Result};
+use crate::factor::Factor;
+use crate::SurvivalData;
+
+/// How `SurvivalData::histogram_with()` places the bin edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Binning {
+    /// Bins of the same width between the minimum and the maximum
+    #[default]
+    EqualWidth,
+    /// Bins with about the same number of values - edges at the quantiles
+    Quantile,
+}
+
+/// The distribution of a numeric column: bin `i` holds the values from `edges[i]` up to
+/// (not including) `edges[i + 1]` - the last bin includes its upper edge.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Histogram {
+    /// Ascending bin edges, one more than there are bins
+    pub edges: Vec<f64>,
+    /// Number of values per bin
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Bin the non-missing `values`. No values give no bins and a constant column one
+    /// degenerate bin (both edges the value); quantile edges that coincide are merged.
+    pub fn fit(values: &[f64], bins: usize, binning: Binning) -> Self {
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+        sorted.sort_by(f64::total_cmp);
+        let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+            return Histogram { edges: Vec::new(), counts: Vec::new() };
+        };
+        if min == max || bins == 0 {
+            return Histogram { edges: vec![min, max], counts: vec![sorted.len()] };
+        }
+        let mut edges: Vec<f64> = match binning {
+            Binning::EqualWidth => (0..bins).map(|i| min + (max - min) * i as f64 / bins as f64).chain([max]).collect(),
+            Binning::Quantile => (0..=bins).filter_map(|i| quantile(&sorted, i as f64 / bins as f64)).collect(),
+        };
+        edges.dedup();
+        let last = edges.len() - 2;
+        let mut counts = vec![0; last + 1];
+        for value in sorted {
+            let bin = edges.partition_point(|&edge| edge <= value).saturating_sub(1);
+            counts[bin.min(last)] += 1;
+        }
+        Histogram { edges, counts }
+    }
+}
+
+/// How `SurvivalData::bin_quantiles_with()` stores the binned column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BinOptions {
+    /// Keep the numeric column and add the factor right after it as `<column>_bin`
+    /// (default: the factor replaces the column)
+    pub keep: bool,
+}
+
+/// `[a,b)` per bin, `[a,b]` for the last one. The edges are written with as few significant
+/// digits (at least 3) as keep them apart - the exact edges are in `Factor::bin_edges()`.
+fn bin_labels(edges: &[f64]) -> Vec<String> {
+    let rounded = |digits: i32| -> Vec<String> { edges.iter().map(|&edge| round_significant(edge, digits).to_string()).collect() };
+    let written = (3..=17)
+        .map(rounded)
+        .find(|written| written.windows(2).all(|pair| pair[0] != pair[1]) || edges.len() == 2)
+        .unwrap_or_else(|| edges.iter().map(f64::to_string).collect());
+    let last = written.len() - 2;
+    (0..=last)
+        .map(|i| format!("[{},{}{}", written[i], written[i + 1], if i == last { ']' } else { ')' }))
+        .collect()
+}
+
+fn round_significant(value: f64, digits: i32) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let decimals = digits - 1 - value.abs().log10().floor() as i32;
+    match decimals >= 0 {
+        true => (value * 10f64.powi(decimals)).round() / 10f64.powi(decimals),
+        false => (value / 10f64.powi(-decimals)).round() * 10f64.powi(-decimals),
+    }
+}
+
+impl SurvivalData {
+    /// Equal width histogram of the numeric column `column` with `bins` bins (missing values
+    /// are left out) - see `Histogram::fit()`.
+    pub fn histogram(&self, column: &str, bins: usize) -> Result<Histogram> {
+        self.histogram_with(column, bins, Binning::EqualWidth)
+    }
+
+    /// Histogram of the numeric column `column` with `bins` bins placed by `binning`.
+    /// A column without any value (`Dtype::AllMissing`) has no bins.
+    pub fn histogram_with(&self, column: &str, bins: usize, binning: Binning) -> Result<Histogram> {
+        match self.column(column) {
+            None => bail!(Column, "Column '{}' not found", column),
+            Some(c) if !(c.dtype.is_numeric() || c.dtype == Dtype::AllMissing) || self.factors.contains_key(column) => {
+                bail!(Column, "Histogram column '{}' must be numeric, but it is {:?}", column, c.dtype)
+            }
+            Some(_) => {}
+        }
+        if bins == 0 {
+            bail!(InvalidArgument, "A histogram needs at least one bin");
+        }
+        Ok(Histogram::fit(&self.as_vec_f64(column), bins, binning))
+    }
+
+    /// `bin_quantiles_with()` replacing the numeric column by the factor.
+    pub fn bin_quantiles(&mut self, column: &str, bins: usize) -> Result<String> {
+        self.bin_quantiles_with(column, bins, &BinOptions::default())
+    }
+
+    /// Discretize the numeric column `column` into an ordered factor of `bins` quantile bins
+    /// (quartiles for 4): the edges of `histogram_with(column, bins, Binning::Quantile)`, the
+    /// levels `[0,0.5)`, `[0.5,1.2)` ... in ascending order with codes in that order. The edges
+    /// are kept in the factor (`Factor::bin_edges()`, `bins` in the factors file); missing
+    /// values stay missing. Returns the name of the factor column.
+    pub fn bin_quantiles_with(&mut self, column: &str, bins: usize, options: &BinOptions) -> Result<String> {
+        let Some(index) = self.column_index(column) else {
+            bail!(Column, "Column '{}' not found", column);
+        };
+        let edges = self.histogram_with(column, bins, Binning::Quantile)?.edges;
+        if edges.is_empty() {
+            bail!(InvalidData, "Cannot bin '{}': the column has no values", column);
+        }
+        let name = match options.keep {
+            true => format!("{}_bin", column),
+            false => column.to_string(),
+        };
+        if options.keep && self.column_index(&name).is_some() {
+            bail!(InvalidArgument, "Cannot bin '{}': the table has a column '{}' already", column, name);
+        }
+
+        let labels = bin_labels(&edges);
+        let mut factor = Factor::new(&name, false).declared();
+        factor.ordered = true;
+        for label in &labels {
+            factor.level_to_index(label);
+        }
+        let last = labels.len() - 1;
+        let codes: Vec<f64> = self
+            .numeric_data
+            .column(index)
+            .iter()
+            .map(|&value| match value.is_nan() {
+                true => factor.count(""),
+                false => factor.count(&labels[edges.partition_point(|&edge| edge <= value).saturating_sub(1).min(last)]),
+            })
+            .collect();
+        factor.set_bin_edges(edges);
+
+        let position = match options.keep {
+            true => {
+                let at = index + 1;
+                self.numeric_data = Array2::from_shape_fn((self.n_rows(), self.headers.len() + 1), |(row, col)| match col.cmp(&at) {
+                    Ordering::Less => self.numeric_data[[row, col]],
+                    Ordering::Equal => codes[row],
+                    Ordering::Greater => self.numeric_data[[row, col - 1]],
+                });
+                let (mut headers, mut columns) = (self.headers.clone(), self.columns.clone());
+                let mut binned = Column::new(&name, Dtype::Factor);
+                binned.missing = self.columns[index].missing;
+                binned.missing_kinds = self.columns[index].missing_kinds;
+                headers.insert(at, name.clone());
+                columns.insert(at, binned);
+                self.set_columns(headers, columns);
+                at
+            }
+            false => {
+                self.numeric_data.column_mut(index).iter_mut().zip(&codes).for_each(|(cell, &code)| *cell = code);
+                index
+            }
+        };
+        let info = &mut self.columns[position];
+        info.dtype = Dtype::Factor;
+        info.cardinality = Some(labels.len());
+        info.summary = None;
+        self.factors.insert(name.clone(), factor);
+        self.mark_constant();
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_histogram() -> Result<()> {
+        let tsv = "score\tdose\tempty\tgroup\n0\t5\tNA\ta\n1\t5\tNA\tb\n2\tNA\tNA\ta\n3\t5\tNA\tb\n10\t5\tNA\ta\nNA\t5\tNA\tb\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let equal = data.histogram("score", 2)?;
+        assert_eq!(equal, Histogram { edges: vec![0.0, 5.0, 10.0], counts: vec![4, 1] }, "the maximum is in the last bin");
+        let quantiles = data.histogram_with("score", 4, Binning::Quantile)?;
+        assert_eq!(quantiles.edges, vec![0.0, 1.0, 2.0, 3.0, 10.0]);
+        assert_eq!(quantiles.counts, vec![1, 1, 1, 2]);
+
+        assert_eq!(data.histogram("dose", 3)?, Histogram { edges: vec![5.0, 5.0], counts: vec![5] }, "constant");
+        assert_eq!(data.histogram("empty", 3)?.counts, Vec::<usize>::new(), "no values, no bins");
+        // ties merge quantile edges
+        assert_eq!(Histogram::fit(&[1.0, 1.0, 1.0, 2.0], 4, Binning::Quantile), Histogram { edges: vec![1.0, 1.25, 2.0], counts: vec![3, 1] });
+
+        assert!(data.histogram("group", 2).unwrap_err().to_string().contains("must be numeric"));
+        assert!(data.histogram("score", 0).is_err());
+        assert!(data.histogram("nope", 2).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bin_quantiles() -> Result<()> {
+        let tsv = "cell\tscore\tdose\tempty\nA\t0\t5\tNA\nB\t1\t5\tNA\nC\tNA\t5\tNA\nD\t3\t5\tNA\nE\t10\t5\tNA\n";
+        let mut data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        assert_eq!(data.bin_quantiles_with("score", 2, &BinOptions { keep: true })?, "score_bin");
+        assert_eq!(data.column_names(), ["cell", "score", "score_bin", "dose", "empty"]);
+        let binned = data.column("score_bin").unwrap();
+        assert_eq!((binned.dtype, binned.cardinality, binned.missing), (Dtype::Factor, Some(2), 1));
+        let factor = &data.factors["score_bin"];
+        assert_eq!(factor.get_levels(), ["[0,2)", "[2,10]"]);
+        assert!(factor.ordered);
+        assert_eq!(factor.bin_edges(), Some(&[0.0, 2.0, 10.0][..]));
+        assert_eq!(data.as_vec_string("score_bin").unwrap(), ["[0,2)", "[0,2)", "NA", "[2,10]", "[2,10]"], "the maximum is in the last bin");
+        assert_eq!(data.as_vec_f64("dose"), vec![5.0; 5], "the later columns moved");
+        let json = data.compute_factors();
+        assert_eq!(json.factors.iter().find(|f| f.column == "score_bin").unwrap().bins, Some(vec![0.0, 2.0, 10.0]));
+
+        assert!(data.bin_quantiles_with("score", 2, &BinOptions { keep: true }).unwrap_err().to_string().contains("has a column 'score_bin' already"));
+        assert_eq!(data.bin_quantiles("score", 3)?, "score");
+        assert_eq!(data.factors["score"].get_levels(), ["[0,1)", "[1,3)", "[3,10]"]);
+        assert_eq!(data.column("score").unwrap().dtype, Dtype::Factor);
+        assert!(data.bin_quantiles("score", 3).unwrap_err().to_string().contains("must be numeric"), "binned already");
+
+        data.bin_quantiles("dose", 4)?;
+        assert_eq!(data.factors["dose"].get_levels(), ["[5,5]"], "constant");
+        assert!(data.bin_quantiles("empty", 4).unwrap_err().to_string().contains("no values"));
+        assert!(data.bin_quantiles("cell", 4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bin_labels() {
+        assert_eq!(bin_labels(&[0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0]), ["[0,0.333)", "[0.333,0.667)", "[0.667,1]"]);
+        assert_eq!(bin_labels(&[1.0, 1.0001, 12345.0]), ["[1,1.0001)", "[1.0001,12345]"], "digits until the edges differ");
+        assert_eq!(bin_labels(&[-0.5, 123456.0]), ["[-0.5,123000]"]);
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, HashSet};
+use crate::column::Dtype;
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+impl SurvivalData {
+    /// The columns that could identify the rows (e.g. a cell barcode): every row has a value
+    /// and no value is there twice. Only factor, free text and integer columns qualify - float,
+    /// date and boolean values are measurements, not names. One-hot columns are left out.
+    pub fn candidate_id_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter(|c| self.id_problem(&c.name).is_none())
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Mark `column` as the row identifier (`Column::id` in the factors file) - an error if it
+    /// is none of the `candidate_id_columns()`, naming a duplicate or missing value.
+    /// An earlier identifier is unmarked.
+    pub fn set_id_column(&mut self, column: &str) -> Result<()> {
+        if let Some(problem) = self.id_problem(column) {
+            bail!(Column, "Column '{}' cannot be the row identifier: {}", column, problem);
+        }
+        for c in self.columns.iter_mut() {
+            c.id = c.name == column;
+        }
+        Ok(())
+    }
+
+    /// The row identifier set with `set_id_column()`.
+    pub fn id_column(&self) -> Option<&str> {
+        self.columns.iter().find(|c| c.id).map(|c| c.name.as_str())
+    }
+
+    /// Why `name` does not identify the rows - `None` if it does.
+    fn id_problem(&self, name: &str) -> Option<String> {
+        let Some(index) = self.column_index(name) else {
+            return Some("no such column".to_string());
+        };
+        let column = &self.columns[index];
+        let indicators: HashSet<String> = self.factors.values().filter(|f| f.one_hot).flat_map(|f| f.all_column_names()).collect();
+        if indicators.contains(name) {
+            return Some("it is a one-hot column".to_string());
+        }
+        if !matches!(column.dtype, Dtype::Factor | Dtype::FreeText | Dtype::Integer) {
+            return Some(format!("it is a {} column", column.dtype));
+        }
+        let rows = self.streamed_rows.unwrap_or(self.n_rows());
+        if rows == 0 {
+            return Some("the table has no rows".to_string());
+        }
+        if column.missing > 0 {
+            return Some(format!("{} row(s) have no value", column.missing));
+        }
+        if self.n_rows() != rows {
+            // streamed without keeping the rows: only the level count is known
+            return match column.cardinality {
+                Some(levels) if levels == rows => None,
+                Some(levels) => Some(format!("{} distinct values in {} rows", levels, rows)),
+                None => Some("the values of a streamed number column are not kept".to_string()),
+            };
+        }
+        let factor = self.factors.get(name);
+        let mut seen: HashMap<u64, usize> = HashMap::with_capacity(rows);
+        for (row, &value) in self.numeric_data.column(index).iter().enumerate() {
+            if let Some(first) = seen.insert(value.to_bits(), row) {
+                let shown = match factor.and_then(|f| f.level_at(value)) {
+                    Some(level) => level.to_string(),
+                    None => value.to_string(),
+                };
+                return Some(format!("'{}' is in data rows {} and {}", shown, first + 1, row + 1));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_id_columns() -> Result<()> {
+        let tsv = "barcode\tcluster\tindex\tscore\tsample\nAAAC\tT\t1\t0.5\ts1\nAAAG\tB\t2\t0.7\tNA\nAAAT\tT\t3\t0.9\ts2\n";
+        let options = ParseOptions { max_levels: Some(2), ..Default::default() };
+        let mut data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.candidate_id_columns(), vec!["barcode", "index"], "floats and columns with gaps or repeats are no ids");
+        assert_eq!(data.id_column(), None);
+
+        data.set_id_column("barcode")?;
+        assert_eq!(data.id_column(), Some("barcode"));
+        assert!(data.compute_factors().column("barcode").unwrap().id);
+        data.set_id_column("index")?;
+        assert_eq!(data.id_column(), Some("index"), "one identifier at a time");
+
+        let err = data.set_id_column("cluster").unwrap_err();
+        assert_eq!(err.to_string(), "Column 'cluster' cannot be the row identifier: 'T' is in data rows 1 and 3");
+        let err = data.set_id_column("sample").unwrap_err();
+        assert_eq!(err.to_string(), "Column 'sample' cannot be the row identifier: 1 row(s) have no value");
+        assert!(data.set_id_column("score").unwrap_err().to_string().ends_with("it is a float column"));
+        assert!(data.set_id_column("nope").is_err());
+        assert_eq!(data.id_column(), Some("index"), "a failed call keeps the identifier");
+        Ok(())
+    }
+}
@@ -0,0 +1,724 @@
+use std::collections::HashSet;
+use crate::error::{bail, DataTableError, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde_json::{Map, Value};
+use crate::column::Dtype;
+use crate::options::{NumberFormat, ParseOptions, SampleCheck, DATE_FORMATS};
+
+/// Is `value` one of the missing value `tokens`? The comparison ignores ASCII case.
+pub(crate) fn is_missing<S: AsRef<str>>(value: &str, tokens: &[S]) -> bool {
+    tokens.iter().any(|t| t.as_ref().eq_ignore_ascii_case(value))
+}
+
+/// Read a cell as boolean - `None` if it is in neither token list (compared ignoring ASCII case).
+pub(crate) fn parse_boolean<S: AsRef<str>>(value: &str, true_tokens: &[S], false_tokens: &[S]) -> Option<bool> {
+    if is_missing(value, true_tokens) {
+        Some(true)
+    } else if is_missing(value, false_tokens) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Is a cell written like a number - digits with an optional leading `-`/`+`, separators and
+/// an exponent? Rust also parses `inf`, `infinity` and `nan`, which are words in a table.
+pub(crate) fn is_number_text(value: &str) -> bool {
+    value.bytes().all(|b| !b.is_ascii_alphabetic() || b == b'e' || b == b'E')
+}
+
+/// Parse a cell as float - a decimal comma is accepted (`0,5`), a sign too (`-2.3`, `+1`).
+pub(crate) fn parse_float(value: &str) -> Option<f64> {
+    if !is_number_text(value) {
+        return None;
+    }
+    value.replace(',', ".").parse::<f64>().ok()
+}
+
+/// Parse a cell as integer - a decimal point disqualifies the value.
+pub(crate) fn parse_integer(value: &str) -> Option<i64> {
+    value.parse::<i64>().ok()
+}
+
+/// Does a number-like cell start with a significant zero (`007`, `-012`)? `0` and `0.5` do not.
+pub(crate) fn has_leading_zero(value: &str) -> bool {
+    let mut chars = value.strip_prefix(['-', '+']).unwrap_or(value).chars();
+    chars.next() == Some('0') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Parse a cell as date (time) in `format` - seconds since 1970-01-01.
+pub(crate) fn parse_date(value: &str, format: &str) -> Option<f64> {
+    let datetime = if has_time(format) {
+        NaiveDateTime::parse_from_str(value, format).ok()?
+    } else {
+        NaiveDate::parse_from_str(value, format).ok()?.and_hms_opt(0, 0, 0)?
+    };
+    Some(datetime.and_utc().timestamp() as f64)
+}
+
+/// The ISO-8601 text of a stored date: `2023-05-14`, or `2023-05-14T08:30:00` if `format` has a time.
+pub(crate) fn format_date(seconds: f64, format: &str) -> String {
+    match DateTime::from_timestamp(seconds as i64, 0) {
+        Some(datetime) if has_time(format) => datetime.naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string(),
+        Some(datetime) => datetime.naive_utc().format("%Y-%m-%d").to_string(),
+        None => "NA".to_string(),
+    }
+}
+
+fn has_time(format: &str) -> bool {
+    format.contains("%H")
+}
+
+/// The first of `DATE_FORMATS` every non-missing cell parses with - `None` if there is none
+/// (or no value at all): mixed formats are no date column.
+pub(crate) fn date_format<S: AsRef<str>>(cells: &[S], options: &ParseOptions) -> Option<&'static str> {
+    let mut values = cells.iter().map(|c| c.as_ref()).filter(|c| !options.is_missing(c)).peekable();
+    values.peek()?;
+    let values: Vec<&str> = values.collect();
+    DATE_FORMATS
+        .iter()
+        .find(|format| values.iter().all(|v| parse_date(v, format).is_some()))
+        .copied()
+}
+
+/// The type of a column after seeing one more (non-missing) `value` written in `format`.
+/// `current` is `None` as long as the column had no value.
+pub(crate) fn refine_dtype(current: Option<Dtype>, value: &str, format: NumberFormat) -> Dtype {
+    match current {
+        Some(Dtype::Factor) | Some(Dtype::FreeText) | Some(Dtype::Date) => Dtype::Factor,
+        Some(Dtype::Float) if format.parse_float(value).is_some() => Dtype::Float,
+        None | Some(Dtype::Integer) if format.parse_integer(value).is_some() => Dtype::Integer,
+        None | Some(Dtype::Integer) if format.parse_float(value).is_some() => Dtype::Float,
+        _ => Dtype::Factor,
+    }
+}
+
+/// Classify the (trimmed) cells of one column, numbers written in `format`.
+///
+/// Cells matching one of the `missing` tokens are ignored.
+/// A column is `Integer` only if every non-missing value parses as `i64`,
+/// `Float` if every non-missing value parses as `f64` and `Factor` otherwise.
+/// A column without any value is reported as `Float`.
+pub(crate) fn infer_number_dtype<S: AsRef<str>, M: AsRef<str>>(values: &[S], missing: &[M], format: NumberFormat) -> Dtype {
+    let mut dtype = None;
+    for value in values.iter().map(|v| v.as_ref()).filter(|v| !is_missing(v, missing)) {
+        let refined = refine_dtype(dtype, value, format);
+        if refined == Dtype::Factor {
+            return Dtype::Factor;
+        }
+        dtype = Some(refined);
+    }
+    dtype.unwrap_or(Dtype::Float)
+}
+
+/// `infer_number_dtype()` - but with `preserve_leading_zeros` a numeric column holding a value
+/// like `007` is an identifier and becomes a `Factor`.
+fn infer_numeric(cells: &[String], options: &ParseOptions) -> Dtype {
+    let dtype = infer_number_dtype(cells, &options.missing_tokens, options.number_format);
+    if dtype.is_numeric() && cells.iter().any(|c| options.keeps_leading_zero(c)) {
+        return Dtype::Factor;
+    }
+    dtype
+}
+
+/// A value in the configured `number_format` and one that is only a number in the default
+/// format - a column mixing them is no number column.
+pub(crate) fn mixed_number_formats<'a>(cells: &'a [String], options: &ParseOptions) -> Option<(&'a str, &'a str)> {
+    let format = options.number_format;
+    if format.is_default() {
+        return None;
+    }
+    let mut values = cells.iter().filter(|c| !options.is_missing(c));
+    if !values.clone().all(|c| format.parse_float(c).is_some() || parse_float(c).is_some()) {
+        return None;
+    }
+    let configured = values.clone().find(|c| format.parse_float(c).is_some())?;
+    let other = values.find(|c| format.parse_float(c).is_none())?;
+    Some((configured, other))
+}
+
+/// The type of the (trimmed) cells of a column - `None` if there is no value.
+/// Columns holding nothing but boolean tokens are `Boolean`, dates in a single format are
+/// `Date` (if `detect_dates` is set), all others go through `infer_numeric()`.
+fn classify(cells: &[String], options: &ParseOptions) -> Option<Dtype> {
+    let mut values = cells.iter().filter(|c| !options.is_missing(c)).peekable();
+    values.peek()?;
+    if values.all(|c| options.parse_boolean(c).is_some()) {
+        return Some(Dtype::Boolean);
+    }
+    if options.detect_dates && date_format(cells, options).is_some() {
+        return Some(Dtype::Date);
+    }
+    Some(infer_numeric(cells, options))
+}
+
+/// The settings of the type inference on its own - see `infer_dtype()`. The defaults are the
+/// ones of `ParseOptions`, and `ParseOptions::infer_options()` gives those of a parse run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferOptions {
+    /// Cells meaning "no value" (compared ignoring ASCII case) - they never decide the type
+    pub missing_tokens: Vec<String>,
+    /// A column holding only these tokens and the `false_tokens` is `Boolean`
+    pub true_tokens: Vec<String>,
+    pub false_tokens: Vec<String>,
+    /// How numbers are written: a column is `Integer` if every value parses as `i64`, `Float` if
+    /// every value parses as a number (`1.0` is a float, signs and exponents are fine, `inf` is text)
+    pub number_format: NumberFormat,
+    /// A column holding only dates in one of `DATE_FORMATS` is `Date` (default: off)
+    pub detect_dates: bool,
+    /// A number column with a value like `007` is a `Factor` - an identifier (default: off)
+    pub preserve_leading_zeros: bool,
+    /// Everything else is a `Factor`, or `FreeText` with more distinct values than this (None: no limit)
+    pub max_levels: Option<usize>,
+}
+
+impl Default for InferOptions {
+    fn default() -> Self {
+        ParseOptions::default().infer_options()
+    }
+}
+
+impl InferOptions {
+    fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            missing_tokens: self.missing_tokens.clone(),
+            true_tokens: self.true_tokens.clone(),
+            false_tokens: self.false_tokens.clone(),
+            number_format: self.number_format,
+            detect_dates: self.detect_dates,
+            preserve_leading_zeros: self.preserve_leading_zeros,
+            max_levels: self.max_levels,
+            ..Default::default()
+        }
+    }
+}
+
+/// The type the parser would infer for a column with these values, without reading a file -
+/// e.g. for columns built at run time. `None` and the missing tokens are no value; the values
+/// are trimmed. A column without any value is `Float`. The rules are those of `InferOptions`,
+/// checked in its field order.
+///
+/// # Example
+/// ```
+/// use rust_data_table::{infer_dtype, Dtype, InferOptions};
+/// let options = InferOptions::default();
+/// assert_eq!(infer_dtype(&[Some("1"), None, Some("-3")], &options), Dtype::Integer);
+/// assert_eq!(infer_dtype(&[Some("TRUE"), Some("no")], &options), Dtype::Boolean);
+/// assert_eq!(infer_dtype(&[Some("T cell"), Some("2.5")], &options), Dtype::Factor);
+/// ```
+pub fn infer_dtype(values: &[Option<&str>], opts: &InferOptions) -> Dtype {
+    let options = opts.parse_options();
+    let cells: Vec<String> = values.iter().flatten().map(|v| v.trim().to_string()).collect();
+    match classify(&cells, &options) {
+        None => Dtype::Float,
+        Some(Dtype::Factor) => {
+            let levels: HashSet<&str> = cells.iter().filter(|c| !options.is_missing(c)).map(String::as_str).collect();
+            match opts.max_levels {
+                Some(max) if levels.len() > max => Dtype::FreeText,
+                _ => Dtype::Factor,
+            }
+        }
+        Some(dtype) => dtype,
+    }
+}
+
+/// Does a (non-missing) value fit the type?
+fn fits(dtype: Dtype, value: &str, options: &ParseOptions) -> bool {
+    match dtype {
+        Dtype::Boolean => options.parse_boolean(value).is_some(),
+        Dtype::Integer => options.number_format.parse_integer(value).is_some() && !options.keeps_leading_zero(value),
+        Dtype::Float => options.number_format.parse_float(value).is_some() && !options.keeps_leading_zero(value),
+        Dtype::Date => DATE_FORMATS.iter().any(|f| parse_date(value, f).is_some()),
+        Dtype::Factor | Dtype::FreeText => true,
+        Dtype::Json => json_object(value).is_some(),
+        Dtype::AllMissing => false,
+    }
+}
+
+/// Does a (non-missing) value fit a declared type? A date has to be in one of `DATE_FORMATS`.
+pub(crate) fn fits_declared(dtype: Dtype, value: &str, options: &ParseOptions) -> bool {
+    match dtype {
+        Dtype::Integer => options.number_format.parse_integer(value).is_some(),
+        Dtype::Float => options.number_format.parse_float(value).is_some(),
+        other => fits(other, value, options),
+    }
+}
+
+/// The error for a value contradicting the declared type of its column.
+pub(crate) fn declared_mismatch(name: &str, line: u64, value: &str, dtype: Dtype) -> DataTableError {
+    DataTableError::InvalidData(format!(
+        "Column '{}' line {}: '{}' is no {} value, but the column is declared as {}",
+        name, line, value, dtype, dtype
+    ))
+}
+
+/// Check the cells of a column with a declared type (see `ParseOptions::dtypes`).
+/// All dates have to share one format - the one of the first date.
+fn check_declared(name: &str, cells: &[String], dtype: Dtype, options: &ParseOptions) -> Result<Dtype> {
+    let format = match dtype {
+        Dtype::Date => date_format(cells, options).or_else(|| {
+            let first = cells.iter().find(|c| !options.is_missing(c))?;
+            DATE_FORMATS.iter().find(|f| parse_date(first, f).is_some()).copied()
+        }),
+        _ => None,
+    };
+    for (i, value) in cells.iter().enumerate().filter(|(_, c)| !options.is_missing(c)) {
+        let fit = match (dtype, format) {
+            (Dtype::Date, Some(format)) => parse_date(value, format).is_some(),
+            _ => fits_declared(dtype, value, options),
+        };
+        if !fit {
+            // header is line 1
+            return Err(declared_mismatch(name, i as u64 + 2, value, dtype));
+        }
+    }
+    Ok(dtype)
+}
+
+/// Infer the type of one column - from the first `sample_rows` cells if configured.
+/// The remaining cells are then checked as `sample_check` says.
+fn infer_column(name: &str, cells: &[String], options: &ParseOptions) -> Result<Dtype> {
+    let (sample, rest) = match options.sample_rows {
+        Some(n) if n < cells.len() && options.sample_check != SampleCheck::Skip => cells.split_at(n),
+        Some(n) if n < cells.len() => (&cells[..n], &[][..]),
+        _ => (cells, &[][..]),
+    };
+    let mut dtype = classify(sample, options);
+    if dtype == Some(Dtype::Factor)
+        && let Some((configured, other)) = mixed_number_formats(cells, options)
+    {
+        log::warn!("Column '{name}' mixes number formats ('{configured}' and '{other}') -> factor");
+    }
+    for (i, value) in rest.iter().enumerate().filter(|(_, c)| !options.is_missing(c)) {
+        let Some(current) = dtype else {
+            // the sample had no value at all - nothing to contradict
+            dtype = classify(std::slice::from_ref(value), options);
+            continue;
+        };
+        if fits(current, value, options) {
+            continue;
+        }
+        // header is line 1
+        let line = sample.len() + i + 2;
+        if options.sample_check == SampleCheck::Error {
+            bail!(
+                InvalidData,
+                "Column '{}' line {}: '{}' does not fit the {} type inferred from the first {} rows \
+                 (use a larger sample or let the type widen)",
+                name, line, value, current, sample.len()
+            );
+        }
+        let widened = match current {
+            // not a boolean after all - the whole column decides again
+            Dtype::Boolean => infer_numeric(cells, options),
+            _ if options.keeps_leading_zero(value) => Dtype::Factor,
+            _ => refine_dtype(Some(current), value, options.number_format),
+        };
+        log::warn!(
+            "Column '{name}' line {line}: '{value}' does not fit the {current} type inferred from the first {} rows -> {widened}",
+            sample.len()
+        );
+        dtype = Some(widened);
+    }
+    // a header without rows has no value in any column either
+    Ok(dtype.unwrap_or(match cells.iter().all(|c| options.is_missing(c)) {
+        true => Dtype::AllMissing,
+        // values after a skipped sample
+        _ => Dtype::Float,
+    }))
+}
+
+/// The numbers and other texts seen in one column - with `strict` a column holding both
+/// is an error instead of a factor.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MixedValues {
+    numbers: usize,
+    texts: usize,
+    number_samples: Vec<String>,
+    text_samples: Vec<String>,
+}
+
+impl MixedValues {
+    /// Distinct example values kept per kind.
+    const SAMPLES: usize = 3;
+
+    /// Count a non-missing value.
+    pub(crate) fn push(&mut self, value: &str, options: &ParseOptions) {
+        let (count, samples) = match options.number_format.parse_float(value) {
+            Some(_) => (&mut self.numbers, &mut self.number_samples),
+            None => (&mut self.texts, &mut self.text_samples),
+        };
+        *count += 1;
+        if samples.len() < Self::SAMPLES && !samples.iter().any(|s| s == value) {
+            samples.push(value.to_string());
+        }
+    }
+
+    pub(crate) fn from_cells(cells: &[String], options: &ParseOptions) -> Self {
+        let mut mixed = MixedValues::default();
+        for cell in cells.iter().filter(|c| !options.is_missing(c)) {
+            mixed.push(cell, options);
+        }
+        mixed
+    }
+
+    /// Examples of the rarer kind - the values that break the column. `None` if it is not mixed.
+    fn offending(&self) -> Option<&[String]> {
+        if self.numbers == 0 || self.texts == 0 {
+            return None;
+        }
+        Some(if self.numbers >= self.texts { &self.text_samples } else { &self.number_samples })
+    }
+}
+
+/// `strict`: fail for the inferred factor columns mixing numbers and text, naming each with
+/// a few of its offending values.
+pub(crate) fn check_strict(columns: &[(&str, MixedValues)]) -> Result<()> {
+    let ambiguous: Vec<String> = columns
+        .iter()
+        .filter_map(|(name, mixed)| {
+            let values: Vec<String> = mixed.offending()?.iter().map(|v| format!("'{}'", v)).collect();
+            Some(format!("'{}' ({})", name, values.join(", ")))
+        })
+        .collect();
+    if ambiguous.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        InvalidData,
+        "Strict mode: {} column(s) mix numbers and text - declare them (categorical, as_numeric, as_string) or clean the input: {}",
+        ambiguous.len(),
+        ambiguous.join(", ")
+    )
+}
+
+/// Report the values of an `as_numeric` column that were no number.
+pub(crate) fn report_coerced(name: &str, coerced: usize) {
+    if coerced > 0 {
+        log::warn!("Column '{name}': {coerced} value(s) are no number -> missing (as_numeric)");
+    }
+}
+
+/// The type of an `as_numeric` column: `Integer` if every number is one, `Float` otherwise.
+/// Values that are no number do not count - they are missing.
+fn coerce_numeric(name: &str, cells: &[String], options: &ParseOptions) -> Dtype {
+    let mut dtype = None;
+    let mut coerced = 0;
+    for value in cells.iter().filter(|c| !options.is_missing(c)) {
+        if options.number_format.parse_float(value).is_some() {
+            dtype = Some(refine_dtype(dtype, value, options.number_format));
+        } else {
+            coerced += 1;
+        }
+    }
+    report_coerced(name, coerced);
+    dtype.unwrap_or(Dtype::Float)
+}
+
+/// The JSON object a cell holds - `None` for anything else (missing cells, numbers, arrays).
+pub(crate) fn json_object(value: &str) -> Option<Map<String, Value>> {
+    if !value.starts_with('{') {
+        return None;
+    }
+    match serde_json::from_str(value) {
+        Ok(Value::Object(object)) => Some(object),
+        _ => None,
+    }
+}
+
+/// Does the column hold JSON objects - and nothing else but missing values?
+pub(crate) fn is_json_column(cells: &[String], options: &ParseOptions) -> bool {
+    let mut values = cells.iter().filter(|c| !options.is_missing(c)).peekable();
+    values.peek().is_some() && values.all(|c| json_object(c).is_some())
+}
+
+/// Split a column of JSON objects into one column per top-level key (`meta.x`, `meta.y`),
+/// sorted by key. Strings are taken as written, other scalars as their JSON text (`true`, `1.5`)
+/// and nested values as compact JSON; `null` and missing cells are missing.
+/// Every object needs the same keys.
+pub(crate) fn flatten_json(name: &str, cells: &[String], options: &ParseOptions) -> Result<Vec<(String, Vec<String>)>> {
+    let missing = options.missing_tokens.first().cloned().unwrap_or_default();
+    let objects: Vec<Option<Map<String, Value>>> = cells.iter().map(|c| json_object(c)).collect();
+    let Some(first) = objects.iter().flatten().next() else {
+        return Ok(Vec::new());
+    };
+    let keys: Vec<&String> = first.keys().collect();
+    let mut columns: Vec<(String, Vec<String>)> =
+        keys.iter().map(|key| (format!("{}.{}", name, key), Vec::with_capacity(cells.len()))).collect();
+    for (i, object) in objects.iter().enumerate() {
+        let Some(object) = object else {
+            columns.iter_mut().for_each(|(_, values)| values.push(missing.clone()));
+            continue;
+        };
+        if !object.keys().eq(keys.iter().copied()) {
+            let found: Vec<&str> = object.keys().map(|k| k.as_str()).collect();
+            let expected: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+            // header is line 1
+            bail!(
+                InvalidData,
+                "Column '{}' line {}: the JSON keys {} differ from {} - flattening needs the same keys in every row",
+                name, i + 2, found.join(", "), expected.join(", ")
+            );
+        }
+        for ((_, values), value) in columns.iter_mut().zip(object.values()) {
+            values.push(match value {
+                Value::Null => missing.clone(),
+                Value::String(text) => text.trim().to_string(),
+                other => other.to_string(),
+            });
+        }
+    }
+    Ok(columns)
+}
+
+/// Infer the type of every column that is not `declared` (declared columns are factors).
+/// Columns with a type in `dtypes` keep it - a value that does not fit is an error.
+/// `as_numeric` columns are numbers whatever they hold.
+///
+/// With the `rayon` feature the columns are classified in parallel (on `options.threads`
+/// threads). The result is in column order either way, so it does not depend on the number of threads.
+pub(crate) fn infer_dtypes(
+    names: &[String],
+    raw: &[Vec<String>],
+    declared: &[bool],
+    options: &ParseOptions,
+) -> Result<Vec<Dtype>> {
+    let infer = |((name, cells), declared): ((&String, &Vec<String>), &bool)| {
+        if *declared {
+            return Ok(Dtype::Factor);
+        }
+        if let Some(&dtype) = options.dtypes.get(name) {
+            return check_declared(name, cells, dtype, options);
+        }
+        if options.as_numeric.contains(name) {
+            return Ok(coerce_numeric(name, cells, options));
+        }
+        infer_column(name, cells, options)
+    };
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        let parallel = || names.par_iter().zip(raw.par_iter()).zip(declared.par_iter()).map(infer).collect();
+        match options.threads {
+            None => parallel(),
+            Some(1) => names.iter().zip(raw.iter()).zip(declared.iter()).map(infer).collect(),
+            Some(n) => rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .map_err(|e| DataTableError::InvalidArgument(format!("Cannot start {} threads: {}", n, e)))?
+                .install(parallel),
+        }
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        names.iter().zip(raw.iter()).zip(declared.iter()).map(infer).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NA: [&str; 2] = ["NA", ""];
+
+    #[test]
+    fn test_infer_dtype() {
+        assert_eq!(infer_number_dtype(&["1", "20", "NA", "-3"], &NA, NumberFormat::default()), Dtype::Integer);
+        assert_eq!(infer_number_dtype(&["1", "2.5", ""], &NA, NumberFormat::default()), Dtype::Float);
+        assert_eq!(infer_number_dtype(&["1.0", "2"], &NA, NumberFormat::default()), Dtype::Float, "a decimal point makes it a float");
+        assert_eq!(infer_number_dtype(&["0,5", "1"], &NA, NumberFormat::default()), Dtype::Float, "decimal comma");
+        assert_eq!(infer_number_dtype(&["1", "T cell"], &NA, NumberFormat::default()), Dtype::Factor);
+        assert_eq!(infer_number_dtype(&["NA", ""], &NA, NumberFormat::default()), Dtype::Float);
+    }
+
+    #[test]
+    fn test_public_infer_dtype() {
+        let options = InferOptions::default();
+        assert_eq!(infer_dtype(&[Some("1"), Some(" 20 "), Some("NA"), None], &options), Dtype::Integer);
+        assert_eq!(infer_dtype(&[Some("1"), Some("2.5")], &options), Dtype::Float);
+        assert_eq!(infer_dtype(&[None, Some("")], &options), Dtype::Float, "no value");
+        assert_eq!(infer_dtype(&[Some("yes"), Some("FALSE"), None], &options), Dtype::Boolean);
+        assert_eq!(infer_dtype(&[Some("2023-05-14")], &options), Dtype::Factor, "dates are off by default");
+        assert_eq!(infer_dtype(&[Some("007"), Some("12")], &options), Dtype::Integer);
+
+        let options = InferOptions { detect_dates: true, preserve_leading_zeros: true, max_levels: Some(2), ..Default::default() };
+        assert_eq!(infer_dtype(&[Some("2023-05-14"), Some("2023-05-15")], &options), Dtype::Date);
+        assert_eq!(infer_dtype(&[Some("007"), Some("12")], &options), Dtype::Factor);
+        assert_eq!(infer_dtype(&[Some("a"), Some("b"), Some("a")], &options), Dtype::Factor);
+        assert_eq!(infer_dtype(&[Some("a"), Some("b"), Some("c")], &options), Dtype::FreeText);
+
+        let options = ParseOptions { number_format: NumberFormat::new(b',', Some(b'.')).unwrap(), ..Default::default() }.infer_options();
+        assert_eq!(infer_dtype(&[Some("1.200,5"), Some("2")], &options), Dtype::Float);
+    }
+
+    #[test]
+    fn test_signed_numbers() -> Result<()> {
+        let cells = |values: &[&str]| -> Vec<String> { values.iter().map(|c| c.to_string()).collect() };
+        let options = ParseOptions::default();
+        let log_fc = cells(&["-2.3", "0.8", "-0.05", "NA", "1"]);
+        assert_eq!(infer_column("log_fc", &log_fc, &options)?, Dtype::Float);
+        assert_eq!(infer_column("delta", &cells(&["-1", "-20", "3"]), &options)?, Dtype::Integer);
+        assert_eq!(infer_column("delta", &cells(&["+1", "+20", "-3", "0"]), &options)?, Dtype::Integer);
+        assert_eq!(infer_column("mixed", &cells(&["-1", "+2", "0.5"]), &options)?, Dtype::Float);
+        assert_eq!(infer_column("signed", &cells(&["+.5", "-.5", "+1e-3", "-5."]), &options)?, Dtype::Float);
+        assert_eq!((parse_float("+2"), parse_float("-0,5"), parse_integer("+2")), (Some(2.0), Some(-0.5), Some(2)));
+
+        // a sign alone, twice or trailing is no number - and neither are Rust's float words
+        for bad in ["-", "+", "--1", "+-1", "1-", "- 1", "inf", "-inf", "+Infinity", "1e5x"] {
+            assert_eq!(parse_float(bad), None, "{bad}");
+            assert_eq!(infer_column("x", &cells(&["1", bad]), &options)?, Dtype::Factor, "{bad}");
+        }
+        assert_eq!(parse_float("nan"), None, "NaN is a missing value token, not a number");
+        let german = NumberFormat::new(b',', Some(b'.')).unwrap();
+        assert_eq!((german.parse_float("-1.234,5"), german.parse_float("+0,5")), (Some(-1234.5), Some(0.5)));
+        assert_eq!((german.parse_float("inf"), german.parse_integer("-1.234")), (None, Some(-1234)));
+
+        // as stored: the signs survive the round trip through the table
+        let tsv = "gene\tlog_fc\tdelta\nA\t-2.3\t+2\nB\t+0.5\t-1\nC\tinf\t0\n";
+        let data = crate::SurvivalData::from_reader(std::io::Cursor::new(tsv), b'\t', std::collections::HashSet::new())?;
+        assert_eq!(data.column("log_fc").unwrap().dtype, Dtype::Factor, "inf makes it text");
+        let tsv = tsv.replace("inf", "1e3");
+        let data = crate::SurvivalData::from_reader(std::io::Cursor::new(tsv), b'\t', std::collections::HashSet::new())?;
+        assert_eq!(data.as_vec_f64("log_fc"), vec![-2.3, 0.5, 1000.0]);
+        assert_eq!((data.column("delta").unwrap().dtype, data.as_vec_f64("delta")), (Dtype::Integer, vec![2.0, -1.0, 0.0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict() -> Result<()> {
+        let cells = |values: &[&str]| -> Vec<String> { values.iter().map(|c| c.to_string()).collect() };
+        let options = ParseOptions::default();
+        let dose = MixedValues::from_cells(&cells(&["1.5", "<0.5", "2", "NA", "n/a", "<0.5", "3"]), &options);
+        let stage = MixedValues::from_cells(&cells(&["low", "high", "2", "low"]), &options);
+        let clean = MixedValues::from_cells(&cells(&["T", "B", "NA"]), &options);
+        assert_eq!((dose.offending(), stage.offending()), (Some(&cells(&["<0.5", "n/a"])[..]), Some(&cells(&["2"])[..])));
+        assert_eq!(clean.offending(), None);
+        let err = check_strict(&[("dose", dose), ("cluster", clean.clone()), ("stage", stage)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Strict mode: 2 column(s) mix numbers and text - declare them (categorical, as_numeric, as_string) \
+             or clean the input: 'dose' ('<0.5', 'n/a'), 'stage' ('2')"
+        );
+        assert!(check_strict(&[("cluster", clean)]).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        let cells = |values: &[&str]| -> Vec<String> { values.iter().map(|c| c.to_string()).collect() };
+        let p_val = cells(&["0.001", "1e-10", "NA", "1.2E-5", "3E8", "+2.5e+3", "-4e0"]);
+        assert_eq!(infer_column("p_val", &p_val, &ParseOptions::default()).unwrap(), Dtype::Float);
+        assert_eq!(parse_float("1e-10"), Some(1e-10));
+        assert_eq!(parse_float("3E8"), Some(3e8));
+        assert_eq!(parse_integer("3E8"), None, "an exponent makes it a float");
+        assert_eq!(infer_number_dtype(&["1e5", "2"], &NA, NumberFormat::default()), Dtype::Float);
+        assert_eq!(infer_number_dtype(&["1e", "e5"], &NA, NumberFormat::default()), Dtype::Factor, "no number");
+
+        let preserving = ParseOptions { preserve_leading_zeros: true, sample_rows: Some(1), ..Default::default() };
+        assert_eq!(infer_column("p_val", &p_val, &preserving).unwrap(), Dtype::Float);
+        let german = NumberFormat::new(b',', Some(b'.')).unwrap();
+        assert_eq!(german.parse_float("1,5e-3"), Some(1.5e-3));
+        assert_eq!(german.parse_float("1.234,5E2"), Some(123450.0));
+    }
+
+    #[test]
+    fn test_missing_tokens() {
+        let tokens = ["NA", "NaN", "", "NULL", "None"];
+        assert!(is_missing("na", &tokens));
+        assert!(is_missing("", &tokens));
+        assert!(is_missing("NULL", &tokens));
+        assert!(!is_missing("Nonesuch", &tokens));
+        assert_eq!(infer_number_dtype(&["1", "None", "NULL", "NaN"], &tokens, NumberFormat::default()), Dtype::Integer);
+        assert_eq!(infer_number_dtype(&["1", "None"], &NA, NumberFormat::default()), Dtype::Factor, "None is only missing if configured");
+    }
+
+    #[test]
+    fn test_sample_rows() {
+        let cells: Vec<String> = ["1", "2", "NA", "3.5", "x"].iter().map(|c| c.to_string()).collect();
+        let sampled = |check| ParseOptions { sample_rows: Some(2), sample_check: check, ..Default::default() };
+
+        assert_eq!(infer_column("n", &cells, &sampled(SampleCheck::Widen)).unwrap(), Dtype::Factor, "widened twice");
+        assert_eq!(infer_column("n", &cells[..4], &sampled(SampleCheck::Widen)).unwrap(), Dtype::Float);
+        assert_eq!(infer_column("n", &cells, &sampled(SampleCheck::Skip)).unwrap(), Dtype::Integer, "sample only");
+        let err = infer_column("n", &cells, &sampled(SampleCheck::Error)).unwrap_err();
+        assert!(err.to_string().contains("Column 'n' line 5: '3.5' does not fit the integer type"), "{err}");
+
+        let flags: Vec<String> = ["0", "1", "2"].iter().map(|c| c.to_string()).collect();
+        assert_eq!(infer_column("f", &flags, &sampled(SampleCheck::Widen)).unwrap(), Dtype::Integer);
+        let empty_sample: Vec<String> = ["NA", "NA", "a"].iter().map(|c| c.to_string()).collect();
+        assert_eq!(infer_column("e", &empty_sample, &sampled(SampleCheck::Error)).unwrap(), Dtype::Factor);
+    }
+
+    #[test]
+    fn test_parse_boolean() {
+        let (t, f) = (["TRUE", "yes", "1"], ["FALSE", "no", "0"]);
+        assert_eq!(parse_boolean("true", &t, &f), Some(true));
+        assert_eq!(parse_boolean("No", &t, &f), Some(false));
+        assert_eq!(parse_boolean("0", &t, &f), Some(false));
+        assert_eq!(parse_boolean("2", &t, &f), None);
+    }
+
+    #[test]
+    fn test_dates() {
+        let cells = |values: &[&str]| -> Vec<String> { values.iter().map(|c| c.to_string()).collect() };
+        let options = ParseOptions { detect_dates: true, ..Default::default() };
+        assert_eq!(date_format(&cells(&["2023-05-14", "NA", "2024-01-02"]), &options), Some("%Y-%m-%d"));
+        assert_eq!(date_format(&cells(&["14.05.2023", "02.01.2024"]), &options), Some("%d.%m.%Y"));
+        assert_eq!(date_format(&cells(&["05/14/2023", "01/02/2024"]), &options), Some("%m/%d/%Y"), "no 14th month");
+        assert_eq!(date_format(&cells(&["2023-05-14", "14.05.2023"]), &options), None, "mixed formats");
+        assert_eq!(date_format(&cells(&["2023-02-30"]), &options), None, "no such day");
+
+        assert_eq!(parse_date("1970-01-02", "%Y-%m-%d"), Some(86400.0));
+        let stamp = parse_date("2023-05-14 08:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(format_date(stamp, "%Y-%m-%d %H:%M:%S"), "2023-05-14T08:30:00");
+        assert_eq!(format_date(parse_date("14.05.2023", "%d.%m.%Y").unwrap(), "%d.%m.%Y"), "2023-05-14");
+
+        assert_eq!(classify(&cells(&["2023-05-14", "2024-01-02"]), &options), Some(Dtype::Date));
+        assert_eq!(classify(&cells(&["2023-05-14", "2024-01-02"]), &ParseOptions::default()), Some(Dtype::Factor), "opt in");
+        assert_eq!(classify(&cells(&["2023-05-14", "soon"]), &options), Some(Dtype::Factor));
+    }
+
+    #[test]
+    fn test_leading_zeros() {
+        let cells = |values: &[&str]| -> Vec<String> { values.iter().map(|c| c.to_string()).collect() };
+        assert!(has_leading_zero("007") && has_leading_zero("-012") && has_leading_zero("00"));
+        assert!(!has_leading_zero("0") && !has_leading_zero("0.5") && !has_leading_zero("10"));
+
+        let options = ParseOptions { preserve_leading_zeros: true, ..Default::default() };
+        let zip_codes = cells(&["01067", "80331", "NA"]);
+        assert_eq!(classify(&zip_codes, &options), Some(Dtype::Factor));
+        assert_eq!(classify(&zip_codes, &ParseOptions::default()), Some(Dtype::Integer), "opt in");
+        assert_eq!(classify(&cells(&["0", "0.5", "12"]), &options), Some(Dtype::Float), "no significant zero");
+
+        let sampled = ParseOptions { sample_rows: Some(2), ..options };
+        assert_eq!(infer_column("id", &cells(&["12", "13", "007"]), &sampled).unwrap(), Dtype::Factor, "widened");
+    }
+
+    #[test]
+    fn test_declared_dtypes() {
+        let cells = |values: &[&str]| -> Vec<String> { values.iter().map(|c| c.to_string()).collect() };
+        let options = ParseOptions::default();
+        assert_eq!(check_declared("n", &cells(&["1", "NA", "2"]), Dtype::Float, &options).unwrap(), Dtype::Float);
+        assert_eq!(check_declared("d", &cells(&["14.05.2023"]), Dtype::Date, &options).unwrap(), Dtype::Date);
+        let err = check_declared("n", &cells(&["1", "2", "1.5"]), Dtype::Integer, &options).unwrap_err();
+        assert_eq!(err.to_string(), "Column 'n' line 4: '1.5' is no integer value, but the column is declared as integer");
+        let err = check_declared("d", &cells(&["2023-05-14", "14.05.2023"]), Dtype::Date, &options).unwrap_err();
+        assert!(err.to_string().contains("'14.05.2023'"), "one format per column: {err}");
+    }
+
+    #[test]
+    fn test_infer_dtypes() {
+        let raw: Vec<Vec<String>> = [["1", "2"], ["1.5", "NA"], ["a", "b"], ["1", "2"], ["TRUE", "no"], ["1", "0"]]
+            .iter()
+            .map(|col| col.iter().map(|c| c.to_string()).collect())
+            .collect();
+        let declared = [false, false, false, true, false, false];
+        let names: Vec<String> = (0..raw.len()).map(|i| format!("c{i}")).collect();
+        let expected = vec![Dtype::Integer, Dtype::Float, Dtype::Factor, Dtype::Factor, Dtype::Boolean, Dtype::Boolean];
+        assert_eq!(infer_dtypes(&names, &raw, &declared, &ParseOptions::default()).unwrap(), expected);
+        for threads in [1, 2] {
+            let options = ParseOptions { threads: Some(threads), ..Default::default() };
+            assert_eq!(infer_dtypes(&names, &raw, &declared, &options).unwrap(), expected, "{threads} thread(s)");
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::compress::has_zst_extension;
+use crate::error::{DataTableError, Result};
+use flate2::read::MultiGzDecoder;
+
+/// The two magic bytes every gzip stream starts with.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The four magic bytes every zstd frame starts with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The UTF-8 byte order mark (`EF BB BF`) Excel on Windows puts in front of exported files.
+pub(crate) const BOM: char = '\u{feff}';
+
+/// Returns true if the buffered bytes start with the gzip magic number.
+pub fn is_gzip_magic(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[..2] == GZIP_MAGIC
+}
+
+/// Returns true if the buffered bytes start with the zstd magic number.
+pub fn is_zstd_magic(bytes: &[u8]) -> bool {
+    bytes.len() >= 4 && bytes[..4] == ZSTD_MAGIC
+}
+
+/// Open a (possibly gzip or zstd compressed) input file as a buffered reader.
+///
+/// Compression is detected by either a `.gz` / `.zst` extension or the magic
+/// number on the first bytes (`1f 8b` for gzip, `28 b5 2f fd` for zstd), so renamed files are still decoded.
+/// Plain text files are returned untouched.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| DataTableError::io(path, e))?;
+    let mut reader = BufReader::new(file);
+
+    let has_gz_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("gz"))
+        .unwrap_or(false);
+    // fill_buf() only peeks - the magic bytes stay in the stream for the decoder
+    let head = reader.fill_buf()?;
+    let has_gz_magic = is_gzip_magic(head);
+    let has_zstd_magic = is_zstd_magic(head);
+
+    if has_zstd_magic || (has_zst_extension(path) && !has_gz_magic) {
+        let decoder = zstd::Decoder::with_buffer(reader).map_err(|e| DataTableError::io(path, e))?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if has_gz_magic || has_gz_extension {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Is `input` an `http://` or `https://` URL rather than a file name?
+pub fn is_url(input: &str) -> bool {
+    let scheme = input.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("http" | "https"))
+}
+
+/// Download `url` (feature `net`) as a buffered reader - the body is streamed, not kept in memory.
+///
+/// A `Content-Encoding: gzip` response and a gzip compressed file (`meta.tsv.gz`, or a body
+/// starting with the gzip magic number) are decoded. A response other than 2xx is an error naming its status.
+#[cfg(feature = "net")]
+pub fn open_url(url: &str) -> Result<Box<dyn BufRead>> {
+    let response = reqwest::blocking::Client::new()
+        .get(url)
+        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+        .send()?;
+    let status = response.status();
+    if !status.is_success() {
+        crate::error::bail!(InvalidData, "Failed to download {}: HTTP {}", url, status);
+    }
+    let gzip_encoded = response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|encoding| encoding.to_str().ok())
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("gzip"));
+    // no timeout: a large table takes its time
+    let mut reader = BufReader::new(response);
+    let body: Box<dyn BufRead> = match gzip_encoded {
+        true => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        false if is_gzip_magic(reader.fill_buf()?) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        false => Box::new(reader),
+    };
+    Ok(body)
+}
+
+/// What a `Progress` callback counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgressUnit {
+    Bytes,
+    Lines,
+}
+
+/// A callback told how far the parser got (see `ParseOptions::progress`) - for a
+/// progress bar while a large file is read.
+///
+/// `Progress::bytes()` reports the bytes of the (decompressed) table read so far,
+/// `Progress::lines()` the lines, for inputs of unknown size like stdin.
+/// The streaming reader reads the file twice, the count restarts with the second pass.
+#[derive(Clone)]
+pub struct Progress {
+    callback: Arc<Mutex<dyn FnMut(u64) + Send>>,
+    unit: ProgressUnit,
+}
+
+impl Progress {
+    pub fn bytes<F: FnMut(u64) + Send + 'static>(callback: F) -> Self {
+        Progress { callback: Arc::new(Mutex::new(callback)), unit: ProgressUnit::Bytes }
+    }
+
+    pub fn lines<F: FnMut(u64) + Send + 'static>(callback: F) -> Self {
+        Progress { callback: Arc::new(Mutex::new(callback)), unit: ProgressUnit::Lines }
+    }
+
+    /// Count what passes through `reader`.
+    pub(crate) fn wrap<R: Read>(&self, reader: R) -> ProgressReader<R> {
+        ProgressReader { inner: reader, progress: self.clone(), count: 0 }
+    }
+
+    fn report(&self, count: u64) {
+        // a callback that panicked once is not called again
+        if let Ok(mut callback) = self.callback.lock() {
+            callback(count);
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Progress({:?})", self.unit)
+    }
+}
+
+/// A reader calling its `Progress` after every read.
+pub(crate) struct ProgressReader<R> {
+    inner: R,
+    progress: Progress,
+    count: u64,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.count += match self.progress.unit {
+                ProgressUnit::Bytes => n as u64,
+                ProgressUnit::Lines => buf[..n].iter().filter(|&&b| b == b'\n').count() as u64,
+            };
+            self.progress.report(self.count);
+        }
+        Ok(n)
+    }
+}
+
+/// Download all of `url` (see `open_url()`) - for the delimiter detection, like `read_stdin()`.
+#[cfg(feature = "net")]
+pub fn read_url(url: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    open_url(url)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Read all of stdin - gzip or zstd compressed input is decoded.
+///
+/// Stdin can only be read once, so the table is buffered: the delimiter detection
+/// looks at the same bytes the parser reads afterwards.
+pub fn read_stdin() -> Result<Vec<u8>> {
+    decode_all(std::io::stdin().lock())
+}
+
+/// Read a stream to the end, decoding it if it starts with the gzip or zstd magic number.
+fn decode_all<R: Read>(reader: R) -> Result<Vec<u8>> {
+    let mut reader = BufReader::new(reader);
+    let mut data = Vec::new();
+    if is_gzip_magic(reader.fill_buf()?) {
+        MultiGzDecoder::new(reader).read_to_end(&mut data)
+    } else if is_zstd_magic(reader.fill_buf()?) {
+        zstd::Decoder::with_buffer(reader)?.read_to_end(&mut data)
+    } else {
+        reader.read_to_end(&mut data)
+    }
+    ?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn read_all(path: &Path) -> String {
+        let mut s = String::new();
+        open_input(path).unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn test_open_input_plain_and_gzip() {
+        let dir = tempdir().unwrap();
+        let content = "a\tb\n1\tx\n";
+
+        let plain = dir.path().join("meta.tsv");
+        std::fs::write(&plain, content).unwrap();
+        assert_eq!(read_all(&plain), content, "plain text must not be decoded");
+
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(content.as_bytes()).unwrap();
+        let compressed = enc.finish().unwrap();
+
+        let gz = dir.path().join("meta.tsv.gz");
+        std::fs::write(&gz, &compressed).unwrap();
+        assert_eq!(read_all(&gz), content, "'.gz' extension is decoded");
+
+        // no extension - only the magic number tells us
+        let sniffed = dir.path().join("meta.tsv");
+        std::fs::write(&sniffed, &compressed).unwrap();
+        assert_eq!(read_all(&sniffed), content, "gzip magic bytes are sniffed");
+
+        assert_eq!(decode_all(&compressed[..]).unwrap(), content.as_bytes(), "compressed stream");
+        assert_eq!(decode_all(content.as_bytes()).unwrap(), content.as_bytes());
+
+        let zstd = zstd::encode_all(content.as_bytes(), 3).unwrap();
+        std::fs::write(&sniffed, &zstd).unwrap();
+        assert_eq!(read_all(&sniffed), content, "zstd magic bytes are sniffed");
+        assert_eq!(decode_all(&zstd[..]).unwrap(), content.as_bytes());
+    }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/meta.tsv"));
+        assert!(is_url("HTTP://example.com/meta.tsv.gz"));
+        assert!(!is_url("ftp://example.com/meta.tsv"));
+        assert!(!is_url("data/meta.tsv"));
+        assert!(!is_url("-"));
+    }
+
+    /// Answer one request on a free local port with `status`, `headers` and `body` - the URL of the server.
+    #[cfg(feature = "net")]
+    fn serve_once(status: &'static str, headers: &'static str, body: Vec<u8>) -> String {
+        use std::io::{BufRead, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/meta.tsv", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(&stream);
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let head = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n", body.len());
+            (&stream).write_all(head.as_bytes()).unwrap();
+            (&stream).write_all(&body).unwrap();
+        });
+        url
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_open_url() {
+        let content = "a\tb\n1\tx\n";
+        let url = serve_once("200 OK", "", content.as_bytes().to_vec());
+        let mut text = String::new();
+        open_url(&url).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, content);
+
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(content.as_bytes()).unwrap();
+        let compressed = enc.finish().unwrap();
+        let url = serve_once("200 OK", "Content-Encoding: gzip\r\n", compressed.clone());
+        assert_eq!(read_url(&url).unwrap(), content.as_bytes(), "gzip content encoding");
+        let url = serve_once("200 OK", "Content-Type: application/gzip\r\n", compressed);
+        assert_eq!(read_url(&url).unwrap(), content.as_bytes(), "a gzip file");
+
+        let url = serve_once("404 Not Found", "", b"gone".to_vec());
+        let err = open_url(&url).err().unwrap();
+        assert_eq!(err.to_string(), format!("Failed to download {url}: HTTP 404 Not Found"));
+    }
+
+    #[test]
+    fn test_progress() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let progress = Progress::bytes(move |bytes| log.lock().unwrap().push(bytes));
+        let mut text = String::new();
+        progress.wrap("a\tb\n1\tx\n".as_bytes()).read_to_string(&mut text).unwrap();
+        assert_eq!(seen.lock().unwrap().last(), Some(&8));
+
+        let lines = Arc::new(Mutex::new(0));
+        let count = Arc::clone(&lines);
+        let progress = Progress::lines(move |n| *count.lock().unwrap() = n);
+        std::io::copy(&mut progress.wrap(&b"a\n1\n2\n3"[..]), &mut std::io::sink()).unwrap();
+        assert_eq!(*lines.lock().unwrap(), 3);
+        assert_eq!(format!("{:?}", progress), "Progress(Lines)");
+    }
+}
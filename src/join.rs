@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use ndarray::Array2;
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+/// What `SurvivalData::left_join_with()` does with a key that is in several rows of the right table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JoinDuplicates {
+    /// An error naming the key and its rows
+    #[default]
+    Error,
+    /// The first row with the key is joined, the later ones are ignored
+    First,
+}
+
+/// How `SurvivalData::left_join_with()` combines two tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinOptions {
+    pub duplicates: JoinDuplicates,
+    /// Appended to a column of the right table whose name the left one has already
+    /// (again, until the name is free - default: `.y`)
+    pub suffix: String,
+}
+
+impl Default for JoinOptions {
+    fn default() -> Self {
+        JoinOptions { duplicates: JoinDuplicates::default(), suffix: ".y".to_string() }
+    }
+}
+
+impl SurvivalData {
+    /// `left_join_with()` failing on duplicate keys in `other`, suffixing name collisions with `.y`.
+    pub fn left_join(&self, other: &SurvivalData, on: &str) -> Result<SurvivalData> {
+        self.left_join_with(other, on, &JoinOptions::default())
+    }
+
+    /// Every row of this table with the columns of `other` appended: the row of `other` with
+    /// the same value in the key column `on` (e.g. `barcode`) or missing values if there is
+    /// none. Keys compare as written, so a factor key matches an integer one; rows without a
+    /// key match nothing. The key column of `other` is not repeated.
+    ///
+    /// Factors of `other` keep their levels and codes, then the levels without a joined row are
+    /// dropped and the missing counts and summaries of all columns are computed again.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let cells = SurvivalData::from_reader(Cursor::new("barcode\tnCount\nAAAC\t10\nAAAG\t20\n"), b'\t', HashSet::new()).unwrap();
+    /// let clusters = SurvivalData::from_reader(Cursor::new("barcode\tcluster\nAAAG\tT\n"), b'\t', HashSet::new()).unwrap();
+    /// let joined = cells.left_join(&clusters, "barcode").unwrap();
+    /// assert_eq!(joined.column_names(), ["barcode", "nCount", "cluster"]);
+    /// assert_eq!(joined.column("cluster").unwrap().missing, 1);
+    /// ```
+    pub fn left_join_with(&self, other: &SurvivalData, on: &str, options: &JoinOptions) -> Result<SurvivalData> {
+        for (table, data) in [("left", self), ("right", other)] {
+            if data.column_index(on).is_none() {
+                bail!(Column, "The {} table has no key column '{}'", table, on);
+            }
+            if data.streamed_rows.is_some() {
+                bail!(InvalidArgument, "The {} table was streamed - its rows are not kept to be joined", table);
+            }
+        }
+        if let Some(factor) = other.factors.values().find(|f| f.one_hot) {
+            bail!(InvalidArgument, "The right table has the one-hot encoded factor '{}' - join before the encoding", factor.column_name);
+        }
+        if options.suffix.is_empty() {
+            bail!(InvalidArgument, "The suffix for colliding column names cannot be empty");
+        }
+
+        // the row of `other` per key
+        let mut right_rows: HashMap<String, usize> = HashMap::with_capacity(other.n_rows());
+        for (row, key) in other.key_values(on).into_iter().enumerate() {
+            let Some(key) = key else { continue };
+            if let Some(&first) = right_rows.get(&key) {
+                match options.duplicates {
+                    JoinDuplicates::First => continue,
+                    JoinDuplicates::Error => bail!(
+                        InvalidData,
+                        "Key '{}' is in data rows {} and {} of the right table (join with JoinDuplicates::First to take the first)",
+                        key, first + 1, row + 1
+                    ),
+                }
+            }
+            right_rows.insert(key, row);
+        }
+        let matches: Vec<Option<usize>> = self.key_values(on).into_iter().map(|key| key.and_then(|k| right_rows.get(&k).copied())).collect();
+
+        // the appended columns and their free names
+        let appended: Vec<usize> = (0..other.headers.len()).filter(|&j| other.headers[j] != on).collect();
+        let mut headers = self.headers.clone();
+        let mut columns = self.columns.clone();
+        let mut factors = self.factors.clone();
+        for &j in &appended {
+            let mut name = other.headers[j].clone();
+            while headers.contains(&name) {
+                name.push_str(&options.suffix);
+            }
+            let mut column = other.columns[j].clone();
+            column.name = name.clone();
+            column.id = false;
+            if let Some(factor) = other.factors.get(&other.headers[j]) {
+                let mut factor = factor.clone();
+                factor.column_name = name.clone();
+                factors.insert(name.clone(), factor);
+            }
+            headers.push(name);
+            columns.push(column);
+        }
+
+        let left = self.headers.len();
+        let numeric_data = Array2::from_shape_fn((self.n_rows(), left + appended.len()), |(row, col)| match col {
+            c if c < left => self.numeric_data[[row, c]],
+            c => matches[row].map_or(f64::NAN, |r| other.numeric_data[[r, appended[c - left]]]),
+        });
+
+        let mut ret = self.select_rows(&[]);
+        ret.numeric_data = numeric_data;
+        ret.factors = factors;
+        ret.set_columns(headers, columns);
+        ret.report = self.report.clone();
+        ret.drop_unused_levels();
+        Ok(ret)
+    }
+
+    /// The key of every row as written - `None` for a missing value.
+    fn key_values(&self, on: &str) -> Vec<Option<String>> {
+        let index = self.column_index(on).expect("the key column exists");
+        let factor = self.factors.get(on);
+        self.numeric_data
+            .column(index)
+            .iter()
+            .map(|&value| match factor {
+                Some(factor) => factor.level_at(value).map(str::to_string),
+                None if value.is_nan() => None,
+                None => Some(value.to_string()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::column::Dtype;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_left_join() -> Result<()> {
+        let options = ParseOptions { max_levels: Some(3), ..Default::default() };
+        let parse = |tsv: &str| SurvivalData::from_reader_with_options(Cursor::new(tsv.to_string()), &options);
+        let cells = parse("barcode\tsample\tscore\nAAAC\ts1\t0.5\nAAAG\ts2\t0.7\nAAAT\ts1\t0.9\nNA\ts2\t1.1\n")?;
+        let clusters = parse("barcode\tcluster\tscore\tday\nAAAT\tT\t3\t1\nAAAC\tB\t4\t2\nGGGG\tNK\t5\t3\n")?;
+
+        let joined = cells.left_join(&clusters, "barcode")?;
+        assert_eq!(joined.column_names(), ["barcode", "sample", "score", "cluster", "score.y", "day"]);
+        assert_eq!(joined.n_rows(), 4);
+        assert_eq!(joined.as_vec_string("cluster").unwrap(), ["B", "NA", "T", "NA"]);
+        let day: Vec<String> = joined.as_vec_f64("day").iter().map(|v| v.to_string()).collect();
+        assert_eq!(day, ["2", "NaN", "1", "NaN"], "no match, no key: missing");
+        assert_eq!(joined.as_vec_f64("score"), cells.as_vec_f64("score"), "the left columns are kept");
+
+        // recomputed on the joined rows: NK has no row, the summaries see only the matches
+        let map = joined.compute_factors();
+        let cluster = map.get("cluster").unwrap();
+        assert_eq!((cluster.levels.clone(), cluster.counts.clone()), (vec!["T".into(), "B".into()], Some(vec![1, 1])));
+        let score = joined.column("score.y").unwrap();
+        assert_eq!((score.dtype, score.missing), (Dtype::Integer, 2));
+        assert_eq!(score.summary.as_ref().and_then(|s| s.max), Some(4.0));
+
+        // duplicate keys on the right
+        let twice = parse("barcode\tcluster\nAAAC\tB\nAAAT\tT\nAAAC\tNK\n")?;
+        let err = cells.left_join(&twice, "barcode").unwrap_err();
+        assert!(err.to_string().starts_with("Key 'AAAC' is in data rows 1 and 3 of the right table"), "{err}");
+        let first = JoinOptions { duplicates: JoinDuplicates::First, suffix: "_right".into() };
+        let joined = cells.left_join_with(&twice, "barcode", &first)?;
+        assert_eq!(joined.as_vec_string("cluster").unwrap(), ["B", "NA", "T", "NA"]);
+
+        // keys compare as written
+        let numbered = parse("id\tvalue\n1\t10\n2\t20\n")?;
+        let labels = parse("id\tlabel\n2\tsecond\n1\tfirst\n")?;
+        let ids = SurvivalData::from_reader_with_options(Cursor::new("id\tlabel\n2\tsecond\n"), &ParseOptions { categorical: ["id".to_string()].into(), ..Default::default() })?;
+        assert_eq!(numbered.left_join(&labels, "id")?.as_vec_string("label").unwrap(), ["first", "second"]);
+        assert_eq!(numbered.left_join(&ids, "id")?.as_vec_string("label").unwrap(), ["NA", "second"]);
+
+        assert_eq!(cells.left_join(&numbered, "barcode").unwrap_err().to_string(), "The right table has no key column 'barcode'");
+        Ok(())
+    }
+}
@@ -1,5 +1,77 @@
+pub mod error;
 pub mod survival_data;
+pub mod builder;
 pub mod factor;
+pub mod factor_map;
+pub mod input;
+pub mod compress;
+pub mod column;
+pub mod column_data;
+pub mod coerce;
+pub mod id_column;
+pub mod one_hot;
+pub mod preview;
+pub mod options;
+pub mod spec;
+pub mod survival;
+pub mod group_stats;
+pub mod histogram;
+pub mod correlation;
+pub mod validate;
+pub mod diff;
+pub mod merge;
+pub mod join;
+pub mod lint;
+pub mod sniff;
+pub mod vocabulary;
+pub mod split;
+pub mod r_source;
+pub mod schema_hash;
+pub mod row;
+pub mod sample;
+pub mod reshape;
+pub mod palette;
+pub mod cache;
+pub mod report;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+#[cfg(feature = "arrow")]
+pub mod record_batch;
+#[cfg(feature = "parquet")]
+pub mod parquet_file;
+#[cfg(feature = "hdf5")]
+pub mod h5ad;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+mod infer;
 
-pub use survival_data::SurvivalData;
-pub use factor::Factor;
\ No newline at end of file
+pub use error::DataTableError;
+pub use survival_data::{FactorFormat, SurvivalData};
+pub use builder::SurvivalDataBuilder;
+pub use factor::Factor;
+pub use factor_map::{ColumnOrder, FactorMap, FactorsFileFormat, JsonLayout, Provenance};
+pub use column::{Column, Dtype, MissingKinds, NumericSummary};
+pub use column_data::ColumnData;
+pub use factor::{FactorSource, FactorSpec, TopKOrder};
+pub use input::Progress;
+pub use compress::Compression;
+pub use options::{ColumnSelection, Delimiter, DuplicateColumns, JsonCells, LevelCase, NumberFormat, ParseOptions, SampleCheck, Separator, TextEncoding, TrailingDelimiter};
+pub use spec::{ColumnSpec, TableSpec};
+pub use survival::{CiTransform, CumulativeIncidence, CumulativeIncidenceStep, KaplanMeier, KaplanMeierStep, LogRankGroup, LogRankResult, SurvivalColumns};
+pub use group_stats::GroupStat;
+pub use histogram::{BinOptions, Binning, Histogram};
+pub use correlation::CorrMatrix;
+pub use validate::Drift;
+pub use diff::{Change, FactorDiff};
+pub use merge::MergeStrategy;
+pub use join::{JoinDuplicates, JoinOptions};
+pub use lint::{LevelLint, LintKind, LintOptions};
+pub use sniff::{LineEndings, SniffReport};
+pub use one_hot::{OneHotMissing, OneHotOptions};
+pub use split::{SplitEntry, SplitIndex};
+pub use row::Row;
+pub use sample::Reservoir;
+pub use palette::Palette;
+pub use cache::FactorCache;
+pub use report::{DropReason, ParseReport};
+pub use infer::{infer_dtype, InferOptions};
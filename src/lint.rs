@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use crate::column::Dtype;
+use crate::error::Result;
+use crate::options::ParseOptions;
+use crate::SurvivalData;
+
+/// What `SurvivalData::lint_levels()` flags besides surrounding whitespace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LintOptions {
+    /// Also flag levels differing only by case (`Treated` and `treated`)
+    pub case: bool,
+}
+
+/// How the spellings of a `LevelLint` differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintKind {
+    /// Only by whitespace around them - the parser trims it, so they are one level already
+    Whitespace,
+    /// Only by case (`LintOptions::case`) - two levels unless `level_case` folds them
+    Case,
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LintKind::Whitespace => "surrounding whitespace",
+            LintKind::Case => "case",
+        })
+    }
+}
+
+/// Spellings of one level in a factor column of the input - see `SurvivalData::lint_levels()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelLint {
+    pub column: String,
+    pub kind: LintKind,
+    /// The spellings as written in the file, in the order they first appear
+    pub levels: Vec<String>,
+}
+
+/// `Column 'treatment': levels differing only by surrounding whitespace: "treated" vs "treated "`
+impl fmt::Display for LevelLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let levels: Vec<String> = self.levels.iter().map(|l| format!("{:?}", l)).collect();
+        write!(f, "Column '{}': levels differing only by {}: {}", self.column, self.kind, levels.join(" vs "))
+    }
+}
+
+/// The distinct spellings per key, in the order the keys and the spellings first appear.
+#[derive(Default)]
+struct Spellings {
+    index: HashMap<String, usize>,
+    groups: Vec<Vec<String>>,
+}
+
+impl Spellings {
+    fn add(&mut self, key: String, spelling: &str) {
+        let groups = &mut self.groups;
+        let group = *self.index.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        if !self.groups[group].iter().any(|s| s == spelling) {
+            self.groups[group].push(spelling.to_string());
+        }
+    }
+
+    fn conflicts(self) -> impl Iterator<Item = Vec<String>> {
+        self.groups.into_iter().filter(|group| group.len() > 1)
+    }
+}
+
+impl SurvivalData {
+    /// Read the table this data was parsed from (`reader`, with the same `options`) once more and
+    /// report the factor columns whose raw values differ only by surrounding whitespace - `treated`
+    /// next to `treated ` - and, with `LintOptions::case`, only by case. Nothing is changed: the
+    /// parser trims the whitespace anyway, `level_case` folds the case.
+    ///
+    /// The lints are in column order, the whitespace one of a column first.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Cursor;
+    /// use rust_data_table::{LintOptions, ParseOptions, SurvivalData};
+    /// let tsv = "cell\tgroup\nA\ttreated\nB\ttreated \nC\tcontrol\n";
+    /// let options = ParseOptions { categorical: ["group".to_string()].into(), ..Default::default() };
+    /// let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).unwrap();
+    /// let lints = data.lint_levels(Cursor::new(tsv), &options, &LintOptions::default()).unwrap();
+    /// assert_eq!(lints[0].levels, ["treated", "treated "]);
+    /// ```
+    pub fn lint_levels<R: Read>(&self, reader: R, options: &ParseOptions, lint: &LintOptions) -> Result<Vec<LevelLint>> {
+        let mut options = options.clone();
+        options.progress = None;
+        let is_factor = |name: &str| self.factors.contains_key(name) && self.column(name).is_none_or(|c| c.dtype == Dtype::Factor);
+        let mut columns: Vec<(String, Spellings, Spellings)> = Vec::new();
+        Self::visit_raw_rows(reader, &options, |names, record| {
+            if columns.is_empty() {
+                columns = names.iter().map(|name| (name.clone(), Spellings::default(), Spellings::default())).collect();
+            }
+            for ((name, whitespace, case), raw) in columns.iter_mut().zip(record.iter()) {
+                let value = raw.trim();
+                if value.is_empty() || options.is_missing_in(name, value) || !is_factor(name) {
+                    continue;
+                }
+                whitespace.add(value.to_string(), raw);
+                if lint.case {
+                    case.add(value.to_lowercase(), value);
+                }
+            }
+        })?;
+
+        let mut lints = Vec::new();
+        for (column, whitespace, case) in columns {
+            for (kind, spellings) in [(LintKind::Whitespace, whitespace), (LintKind::Case, case)] {
+                lints.extend(spellings.conflicts().map(|levels| LevelLint { column: column.clone(), kind, levels }));
+            }
+        }
+        Ok(lints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_lint_levels() -> Result<()> {
+        let tsv = "cell\tgroup\tsex\tscore\nA\ttreated\tM\t 1\nB\t treated \tf\t1\nC\tTreated\tm\t1 \nD\ttreated \tF\tNA\nE\tNA \tM\t2\n";
+        let options = ParseOptions { categorical: ["group".to_string(), "sex".to_string()].into(), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.compute_factors().get("group").unwrap().levels, ["treated", "Treated"], "the parser trims");
+
+        let lints = data.lint_levels(Cursor::new(tsv), &options, &LintOptions::default())?;
+        assert_eq!(lints, [LevelLint {
+            column: "group".into(),
+            kind: LintKind::Whitespace,
+            levels: vec!["treated".into(), " treated ".into(), "treated ".into()],
+        }], "not the numeric score, not the missing values");
+        assert_eq!(lints[0].to_string(), "Column 'group': levels differing only by surrounding whitespace: \"treated\" vs \" treated \" vs \"treated \"");
+
+        let lints = data.lint_levels(Cursor::new(tsv), &options, &LintOptions { case: true })?;
+        let found: Vec<(&str, LintKind, Vec<String>)> = lints.iter().map(|l| (l.column.as_str(), l.kind, l.levels.clone())).collect();
+        assert_eq!(found, [
+            ("group", LintKind::Whitespace, vec!["treated".into(), " treated ".into(), "treated ".into()]),
+            ("group", LintKind::Case, vec!["treated".into(), "Treated".into()]),
+            ("sex", LintKind::Case, vec!["M".into(), "m".into()]),
+            ("sex", LintKind::Case, vec!["f".into(), "F".into()]),
+        ]);
+
+        let clean = "cell\tgroup\nA\tx\nB\ty\n";
+        let options = ParseOptions { categorical: ["group".to_string()].into(), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(clean), &options)?;
+        assert!(data.lint_levels(Cursor::new(clean), &options, &LintOptions { case: true })?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::BTreeMap;
+use crate::column::{Column, Dtype, MissingKinds, NumericSummary};
+use crate::error::{bail, Result};
+use crate::factor::{FactorJson, FactorSource};
+use crate::factor_map::{FactorMap, Provenance};
+
+/// How `FactorMap::merge()` combines the levels of a factor both maps have.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Every level of either map - the first map's levels in their order, then the new ones of the second
+    #[default]
+    Union,
+    /// Only the levels (and columns) both maps have, in the order of the first map
+    Intersection,
+    /// The maps have to have the same columns, types and level sets - any difference is an error
+    Strict,
+}
+
+impl MergeStrategy {
+    /// `union`, `intersection` or `strict`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        match arg.to_ascii_lowercase().as_str() {
+            "union" => Ok(MergeStrategy::Union),
+            "intersection" => Ok(MergeStrategy::Intersection),
+            "strict" => Ok(MergeStrategy::Strict),
+            _ => bail!(InvalidArgument, "Unknown merge strategy '{}' (union, intersection or strict)", arg),
+        }
+    }
+}
+
+impl FactorMap {
+    /// Combine the factor definitions of two related tables into one shared vocabulary, e.g.
+    /// the samples of an atlas. The levels of every factor get new codes (`0, 1, ...` or
+    /// starting at the first code of this map), so the result is consistent in itself - tables
+    /// encoded with one of the inputs have to be encoded again.
+    ///
+    /// Counts, missing values and row numbers add up where both maps have them. Numeric
+    /// columns merge their summaries (count, min of the minima, max of the maxima and the
+    /// weighted mean; quartiles cannot be merged and are dropped), an integer and a float
+    /// column become float. Other type differences are an error. Labels and colors come
+    /// from this map first; `_meta` is left out - the merged map comes from no single input.
+    pub fn merge(&self, other: &FactorMap, strategy: MergeStrategy) -> Result<FactorMap> {
+        let mut problems: Vec<String> = Vec::new();
+        if strategy == MergeStrategy::Strict {
+            let only = |a: &[String], b: &[String]| a.iter().filter(|c| !b.contains(c)).map(|c| format!("'{}'", c)).collect::<Vec<_>>();
+            let (ours, theirs) = (self.column_names(), other.column_names());
+            for (map, columns) in [("first", only(&ours, &theirs)), ("second", only(&theirs, &ours))] {
+                if !columns.is_empty() {
+                    problems.push(format!("only the {} map has {}", map, columns.join(", ")));
+                }
+            }
+        }
+
+        let mut columns: Vec<Column> = Vec::new();
+        for column in &self.columns {
+            match other.column(&column.name) {
+                Some(theirs) => match merge_columns(column, theirs) {
+                    Ok(merged) => columns.push(merged),
+                    Err(problem) => problems.push(problem),
+                },
+                None if strategy == MergeStrategy::Union => columns.push(column.clone()),
+                None => {}
+            }
+        }
+        if strategy == MergeStrategy::Union {
+            columns.extend(other.columns.iter().filter(|c| self.column(&c.name).is_none()).cloned());
+        }
+
+        let mut factors: Vec<FactorJson> = Vec::new();
+        for factor in &self.factors {
+            match other.get(&factor.column) {
+                Some(theirs) => match merge_factors(factor, theirs, strategy) {
+                    Ok(merged) => factors.push(merged),
+                    Err(problem) => problems.push(problem),
+                },
+                None if strategy == MergeStrategy::Union => factors.push(factor.clone()),
+                None => {}
+            }
+        }
+        if strategy == MergeStrategy::Union {
+            factors.extend(other.factors.iter().filter(|f| self.get(&f.column).is_none()).cloned());
+        }
+        if !problems.is_empty() {
+            bail!(InvalidData, "The factor maps cannot be merged: {}", problems.join("; "));
+        }
+
+        // the cardinality follows the merged levels
+        for column in &mut columns {
+            if let Some(factor) = factors.iter().find(|f| f.column == column.name) {
+                column.cardinality = column.cardinality.map(|_| factor.levels.len());
+            }
+        }
+        Ok(FactorMap {
+            n_rows: self.n_rows.zip(other.n_rows).map(|(a, b)| a + b),
+            columns,
+            factors,
+            ..Default::default()
+        })
+    }
+
+    /// Add the columns of `other` - computed later, e.g. from new annotations of the same cells -
+    /// after the columns of this map, with their factor definitions as they are. A column both
+    /// maps have is an error naming all of them, unless `overwrite` replaces it in place. `_meta`
+    /// lists the inputs of both maps and the time and command line of `other`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let read = |tsv: &str| SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap().compute_factors();
+    /// let mut map = read("cell\tcluster\nA\tT\nB\tB\n");
+    /// map.append(read("score\tphase\n1.5\tG1\n2.5\tS\n"), false).unwrap();
+    /// assert_eq!(map.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["cell", "cluster", "score", "phase"]);
+    /// ```
+    pub fn append(&mut self, other: FactorMap, overwrite: bool) -> Result<()> {
+        let ours = self.column_names();
+        let taken: Vec<String> = other.column_names().into_iter().filter(|name| ours.contains(name)).collect();
+        if !taken.is_empty() && !overwrite {
+            let listed: Vec<String> = taken.iter().map(|name| format!("'{}'", name)).collect();
+            bail!(InvalidData, "Cannot append: the factors file has the column(s) {} already - overwrite to replace them", listed.join(", "));
+        }
+        if let (Some(a), Some(b)) = (self.n_rows, other.n_rows)
+            && a != b
+        {
+            log::warn!("Appending the columns of a table with {} rows to the factors of {} rows", b, a);
+        }
+        for name in &taken {
+            self.factors.retain(|f| &f.column != name);
+        }
+        for column in other.columns {
+            match self.columns.iter_mut().find(|c| c.name == column.name) {
+                Some(existing) => *existing = column,
+                None => self.columns.push(column),
+            }
+        }
+        self.factors.extend(other.factors);
+        // a replaced factor goes back to where its column is
+        let position = |name: &str| self.columns.iter().position(|c| c.name == name).unwrap_or(usize::MAX);
+        let mut factors = std::mem::take(&mut self.factors);
+        factors.sort_by_key(|f| position(&f.column));
+        self.factors = factors;
+
+        self.meta = match (self.meta.take(), other.meta) {
+            (Some(mut ours), Some(theirs)) => {
+                for input in theirs.inputs {
+                    if !ours.inputs.contains(&input) {
+                        ours.inputs.push(input);
+                    }
+                }
+                for column in theirs.categorical {
+                    if !ours.categorical.contains(&column) {
+                        ours.categorical.push(column);
+                    }
+                }
+                Some(Provenance { generated: theirs.generated, tool: theirs.tool, command: theirs.command, ..ours })
+            }
+            (ours, theirs) => ours.or(theirs),
+        };
+        Ok(())
+    }
+
+    /// The names of the columns - or of the factors for maps without columns.
+    fn column_names(&self) -> Vec<String> {
+        match self.columns.is_empty() {
+            true => self.factors.iter().map(|f| f.column.clone()).collect(),
+            false => self.columns.iter().map(|c| c.name.clone()).collect(),
+        }
+    }
+}
+
+/// One column of both maps - `Err` explains a type conflict.
+fn merge_columns(ours: &Column, theirs: &Column) -> std::result::Result<Column, String> {
+    // a column without any value takes the type (and summary) of the other map
+    let (ours, theirs) = match ours.dtype {
+        Dtype::AllMissing => (theirs, ours),
+        _ => (ours, theirs),
+    };
+    let dtype = match (ours.dtype, theirs.dtype) {
+        (a, b) if a == b => a,
+        (a, Dtype::AllMissing) => a,
+        (Dtype::Integer, Dtype::Float) | (Dtype::Float, Dtype::Integer) => Dtype::Float,
+        (a, b) => return Err(format!("column '{}' is {} in the first map and {} in the second", ours.name, a, b)),
+    };
+    let summary = match (&ours.summary, &theirs.summary) {
+        (Some(a), Some(b)) => Some(merge_summaries(a, b)),
+        (Some(a), None) if theirs.dtype == Dtype::AllMissing => Some(a.clone()),
+        _ => None,
+    };
+    let missing_kinds = match (ours.missing_kinds, theirs.missing_kinds) {
+        (Some(a), Some(b)) => Some(MissingKinds { empty: a.empty + b.empty, na: a.na + b.na }),
+        (Some(kinds), None) if theirs.missing == 0 => Some(kinds),
+        (None, Some(kinds)) if ours.missing == 0 => Some(kinds),
+        _ => None,
+    };
+    let constant = ours.constant && theirs.constant && summary.as_ref().is_none_or(|s| s.min == s.max);
+    Ok(Column {
+        dtype,
+        missing: ours.missing + theirs.missing,
+        missing_kinds,
+        summary,
+        constant,
+        ..ours.clone()
+    })
+}
+
+/// Count, min, max and mean of the values behind both summaries.
+fn merge_summaries(a: &NumericSummary, b: &NumericSummary) -> NumericSummary {
+    let pick = |x: Option<f64>, y: Option<f64>, f: fn(f64, f64) -> f64| match (x, y) {
+        (Some(x), Some(y)) => Some(f(x, y)),
+        (x, y) => x.or(y),
+    };
+    let count = a.count + b.count;
+    let mean = match (a.mean, b.mean) {
+        // weighted by the shares - a product with the counts could overflow for large values
+        (Some(x), Some(y)) if count > 0 => Some(x * (a.count as f64 / count as f64) + y * (b.count as f64 / count as f64)),
+        (x, y) => x.or(y),
+    };
+    NumericSummary { count, min: pick(a.min, b.min, f64::min), max: pick(a.max, b.max, f64::max), mean, ..Default::default() }
+}
+
+/// One factor of both maps with new codes - `Err` explains why `Strict` refuses it.
+fn merge_factors(ours: &FactorJson, theirs: &FactorJson, strategy: MergeStrategy) -> std::result::Result<FactorJson, String> {
+    let levels: Vec<String> = match strategy {
+        MergeStrategy::Union => ours.levels.iter().chain(theirs.levels.iter().filter(|l| !ours.levels.contains(l))).cloned().collect(),
+        MergeStrategy::Intersection => ours.levels.iter().filter(|l| theirs.levels.contains(l)).cloned().collect(),
+        MergeStrategy::Strict => {
+            let missing = |a: &[String], b: &[String]| a.iter().filter(|l| !b.contains(l)).cloned().collect::<Vec<_>>();
+            let (ours_only, theirs_only) = (missing(&ours.levels, &theirs.levels), missing(&theirs.levels, &ours.levels));
+            if !ours_only.is_empty() || !theirs_only.is_empty() {
+                return Err(format!(
+                    "factor '{}' has the levels [{}] only in the first and [{}] only in the second map",
+                    ours.column,
+                    ours_only.join(", "),
+                    theirs_only.join(", ")
+                ));
+            }
+            ours.levels.clone()
+        }
+    };
+    // per level value of either map, ours first
+    let lookup = |values: fn(&FactorJson) -> Option<&Vec<String>>, fallback: fn(&str) -> String| -> Option<Vec<String>> {
+        if values(ours).is_none() && values(theirs).is_none() {
+            return None;
+        }
+        let of = |factor: &FactorJson, level: &String| {
+            let position = factor.levels.iter().position(|l| l == level)?;
+            values(factor)?.get(position).filter(|v| !v.is_empty()).cloned()
+        };
+        Some(levels.iter().map(|l| of(ours, l).or_else(|| of(theirs, l)).unwrap_or_else(|| fallback(l))).collect())
+    };
+    let counts = match (&ours.counts, &theirs.counts) {
+        (Some(_), Some(_)) => {
+            let count = |factor: &FactorJson, level: &String| {
+                factor.levels.iter().position(|l| l == level).and_then(|i| factor.counts.as_ref()?.get(i).copied()).unwrap_or(0)
+            };
+            Some(levels.iter().map(|l| count(ours, l) + count(theirs, l)).collect())
+        }
+        _ => None,
+    };
+    let first_code = ours.numeric.as_ref().and_then(|codes| codes.iter().copied().reduce(f64::min)).unwrap_or(0.0);
+    let merged: BTreeMap<String, String> = theirs.merged.iter().chain(&ours.merged).flatten().map(|(a, b)| (a.clone(), b.clone())).collect();
+    Ok(FactorJson {
+        column: ours.column.clone(),
+        numeric: Some((0..levels.len()).map(|i| first_code + i as f64).collect()),
+        matching: ours.matching.clone(),
+        one_hot: ours.one_hot || theirs.one_hot,
+        ordered: ours.ordered || theirs.ordered,
+        labels: lookup(|f| f.labels.as_ref(), str::to_string),
+        colors: lookup(|f| f.colors.as_ref(), |_| String::new()),
+        counts,
+        missing: ours.missing.zip(theirs.missing).map(|(a, b)| a + b),
+        merged: (!merged.is_empty()).then_some(merged),
+        source: match (ours.source, theirs.source) {
+            (Some(FactorSource::Inferred), Some(FactorSource::Inferred)) => Some(FactorSource::Inferred),
+            (None, None) => None,
+            _ => Some(FactorSource::Declared),
+        },
+        bins: ours.bins.clone().filter(|edges| theirs.bins.as_ref() == Some(edges)),
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use crate::SurvivalData;
+
+    fn factors(tsv: &str) -> FactorMap {
+        SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap().compute_factors()
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let a = factors("cluster\tage\tbatch\nT\t40\tb1\nB\t52\tb1\nT\t60\tb1\n");
+        let b = factors("cluster\tage\tdose\nNK\t20.5\t1\nB\tNA\t2\n");
+
+        let union = a.merge(&b, MergeStrategy::Union)?;
+        let cluster = union.get("cluster").unwrap();
+        assert_eq!(cluster.levels, ["T", "B", "NK"]);
+        assert_eq!((cluster.numeric.clone(), cluster.counts.clone()), (Some(vec![0.0, 1.0, 2.0]), Some(vec![2, 2, 1])));
+        assert_eq!(union.column("cluster").unwrap().cardinality, Some(3));
+        let age = union.column("age").unwrap();
+        assert_eq!((age.dtype, age.missing), (Dtype::Float, 1), "integer and float");
+        let summary = age.summary.clone().unwrap();
+        assert_eq!((summary.count, summary.min, summary.max, summary.median), (4, Some(20.5), Some(60.0), None));
+        assert!((summary.mean.unwrap() - (40.0 + 52.0 + 60.0 + 20.5) / 4.0).abs() < 1e-12);
+        let names: Vec<&str> = union.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["cluster", "age", "batch", "dose"]);
+        assert_eq!((union.n_rows, union.meta.is_none()), (Some(5), true));
+
+        // a shard without any age takes the type of the other one
+        let empty = factors("cluster\tage\nT\tNA\n");
+        let age = empty.merge(&a, MergeStrategy::Union)?.column("age").cloned().unwrap();
+        assert_eq!((age.dtype, age.missing, age.summary.map(|s| s.count)), (Dtype::Integer, 1, Some(3)));
+
+        let both = a.merge(&b, MergeStrategy::Intersection)?;
+        assert_eq!(both.get("cluster").unwrap().levels, ["B"]);
+        assert_eq!(both.get("cluster").unwrap().numeric, Some(vec![0.0]), "new codes");
+        assert_eq!(both.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["cluster", "age"]);
+
+        let err = a.merge(&b, MergeStrategy::Strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The factor maps cannot be merged: only the first map has 'batch'; only the second map has 'dose'; \
+             factor 'cluster' has the levels [T] only in the first and [NK] only in the second map"
+        );
+        assert_eq!(a.merge(&a, MergeStrategy::Strict)?.get("cluster").unwrap().counts, Some(vec![4, 2]));
+
+        // labels of either map, codes starting where the first map starts
+        let mut labelled = b.clone();
+        labelled.factors[0].labels = Some(vec!["Natural killer".into(), "B cell".into()]);
+        let mut one_based = a.clone();
+        one_based.factors[0].numeric = Some(vec![1.0, 2.0]);
+        let merged = one_based.merge(&labelled, MergeStrategy::Union)?;
+        let cluster = merged.get("cluster").unwrap();
+        assert_eq!(cluster.labels, Some(vec!["T".into(), "B cell".into(), "Natural killer".into()]));
+        assert_eq!(cluster.numeric, Some(vec![1.0, 2.0, 3.0]));
+
+        let text = factors("cluster\tage\nT\tyoung\n");
+        let err = a.merge(&text, MergeStrategy::Union).unwrap_err();
+        assert!(err.to_string().contains("column 'age' is integer in the first map and factor in the second"), "{err}");
+        assert_eq!(MergeStrategy::parse("Intersection")?, MergeStrategy::Intersection);
+        assert!(MergeStrategy::parse("outer").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_append() -> Result<()> {
+        let mut map = factors("cell\tcluster\tage\nA\tT\t40\nB\tB\t52\n");
+        map.meta = Some(Provenance { inputs: vec!["meta.tsv".into()], generated: "2024-01-01T00:00:00Z".into(), ..Default::default() });
+        let mut later = factors("phase\tscore\nG1\t1.5\nS\t2\n");
+        later.meta = Some(Provenance { inputs: vec!["phase.tsv".into()], generated: "2024-02-01T00:00:00Z".into(), ..Default::default() });
+
+        map.append(later, false)?;
+        let names: Vec<String> = map.columns.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, ["cell", "cluster", "age", "phase", "score"], "new columns last");
+        let factors_in: Vec<&str> = map.factors.iter().map(|f| f.column.as_str()).collect();
+        assert_eq!(factors_in, ["cell", "cluster", "phase"]);
+        let meta = map.meta.clone().unwrap();
+        assert_eq!((meta.inputs, meta.generated.as_str()), (vec!["meta.tsv".to_string(), "phase.tsv".to_string()], "2024-02-01T00:00:00Z"));
+
+        let renamed = factors("cluster\tage\nNK\tyoung\n");
+        let err = map.clone().append(renamed.clone(), false).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot append: the factors file has the column(s) 'cluster', 'age' already - overwrite to replace them");
+        map.append(renamed, true)?;
+        assert_eq!(map.columns.iter().map(|c| c.name.clone()).collect::<Vec<_>>(), names, "replaced in place");
+        assert_eq!(map.get("cluster").unwrap().levels, ["NK"]);
+        assert_eq!(map.column("age").unwrap().dtype, Dtype::Factor);
+        let factors_in: Vec<&str> = map.factors.iter().map(|f| f.column.as_str()).collect();
+        assert_eq!(factors_in, ["cell", "cluster", "age", "phase"], "in column order");
+        Ok(())
+    }
+}
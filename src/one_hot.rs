@@ -0,0 +1,126 @@
+use ndarray::Array2;
+use crate::column::{Column, Dtype, NumericSummary};
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+/// What the indicator columns of `SurvivalData::one_hot_with()` hold in a row without a level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OneHotMissing {
+    /// 0 in every indicator - the row is in none of the levels
+    #[default]
+    Zeros,
+    /// Missing in every indicator
+    Missing,
+}
+
+/// How `SurvivalData::one_hot_with()` expands a factor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OneHotOptions {
+    pub missing: OneHotMissing,
+    /// Leave out the indicator of the reference (first) level - the others are then
+    /// independent of each other, as regression models need (default: off)
+    pub drop_reference: bool,
+}
+
+impl SurvivalData {
+    /// `one_hot_with()` with an indicator per level and 0 for missing values.
+    pub fn one_hot(&self, column: &str) -> Result<SurvivalData> {
+        self.one_hot_with(column, &OneHotOptions::default())
+    }
+
+    /// A copy of the table with the factor `column` replaced by 0/1 integer columns named
+    /// `<column>_<level>`, one per level in level order, at the place of the factor.
+    /// Factors that are one-hot encoded while reading (`Factor::one_hot`) have their
+    /// indicators already and are an error.
+    pub fn one_hot_with(&self, column: &str, options: &OneHotOptions) -> Result<SurvivalData> {
+        let Some(index) = self.column_index(column) else {
+            bail!(Column, "Column '{}' not found", column);
+        };
+        let Some(factor) = self.factors.get(column).filter(|_| self.columns[index].dtype == Dtype::Factor) else {
+            bail!(Column, "Column '{}' is not a factor column, but {}", column, self.columns[index].dtype);
+        };
+        if factor.one_hot {
+            bail!(InvalidArgument, "Factor '{}' has its one-hot columns already", column);
+        }
+        let skip = usize::from(options.drop_reference);
+        let levels: Vec<(String, f64)> = factor
+            .get_levels()
+            .iter()
+            .skip(skip)
+            .map(|level| (format!("{}_{}", column, level), factor.get_f64(level)))
+            .collect();
+        if let Some((name, _)) = levels.iter().find(|(name, _)| self.column_index(name).is_some()) {
+            bail!(InvalidArgument, "Cannot one-hot encode '{}': the table has a column '{}' already", column, name);
+        }
+
+        // per new column: the old column it is copied from, or the code it indicates
+        let mut plan: Vec<std::result::Result<usize, f64>> = (0..index).map(Ok).collect();
+        plan.extend(levels.iter().map(|&(_, code)| Err(code)));
+        plan.extend((index + 1..self.headers.len()).map(Ok));
+        let empty = match options.missing {
+            OneHotMissing::Zeros => 0.0,
+            OneHotMissing::Missing => f64::NAN,
+        };
+        let codes = self.numeric_data.column(index);
+        let numeric_data = Array2::from_shape_fn((self.n_rows(), plan.len()), |(row, col)| match plan[col] {
+            Ok(j) => self.numeric_data[[row, j]],
+            Err(_) if codes[row].is_nan() => empty,
+            Err(code) => f64::from(u8::from(codes[row] == code)),
+        });
+
+        let mut headers = self.headers[..index].to_vec();
+        let mut columns = self.columns[..index].to_vec();
+        for (j, (name, _)) in levels.iter().enumerate() {
+            let values = numeric_data.column(index + j).to_vec();
+            let mut indicator = Column::new(name, Dtype::Integer);
+            indicator.missing = values.iter().filter(|v| v.is_nan()).count();
+            indicator.summary = Some(NumericSummary::from_values(&values));
+            headers.push(name.clone());
+            columns.push(indicator);
+        }
+        headers.extend_from_slice(&self.headers[index + 1..]);
+        columns.extend_from_slice(&self.columns[index + 1..]);
+
+        let mut ret = self.select_rows(&[]);
+        ret.numeric_data = numeric_data;
+        ret.factors.remove(column);
+        ret.set_columns(headers, columns);
+        ret.report = self.report.clone();
+        ret.mark_constant();
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_one_hot() -> Result<()> {
+        let tsv = "cell\tgroup\tn\nA\tx\t1\nB\ty\t2\nC\tNA\t3\nD\tz\t4\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let encoded = data.one_hot("group")?;
+        assert_eq!(encoded.column_names(), ["cell", "group_x", "group_y", "group_z", "n"]);
+        assert_eq!(encoded.as_vec_f64("group_y"), vec![0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(encoded.column("group_x").unwrap().dtype, Dtype::Integer);
+        assert!(encoded.compute_factors().get("group").is_none());
+        assert_eq!(encoded.as_vec_string("cell").unwrap(), ["A", "B", "C", "D"], "the other columns are kept");
+        assert_eq!(encoded.as_vec_f64("n"), data.as_vec_f64("n"));
+
+        let options = OneHotOptions { missing: OneHotMissing::Missing, drop_reference: true };
+        let encoded = data.one_hot_with("group", &options)?;
+        assert_eq!(encoded.column_names(), ["cell", "group_y", "group_z", "n"]);
+        let z: Vec<String> = encoded.as_vec_f64("group_z").iter().map(|v| v.to_string()).collect();
+        assert_eq!(z, ["0", "0", "NaN", "1"]);
+        assert_eq!(encoded.column("group_z").unwrap().missing, 1);
+
+        assert_eq!(data.one_hot("n").unwrap_err().to_string(), "Column 'n' is not a factor column, but integer");
+        assert!(data.one_hot("nope").is_err());
+        let twice = data.one_hot("group")?.one_hot("cell")?;
+        assert_eq!(twice.n_cols(), 8);
+        Ok(())
+    }
+}
@@ -0,0 +1,1283 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+use std::fmt;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use regex::bytes::Regex;
+use std::path::Path;
+use crate::error::{bail, DataTableError, Result};
+use crate::column::{Dtype, MissingKinds};
+use crate::factor::FactorSpec;
+use crate::factor_map::{check_level_codes, LevelCodes};
+use crate::infer::{has_leading_zero, is_missing, is_number_text, parse_boolean, parse_float, parse_integer, InferOptions};
+use crate::input::{open_input, Progress, BOM};
+
+/// Cells treated as missing unless configured otherwise (compared ignoring ASCII case).
+pub const DEFAULT_MISSING_TOKENS: [&str; 5] = ["NA", "NaN", "", "NULL", "None"];
+
+/// Cells read as `true` / `false` in boolean columns unless configured otherwise (ignoring ASCII case).
+pub const DEFAULT_TRUE_TOKENS: [&str; 3] = ["TRUE", "yes", "1"];
+pub const DEFAULT_FALSE_TOKENS: [&str; 3] = ["FALSE", "no", "0"];
+
+/// Date formats tried (in this order) by `detect_dates` - a column has to match one of them completely.
+pub const DATE_FORMATS: [&str; 7] = [
+    "%Y-%m-%d",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y/%m/%d",
+    "%d.%m.%Y",
+    "%d/%m/%Y",
+    "%m/%d/%Y",
+];
+
+/// Inferred factors with more levels than this are treated as free text.
+pub const DEFAULT_MAX_LEVELS: usize = 200;
+
+/// Delimiters tried by `Delimiter::Auto`.
+pub const CANDIDATE_DELIMITERS: [u8; 4] = [b'\t', b',', b';', b'|'];
+
+/// Number of lines `Delimiter::Auto` looks at.
+pub(crate) const SNIFF_LINES: usize = 20;
+
+/// The field delimiter of a table - either given or detected from the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Sniff the first lines of the file (see `sniff_delimiter()`)
+    Auto,
+    Byte(u8),
+}
+
+impl Delimiter {
+    /// Parse a command line value: `auto`, `tab`, `comma`, `semicolon`, `pipe` or a single byte
+    /// after `unescape_delimiter()` - `\t` and a real tab are both a tab, `\\` is a backslash.
+    /// A line break cannot separate fields.
+    pub fn parse(arg: &str) -> Result<Self> {
+        let byte = match arg {
+            "auto" => return Ok(Delimiter::Auto),
+            "tab" => b'\t',
+            "comma" => b',',
+            "semicolon" => b';',
+            "pipe" => b'|',
+            _ => match unescape_delimiter(arg)?.as_bytes() {
+                [b'\n' | b'\r'] => bail!(BadDelimiter, "Invalid delimiter '{}': a line break cannot separate fields", arg),
+                &[byte] => byte,
+                _ => bail!(BadDelimiter, "Invalid delimiter '{}': not a single byte", arg),
+            },
+        };
+        Ok(Delimiter::Byte(byte))
+    }
+
+    /// The delimiter to use for the file at `path` - `Auto` reads its first lines.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Result<u8> {
+        self.resolve_with_comment(path, None)
+    }
+
+    /// Like `resolve()`, ignoring the lines starting with `comment` (see `ParseOptions::comment`).
+    pub fn resolve_with_comment<P: AsRef<Path>>(&self, path: P, comment: Option<u8>) -> Result<u8> {
+        self.resolve_skipping(path, comment, 0)
+    }
+
+    /// Like `resolve_with_comment()`, also ignoring the `skip` lines above the header
+    /// (see `ParseOptions::header_row`).
+    pub fn resolve_skipping<P: AsRef<Path>>(&self, path: P, comment: Option<u8>, skip: usize) -> Result<u8> {
+        match self {
+            Delimiter::Byte(delimiter) => Ok(*delimiter),
+            Delimiter::Auto => self.detect_skipping(open_input(path)?, comment, skip),
+        }
+    }
+
+    /// Like `resolve()`, for a table that is already open (e.g. buffered stdin).
+    pub fn detect<R: BufRead>(&self, reader: R) -> Result<u8> {
+        self.detect_with_comment(reader, None)
+    }
+
+    /// Like `detect()`, ignoring the lines starting with `comment`.
+    pub fn detect_with_comment<R: BufRead>(&self, reader: R, comment: Option<u8>) -> Result<u8> {
+        self.detect_skipping(reader, comment, 0)
+    }
+
+    /// Like `detect_with_comment()`, also ignoring the first `skip` lines that are neither
+    /// comments nor blank - the title lines above the header (see `ParseOptions::header_row`).
+    pub fn detect_skipping<R: BufRead>(&self, reader: R, comment: Option<u8>, mut skip: usize) -> Result<u8> {
+        match self {
+            Delimiter::Byte(delimiter) => Ok(*delimiter),
+            Delimiter::Auto => {
+                let mut sample = String::new();
+                let mut n_lines = 0;
+                // the delimiters are ASCII - the encoding of the other bytes does not matter here
+                for line in reader.split(b'\n') {
+                    let mut line = String::from_utf8_lossy(&line?).trim_end_matches('\r').to_string();
+                    if n_lines == 0 && line.starts_with(BOM) {
+                        line.remove(0);
+                    }
+                    if comment.is_some_and(|c| line.as_bytes().first() == Some(&c)) {
+                        continue;
+                    }
+                    if skip > 0 {
+                        skip -= usize::from(!line.is_empty());
+                        continue;
+                    }
+                    sample.push_str(&line);
+                    sample.push('\n');
+                    n_lines += 1;
+                    if n_lines == SNIFF_LINES {
+                        break;
+                    }
+                }
+                sniff_delimiter(&sample)
+            }
+        }
+    }
+}
+
+/// Detect the delimiter of a table from its first lines.
+///
+/// Every candidate in `CANDIDATE_DELIMITERS` splits the lines (quote aware); the one for
+/// which the most lines have the column count of the header (and more than one column) wins.
+/// A table without any candidate is a single tab separated column.
+/// Two equally good candidates are an error - the delimiter has to be given explicitly.
+pub fn sniff_delimiter(sample: &str) -> Result<u8> {
+    let mut scores = delimiter_scores(sample);
+    scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    match scores.as_slice() {
+        [(_, 0), ..] => Ok(b'\t'),
+        [(best, a), (second, b), ..] if a == b => bail!(
+            BadDelimiter,
+            "Cannot detect the delimiter: '{}' and '{}' fit equally well - please specify it explicitly",
+            (*best as char).escape_default(),
+            (*second as char).escape_default()
+        ),
+        [(best, _), ..] => Ok(*best),
+        [] => unreachable!("there are candidate delimiters"),
+    }
+}
+
+/// Per candidate in `CANDIDATE_DELIMITERS` the number of lines of `sample` that have the column
+/// count of the header - 0 for a candidate that leaves the header a single column.
+pub(crate) fn delimiter_scores(sample: &str) -> Vec<(u8, usize)> {
+    CANDIDATE_DELIMITERS
+        .iter()
+        .map(|&delimiter| {
+            let counts: Vec<usize> = csv::ReaderBuilder::new()
+                .delimiter(delimiter)
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(sample.as_bytes())
+                .records()
+                .map_while(|r| r.ok())
+                .map(|r| r.len())
+                .collect();
+            let score = match counts.first() {
+                Some(&n) if n > 1 => counts.iter().filter(|&&c| c == n).count(),
+                _ => 0,
+            };
+            (delimiter, score)
+        })
+        .collect()
+}
+
+/// What happens to the rows after `sample_rows` in type inference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleCheck {
+    /// A value not fitting the sampled type widens it (integer -> float -> factor)
+    #[default]
+    Widen,
+    /// A value not fitting the sampled type is an error naming its line
+    Error,
+    /// Trust the sample - values not fitting a numeric type become missing
+    Skip,
+}
+
+/// What happens to a header naming a column that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateColumns {
+    /// The first column keeps its name, later ones get `.1`, `.2`, ... (like R's `make.unique()`)
+    #[default]
+    Rename,
+    /// A duplicate name is an error
+    Error,
+}
+
+/// What happens to a delimiter at the end of every line - an empty last field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingDelimiter {
+    /// The phantom field is dropped: an empty last header name (unless the first row has a
+    /// value below it) and the empty last field of rows with one field more than the header
+    #[default]
+    Strip,
+    /// An empty last header name is a real column (`V<n>`, for its position); rows with one
+    /// field more than the header are an error
+    Keep,
+}
+
+impl TrailingDelimiter {
+    /// `strip` or `keep`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        match arg.to_ascii_lowercase().as_str() {
+            "strip" => Ok(TrailingDelimiter::Strip),
+            "keep" => Ok(TrailingDelimiter::Keep),
+            _ => bail!(InvalidArgument, "Unknown trailing delimiter handling '{}' (strip or keep)", arg),
+        }
+    }
+}
+
+/// Which columns of the file are read - the others are skipped right after the header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ColumnSelection {
+    #[default]
+    All,
+    /// Only these columns (they stay in file order)
+    Include(Vec<String>),
+    /// All but these columns
+    Exclude(Vec<String>),
+}
+
+impl ColumnSelection {
+    /// The listed column names.
+    pub fn names(&self) -> &[String] {
+        match self {
+            ColumnSelection::All => &[],
+            ColumnSelection::Include(names) | ColumnSelection::Exclude(names) => names,
+        }
+    }
+
+    /// Is the column `name` read?
+    pub fn keeps(&self, name: &str) -> bool {
+        match self {
+            ColumnSelection::All => true,
+            ColumnSelection::Include(names) => names.iter().any(|n| n == name),
+            ColumnSelection::Exclude(names) => !names.iter().any(|n| n == name),
+        }
+    }
+
+    /// The positions in `header` of the columns that are read - one hash lookup per column,
+    /// so listing thousands of the columns of a wide file stays linear.
+    pub fn keep_indices(&self, header: &[String]) -> Vec<usize> {
+        let listed: HashSet<&str> = self.names().iter().map(String::as_str).collect();
+        let include = matches!(self, ColumnSelection::Include(_));
+        (0..header.len())
+            .filter(|&i| matches!(self, ColumnSelection::All) || listed.contains(header[i].as_str()) == include)
+            .collect()
+    }
+}
+
+/// How factor values are normalized before they become levels.
+/// Surrounding whitespace is always trimmed.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum LevelCase {
+    /// Levels are kept as written
+    #[default]
+    Keep,
+    /// `CD8` and `cd8` become the level `cd8`
+    Lower,
+    /// `CD8` and `cd8` become the level `CD8`
+    Upper,
+    /// A supplied case-fold
+    Custom(fn(&str) -> String),
+}
+
+impl LevelCase {
+    /// Parse a command line value: `keep`, `lower` or `upper`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        Ok(match arg {
+            "keep" => LevelCase::Keep,
+            "lower" => LevelCase::Lower,
+            "upper" => LevelCase::Upper,
+            _ => bail!(InvalidArgument, "Invalid level case '{}' - expected keep, lower or upper", arg),
+        })
+    }
+
+    /// The normalized form of a (trimmed) value.
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            LevelCase::Keep => value.to_string(),
+            LevelCase::Lower => value.to_lowercase(),
+            LevelCase::Upper => value.to_uppercase(),
+            LevelCase::Custom(fold) => fold(value),
+        }
+    }
+
+    /// Can two different values become the same level?
+    pub fn folds(&self) -> bool {
+        !matches!(self, LevelCase::Keep)
+    }
+}
+
+/// What happens to columns holding nothing but JSON objects (`{"x":1,"y":2}`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonCells {
+    /// They are not detected - every object is a factor level (or the column free text)
+    #[default]
+    Off,
+    /// They are `Json` columns: kept as written, without factor levels
+    Keep,
+    /// Every top-level key becomes a column `col.key` - all objects need the same keys
+    Flatten,
+}
+
+impl JsonCells {
+    /// Parse a command line value: `off`, `keep` or `flatten`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        Ok(match arg {
+            "off" => JsonCells::Off,
+            "keep" => JsonCells::Keep,
+            "flatten" => JsonCells::Flatten,
+            _ => bail!(InvalidArgument, "Invalid JSON cell handling '{}' - expected off, keep or flatten", arg),
+        })
+    }
+}
+
+/// The character encoding of the input - everything is transcoded to UTF-8 while reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// Windows-1252, the superset of Latin-1 (ISO-8859-1) legacy Windows tools write
+    Windows1252,
+}
+
+/// `utf-8` or `windows-1252` - the names `parse()` takes.
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Windows1252 => "windows-1252",
+        })
+    }
+}
+
+impl TextEncoding {
+    /// Parse a command line value: `utf-8`, `latin1` (`iso-8859-1`) or `windows-1252` (`cp1252`), ignoring case.
+    pub fn parse(arg: &str) -> Result<Self> {
+        Ok(match arg.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => TextEncoding::Utf8,
+            "latin1" | "latin-1" | "iso-8859-1" | "windows-1252" | "cp1252" => TextEncoding::Windows1252,
+            _ => bail!(
+                InvalidArgument,
+                "Unsupported encoding '{}' - supported are utf-8, latin1 (iso-8859-1) and windows-1252 (cp1252)",
+                arg
+            ),
+        })
+    }
+
+    /// The input as UTF-8 - UTF-8 is passed through unchanged.
+    pub fn decode<'a, R: Read + 'a>(&self, reader: R) -> Box<dyn Read + 'a> {
+        match self {
+            TextEncoding::Utf8 => Box::new(reader),
+            TextEncoding::Windows1252 => Box::new(
+                DecodeReaderBytesBuilder::new()
+                    .encoding(Some(encoding_rs::WINDOWS_1252))
+                    .build(reader),
+            ),
+        }
+    }
+}
+
+/// How numbers are written - e.g. `1.234,56` (decimal comma, dot thousands) in European exports.
+///
+/// The default accepts a decimal point or comma (`0,5`) and no thousands separator. A configured
+/// format is strict: thousands separators have to group by three digits and only the decimal
+/// separator may start the fraction, so `1,234.5` is no number with a decimal comma.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    decimal: Option<u8>,
+    thousands: Option<u8>,
+}
+
+impl NumberFormat {
+    /// `decimal` has to be `.` or `,`; `thousands` (e.g. `.`, `,`, `'` or a space) must differ from it.
+    pub fn new(decimal: u8, thousands: Option<u8>) -> Result<Self> {
+        if decimal != b'.' && decimal != b',' {
+            bail!(InvalidArgument, "The decimal separator has to be '.' or ',', not '{}'", decimal as char);
+        }
+        match thousands {
+            Some(t) if t == decimal => {
+                bail!(InvalidArgument, "The thousands separator '{}' is also the decimal separator", t as char)
+            }
+            Some(t) if t.is_ascii_digit() || t == b'-' || t == b'+' => {
+                bail!(InvalidArgument, "Invalid thousands separator '{}'", t as char)
+            }
+            _ => Ok(NumberFormat { decimal: Some(decimal), thousands }),
+        }
+    }
+
+    /// Is this the default (lenient) format?
+    pub fn is_default(&self) -> bool {
+        self.decimal.is_none()
+    }
+
+    /// The number as Rust writes it (`1.234,5` -> `1234.5`) - `None` if `value` is no number in this format.
+    fn canonical(&self, value: &str, decimal: u8) -> Option<String> {
+        let (sign, digits) = match value.as_bytes().first() {
+            Some(b'-' | b'+') => value.split_at(1),
+            _ => ("", value),
+        };
+        let (int_part, fraction) = match digits.split_once(decimal as char) {
+            Some((int_part, fraction)) => (int_part, Some(fraction)),
+            None => (digits, None),
+        };
+        let mut text = sign.to_string();
+        match self.thousands {
+            Some(t) if int_part.contains(t as char) => {
+                let groups: Vec<&str> = int_part.split(t as char).collect();
+                let digits_only = |g: &str| g.bytes().all(|b| b.is_ascii_digit());
+                let first_ok = (1..=3).contains(&groups[0].len()) && digits_only(groups[0]);
+                if !first_ok || !groups[1..].iter().all(|g| g.len() == 3 && digits_only(g)) {
+                    return None;
+                }
+                text.extend(groups);
+            }
+            _ => text.push_str(int_part),
+        }
+        if let Some(fraction) = fraction {
+            text.push('.');
+            text.push_str(fraction);
+        }
+        // the other separator must not show up anywhere else
+        let points = text.bytes().filter(|&b| b == b'.' || b == b',').count();
+        (points == usize::from(fraction.is_some()) && text.len() > sign.len()).then_some(text)
+    }
+
+    /// Parse a (trimmed) cell as float.
+    pub fn parse_float(&self, value: &str) -> Option<f64> {
+        match self.decimal {
+            None => parse_float(value),
+            Some(_) if !is_number_text(value) => None,
+            Some(decimal) => self.canonical(value, decimal)?.parse().ok(),
+        }
+    }
+
+    /// Parse a (trimmed) cell as integer - a decimal separator disqualifies the value.
+    pub fn parse_integer(&self, value: &str) -> Option<i64> {
+        match self.decimal {
+            None => parse_integer(value),
+            Some(decimal) if value.contains(decimal as char) => None,
+            Some(decimal) => self.canonical(value, decimal)?.parse().ok(),
+        }
+    }
+}
+
+/// The byte the fields are joined with after a `Separator` split them (ASCII unit separator).
+pub(crate) const SPLIT_DELIMITER: u8 = 0x1f;
+
+/// A delimiter the csv reader cannot handle: a text like `::` or a regex like `\s+`.
+///
+/// Each line is split and handed on joined by `SPLIT_DELIMITER` - slower than a single
+/// byte delimiter, and quotes have no special meaning.
+#[derive(Debug, Clone)]
+pub struct Separator {
+    pattern: Regex,
+    /// Regex matches at the start and end of a line are ignored (aligned columns)
+    trim: bool,
+}
+
+/// Resolve the escapes `\t`, `\n`, `\r` and `\\` of a delimiter argument - shells pass them
+/// as two characters. Other escapes and a trailing backslash are errors, so a lone `\` never
+/// becomes the delimiter by accident.
+pub fn unescape_delimiter(arg: &str) -> Result<String> {
+    let mut text = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        text.push(match chars.next() {
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('\\') => '\\',
+            Some(other) => bail!(BadDelimiter, "Invalid delimiter '{}': unknown escape '\\{}' (use \\t, \\n, \\r or \\\\)", arg, other),
+            None => bail!(BadDelimiter, "Invalid delimiter '{}': it ends in a lone backslash (write \\\\ for a backslash)", arg),
+        });
+    }
+    Ok(text)
+}
+
+impl Separator {
+    /// Split at every occurrence of `text`.
+    pub fn text(text: &str) -> Result<Self> {
+        if text.is_empty() {
+            bail!(BadDelimiter, "The delimiter must not be empty");
+        }
+        if text.contains(['\n', '\r']) {
+            bail!(BadDelimiter, "The delimiter must not contain a line break");
+        }
+        let pattern = Regex::new(&regex::escape(text)).expect("an escaped text is a valid regex");
+        Ok(Separator { pattern, trim: false })
+    }
+
+    /// Split at every match of `pattern`, e.g. `\s+` for runs of whitespace.
+    /// Capture groups are not supported.
+    pub fn regex(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|err| DataTableError::BadDelimiter(format!("Invalid delimiter regex '{}': {}", pattern, err)))?;
+        if regex.captures_len() > 1 {
+            bail!(BadDelimiter, "Delimiter regex '{}': capture groups are not supported", pattern);
+        }
+        if regex.is_match(b"") {
+            bail!(BadDelimiter, "Delimiter regex '{}' matches the empty string", pattern);
+        }
+        Ok(Separator { pattern: regex, trim: true })
+    }
+
+    /// One line (without its line break) with the fields joined by `SPLIT_DELIMITER`.
+    fn split_line(&self, mut line: &[u8], out: &mut Vec<u8>) {
+        if self.trim {
+            if let Some(first) = self.pattern.find(line)
+                && first.start() == 0
+            {
+                line = &line[first.end()..];
+            }
+            if let Some(last) = self.pattern.find_iter(line).last()
+                && last.end() == line.len()
+            {
+                line = &line[..last.start()];
+            }
+        }
+        let mut start = 0;
+        for found in self.pattern.find_iter(line) {
+            out.extend_from_slice(&line[start..found.start()]);
+            out.push(SPLIT_DELIMITER);
+            start = found.end();
+        }
+        out.extend_from_slice(&line[start..]);
+    }
+
+    /// Rewrite `reader` so that the csv reader can split it at `SPLIT_DELIMITER`.
+    pub(crate) fn split<'a, R: Read + 'a>(&self, reader: R) -> Box<dyn Read + 'a> {
+        Box::new(SplitReader { inner: BufReader::new(reader), separator: self.clone(), line: Vec::new(), out: Vec::new(), pos: 0 })
+    }
+}
+
+/// The reader behind `Separator::split()` - works line by line.
+struct SplitReader<R> {
+    inner: BufReader<R>,
+    separator: Separator,
+    line: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Read for SplitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.out.len() {
+            self.line.clear();
+            self.out.clear();
+            self.pos = 0;
+            if self.inner.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(0);
+            }
+            let content = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            self.separator.split_line(content, &mut self.out);
+            self.out.push(b'\n');
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Everything that controls how a table is parsed.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Field delimiter (default: tab)
+    pub delimiter: u8,
+    /// Columns that are always treated as factors
+    pub categorical: HashSet<String>,
+    /// Columns that are always numbers - values that are no number become missing (and are counted)
+    pub as_numeric: HashSet<String>,
+    /// Columns that are always free text, kept as written (no factor levels, no number normalization)
+    pub as_string: HashSet<String>,
+    /// User supplied factor setups (ordered levels etc.) by column name
+    pub factor_specs: HashMap<String, FactorSpec>,
+    /// Fixed codes per column and level (`factor_map::read_level_codes()`) - the columns are
+    /// factors, levels not listed get the codes above the largest (see `Factor::pin_codes()`)
+    pub level_codes: LevelCodes,
+    /// Cells meaning "no value" - never a factor level and never breaking a numeric column
+    pub missing_tokens: Vec<String>,
+    /// Inferred text columns with more distinct values become `FreeText` (None: no limit)
+    pub max_levels: Option<usize>,
+    /// Columns holding only these tokens (and missing values) are `Boolean`
+    pub true_tokens: Vec<String>,
+    pub false_tokens: Vec<String>,
+    /// Infer the column types from the first rows only (None: all rows)
+    pub sample_rows: Option<usize>,
+    /// How the rows after the sample are checked
+    pub sample_check: SampleCheck,
+    /// How repeated column names are handled
+    pub duplicate_columns: DuplicateColumns,
+    /// Columns holding only dates in one of `DATE_FORMATS` are `Date` (default: off)
+    pub detect_dates: bool,
+    /// Normalization of factor values (default: kept as written)
+    pub level_case: LevelCase,
+    /// Drop rows with a different number of fields than the header instead of failing
+    pub skip_bad_rows: bool,
+    /// Numeric columns with values like `007` (zip codes, sample ids) are factors (default: off)
+    pub preserve_leading_zeros: bool,
+    /// Lines starting with this byte (e.g. `#` provenance lines) are skipped - a `#` inside
+    /// a field is data (default: none)
+    pub comment: Option<u8>,
+    /// Sort the levels of inferred factors (numbers by value, text alphabetically) instead of
+    /// keeping them in order of first appearance (default: off)
+    pub sort_levels: bool,
+    /// The encoding of the input (default: UTF-8)
+    pub encoding: TextEncoding,
+    /// Declared column types - every value has to fit, nothing is inferred.
+    /// `Factor` (or `FreeText`) is the same as listing the column in `categorical`.
+    pub dtypes: HashMap<String, Dtype>,
+    /// Called while the table is read, e.g. to draw a progress bar (default: none)
+    pub progress: Option<Progress>,
+    /// The columns that are read (default: all)
+    pub columns: ColumnSelection,
+    /// A multi-character or regex delimiter used instead of `delimiter` (default: none)
+    pub separator: Option<Separator>,
+    /// Decimal and thousands separator of the numbers (default: `.` or `,` decimals, no thousands separator)
+    pub number_format: NumberFormat,
+    /// Number of threads of the parallel type inference (`rayon` feature); `Some(1)` is sequential
+    /// (default: rayon's global pool - all cores or `RAYON_NUM_THREADS`)
+    pub threads: Option<usize>,
+    /// Number the levels of inferred and declared factors 1, 2, ... like R instead of 0, 1, ...
+    /// (default: off). Codes from a factors file stay as they are.
+    pub one_based_codes: bool,
+    /// Detect the columns of JSON objects and keep them as `Json` or flatten them (default: off).
+    /// Only the in-memory reader does this.
+    pub json_cells: JsonCells,
+    /// Header names replaced right after the header is read, as `(original, new)` pairs - the
+    /// other settings may name a column either way (default: none)
+    pub renames: Vec<(String, String)>,
+    /// Inferred columns mixing numbers and text are an error instead of a factor (default: off)
+    pub strict: bool,
+    /// Report the missing cells of a column as one count, not split into empty and `NA` ones
+    /// (`Column::missing_kinds`)
+    pub collapse_missing: bool,
+    /// Numbers meaning "no value" in every column, e.g. `-999` - compared by value, so `-999.0`
+    /// is one as well. `as_string` columns keep them (default: none)
+    pub na_values: Vec<f64>,
+    /// Like `na_values`, for one column (e.g. `age: -1`)
+    pub column_na_values: ColumnNaValues,
+    /// The file has the variables as rows: its first column becomes the header and the
+    /// types are inferred on the transposed table. Only the in-memory reader does this (default: off)
+    pub transpose: bool,
+    /// The number of lines above the header - title lines that are no comments. They are
+    /// dropped and counted in the parse report; comment and blank lines do not count (default: 0)
+    pub header_row: usize,
+    /// Lines ending with a delimiter: the empty last field is dropped or a column. Every row
+    /// has to end the same way as the first one (default: dropped)
+    pub trailing_delimiter: TrailingDelimiter,
+    /// Stop after this many rows - counted after the `header_row` titles, comments and the rows
+    /// dropped by `skip_bad_rows`. Factors and summaries describe only these rows and the
+    /// parse report notes the cut (default: all rows)
+    pub max_rows: Option<usize>,
+    /// Columns the header must have, whatever their type - a `MissingColumns` error lists
+    /// the absent ones before anything is parsed (default: none)
+    pub required_columns: Vec<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            delimiter: b'\t',
+            categorical: HashSet::new(),
+            as_numeric: HashSet::new(),
+            as_string: HashSet::new(),
+            factor_specs: HashMap::new(),
+            level_codes: HashMap::new(),
+            missing_tokens: DEFAULT_MISSING_TOKENS.iter().map(|t| t.to_string()).collect(),
+            max_levels: Some(DEFAULT_MAX_LEVELS),
+            true_tokens: DEFAULT_TRUE_TOKENS.iter().map(|t| t.to_string()).collect(),
+            false_tokens: DEFAULT_FALSE_TOKENS.iter().map(|t| t.to_string()).collect(),
+            sample_rows: None,
+            sample_check: SampleCheck::default(),
+            duplicate_columns: DuplicateColumns::default(),
+            detect_dates: false,
+            level_case: LevelCase::default(),
+            skip_bad_rows: false,
+            preserve_leading_zeros: false,
+            comment: None,
+            sort_levels: false,
+            encoding: TextEncoding::default(),
+            dtypes: HashMap::new(),
+            progress: None,
+            columns: ColumnSelection::default(),
+            separator: None,
+            number_format: NumberFormat::default(),
+            threads: None,
+            one_based_codes: false,
+            trailing_delimiter: TrailingDelimiter::default(),
+            max_rows: None,
+            required_columns: Vec::new(),
+            json_cells: JsonCells::default(),
+            renames: Vec::new(),
+            strict: false,
+            collapse_missing: false,
+            na_values: Vec::new(),
+            column_na_values: HashMap::new(),
+            transpose: false,
+            header_row: 0,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new(delimiter: u8, categorical: HashSet<String>) -> Self {
+        ParseOptions {
+            delimiter,
+            categorical,
+            ..Default::default()
+        }
+    }
+
+    /// The settings of the type inference alone (see `infer_dtype()`).
+    pub fn infer_options(&self) -> InferOptions {
+        InferOptions {
+            missing_tokens: self.missing_tokens.clone(),
+            true_tokens: self.true_tokens.clone(),
+            false_tokens: self.false_tokens.clone(),
+            number_format: self.number_format,
+            detect_dates: self.detect_dates,
+            preserve_leading_zeros: self.preserve_leading_zeros,
+            max_levels: self.max_levels,
+        }
+    }
+
+    /// The code of the first level of a new factor (see `one_based_codes`).
+    pub fn first_code(&self) -> f64 {
+        if self.one_based_codes { 1.0 } else { 0.0 }
+    }
+
+    /// Is this (trimmed) cell a missing value?
+    pub fn is_missing(&self, value: &str) -> bool {
+        is_missing(value, &self.missing_tokens)
+    }
+
+    /// The kinds of `missing` cells of a column with `empty` empty cells - `None` with
+    /// `collapse_missing` or without missing cells. Empty cells are values unless `""` is a missing token.
+    pub(crate) fn missing_kinds(&self, missing: usize, empty: usize) -> Option<MissingKinds> {
+        if self.collapse_missing || missing == 0 {
+            return None;
+        }
+        let empty = if self.is_missing("") { empty.min(missing) } else { 0 };
+        Some(MissingKinds { empty, na: missing - empty })
+    }
+
+    /// Is this (trimmed) cell of `column` one of the `na_values` sentinels?
+    pub fn is_na_value(&self, column: &str, value: &str) -> bool {
+        let per_column = self.column_na_values.get(column).map_or(&[][..], Vec::as_slice);
+        if (self.na_values.is_empty() && per_column.is_empty()) || self.as_string.contains(column) {
+            return false;
+        }
+        self.number_format
+            .parse_float(value)
+            .is_some_and(|number| self.na_values.iter().chain(per_column).any(|&sentinel| sentinel == number))
+    }
+
+    /// `is_missing()` or `is_na_value()`.
+    pub fn is_missing_in(&self, column: &str, value: &str) -> bool {
+        self.is_missing(value) || self.is_na_value(column, value)
+    }
+
+    /// The cell as the parser sees it: a sentinel of `column` becomes the first missing token
+    /// (`check_columns()` makes sure there is one).
+    pub(crate) fn mask_na_value<'a>(&'a self, column: &str, value: &'a str) -> &'a str {
+        match self.missing_tokens.first() {
+            Some(token) if self.is_na_value(column, value) => token,
+            _ => value,
+        }
+    }
+
+    /// The options with every column that is renamed referred to by its new name.
+    pub(crate) fn resolve_renames(&self) -> Cow<'_, ParseOptions> {
+        if self.renames.is_empty() {
+            return Cow::Borrowed(self);
+        }
+        let new_name = |name: &String| match self.renames.iter().find(|(original, _)| original == name) {
+            Some((_, new)) => new.clone(),
+            None => name.clone(),
+        };
+        let mut resolved = self.clone();
+        for set in [&mut resolved.categorical, &mut resolved.as_numeric, &mut resolved.as_string] {
+            *set = set.iter().map(new_name).collect();
+        }
+        resolved.factor_specs = self.factor_specs.iter().map(|(name, spec)| (new_name(name), spec.clone())).collect();
+        resolved.level_codes = self.level_codes.iter().map(|(name, codes)| (new_name(name), codes.clone())).collect();
+        resolved.dtypes = self.dtypes.iter().map(|(name, dtype)| (new_name(name), *dtype)).collect();
+        resolved.column_na_values = self.column_na_values.iter().map(|(name, values)| (new_name(name), values.clone())).collect();
+        resolved.required_columns = self.required_columns.iter().map(new_name).collect();
+        if let ColumnSelection::Include(names) | ColumnSelection::Exclude(names) = &mut resolved.columns {
+            *names = names.iter().map(new_name).collect();
+        }
+        Cow::Owned(resolved)
+    }
+
+    /// Every column named in `categorical`, `as_numeric`, `as_string`, `factor_specs`, `level_codes`, `dtypes`, `column_na_values`
+    /// or `columns` has to be one of `names`. The error lists all unknown names, with the closest real column as suggestion.
+    /// A column may only get one of these types. The `required_columns` are checked first.
+    pub fn check_columns(&self, names: &[String]) -> Result<()> {
+        self.check_required(names)?;
+        self.check_overrides()?;
+        check_level_codes(&self.level_codes)?;
+        if self.missing_tokens.is_empty() && (!self.na_values.is_empty() || !self.column_na_values.is_empty()) {
+            bail!(InvalidArgument, "Missing value sentinels (na_values) need a missing token to stand for them");
+        }
+        let known: HashSet<&String> = names.iter().collect();
+        let mut unknown: Vec<&String> = self
+            .categorical
+            .iter()
+            .chain(&self.as_numeric)
+            .chain(&self.as_string)
+            .chain(self.factor_specs.keys())
+            .chain(self.level_codes.keys())
+            .chain(self.dtypes.keys())
+            .chain(self.column_na_values.keys())
+            .chain(self.columns.names())
+            .filter(|c| !known.contains(c))
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        unknown.sort();
+        unknown.dedup();
+        let listed: Vec<String> = unknown
+            .iter()
+            .map(|c| match closest_name(c, names) {
+                Some(real) => format!("'{}' (did you mean '{}'?)", c, real),
+                None => format!("'{}'", c),
+            })
+            .collect();
+        Err(DataTableError::UnknownColumns {
+            columns: unknown.into_iter().cloned().collect(),
+            message: listed.join(", "),
+        })
+    }
+
+    /// Are all `required_columns` in `names`? The error lists the missing ones in the order they
+    /// were required, with the closest real column as suggestion.
+    pub fn check_required(&self, names: &[String]) -> Result<()> {
+        let missing: Vec<&String> = self.required_columns.iter().filter(|c| !names.contains(c)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let listed: Vec<String> = missing
+            .iter()
+            .map(|c| match closest_name(c, names) {
+                Some(real) => format!("'{}' (did you mean '{}'?)", c, real),
+                None => format!("'{}'", c),
+            })
+            .collect();
+        Err(DataTableError::MissingColumns {
+            columns: missing.into_iter().cloned().collect(),
+            message: listed.join(", "),
+        })
+    }
+
+    /// A column that is coerced (`as_numeric`, `as_string`) can not be set up otherwise as well.
+    fn check_overrides(&self) -> Result<()> {
+        let settings = |name: &String| {
+            [
+                ("as_numeric", self.as_numeric.contains(name)),
+                ("as_string", self.as_string.contains(name)),
+                ("categorical", self.categorical.contains(name)),
+                ("factor_specs", self.factor_specs.contains_key(name)),
+                ("level_codes", self.level_codes.contains_key(name)),
+                ("dtypes", self.dtypes.contains_key(name)),
+            ]
+            .into_iter()
+            .filter_map(|(setting, set)| set.then_some(setting))
+            .collect::<Vec<_>>()
+        };
+        let mut coerced: Vec<&String> = self.as_numeric.iter().chain(&self.as_string).collect();
+        coerced.sort();
+        for name in coerced {
+            let settings = settings(name);
+            if settings.len() > 1 {
+                bail!(InvalidArgument, "Column '{}' has conflicting type settings: {}", name, settings.join(" and "));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the column names unique as `duplicate_columns` says.
+    ///
+    /// Renaming is deterministic: the n-th repeat of `name` becomes `name.n`, skipping
+    /// suffixes that are already taken by another column, so the same header always
+    /// gives the same names.
+    pub fn unique_names(&self, names: Vec<String>) -> Result<Vec<String>> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let duplicates: Vec<&String> = names.iter().filter(|n| !seen.insert(n.to_string())).collect();
+        if duplicates.is_empty() {
+            return Ok(names);
+        }
+        let listed: Vec<String> = duplicates.iter().map(|n| format!("'{}'", n)).collect();
+        if self.duplicate_columns == DuplicateColumns::Error {
+            bail!(
+                InvalidData,
+                "Duplicate column name(s) in the header: {} (rename them or let them get a '.1', '.2' suffix)",
+                listed.join(", ")
+            );
+        }
+        let mut taken: HashSet<String> = names.iter().cloned().collect();
+        let mut used: HashSet<&String> = HashSet::new();
+        let mut unique = Vec::with_capacity(names.len());
+        for name in &names {
+            if used.insert(name) {
+                unique.push(name.clone());
+                continue;
+            }
+            let renamed = (1..)
+                .map(|i| format!("{}.{}", name, i))
+                .find(|candidate| !taken.contains(candidate))
+                .expect("an unused suffix exists");
+            log::warn!("Duplicate column name '{name}' -> renamed to '{renamed}' (a duplicate name can also be an error)");
+            taken.insert(renamed.clone());
+            unique.push(renamed);
+        }
+        Ok(unique)
+    }
+
+    /// Apply `renames` to the column names of a header. Renaming an unknown column or to
+    /// the name of another column is an error.
+    pub(crate) fn rename_header(&self, mut names: Vec<String>) -> Result<Vec<String>> {
+        if self.renames.is_empty() {
+            return Ok(names);
+        }
+        let unknown: Vec<String> = self
+            .renames
+            .iter()
+            .filter(|(original, _)| !names.contains(original))
+            .map(|(original, _)| original.clone())
+            .collect();
+        if !unknown.is_empty() {
+            let listed: Vec<String> = unknown
+                .iter()
+                .map(|c| match closest_name(c, &names) {
+                    Some(real) => format!("'{}' (did you mean '{}'?)", c, real),
+                    None => format!("'{}'", c),
+                })
+                .collect();
+            return Err(DataTableError::UnknownColumns { columns: unknown, message: listed.join(", ") });
+        }
+        let original = names.clone();
+        for name in names.iter_mut() {
+            if let Some((_, new)) = self.renames.iter().find(|(o, _)| o == name) {
+                *name = new.clone();
+            }
+        }
+        let mut seen: HashSet<&String> = HashSet::new();
+        if let Some(i) = names.iter().position(|n| !seen.insert(n)) {
+            let first = names.iter().position(|n| *n == names[i]).expect("seen before");
+            let (a, b) = (&original[first], &original[i]);
+            match (a == &names[i], b == &names[i]) {
+                (true, _) | (_, true) => bail!(
+                    InvalidArgument,
+                    "Cannot rename '{}' to '{}' - the header has a column '{}' already",
+                    if a == &names[i] { b } else { a },
+                    names[i],
+                    names[i]
+                ),
+                _ => bail!(InvalidArgument, "Cannot rename both '{}' and '{}' to '{}'", a, b, names[i]),
+            }
+        }
+        Ok(names)
+    }
+
+    /// Does `preserve_leading_zeros` make this (trimmed) cell an identifier rather than a number?
+    pub fn keeps_leading_zero(&self, value: &str) -> bool {
+        self.preserve_leading_zeros && has_leading_zero(value)
+    }
+
+    /// Read a (trimmed) cell of a boolean column - `None` if it is no boolean token.
+    pub fn parse_boolean(&self, value: &str) -> Option<bool> {
+        parse_boolean(value, &self.true_tokens, &self.false_tokens)
+    }
+}
+
+/// Edit distance between two strings (insertions, deletions and substitutions).
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The column name closest to `name` - if it is close enough to be a typo.
+fn closest_name<'a>(name: &str, names: &'a [String]) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    names
+        .iter()
+        .map(|n| (levenshtein(&name.to_lowercase(), &n.to_lowercase()), n))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, n)| n.as_str())
+}
+
+/// Split a `column:value` command line definition at the **last** `:`,
+/// so column names containing a colon still work.
+pub fn split_column_arg(arg: &str) -> Result<(&str, &str)> {
+    match arg.rsplit_once(':') {
+        Some((col, value)) if !col.trim().is_empty() && !value.trim().is_empty() => {
+            Ok((col.trim(), value.trim()))
+        }
+        _ => bail!(InvalidArgument, "Expected '<column>:<value>' but got '{}'", arg),
+    }
+}
+
+/// Parse renames written as `original=new,original=new` (e.g. `percent.mt=mito_pct`).
+pub fn parse_renames(arg: &str) -> Result<Vec<(String, String)>> {
+    arg.split(',')
+        .map(|pair| match pair.split_once('=') {
+            Some((original, new)) if !original.trim().is_empty() && !new.trim().is_empty() => {
+                Ok((original.trim().to_string(), new.trim().to_string()))
+            }
+            _ => bail!(InvalidArgument, "Expected '<column>=<new name>' but got '{}'", pair),
+        })
+        .collect()
+}
+
+/// Missing value sentinels by column name (see `ParseOptions::column_na_values`).
+pub type ColumnNaValues = HashMap<String, Vec<f64>>;
+
+/// Parse missing value sentinels written as `-999,9999,age:-1`: a number counts for every
+/// column, `<column>:<number>` for that column only.
+pub fn parse_na_values(arg: &str) -> Result<(Vec<f64>, ColumnNaValues)> {
+    let mut values = Vec::new();
+    let mut by_column = ColumnNaValues::new();
+    let number = |text: &str| match text.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => bail!(InvalidArgument, "Expected a number or '<column>:<number>' as missing value but got '{}'", text),
+    };
+    for item in arg.split(',') {
+        if item.trim().parse::<f64>().is_ok() {
+            values.push(number(item)?);
+        } else {
+            let (column, value) = split_column_arg(item)?;
+            by_column.entry(column.to_string()).or_default().push(number(value)?);
+        }
+    }
+    Ok((values, by_column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_columns() {
+        let names: Vec<String> = ["barcode", "cluster", "sample"].iter().map(|s| s.to_string()).collect();
+        let ok = ParseOptions::new(b'\t', ["cluster".to_string()].into());
+        assert!(ok.check_columns(&names).is_ok());
+
+        let typo = ParseOptions::new(b'\t', ["clustr".to_string(), "treatment".to_string()].into());
+        let err = typo.check_columns(&names).unwrap_err();
+        assert!(
+            matches!(&err, DataTableError::UnknownColumns { columns, .. } if columns == &["clustr", "treatment"]),
+            "{err:?}"
+        );
+        let err = err.to_string();
+        assert!(err.contains("'clustr' (did you mean 'cluster'?)"), "{err}");
+        assert!(err.contains("'treatment'") && !err.contains("'treatment' (did"), "{err}");
+
+        let required = ParseOptions { required_columns: vec!["time".into(), "sample".into(), "clustr".into()], ..Default::default() };
+        let err = required.check_columns(&names).unwrap_err();
+        assert!(matches!(&err, DataTableError::MissingColumns { columns, .. } if columns == &["time", "clustr"]), "{err:?}");
+        assert_eq!(err.to_string(), "Missing required column(s): 'time', 'clustr' (did you mean 'cluster'?)");
+
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let names = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+        let options = ParseOptions::default();
+        assert_eq!(options.unique_names(names(&["a", "b"])).unwrap(), names(&["a", "b"]));
+        assert_eq!(
+            options.unique_names(names(&["orig.ident", "x", "orig.ident", "orig.ident"])).unwrap(),
+            names(&["orig.ident", "x", "orig.ident.1", "orig.ident.2"])
+        );
+        // an existing 'a.1' is not overwritten
+        assert_eq!(options.unique_names(names(&["a", "a", "a.1"])).unwrap(), names(&["a", "a.2", "a.1"]));
+
+        let strict = ParseOptions { duplicate_columns: DuplicateColumns::Error, ..Default::default() };
+        let err = strict.unique_names(names(&["a", "b", "a"])).unwrap_err();
+        assert!(err.to_string().contains("Duplicate column name(s) in the header: 'a'"), "{err}");
+    }
+
+    #[test]
+    fn test_text_encoding() {
+        assert_eq!(TextEncoding::parse("Latin1").unwrap(), TextEncoding::Windows1252);
+        assert_eq!(TextEncoding::parse("utf8").unwrap(), TextEncoding::Utf8);
+        let err = TextEncoding::parse("ebcdic").unwrap_err();
+        assert!(err.to_string().contains("supported are utf-8, latin1"), "{err}");
+
+        let mut decoded = String::new();
+        TextEncoding::Windows1252.decode(&b"Gr\xfc\xdfe \x80"[..]).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, "Grüße €");
+        assert_eq!(Delimiter::Auto.detect(&b"caf\xe9;n\n\xe9t\xe9;1\n"[..]).unwrap(), b';', "not UTF-8");
+    }
+
+    #[test]
+    fn test_level_case() {
+        assert_eq!(LevelCase::parse("lower").unwrap().apply("CD8"), "cd8");
+        assert_eq!(LevelCase::Upper.apply("cd8"), "CD8");
+        assert_eq!(LevelCase::Keep.apply("Cd8"), "Cd8");
+        assert_eq!(LevelCase::Custom(|v| v.replace('-', "")).apply("CD-8"), "CD8");
+        assert!(LevelCase::parse("title").is_err());
+        assert!(!LevelCase::Keep.folds());
+    }
+
+    #[test]
+    fn test_sniff_delimiter() {
+        assert_eq!(sniff_delimiter("a\tb\tc\n1\t2,5\t3\n").unwrap(), b'\t');
+        assert_eq!(sniff_delimiter("a,b,c\n1,2,3\n4,5,6\n").unwrap(), b',');
+        // European export: decimal commas must not win
+        assert_eq!(sniff_delimiter("a;b\n1,5;2,25\n3,5;4,75\n").unwrap(), b';');
+        assert_eq!(sniff_delimiter("a|b\n1|2\n").unwrap(), b'|');
+        // quoted delimiters do not count
+        assert_eq!(sniff_delimiter("cell,type\n1,\"T;cell\"\n2,B\n").unwrap(), b',');
+        assert_eq!(sniff_delimiter("barcode\nAAAC\n").unwrap(), b'\t', "a single column");
+        let err = sniff_delimiter("a,b;c\n1,2;3\n").unwrap_err();
+        assert!(err.to_string().contains("specify it explicitly"), "{err}");
+
+        assert_eq!(Delimiter::Auto.detect("a;b\n1;2\n".as_bytes()).unwrap(), b';');
+        assert_eq!(Delimiter::Byte(b',').detect("a;b\n".as_bytes()).unwrap(), b',');
+        let commented = "# tool v1.2, run 3; seed 1\n# date 2024\na\tb\n1\t2\n";
+        assert_eq!(Delimiter::Auto.detect_with_comment(commented.as_bytes(), Some(b'#')).unwrap(), b'\t');
+        let bom = format!("{BOM}{commented}");
+        assert_eq!(Delimiter::Auto.detect_with_comment(bom.as_bytes(), Some(b'#')).unwrap(), b'\t', "BOM before a comment");
+        let titled = "Export of 2024-05-01\n\nsite B; run 2\na,b,c\n1,2,3\n4,5,6\n";
+        assert_eq!(Delimiter::Auto.detect_skipping(titled.as_bytes(), None, 2).unwrap(), b',', "the title lines are skipped");
+        assert_eq!(Delimiter::parse("auto").unwrap(), Delimiter::Auto);
+        assert_eq!(Delimiter::parse("\\t").unwrap(), Delimiter::Byte(b'\t'));
+        assert_eq!(Delimiter::parse(";").unwrap(), Delimiter::Byte(b';'));
+        assert!(matches!(Delimiter::parse("ab"), Err(DataTableError::BadDelimiter(_))));
+    }
+
+    #[test]
+    fn test_na_values() {
+        let (values, by_column) = parse_na_values("-999, 9999,age:-1,visit:date:0,age:99").unwrap();
+        assert_eq!(values, vec![-999.0, 9999.0]);
+        assert_eq!(by_column["age"], vec![-1.0, 99.0]);
+        assert_eq!(by_column["visit:date"], vec![0.0]);
+        assert!(parse_na_values("age:old").is_err());
+        assert!(parse_na_values("nan").is_err());
+        assert!(parse_na_values("-999,").is_err());
+
+        let options = ParseOptions { na_values: values, column_na_values: by_column, as_string: ["id".to_string()].into(), ..Default::default() };
+        assert!(options.is_na_value("bmi", "-999"));
+        assert!(options.is_na_value("bmi", "-999.0"));
+        assert!(!options.is_na_value("bmi", "-1"));
+        assert!(options.is_na_value("age", "-1"));
+        assert!(!options.is_na_value("age", "-1a"));
+        assert!(!options.is_na_value("id", "9999"), "free text keeps them");
+        assert!(options.is_missing_in("bmi", "NA"));
+    }
+
+    #[test]
+    fn test_keep_indices() {
+        let header: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+        let list = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(ColumnSelection::All.keep_indices(&header), vec![0, 1, 2, 3]);
+        assert_eq!(ColumnSelection::Include(list(&["d", "b"])).keep_indices(&header), vec![1, 3], "file order");
+        assert_eq!(ColumnSelection::Exclude(list(&["a", "c"])).keep_indices(&header), vec![1, 3]);
+        for selection in [ColumnSelection::Include(list(&["c"])), ColumnSelection::Exclude(list(&["c"]))] {
+            let expected: Vec<usize> = (0..header.len()).filter(|&i| selection.keeps(&header[i])).collect();
+            assert_eq!(selection.keep_indices(&header), expected);
+        }
+    }
+
+    #[test]
+    fn test_delimiter_escapes() {
+        assert_eq!(unescape_delimiter("\\t").unwrap(), "\t");
+        assert_eq!(unescape_delimiter("\\n").unwrap(), "\n");
+        assert_eq!(unescape_delimiter("\\r").unwrap(), "\r");
+        assert_eq!(unescape_delimiter("\\\\").unwrap(), "\\");
+        assert_eq!(unescape_delimiter(":\\t:").unwrap(), ":\t:");
+        assert_eq!(unescape_delimiter("\t").unwrap(), "\t", "a real tab stays");
+        let err = unescape_delimiter("\\s").unwrap_err();
+        assert!(err.to_string().contains("unknown escape '\\s'"), "{err}");
+        let err = unescape_delimiter("\\").unwrap_err();
+        assert!(err.to_string().contains("lone backslash"), "{err}");
+
+        assert_eq!(Delimiter::parse("\\t").unwrap(), Delimiter::Byte(b'\t'));
+        assert_eq!(Delimiter::parse("\t").unwrap(), Delimiter::Byte(b'\t'));
+        assert_eq!(Delimiter::parse("tab").unwrap(), Delimiter::Byte(b'\t'));
+        assert_eq!(Delimiter::parse("\\\\").unwrap(), Delimiter::Byte(b'\\'));
+        assert_eq!(Delimiter::parse("pipe").unwrap(), Delimiter::Byte(b'|'));
+        assert!(Delimiter::parse("\\").is_err(), "a lone backslash");
+        for line_break in ["\\n", "\\r", "\n"] {
+            let err = Delimiter::parse(line_break).unwrap_err();
+            assert!(err.to_string().contains("line break"), "{err}");
+        }
+        let err = Delimiter::parse("\\t\\t").unwrap_err();
+        assert!(err.to_string().contains("not a single byte"), "{err}");
+        assert!(Delimiter::parse("§").is_err(), "two bytes in UTF-8");
+        assert!(Separator::text(&unescape_delimiter(":\\n").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_split_column_arg() {
+        assert_eq!(split_column_arg("stage:low,high").unwrap(), ("stage", "low,high"));
+        assert_eq!(split_column_arg("a:b:5").unwrap(), ("a:b", "5"));
+        assert!(split_column_arg("stage").is_err());
+        assert!(split_column_arg("stage:").is_err());
+        assert!(split_column_arg(":5").is_err());
+    }
+
+    #[test]
+    fn test_number_format() {
+        let european = NumberFormat::new(b',', Some(b'.')).unwrap();
+        assert_eq!(european.parse_float("1.234,56"), Some(1234.56));
+        assert_eq!(european.parse_float("-12.345.678,5"), Some(-12345678.5));
+        assert_eq!(european.parse_float("0,5"), Some(0.5));
+        assert_eq!(european.parse_integer("1.234"), Some(1234));
+        assert_eq!(european.parse_integer("1,5"), None);
+        for invalid in ["1,234.56", "1.23,4", "12.34", "1.2345", ".5", "1,2,3", "-", "x"] {
+            assert_eq!(european.parse_float(invalid), None, "{invalid}");
+        }
+        let english = NumberFormat::new(b'.', Some(b',')).unwrap();
+        assert_eq!(english.parse_float("1,234.56"), Some(1234.56));
+        assert_eq!(english.parse_float("1.234,56"), None);
+        assert_eq!(english.parse_float("1e3"), Some(1000.0));
+        let swiss = NumberFormat::new(b'.', Some(b'\'')).unwrap();
+        assert_eq!(swiss.parse_integer("1'000'000"), Some(1_000_000));
+        let comma_only = NumberFormat::new(b',', None).unwrap();
+        assert_eq!((comma_only.parse_float("2,5"), comma_only.parse_float("2.5")), (Some(2.5), None));
+
+        let lenient = NumberFormat::default();
+        assert_eq!((lenient.parse_float("0,5"), lenient.parse_float("0.5")), (Some(0.5), Some(0.5)));
+        assert!(NumberFormat::new(b',', Some(b',')).is_err());
+        assert!(NumberFormat::new(b';', None).is_err());
+    }
+
+    #[test]
+    fn test_separator() {
+        let split = |separator: &Separator, line: &str| {
+            let mut out = Vec::new();
+            separator.split_line(line.as_bytes(), &mut out);
+            String::from_utf8(out).unwrap().replace(SPLIT_DELIMITER as char, "|")
+        };
+        let text = Separator::text("::").unwrap();
+        assert_eq!(split(&text, "a::b::::c"), "a|b||c");
+        assert_eq!(split(&text, "::a"), "|a", "a text keeps empty fields");
+        let whitespace = Separator::regex(r"\s+").unwrap();
+        assert_eq!(split(&whitespace, "  id   value\t x"), "id|value|x", "aligned columns");
+        assert_eq!(split(&whitespace, "1 2  "), "1|2");
+
+        assert!(Separator::text("").is_err());
+        let err = Separator::regex(r"(\s)+").unwrap_err();
+        assert!(err.to_string().contains("capture groups are not supported"), "{err}");
+        assert!(Separator::regex(r"(?:\s)+").is_ok());
+        assert!(Separator::regex(r"\s*").is_err(), "matches nothing");
+        assert!(Separator::regex("[").is_err());
+    }
+}
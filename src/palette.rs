@@ -0,0 +1,147 @@
+use crate::error::{bail, Result};
+use crate::factor_map::FactorMap;
+
+/// Color schemes for the `colors` hint of the factors file (see `FactorMap::apply_palette()`).
+///
+/// Qualitative schemes repeat their colors for factors with more levels,
+/// sequential ones are interpolated over the levels - both deterministic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    /// The 10 matplotlib / d3 category colors (qualitative)
+    Tab10,
+    /// 20 colors, pairs of a dark and a light tone (qualitative)
+    Tab20,
+    /// The 8 ColorBrewer Set2 pastels (qualitative)
+    Set2,
+    /// Dark blue over green to yellow - perceptually uniform (sequential)
+    Viridis,
+    /// Black over purple and red to light yellow (sequential)
+    Magma,
+}
+
+const TAB10: &[&str] = &["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf"];
+const TAB20: &[&str] = &[
+    "#1f77b4", "#aec7e8", "#ff7f0e", "#ffbb78", "#2ca02c", "#98df8a", "#d62728", "#ff9896", "#9467bd", "#c5b0d5",
+    "#8c564b", "#c49c94", "#e377c2", "#f7b6d2", "#7f7f7f", "#c7c7c7", "#bcbd22", "#dbdb8d", "#17becf", "#9edae5",
+];
+const SET2: &[&str] = &["#66c2a5", "#fc8d62", "#8da0cb", "#e78ac3", "#a6d854", "#ffd92f", "#e5c494", "#b3b3b3"];
+/// The scheme at 0.0, 0.1, ..., 1.0
+const VIRIDIS: &[&str] = &["#440154", "#482475", "#414487", "#355f8d", "#2a788e", "#21918c", "#22a884", "#44bf70", "#7ad151", "#bddf26", "#fde725"];
+const MAGMA: &[&str] = &["#000004", "#140e36", "#3b0f70", "#641a80", "#8c2981", "#b73779", "#de4968", "#f7705c", "#fe9f6d", "#fecf92", "#fcfdbf"];
+
+impl Palette {
+    /// Parse a command line value: `tab10`, `tab20`, `set2`, `viridis` or `magma`.
+    pub fn parse(arg: &str) -> Result<Self> {
+        Ok(match arg.trim().to_ascii_lowercase().as_str() {
+            "tab10" => Palette::Tab10,
+            "tab20" => Palette::Tab20,
+            "set2" => Palette::Set2,
+            "viridis" => Palette::Viridis,
+            "magma" => Palette::Magma,
+            _ => bail!(InvalidArgument, "Unknown palette '{}' - expected tab10, tab20, set2, viridis or magma", arg),
+        })
+    }
+
+    /// Parse `--palette tab10,magma`: one qualitative scheme for the nominal and one sequential
+    /// scheme for the ordered factors, in any order. A missing one is `Tab10` or `Viridis`.
+    pub fn parse_pair(arg: &str) -> Result<(Palette, Palette)> {
+        let (mut nominal, mut ordered) = (None, None);
+        for name in arg.split(',') {
+            let palette = Palette::parse(name)?;
+            let slot = if palette.is_sequential() { &mut ordered } else { &mut nominal };
+            if slot.replace(palette).is_some() {
+                bail!(InvalidArgument, "Palette '{}': give at most one qualitative and one sequential scheme", arg);
+            }
+        }
+        Ok((nominal.unwrap_or(Palette::Tab10), ordered.unwrap_or(Palette::Viridis)))
+    }
+
+    /// Sequential schemes suit ordered factors, qualitative ones nominal factors.
+    pub fn is_sequential(&self) -> bool {
+        matches!(self, Palette::Viridis | Palette::Magma)
+    }
+
+    fn stops(&self) -> &'static [&'static str] {
+        match self {
+            Palette::Tab10 => TAB10,
+            Palette::Tab20 => TAB20,
+            Palette::Set2 => SET2,
+            Palette::Viridis => VIRIDIS,
+            Palette::Magma => MAGMA,
+        }
+    }
+
+    /// `n` hex colors (`#rrggbb`): a qualitative scheme cycles, a sequential one spans
+    /// its whole range (one level gets the middle color).
+    pub fn colors(&self, n: usize) -> Vec<String> {
+        let stops = self.stops();
+        if !self.is_sequential() {
+            return (0..n).map(|i| stops[i % stops.len()].to_string()).collect();
+        }
+        (0..n)
+            .map(|i| {
+                let t = if n == 1 { 0.5 } else { i as f64 / (n - 1) as f64 };
+                interpolate(stops, t)
+            })
+            .collect()
+    }
+}
+
+/// The color at `t` (0..=1) between evenly spaced `stops`, mixed in RGB.
+fn interpolate(stops: &[&str], t: f64) -> String {
+    let rgb = |hex: &str| -> [f64; 3] {
+        let channel = |i: usize| u8::from_str_radix(&hex[1 + 2 * i..3 + 2 * i], 16).expect("palette colors are #rrggbb") as f64;
+        [channel(0), channel(1), channel(2)]
+    };
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let low = (position.floor() as usize).min(stops.len() - 2);
+    let weight = position - low as f64;
+    let (a, b) = (rgb(stops[low]), rgb(stops[low + 1]));
+    let mixed: Vec<String> = a.iter().zip(&b).map(|(a, b)| format!("{:02x}", (a + (b - a) * weight).round() as u8)).collect();
+    format!("#{}", mixed.concat())
+}
+
+impl FactorMap {
+    /// Give every factor a color per level (`colors`, aligned with `levels`):
+    /// ordered factors from the `ordered` scheme, all others from `nominal`.
+    pub fn apply_palette(&mut self, nominal: Palette, ordered: Palette) {
+        for def in &mut self.factors {
+            let palette = if def.ordered { ordered } else { nominal };
+            def.colors = Some(palette.colors(def.levels.len()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Factor;
+
+    #[test]
+    fn test_palette() {
+        assert_eq!(Palette::Tab10.colors(12)[10], "#1f77b4", "qualitative colors cycle");
+        assert_eq!(Palette::Set2.colors(2), ["#66c2a5", "#fc8d62"]);
+        let viridis = Palette::Viridis.colors(3);
+        assert_eq!(viridis, ["#440154", "#21918c", "#fde725"]);
+        assert_eq!(Palette::Viridis.colors(21)[1], "#461365", "interpolated between the stops");
+        assert_eq!(Palette::Magma.colors(1), ["#b73779"]);
+        assert_eq!(Palette::parse("Viridis").unwrap(), Palette::Viridis);
+        assert!(Palette::parse("rainbow").is_err());
+        assert_eq!(Palette::parse_pair("magma").unwrap(), (Palette::Tab10, Palette::Magma));
+        assert_eq!(Palette::parse_pair("viridis,set2").unwrap(), (Palette::Set2, Palette::Viridis));
+        assert!(Palette::parse_pair("tab10,set2").is_err());
+
+        let mut stage = Factor::new("stage", false);
+        stage.ordered = true;
+        let mut sex = Factor::new("sex", false);
+        for level in ["low", "high"] {
+            stage.push(level);
+        }
+        sex.push("f");
+        let mut map = FactorMap::new(vec![stage.as_json("stage"), sex.as_json("sex")]);
+        map.apply_palette(Palette::Tab10, Palette::Viridis);
+        assert_eq!(map.get("stage").unwrap().colors, Some(vec!["#440154".to_string(), "#fde725".to_string()]));
+        assert_eq!(map.get("sex").unwrap().colors, Some(vec!["#1f77b4".to_string()]));
+        assert_eq!(Factor::from_def(map.get("sex").unwrap()).as_json("sex").colors, map.get("sex").unwrap().colors, "kept on reading");
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use arrow_array::{Array, ArrayRef, Date32Array, Float64Array, RecordBatch, TimestampSecondArray};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use crate::column::Dtype;
+use crate::error::{DataTableError, Result};
+use crate::SurvivalData;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+impl SurvivalData {
+    /// Write the table as a (snappy compressed) Parquet file - the columns of `to_arrow()`.
+    ///
+    /// Factors are dictionary encoded with their levels in factor order, integers are `INT64`,
+    /// floats `DOUBLE` and booleans `BOOLEAN`. Date columns become `DATE` (or a timestamp in
+    /// seconds if they were read with a time). Missing values are nulls.
+    pub fn write_parquet<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let batch = self.to_arrow().map_err(parquet::errors::ParquetError::from)?;
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(&self.columns)
+            .map(|(array, column)| match column.dtype {
+                Dtype::Date => date_array(array, column.format.as_deref().is_some_and(|f| f.contains("%H"))),
+                _ => array.clone(),
+            });
+        let names = self.headers.iter().map(|h| h.as_str());
+        let batch = RecordBatch::try_from_iter(names.zip(columns)).map_err(parquet::errors::ParquetError::from)?;
+
+        let file = File::create(path.as_ref()).map_err(|e| DataTableError::io(&path, e))?;
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Seconds since 1970-01-01 (`Float64`) as days or as a timestamp.
+fn date_array(array: &ArrayRef, with_time: bool) -> ArrayRef {
+    let seconds = array.as_any().downcast_ref::<Float64Array>().expect("to_arrow() writes dates as Float64");
+    if with_time {
+        Arc::new(seconds.iter().map(|s| s.map(|s| s as i64)).collect::<TimestampSecondArray>())
+    } else {
+        Arc::new(seconds.iter().map(|s| s.map(|s| (s / SECONDS_PER_DAY).floor() as i32)).collect::<Date32Array>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::types::Int32Type;
+    use arrow_array::{DictionaryArray, Int64Array};
+    use arrow_schema::DataType;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+    use crate::ParseOptions;
+
+    #[test]
+    fn test_write_parquet() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tsv = "cluster\tn\tscore\tdoublet\tsampled\nB cell\t3\t0.5\tTRUE\t2023-05-14\nT cell\tNA\t1.5\tFALSE\tNA\nNA\t5\tNA\tNA\t1970-01-02\n";
+        let options = ParseOptions { detect_dates: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.parquet");
+        data.write_parquet(&path)?;
+
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?.build()?.next().unwrap()?;
+        let dtypes: Vec<DataType> = batch.schema().fields().iter().map(|f| f.data_type().clone()).collect();
+        assert_eq!(dtypes, vec![
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            DataType::Int64,
+            DataType::Float64,
+            DataType::Boolean,
+            DataType::Date32,
+        ]);
+        for column in batch.columns() {
+            assert_eq!(column.null_count(), 1, "missing values are nulls");
+        }
+        let n = batch.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(n.iter().collect::<Vec<_>>(), vec![Some(3), None, Some(5)]);
+        let cluster = batch.column(0).as_any().downcast_ref::<DictionaryArray<Int32Type>>().unwrap();
+        assert_eq!(cluster.keys().iter().collect::<Vec<_>>(), vec![Some(0), Some(1), None]);
+        let sampled = batch.column(4).as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(sampled.value(2), 1, "days since 1970-01-01");
+        Ok(())
+    }
+}
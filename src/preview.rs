@@ -0,0 +1,93 @@
+use std::ops::Range;
+use crate::column::Dtype;
+use crate::infer::format_date;
+use crate::options::DATE_FORMATS;
+use crate::SurvivalData;
+
+impl SurvivalData {
+    /// The first `n` rows as an aligned text table (see `preview()`).
+    pub fn head_table(&self, n: usize, codes: bool) -> String {
+        self.preview(0..n.min(self.n_rows()), codes)
+    }
+
+    /// The last `n` rows as an aligned text table (see `preview()`).
+    pub fn tail_table(&self, n: usize, codes: bool) -> String {
+        self.preview(self.n_rows().saturating_sub(n)..self.n_rows(), codes)
+    }
+
+    /// The rows `rows` as a plain text table for a quick look: the column names, their dtypes
+    /// below them, then one line per row. Factors show their level - `level(code)` with `codes`,
+    /// the code of the factors file -, dates ISO-8601, missing values `NA`; numbers are right aligned.
+    pub fn preview(&self, rows: Range<usize>, codes: bool) -> String {
+        let rows = rows.start.min(self.n_rows())..rows.end.min(self.n_rows());
+        let mut cells: Vec<Vec<String>> = Vec::with_capacity(rows.len() + 2);
+        cells.push(self.headers.clone());
+        cells.push(self.columns.iter().map(|c| c.dtype.to_string()).collect());
+        for row in rows {
+            let values = self.numeric_data.row(row);
+            cells.push(values.iter().enumerate().map(|(j, &value)| self.preview_cell(j, value, codes)).collect());
+        }
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|j| cells.iter().map(|line| line[j].chars().count()).max().unwrap_or(0))
+            .collect();
+        let mut table = String::new();
+        for line in &cells {
+            let fields: Vec<String> = line
+                .iter()
+                .zip(&widths)
+                .zip(&self.columns)
+                .map(|((cell, &width), column)| match column.dtype {
+                    Dtype::Integer | Dtype::Float => format!("{:>width$}", cell),
+                    _ => format!("{:<width$}", cell),
+                })
+                .collect();
+            table.push_str(fields.join("  ").trim_end());
+            table.push('\n');
+        }
+        table
+    }
+
+    fn preview_cell(&self, j: usize, value: f64, codes: bool) -> String {
+        if value.is_nan() {
+            return "NA".to_string();
+        }
+        let column = &self.columns[j];
+        if let Some(factor) = self.factors.get(&self.headers[j]) {
+            let level = factor.level_at(value).unwrap_or("NA");
+            return match codes && column.dtype == Dtype::Factor {
+                true => format!("{}({})", level, value),
+                false => level.to_string(),
+            };
+        }
+        match column.dtype {
+            Dtype::Boolean => (value != 0.0).to_string(),
+            Dtype::Date => format_date(value, column.format.as_deref().unwrap_or(DATE_FORMATS[0])),
+            _ => value.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_preview() -> crate::error::Result<()> {
+        let tsv = "cell\tgroup\tn\tscore\tok\nA\tx\t1\t0.5\tyes\nB\ty\t120\tNA\tno\nC\tx\t3\t12.25\tyes\n";
+        let options = ParseOptions { max_levels: Some(2), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let head: Vec<String> = data.head_table(2, false).lines().map(str::to_string).collect();
+        assert_eq!(head, [
+            "cell       group         n  score  ok",
+            "free_text  factor  integer  float  boolean",
+            "A          x             1    0.5  true",
+            "B          y           120     NA  false",
+        ]);
+        assert_eq!(data.tail_table(1, true).lines().last(), Some("C          x(0)          3  12.25  true"));
+        assert_eq!(data.tail_table(10, false).lines().count(), 5, "all rows and the two header lines");
+        assert_eq!(data.preview(5..9, false).lines().count(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::column::Dtype;
+use crate::error::{DataTableError, Result};
+use crate::factor::FactorJson;
+use crate::factor_map::FactorMap;
+
+/// R's reserved words - a column named like one of them needs `[["..."]]`.
+const R_RESERVED: &[&str] = &[
+    "if", "else", "repeat", "while", "function", "for", "next", "break", "TRUE", "FALSE", "NULL",
+    "Inf", "NaN", "NA", "NA_integer_", "NA_real_", "NA_character_", "NA_complex_", "in",
+];
+
+/// `text` as an R string literal: double quoted, with `\`, `"` and control characters escaped.
+pub fn r_string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{{{:04x}}}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// `frame$column` for a syntactic R name, `frame[["column"]]` for any other.
+fn r_column(frame: &str, column: &str) -> String {
+    let mut chars = column.chars();
+    let syntactic = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => true,
+        Some('.') => !chars.next().is_some_and(|c| c.is_ascii_digit()),
+        _ => false,
+    } && column.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_'))
+        && !R_RESERVED.contains(&column);
+    match syntactic {
+        true => format!("{frame}${column}"),
+        false => format!("{frame}[[{}]]", r_string(column)),
+    }
+}
+
+/// `c("a", "b")`
+fn r_vector(values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|v| r_string(v)).collect();
+    format!("c({})", values.join(", "))
+}
+
+impl FactorMap {
+    /// R code recreating every factor column of the map on the data frame `frame`:
+    /// `df$cluster <- factor(df$cluster, levels = c(...), labels = c(...), ordered = TRUE)`,
+    /// with `labels` and `ordered` only where the map has them. Levels merged by
+    /// `merge_rare` are recoded to their new level first. Free text and other columns are left alone.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let tsv = "cell\tgroup\nA\tx\nB\ty\nC\tx\n";
+    /// let map = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::from(["group".to_string()]))
+    ///     .unwrap()
+    ///     .compute_factors();
+    /// assert!(map.to_r_source("df").contains("df$group <- factor(df$group, levels = c(\"x\", \"y\"))"));
+    /// ```
+    pub fn to_r_source(&self, frame: &str) -> String {
+        let mut source = String::from("# Factor definitions written by rust_data_table");
+        if let Some(meta) = &self.meta {
+            source.push_str(&format!(" from {}", meta.inputs.join(", ")));
+        }
+        source.push('\n');
+        for factor in self.factors.iter().filter(|f| self.column(&f.column).is_none_or(|c| c.dtype == Dtype::Factor)) {
+            source.push_str(&r_factor(frame, factor));
+        }
+        source
+    }
+
+    /// Write `to_r_source(frame)` to `path`.
+    pub fn write_r_source<P: AsRef<Path>>(&self, path: P, frame: &str) -> Result<()> {
+        fs::write(path.as_ref(), self.to_r_source(frame)).map_err(|e| DataTableError::io(&path, e))
+    }
+}
+
+/// The lines of one factor.
+fn r_factor(frame: &str, factor: &FactorJson) -> String {
+    let column = r_column(frame, &factor.column);
+    let mut lines = String::new();
+    let mut merged: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (original, level) in factor.merged.iter().flatten() {
+        merged.entry(level.as_str()).or_default().push(original.clone());
+    }
+    for (level, originals) in merged {
+        lines.push_str(&format!("{column}[{column} %in% {}] <- {}\n", r_vector(&originals), r_string(level)));
+    }
+    let mut args = format!("levels = {}", r_vector(&factor.levels));
+    if let Some(labels) = factor.labels.as_ref().filter(|l| l.len() == factor.levels.len()) {
+        args.push_str(&format!(", labels = {}", r_vector(labels)));
+    }
+    if factor.ordered {
+        args.push_str(", ordered = TRUE");
+    }
+    lines.push_str(&format!("{column} <- factor({column}, {args})\n"));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::factor::FactorSpec;
+    use crate::options::ParseOptions;
+    use crate::SurvivalData;
+
+    #[test]
+    fn test_r_source() -> Result<()> {
+        let tsv = "cell\tstage\tname with space\tnote\nA\tlow\tsay \"hi\"\tx\nB\thigh\ta\\b\ty\nC\tlow\tsay \"hi\"\tz\n";
+        let mut options = ParseOptions { max_levels: Some(2), ..Default::default() };
+        options.factor_specs.insert("stage".into(), FactorSpec::ordered(vec!["low".into(), "high".into()]));
+        let mut map = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?.compute_factors();
+        map.factors.iter_mut().find(|f| f.column == "stage").unwrap().labels = Some(vec!["Low".into(), "High".into()]);
+
+        let source = map.to_r_source("df");
+        let lines: Vec<&str> = source.lines().collect();
+        assert_eq!(lines, [
+            "# Factor definitions written by rust_data_table",
+            "df$stage <- factor(df$stage, levels = c(\"low\", \"high\"), labels = c(\"Low\", \"High\"), ordered = TRUE)",
+            "df[[\"name with space\"]] <- factor(df[[\"name with space\"]], levels = c(\"say \\\"hi\\\"\", \"a\\\\b\"))",
+        ], "free text (cell, note) is left alone");
+
+        assert_eq!(r_string("tab\there\u{1}"), "\"tab\\there\\u{0001}\"");
+        assert_eq!(r_column("d", ".x1"), "d$.x1");
+        assert_eq!(r_column("d", ".1x"), "d[[\".1x\"]]");
+        assert_eq!(r_column("d", "if"), "d[[\"if\"]]");
+        assert_eq!(r_column("d", "_x"), "d[[\"_x\"]]");
+
+        // merged levels are recoded before the factor is built
+        let factor = FactorJson {
+            column: "cluster".into(),
+            levels: vec!["T".into(), "Other".into()],
+            numeric: None,
+            matching: None,
+            one_hot: false,
+            ordered: false,
+            labels: None,
+            counts: None,
+            missing: None,
+            merged: Some([("B".to_string(), "Other".to_string()), ("NK".to_string(), "Other".to_string())].into()),
+            colors: None,
+            bins: None,
+            source: None,
+        };
+        assert_eq!(
+            r_factor("df", &factor),
+            "df$cluster[df$cluster %in% c(\"B\", \"NK\")] <- \"Other\"\ndf$cluster <- factor(df$cluster, levels = c(\"T\", \"Other\"))\n"
+        );
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("factors.R");
+        map.write_r_source(&path, "meta")?;
+        assert!(fs::read_to_string(&path)?.contains("meta$stage <- factor(meta$stage"));
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use arrow_array::types::Int32Type;
+use arrow_array::{ArrayRef, BooleanArray, DictionaryArray, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::ArrowError;
+use crate::column::Dtype;
+use crate::{Factor, SurvivalData};
+
+impl SurvivalData {
+    /// Convert the table into an arrow `RecordBatch` - in header order.
+    ///
+    /// Factor and free text columns become `DictionaryArray<Int32Type>`: the dictionary holds
+    /// the levels in factor level order (`get_levels()`) and the keys index into it. Integer
+    /// columns become `Int64`, booleans `Boolean` and all others `Float64` (dates as seconds
+    /// since 1970-01-01). Missing values are nulls.
+    pub fn to_arrow(&self) -> Result<RecordBatch, ArrowError> {
+        let columns = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                let values = self.numeric_data.column(id);
+                let dtype = self.columns.get(id).map_or(Dtype::Float, |c| c.dtype);
+                let array: ArrayRef = match (dtype, self.factors.get(name)) {
+                    (Dtype::Factor | Dtype::FreeText | Dtype::Json, Some(factor)) => {
+                        let keys = level_keys(factor);
+                        let keys: Int32Array = values.iter().map(|v| keys.get(&v.to_bits()).copied()).collect();
+                        let levels = StringArray::from_iter_values(factor.get_levels());
+                        Arc::new(DictionaryArray::<Int32Type>::try_new(keys, Arc::new(levels))?)
+                    }
+                    (Dtype::Integer, _) => {
+                        Arc::new(values.iter().map(|v| (!v.is_nan()).then_some(*v as i64)).collect::<Int64Array>())
+                    }
+                    (Dtype::Boolean, _) => {
+                        Arc::new(values.iter().map(|v| (!v.is_nan()).then_some(*v != 0.0)).collect::<BooleanArray>())
+                    }
+                    _ => Arc::new(values.iter().map(|v| (!v.is_nan()).then_some(*v)).collect::<Float64Array>()),
+                };
+                Ok((name.as_str(), array))
+            })
+            .collect::<Result<Vec<_>, ArrowError>>()?;
+        RecordBatch::try_from_iter(columns)
+    }
+}
+
+/// The dictionary key of every code of a factor - codes can be hand edited, so they are
+/// not necessarily the level positions.
+fn level_keys(factor: &Factor) -> HashMap<u64, i32> {
+    factor
+        .get_levels()
+        .iter()
+        .enumerate()
+        .map(|(key, level)| (factor.get_f64(level).to_bits(), key as i32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use arrow_schema::DataType;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use crate::{FactorMap, ParseOptions};
+
+    #[test]
+    fn test_to_arrow() {
+        let tsv = "cluster\tn\tscore\tdoublet\nB cell\t3\t0.5\tTRUE\nT cell\tNA\t1.5\tFALSE\nNA\t5\tNA\tNA\nB cell\t1\t2\tTRUE\n";
+        // hand edited codes - the keys still have to follow the level order
+        let factors = FactorMap::from_value(serde_json::json!([
+            {"column": "cluster", "levels": ["T cell", "B cell"], "numeric": [10.0, 20.0], "matching": null, "one_hot": false}
+        ]))
+        .unwrap();
+        let options = ParseOptions::new(b'\t', HashSet::new());
+        let data = SurvivalData::from_reader_with_factors(Cursor::new(tsv), &options, &factors).unwrap();
+        let batch = data.to_arrow().unwrap();
+
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["cluster", "n", "score", "doublet"], "header order is kept");
+        assert_eq!(schema.field(0).data_type(), &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)));
+        let dtypes: Vec<&DataType> = schema.fields().iter().skip(1).map(|f| f.data_type()).collect();
+        assert_eq!(dtypes, vec![&DataType::Int64, &DataType::Float64, &DataType::Boolean]);
+        for column in batch.columns() {
+            assert_eq!(column.null_count(), 1, "missing values are nulls");
+        }
+
+        let cluster = batch.column(0).as_any().downcast_ref::<DictionaryArray<Int32Type>>().unwrap();
+        let levels = cluster.values().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(levels.iter().flatten().collect::<Vec<_>>(), data.factors["cluster"].get_levels());
+        assert_eq!(cluster.keys().iter().collect::<Vec<_>>(), vec![Some(1), Some(0), None, Some(1)], "keys follow the level order");
+    }
+}
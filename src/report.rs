@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::fmt;
+use serde::Serialize;
+
+/// Why a row did not make it (see `ParseReport`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropReason {
+    /// Another number of fields than the header (`skip_bad_rows`) - the row is not in the table
+    WrongFieldCount,
+    /// No survival time or event - the row stays in the table, but not in `survival_pairs()`
+    MissingSurvival,
+    /// A line above the header (`ParseOptions::header_row`)
+    BeforeHeader,
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DropReason::WrongFieldCount => "wrong number of fields",
+            DropReason::MissingSurvival => "no survival time or event",
+            DropReason::BeforeHeader => "line before the header",
+        })
+    }
+}
+
+/// How many rows were read and how many of them were dropped why - see `SurvivalData::parse_report()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ParseReport {
+    /// The data rows of the input (header and comment lines not counted)
+    pub rows_read: usize,
+    /// `rows_read` minus `rows_dropped`
+    pub rows_kept: usize,
+    pub rows_dropped: usize,
+    /// The dropped rows by reason - reasons without a row have no entry
+    pub reasons: HashMap<DropReason, usize>,
+    /// Set if the reading stopped at `ParseOptions::max_rows` with more rows left: the
+    /// factors and summaries describe only the rows read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl ParseReport {
+    pub fn new(rows_read: usize) -> Self {
+        ParseReport { rows_read, rows_kept: rows_read, ..Default::default() }
+    }
+
+    /// Were all rows kept?
+    pub fn is_clean(&self) -> bool {
+        self.rows_dropped == 0
+    }
+
+    /// The number of rows dropped for `reason`.
+    pub fn dropped(&self, reason: DropReason) -> usize {
+        self.reasons.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// Set the rows dropped for `reason` - replacing an earlier count of it.
+    pub(crate) fn set(&mut self, reason: DropReason, rows: usize) {
+        if rows == 0 {
+            self.reasons.remove(&reason);
+        } else {
+            self.reasons.insert(reason, rows);
+        }
+        self.rows_dropped = self.reasons.values().sum();
+        self.rows_kept = self.rows_read.saturating_sub(self.rows_dropped);
+    }
+}
+
+/// `120 rows read, 117 kept, 3 dropped (2 wrong number of fields, 1 no survival time or event)`,
+/// and `- stopped after 100 rows (max_rows)` for a cut table.
+impl fmt::Display for ParseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rows read, {} kept, {} dropped", self.rows_read, self.rows_kept, self.rows_dropped)?;
+        if !self.reasons.is_empty() {
+            let mut reasons: Vec<(&DropReason, &usize)> = self.reasons.iter().collect();
+            reasons.sort();
+            let reasons: Vec<String> = reasons.iter().map(|(reason, rows)| format!("{} {}", rows, reason)).collect();
+            write!(f, " ({})", reasons.join(", "))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " - stopped after {} rows (max_rows)", limit)?;
+        }
+        Ok(())
+    }
+}
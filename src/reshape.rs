@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use ndarray::Array2;
+use crate::column::{Column, Dtype, NumericSummary};
+use crate::error::{bail, Result};
+use crate::factor::Factor;
+use crate::SurvivalData;
+
+/// The factor of a melted table naming the value column a row comes from (see `SurvivalData::melt()`).
+pub const VARIABLE_COLUMN: &str = "variable";
+/// The numeric column of a melted table.
+pub const VALUE_COLUMN: &str = "value";
+
+impl SurvivalData {
+    /// Reshape from wide to long (pandas' `melt`): every row becomes one row per `value_cols`
+    /// entry, holding the `id_cols`, the factor `variable` (the name of the value column) and
+    /// the number as `value`. The rows are ordered by variable, then by the original row,
+    /// so there are `n_rows() * value_cols.len()` of them.
+    ///
+    /// The value columns have to be numeric; factors of the id columns are kept.
+    pub fn melt(&self, id_cols: &[&str], value_cols: &[&str]) -> Result<SurvivalData> {
+        if value_cols.is_empty() {
+            bail!(InvalidArgument, "melt() needs at least one value column");
+        }
+        for name in id_cols.iter().chain(value_cols) {
+            if self.column(name).is_none() {
+                bail!(Column, "Unknown column '{}'", name);
+            }
+            if *name == VARIABLE_COLUMN || *name == VALUE_COLUMN {
+                bail!(InvalidArgument, "Column '{}' cannot be melted - the long table creates a column of that name", name);
+            }
+        }
+        for name in value_cols {
+            let column = self.column(name).expect("checked above");
+            if !column.dtype.is_numeric() || self.factors.contains_key(*name) {
+                bail!(Column, "melt(): value column '{}' must be numeric, but it is {}", name, column.dtype);
+            }
+        }
+
+        // an id factor brings its one-hot columns along
+        let mut ids: Vec<usize> = Vec::new();
+        for name in id_cols {
+            ids.push(self.column_index(name).expect("checked above"));
+            if let Some(factor) = self.factors.get(*name)
+                && factor.one_hot
+            {
+                ids.extend(factor.all_column_names().iter().filter_map(|c| self.column_index(c)));
+            }
+        }
+        let values: Vec<usize> = value_cols.iter().map(|name| self.column_index(name).expect("checked above")).collect();
+
+        let n = self.numeric_data.nrows();
+        let numeric_data = Array2::from_shape_fn((n * values.len(), ids.len() + 2), |(row, col)| {
+            let (variable, i) = (row / n, row % n);
+            match col {
+                c if c < ids.len() => self.numeric_data[[i, ids[c]]],
+                c if c == ids.len() => variable as f64,
+                _ => self.numeric_data[[i, values[variable]]],
+            }
+        });
+
+        let mut variable = Factor::new(VARIABLE_COLUMN, false);
+        for name in value_cols {
+            variable.level_to_index(name);
+        }
+        let mut factors: HashMap<String, Factor> = id_cols
+            .iter()
+            .filter_map(|name| self.factors.get(*name).map(|f| (name.to_string(), f.clone())))
+            .collect();
+        factors.insert(VARIABLE_COLUMN.to_string(), variable);
+
+        let mut headers: Vec<String> = ids.iter().map(|&j| self.headers[j].clone()).collect();
+        let mut columns: Vec<Column> = ids.iter().map(|&j| self.columns[j].clone()).collect();
+        headers.extend([VARIABLE_COLUMN.to_string(), VALUE_COLUMN.to_string()]);
+        columns.push(Column::new(VARIABLE_COLUMN, Dtype::Factor));
+        let mut value = Column::new(VALUE_COLUMN, Dtype::Float);
+        value.summary = Some(NumericSummary::default());
+        columns.push(value);
+
+        let mut ret = SurvivalData::default();
+        ret.numeric_data = numeric_data;
+        ret.factors = factors;
+        ret.delimiter = self.delimiter;
+        ret.set_columns(headers, columns);
+        // counts, missing values and summaries of the long table
+        ret.drop_unused_levels();
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_melt() -> Result<()> {
+        let tsv = "cell\tgroup\tday1\tday2\tnote\nA\tx\t1.5\t2\tok\nB\ty\tNA\t4\tok\nC\tx\t3\t6\tbad\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let long = data.melt(&["cell", "group"], &["day1", "day2"])?;
+        assert_eq!(long.n_rows(), 6);
+        assert_eq!(long.column_names(), ["cell", "group", VARIABLE_COLUMN, VALUE_COLUMN]);
+        assert_eq!(long.as_vec_string("cell").unwrap(), ["A", "B", "C", "A", "B", "C"]);
+        assert_eq!(long.as_vec_string(VARIABLE_COLUMN).unwrap(), ["day1", "day1", "day1", "day2", "day2", "day2"]);
+        let value: Vec<String> = long.as_vec_f64(VALUE_COLUMN).iter().map(|v| v.to_string()).collect();
+        assert_eq!(value, ["1.5", "NaN", "3", "2", "4", "6"]);
+        assert_eq!(long.column(VALUE_COLUMN).unwrap().missing, 1);
+        assert_eq!(long.column(VALUE_COLUMN).unwrap().summary.as_ref().unwrap().max, Some(6.0));
+        let variable = long.compute_factors();
+        let variable = variable.get(VARIABLE_COLUMN).unwrap();
+        assert_eq!((variable.levels.clone(), variable.counts.clone()), (vec!["day1".into(), "day2".into()], Some(vec![3, 3])));
+        assert_eq!(long.compute_factors().get("group").unwrap().counts, Some(vec![4, 2]));
+
+        let err = data.melt(&["cell"], &["day1", "note"]).unwrap_err();
+        assert_eq!(err.to_string(), "melt(): value column 'note' must be numeric, but it is factor");
+        assert!(data.melt(&["cell"], &["day3"]).is_err());
+        assert!(data.melt(&["cell"], &[]).is_err());
+        Ok(())
+    }
+}
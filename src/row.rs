@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use ndarray::ArrayView1;
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+/// One row of a table - a view into `numeric_data` and the factor levels,
+/// nothing is copied (see `SurvivalData::rows()`).
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    data: &'a SurvivalData,
+    index: usize,
+}
+
+impl<'a> Row<'a> {
+    /// The position of the row in the table.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// All values of the row in `column_names()` order (NaN for missing values).
+    pub fn values(&self) -> ArrayView1<'a, f64> {
+        self.data.numeric_data.row(self.index)
+    }
+
+    /// The value of column `name` - `None` for a missing value or an unknown column.
+    /// Factors give their numeric code, dates seconds since 1970-01-01.
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        let value = self.data.numeric_data[[self.index, self.data.column_index(name)?]];
+        (!value.is_nan()).then_some(value)
+    }
+
+    /// The level of factor column `name`, borrowed from the factor -
+    /// `None` for a missing value or if the column is no factor.
+    pub fn get_str(&self, name: &str) -> Option<&'a str> {
+        let factor = self.data.factors.get(name)?;
+        factor.level_at(self.get_f64(name)?)
+    }
+}
+
+impl SurvivalData {
+    /// Iterate over the rows without copying them - e.g. for a quick scan of a large table.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let tsv = "cluster\tnCount\nT\t120\nB\tNA\n";
+    /// let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+    /// let counts: Vec<(Option<&str>, Option<f64>)> = data.rows().map(|r| (r.get_str("cluster"), r.get_f64("nCount"))).collect();
+    /// assert_eq!(counts, vec![(Some("T"), Some(120.0)), (Some("B"), None)]);
+    /// ```
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = Row<'_>> {
+        (0..self.numeric_data.nrows()).map(move |index| Row { data: self, index })
+    }
+
+    /// The number of missing fields of every row. A field is missing if it held one of the
+    /// `missing_tokens` (or was coerced, see `ParseOptions::as_numeric`) - it is NaN after parsing.
+    ///
+    /// Counted on the current rows, so filtered tables stay right. The columns of a one-hot
+    /// factor count once (its source column), not once per level.
+    pub fn row_missing_counts(&self) -> Vec<usize> {
+        let indicators: HashSet<String> = self.factors.values().filter(|f| f.one_hot).flat_map(|f| f.all_column_names()).collect();
+        let counted: Vec<usize> = (0..self.headers.len()).filter(|&j| !indicators.contains(&self.headers[j])).collect();
+        self.numeric_data
+            .rows()
+            .into_iter()
+            .map(|row| counted.iter().filter(|&&j| row[j].is_nan()).count())
+            .collect()
+    }
+
+    /// A new table with the rows `predicate` accepts.
+    ///
+    /// The factors of the result only have the levels that still occur (a filtered table does
+    /// not list stale levels); the remaining levels keep their order and numeric codes. The
+    /// one-hot columns of dropped levels are removed, missing counts and numeric summaries recounted.
+    pub fn filter<F: Fn(&Row) -> bool>(&self, predicate: F) -> SurvivalData {
+        let keep: Vec<usize> = self.rows().filter(|row| predicate(row)).map(|row| row.index()).collect();
+        let mut subset = self.select_rows(&keep);
+        subset.drop_unused_levels();
+        subset
+    }
+
+    /// The rows where `column` is `value` - a level (or label) of a factor, or a number - see `filter()`.
+    pub fn filter_eq(&self, column: &str, value: &str) -> Result<SurvivalData> {
+        let Some(id) = self.column_index(column) else {
+            bail!(Column, "Unknown column '{}'", column);
+        };
+        let code = match self.factors.get(column) {
+            Some(factor) => match factor.get_levels().iter().find(|l| *l == value || factor.label(l) == value) {
+                Some(level) => factor.get_f64(level),
+                None => bail!(Column, "'{}' is no level of factor '{}' - levels: {}", value, column, factor.get_levels().join(", ")),
+            },
+            None => match value.trim().parse::<f64>() {
+                Ok(number) => number,
+                Err(_) => bail!(Column, "Column '{}' is {} - '{}' is no number", column, self.columns[id].dtype, value),
+            },
+        };
+        Ok(self.filter(|row| row.values()[id] == code))
+    }
+
+    /// The indices of the rows with more than `threshold` missing fields (see `row_missing_counts()`).
+    pub fn rows_with_missing_above(&self, threshold: usize) -> Vec<usize> {
+        self.row_missing_counts()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(row, missing)| (missing > threshold).then_some(row))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use crate::SurvivalData;
+
+    #[test]
+    fn test_rows() {
+        let tsv = "barcode\tcluster\tnCount\nAAAC\t3\t10.5\nAAAG\tNA\t20\n";
+        let categorical: HashSet<String> = ["cluster".to_string()].into();
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', categorical).unwrap();
+        let rows: Vec<_> = data.rows().collect();
+        assert_eq!(data.rows().len(), 2);
+        assert_eq!(rows[0].get_str("barcode"), Some("AAAC"));
+        assert_eq!(rows[0].get_str("cluster"), Some("3"));
+        assert_eq!(rows[1].get_str("cluster"), None, "missing");
+        assert_eq!(rows[1].get_f64("nCount"), Some(20.0));
+        assert_eq!(rows[0].get_str("nCount"), None, "no factor");
+        assert_eq!(rows[0].get_f64("unknown"), None);
+        assert_eq!(rows[1].index(), 1);
+        assert_eq!(rows[0].values().len(), data.n_cols());
+    }
+
+    #[test]
+    fn test_row_missing_counts() {
+        let tsv = "cell	cluster	nCount	score
+A	x	10	1.5
+B	-	NA	
+C	y	n/a	2
+";
+        let options = crate::ParseOptions {
+            missing_tokens: vec!["NA".into(), "".into(), "-".into(), "n/a".into()],
+            ..Default::default()
+        };
+        let mut data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).unwrap();
+        assert_eq!(data.row_missing_counts(), vec![0, 3, 1]);
+        assert_eq!(data.rows_with_missing_above(0), vec![1, 2]);
+        assert_eq!(data.rows_with_missing_above(1), vec![1]);
+
+        data.factors.get_mut("cluster").unwrap().one_hot = true;
+        let one_hot = SurvivalData::from_reader_with_factors(Cursor::new(tsv), &options, &data.compute_factors()).unwrap();
+        assert!(one_hot.n_cols() > data.n_cols());
+        assert_eq!(one_hot.row_missing_counts(), vec![0, 3, 1], "one-hot columns count once");
+    }
+
+    #[test]
+    fn test_filter() {
+        let tsv = "condition	cluster	nCount
+treated	a	10
+control	b	20
+treated	c	NA
+control	a	40
+";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+
+        let treated = data.filter_eq("condition", "treated").unwrap();
+        assert_eq!(treated.n_rows(), 2);
+        assert_eq!(treated.factors["condition"].get_levels(), ["treated"]);
+        assert_eq!(treated.factors["cluster"].get_levels(), ["a", "c"], "'b' has no rows left");
+        assert_eq!(treated.as_vec_f64("cluster"), vec![0.0, 2.0], "codes are kept");
+        let n_count = treated.column("nCount").unwrap();
+        assert_eq!((n_count.missing, n_count.summary.as_ref().unwrap().max), (1, Some(10.0)));
+        assert_eq!(treated.column("cluster").unwrap().cardinality, Some(2));
+        assert_eq!(data.factors["cluster"].get_levels(), ["a", "b", "c"], "the source is untouched");
+
+        let large = data.filter(|row| row.get_f64("nCount").is_some_and(|n| n > 15.0));
+        assert_eq!(large.as_vec_f64("nCount"), vec![20.0, 40.0]);
+        assert_eq!(data.filter_eq("nCount", "40").unwrap().n_rows(), 1);
+        assert!(data.filter_eq("condition", "tretaed").unwrap_err().to_string().contains("levels: treated, control"));
+        assert!(data.filter_eq("nCount", "many").is_err());
+
+        let mut one_hot = data.clone();
+        one_hot.factors.get_mut("cluster").unwrap().one_hot = true;
+        let one_hot = SurvivalData::from_reader_with_factors(Cursor::new(tsv), &Default::default(), &one_hot.compute_factors()).unwrap();
+        let treated = one_hot.filter_eq("condition", "treated").unwrap();
+        assert_eq!(treated.n_cols(), one_hot.n_cols() - 1, "the indicator of 'b' is gone");
+        assert_eq!(treated.compute_factors().columns.len(), treated.n_cols());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::column::Dtype;
+use crate::error::{bail, Result};
+use crate::SurvivalData;
+
+/// A uniform random sample of `n` items from a stream of unknown length
+/// (reservoir sampling, Algorithm R). The same seed gives the same sample.
+#[derive(Debug, Clone)]
+pub struct Reservoir<T> {
+    n: usize,
+    seen: usize,
+    rng: StdRng,
+    items: Vec<(usize, T)>,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(n: usize, seed: u64) -> Self {
+        Reservoir { n, seen: 0, rng: StdRng::seed_from_u64(seed), items: Vec::with_capacity(n) }
+    }
+
+    /// Offer the next item - it is kept with probability `n / seen`.
+    pub fn push(&mut self, item: T) {
+        if self.items.len() < self.n {
+            self.items.push((self.seen, item));
+        } else {
+            let slot = self.rng.random_range(0..=self.seen);
+            if slot < self.n {
+                self.items[slot] = (self.seen, item);
+            }
+        }
+        self.seen += 1;
+    }
+
+    /// Number of items offered so far.
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// The sampled items in the order they were pushed.
+    pub fn into_sorted(mut self) -> Vec<T> {
+        self.items.sort_by_key(|(position, _)| *position);
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+impl SurvivalData {
+    /// A random sample of `n` rows (all rows if the table is smaller), in table order.
+    ///
+    /// The sample keeps the columns and factors of the full table - every factor level
+    /// stays defined even if none of the sampled rows has it. The same `seed` gives the same rows.
+    pub fn sample_rows(&self, n: usize, seed: u64) -> SurvivalData {
+        let mut reservoir = Reservoir::new(n, seed);
+        for row in 0..self.numeric_data.nrows() {
+            reservoir.push(row);
+        }
+        self.select_rows(&reservoir.into_sorted())
+    }
+
+    /// A random sample of at most `per_level` rows per level of the factor `by`, in table order -
+    /// a level with fewer rows keeps all of them. Rows without a level are left out.
+    ///
+    /// Unlike `sample_rows()` a dominant level does not crowd out the small ones. Columns and
+    /// factors are kept like there; the same `seed` gives the same rows.
+    pub fn downsample_balanced(&self, by: &str, per_level: usize, seed: u64) -> Result<SurvivalData> {
+        match self.column(by) {
+            None => bail!(Column, "Unknown column '{}'", by),
+            Some(column) if column.dtype != Dtype::Factor => {
+                bail!(Column, "downsample_balanced(): column '{}' must be a factor, but it is {}", by, column.dtype)
+            }
+            Some(_) => {}
+        }
+        // one reservoir per level, seeded by its code - independent of the other levels
+        let mut levels: BTreeMap<u64, Reservoir<usize>> = BTreeMap::new();
+        for (row, code) in self.as_vec_f64(by).into_iter().enumerate() {
+            if code.is_nan() {
+                continue;
+            }
+            levels
+                .entry(code.to_bits())
+                .or_insert_with(|| Reservoir::new(per_level, seed ^ code.to_bits().wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+                .push(row);
+        }
+        let mut rows: Vec<usize> = levels.into_values().flat_map(Reservoir::into_sorted).collect();
+        rows.sort_unstable();
+        Ok(self.select_rows(&rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sample_rows() {
+        let mut tsv = String::from("id\tgroup\n");
+        for i in 0..100 {
+            tsv.push_str(&format!("{i}\t{}\n", if i == 99 { "rare" } else { "common" }));
+        }
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+
+        let sample = data.sample_rows(10, 7);
+        let ids = sample.as_vec_f64("id");
+        assert_eq!(ids.len(), 10);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]), "table order: {ids:?}");
+        assert_eq!(data.sample_rows(10, 7).as_vec_f64("id"), ids, "reproducible");
+        assert_ne!(data.sample_rows(10, 8).as_vec_f64("id"), ids);
+        assert_eq!(sample.factors["group"].get_levels(), ["common", "rare"], "levels of the full table");
+        assert_eq!(sample.columns, data.columns);
+        assert_eq!(data.sample_rows(500, 1).n_rows(), 100);
+
+        // every position is equally likely
+        let mut hits = [0_usize; 4];
+        for seed in 0..4000 {
+            let mut reservoir = Reservoir::new(1, seed);
+            (0..4).for_each(|i| reservoir.push(i));
+            hits[reservoir.into_sorted()[0]] += 1;
+        }
+        assert!(hits.iter().all(|&h| (850..1150).contains(&h)), "{hits:?}");
+    }
+
+    #[test]
+    fn test_downsample_balanced() -> Result<()> {
+        let mut tsv = String::from("id\tcluster\n");
+        for i in 0..100 {
+            let cluster = match i {
+                0..90 => "T",
+                90..97 => "B",
+                _ if i == 97 => "NA",
+                _ => "NK",
+            };
+            tsv.push_str(&format!("{i}\t{cluster}\n"));
+        }
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let balanced = data.downsample_balanced("cluster", 5, 3)?;
+        let mut counts = std::collections::HashMap::new();
+        for level in balanced.as_vec_string("cluster").unwrap() {
+            *counts.entry(level).or_insert(0) += 1;
+        }
+        assert_eq!((counts["T"], counts["B"], counts["NK"], counts.len()), (5, 5, 2, 3), "NK keeps both rows, NA is no level");
+        let ids = balanced.as_vec_f64("id");
+        assert!(ids.windows(2).all(|w| w[0] < w[1]), "table order: {ids:?}");
+        assert_eq!(data.downsample_balanced("cluster", 5, 3)?.as_vec_f64("id"), ids, "reproducible");
+        assert_ne!(data.downsample_balanced("cluster", 5, 4)?.as_vec_f64("id"), ids);
+        assert_eq!(balanced.factors["cluster"].get_levels(), data.factors["cluster"].get_levels());
+        assert_eq!(data.downsample_balanced("cluster", 1000, 0)?.n_rows(), 99);
+        assert_eq!(data.downsample_balanced("cluster", 0, 0)?.n_rows(), 0);
+
+        assert!(data.downsample_balanced("id", 5, 0).is_err(), "no factor");
+        assert!(data.downsample_balanced("sample", 5, 0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::cache::Fnv1a;
+use crate::column::Dtype;
+use crate::factor_map::FactorMap;
+use crate::SurvivalData;
+
+/// The version of the hashed representation - part of the hash, changed only with it.
+const SCHEMA_HASH_FORMAT: &str = "rust_data_table schema hash 1";
+
+/// The hash of a schema: `SCHEMA_HASH_FORMAT`, then in column order the name and the dtype
+/// (`integer`, `float`, `boolean`, `date`, `factor`, `free_text`, `json`) of every column and for
+/// factors `ordered`/`nominal`, the number of levels and the levels in their order. Every part
+/// is hashed as its UTF-8 bytes followed by a `0xff` byte (64 bit FNV-1a, printed as 16 hex
+/// digits) - nothing else goes in: no provenance, counts, codes, labels, colors or crate version.
+fn hash_schema<'a, I>(columns: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, Dtype, Option<(bool, &'a [String])>)>,
+{
+    let mut hash = Fnv1a::default();
+    hash.write(SCHEMA_HASH_FORMAT.as_bytes());
+    for (name, dtype, factor) in columns {
+        hash.write(name.as_bytes());
+        hash.write(dtype.to_string().as_bytes());
+        if let Some((ordered, levels)) = factor {
+            hash.write(if ordered { b"ordered" } else { b"nominal" });
+            hash.write(levels.len().to_string().as_bytes());
+            for level in levels {
+                hash.write(level.as_bytes());
+            }
+        }
+    }
+    format!("{:016x}", hash.0)
+}
+
+impl SurvivalData {
+    /// A short fingerprint of the schema - the column names, their dtypes and the levels (and
+    /// their order) of the factors - to tell cheaply whether two runs produced the same layout,
+    /// e.g. in CI. It does not depend on the values, the machine or the crate version;
+    /// `FactorMap::schema_hash()` gives the same for the factors file of the table.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let first = SurvivalData::from_reader(Cursor::new("cluster\tn\nT\t1\nB\t2\n"), b'\t', HashSet::new()).unwrap();
+    /// let second = SurvivalData::from_reader(Cursor::new("cluster\tn\nT\t7\nB\t9\n"), b'\t', HashSet::new()).unwrap();
+    /// assert_eq!(first.schema_hash(), second.schema_hash());
+    /// ```
+    pub fn schema_hash(&self) -> String {
+        hash_schema(self.columns.iter().map(|column| {
+            let factor = self
+                .factors
+                .get(&column.name)
+                .filter(|_| column.dtype == Dtype::Factor)
+                .map(|f| (f.ordered, f.get_levels()));
+            (column.name.as_str(), column.dtype, factor)
+        }))
+    }
+}
+
+impl FactorMap {
+    /// `SurvivalData::schema_hash()` of the table the map describes - compare a new run with a stored factors file.
+    pub fn schema_hash(&self) -> String {
+        hash_schema(self.columns.iter().map(|column| {
+            let factor = self
+                .get(&column.name)
+                .filter(|_| column.dtype == Dtype::Factor)
+                .map(|f| (f.ordered, f.levels.as_slice()));
+            (column.name.as_str(), column.dtype, factor)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::factor::FactorSpec;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_schema_hash() -> crate::error::Result<()> {
+        let parse = |tsv: &str, options: &ParseOptions| SurvivalData::from_reader_with_options(Cursor::new(tsv.to_string()), options);
+        let options = ParseOptions { max_levels: Some(2), ..Default::default() };
+        let data = parse("cell\tgroup\tn\nA\tx\t1\nB\ty\t2\nC\tx\t3\n", &options)?;
+        let hash = data.schema_hash();
+        // pinned: the representation is part of the contract
+        assert_eq!(hash, "f54c62830e5b6c1d");
+        assert_eq!(hash, data.compute_factors().schema_hash(), "table and factors file agree");
+
+        // other values, same schema - free text values are no levels
+        assert_eq!(hash, parse("cell\tgroup\tn\nD\tx\t10\nE\ty\t20\nF\ty\t30\n", &options)?.schema_hash());
+        // level order, levels, dtypes, names and ordered all count
+        assert_ne!(hash, parse("cell\tgroup\tn\nA\ty\t1\nB\tx\t2\nC\tx\t3\n", &options)?.schema_hash());
+        assert_ne!(hash, parse("cell\tgroup\tn\nA\tx\t1\nB\tz\t2\nC\tx\t3\n", &options)?.schema_hash());
+        assert_ne!(hash, parse("cell\tgroup\tn\nA\tx\t1.5\nB\ty\t2\nC\tx\t3\n", &options)?.schema_hash());
+        assert_ne!(hash, parse("cell\tgrp\tn\nA\tx\t1\nB\ty\t2\nC\tx\t3\n", &options)?.schema_hash());
+        let mut ordered = options.clone();
+        ordered.factor_specs.insert("group".into(), FactorSpec::ordered(vec!["x".into(), "y".into()]));
+        assert_ne!(hash, parse("cell\tgroup\tn\nA\tx\t1\nB\ty\t2\nC\tx\t3\n", &ordered)?.schema_hash());
+
+        // provenance is left out
+        let mut map = data.compute_factors();
+        map.meta = Some(crate::factor_map::Provenance::now(&["meta.tsv"]));
+        assert_eq!(map.schema_hash(), hash);
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, Cursor, Read};
+use std::path::Path;
+use crate::column::Column;
+use crate::error::{DataTableError, Result};
+use crate::input::{is_gzip_magic, is_zstd_magic, open_input, BOM};
+use crate::options::{delimiter_scores, sniff_delimiter, ParseOptions, TextEncoding, SNIFF_LINES};
+use crate::SurvivalData;
+
+/// Bytes at the start of the (decompressed) text the column types of `sniff()` are guessed from.
+pub const SNIFF_SAMPLE_BYTES: usize = 1 << 20;
+
+/// How the lines of a file end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEndings {
+    Lf,
+    CrLf,
+    Cr,
+    /// More than one style - the parser accepts that
+    Mixed,
+    /// A single line without a line break
+    None,
+}
+
+impl fmt::Display for LineEndings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineEndings::Lf => "LF",
+            LineEndings::CrLf => "CRLF",
+            LineEndings::Cr => "CR",
+            LineEndings::Mixed => "mixed",
+            LineEndings::None => "none",
+        })
+    }
+}
+
+/// What `sniff()` found out about a table file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SniffReport {
+    /// `gzip` or `zstd` for a compressed file - everything else describes the decompressed text
+    pub compression: Option<&'static str>,
+    /// UTF-8 if the whole text is valid UTF-8, Windows-1252 (`--encoding latin1`) otherwise
+    pub encoding: TextEncoding,
+    /// The text starts with a UTF-8 byte order mark
+    pub bom: bool,
+    pub line_endings: LineEndings,
+    /// What `Delimiter::Auto` picks - `None` if two candidates fit equally well
+    pub delimiter: Option<u8>,
+    /// Per candidate delimiter the number of the first `sample_lines` lines with the column
+    /// count of the header (see `sniff_delimiter()`)
+    pub candidates: Vec<(u8, usize)>,
+    pub sample_lines: usize,
+    /// The number of columns of the header
+    pub n_columns: usize,
+    /// The number of data rows - a quoted line break does not start a row
+    pub n_rows: usize,
+    /// The columns with the dtype inferred from the first `sampled_rows` rows
+    pub columns: Vec<Column>,
+    pub sampled_rows: usize,
+}
+
+/// A delimiter as the report names it: `tab` or the quoted character.
+fn delimiter_name(delimiter: u8) -> String {
+    match delimiter {
+        b'\t' => "tab".to_string(),
+        other => format!("'{}'", other as char),
+    }
+}
+
+impl fmt::Display for SniffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Compression:  {}", self.compression.unwrap_or("none"))?;
+        writeln!(f, "Encoding:     {}{}", self.encoding, if self.bom { " with BOM" } else { "" })?;
+        writeln!(f, "Line endings: {}", self.line_endings)?;
+        let candidates: Vec<String> = self
+            .candidates
+            .iter()
+            .map(|&(delimiter, score)| format!("{} {}", delimiter_name(delimiter), score))
+            .collect();
+        let delimiter = self.delimiter.map_or_else(|| "ambiguous (give --delimiter)".to_string(), delimiter_name);
+        writeln!(f, "Delimiter:    {} - lines fitting the header of the first {}: {}", delimiter, self.sample_lines, candidates.join(", "))?;
+        writeln!(f, "Columns:      {}", self.n_columns)?;
+        writeln!(f, "Rows:         {}", self.n_rows)?;
+        writeln!(f, "Column types (first {} rows):", self.sampled_rows)?;
+        let width = self.columns.iter().map(|c| c.name.chars().count()).max().unwrap_or(0);
+        for column in &self.columns {
+            writeln!(f, "  {:<width$}  {}", column.name, column.dtype)?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe the table file at `path` without options: compression, encoding, byte order mark,
+/// line endings, the delimiter with the scores of all candidates, the number of columns and
+/// rows, and the column types inferred from the first `SNIFF_SAMPLE_BYTES` of the text.
+///
+/// The file is read twice - once for the bytes, once more to count the rows.
+pub fn sniff<P: AsRef<Path>>(path: P) -> Result<SniffReport> {
+    let path = path.as_ref();
+    let mut magic = Vec::with_capacity(4);
+    File::open(path).and_then(|file| file.take(4).read_to_end(&mut magic)).map_err(|e| DataTableError::io(path, e))?;
+    let compression = match (is_gzip_magic(&magic), is_zstd_magic(&magic)) {
+        (true, _) => Some("gzip"),
+        (_, true) => Some("zstd"),
+        _ => None,
+    };
+
+    let mut reader = open_input(path)?;
+    let mut sample = Vec::new();
+    let (mut lf, mut crlf, mut cr) = (0usize, 0usize, 0usize);
+    let mut after_cr = false;
+    let mut utf8 = true;
+    // an incomplete UTF-8 sequence at the end of a chunk
+    let mut pending: Vec<u8> = Vec::new();
+    let mut truncated = false;
+    loop {
+        let chunk = reader.fill_buf().map_err(|e| DataTableError::io(path, e))?;
+        if chunk.is_empty() {
+            break;
+        }
+        for &byte in chunk {
+            match byte {
+                b'\n' if after_cr => crlf += 1,
+                b'\n' => lf += 1,
+                _ if after_cr => cr += 1,
+                _ => {}
+            }
+            after_cr = byte == b'\r';
+        }
+        if utf8 {
+            pending.extend_from_slice(chunk);
+            match std::str::from_utf8(&pending) {
+                Ok(_) => pending.clear(),
+                Err(e) if e.error_len().is_none() => drop(pending.drain(..e.valid_up_to())),
+                Err(_) => utf8 = false,
+            }
+        }
+        let room = SNIFF_SAMPLE_BYTES - sample.len();
+        truncated |= chunk.len() > room;
+        sample.extend_from_slice(&chunk[..room.min(chunk.len())]);
+        let n = chunk.len();
+        reader.consume(n);
+    }
+    cr += usize::from(after_cr);
+    utf8 &= pending.is_empty();
+    if truncated && let Some(end) = sample.iter().rposition(|&b| b == b'\n') {
+        sample.truncate(end + 1);
+    }
+    let line_endings = match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => LineEndings::None,
+        (true, false, false) => LineEndings::Lf,
+        (false, true, false) => LineEndings::CrLf,
+        (false, false, true) => LineEndings::Cr,
+        _ => LineEndings::Mixed,
+    };
+    let encoding = if utf8 { TextEncoding::Utf8 } else { TextEncoding::Windows1252 };
+    let bom = sample.starts_with(BOM.to_string().as_bytes());
+
+    // the delimiters are ASCII - the encoding of the other bytes does not matter here
+    let text = String::from_utf8_lossy(&sample);
+    let head: Vec<&str> = text.trim_start_matches(BOM).split('\n').map(|line| line.trim_end_matches('\r')).take(SNIFF_LINES).collect();
+    let sample_lines = head.iter().rposition(|line| !line.is_empty()).map_or(0, |last| last + 1);
+    let head = head.join("\n");
+    let candidates = delimiter_scores(&head);
+    let delimiter = sniff_delimiter(&head).ok();
+
+    let mut options = ParseOptions::new(delimiter.unwrap_or_else(|| best_candidate(&candidates)), Default::default());
+    options.encoding = encoding;
+    let mut rows = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .terminator(csv::Terminator::CRLF)
+        .from_reader(encoding.decode(open_input(path)?));
+    let n_columns = rows.byte_headers()?.len();
+    let mut record = csv::ByteRecord::new();
+    let mut n_rows = 0;
+    while rows.read_byte_record(&mut record)? {
+        n_rows += 1;
+    }
+
+    let data = SurvivalData::from_reader_with_options(Cursor::new(sample), &options)?;
+    Ok(SniffReport {
+        compression,
+        encoding,
+        bom,
+        line_endings,
+        delimiter,
+        candidates,
+        sample_lines,
+        n_columns,
+        n_rows,
+        sampled_rows: data.n_rows(),
+        columns: data.columns,
+    })
+}
+
+/// The first of the best scoring candidates.
+fn best_candidate(candidates: &[(u8, usize)]) -> u8 {
+    candidates.iter().fold((b'\t', 0), |best, &(delimiter, score)| if score > best.1 { (delimiter, score) } else { best }).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::column::Dtype;
+
+    #[test]
+    fn test_sniff() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("export.csv");
+        std::fs::write(&path, "\u{feff}patient;age;note;ok\r\nP1;40;\"two\r\nlines\";yes\r\nP2;52;café;no\r\nP3;61;x;yes\r\n")?;
+
+        let report = sniff(&path)?;
+        assert_eq!((report.compression, report.encoding, report.bom), (None, TextEncoding::Utf8, true));
+        assert_eq!(report.line_endings, LineEndings::CrLf);
+        assert_eq!(report.delimiter, Some(b';'));
+        assert_eq!(report.candidates, [(b'\t', 0), (b',', 0), (b';', 4), (b'|', 0)], "the quoted line break splits a line");
+        assert_eq!((report.n_columns, report.n_rows, report.sampled_rows), (4, 3, 3));
+        let dtypes: Vec<(&str, Dtype)> = report.columns.iter().map(|c| (c.name.as_str(), c.dtype)).collect();
+        assert_eq!(dtypes, [("patient", Dtype::Factor), ("age", Dtype::Integer), ("note", Dtype::Factor), ("ok", Dtype::Boolean)]);
+        let text = report.to_string();
+        assert!(text.contains("Encoding:     utf-8 with BOM\n"), "{text}");
+        assert!(text.contains("Delimiter:    ';' - lines fitting the header of the first 5: tab 0, ',' 0, ';' 4, '|' 0\n"), "{text}");
+
+        let gz = dir.path().join("plain.tsv.gz");
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&gz)?, flate2::Compression::default());
+        // Windows-1252: 0xE9 is an e with acute accent
+        encoder.write_all(b"a\tb\n1\tcaf\xe9\n3\tx")?;
+        encoder.finish()?;
+        let report = sniff(&gz)?;
+        assert_eq!((report.compression, report.encoding, report.bom), (Some("gzip"), TextEncoding::Windows1252, false));
+        assert_eq!((report.line_endings, report.delimiter, report.n_rows), (LineEndings::Lf, Some(b'\t'), 2));
+        Ok(())
+    }
+}
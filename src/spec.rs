@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::column::Dtype;
+use crate::error::{bail, DataTableError, Result};
+use crate::factor::FactorSpec;
+use crate::factor_map::LevelLabels;
+use crate::options::ParseOptions;
+
+/// The settings of one column in a spec file - every field is optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnSpec {
+    /// The type of the column (`integer`, `float`, `boolean`, `date` or `factor`), checked against every value
+    pub dtype: Option<Dtype>,
+    /// Read the column as factor (same as `dtype: factor`)
+    pub categorical: bool,
+    /// Explicit level order - values outside of it are an error unless `other` is set
+    pub levels: Option<Vec<String>>,
+    /// The level order is meaningful (`low < medium < high`)
+    pub ordered: bool,
+    /// Trailing level collecting the values not listed in `levels`
+    pub other: Option<String>,
+    /// Baseline level moved to index 0
+    pub reference: Option<String>,
+    /// Display labels by level (see `SurvivalData::relabel_levels()`)
+    pub labels: Option<BTreeMap<String, String>>,
+}
+
+impl ColumnSpec {
+    /// Do the settings make the column a factor?
+    fn is_factor(&self) -> bool {
+        self.categorical
+            || matches!(self.dtype, Some(Dtype::Factor | Dtype::FreeText))
+            || self.levels.is_some()
+            || self.ordered
+            || self.other.is_some()
+            || self.reference.is_some()
+            || self.labels.is_some()
+    }
+}
+
+/// Per column parser settings read from a JSON file - the alternative to a long command line:
+///
+/// ```json
+/// {
+///   "columns": {
+///     "stage": { "levels": ["low", "medium", "high"], "ordered": true },
+///     "condition": { "reference": "control" },
+///     "cluster": { "categorical": true, "labels": { "0": "Naive CD4" } },
+///     "age": { "dtype": "integer" }
+///   }
+/// }
+/// ```
+///
+/// Columns that are not listed are inferred as usual.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableSpec {
+    pub columns: BTreeMap<String, ColumnSpec>,
+}
+
+impl TableSpec {
+    /// Read a spec file.
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| DataTableError::io(path, e))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|source| DataTableError::Json { path: Some(path.to_path_buf()), source })
+    }
+
+    /// Add the settings to `options` - a column spec replaces flags given for the same column.
+    /// Factor settings on a column declared as numeric, boolean or date are an error; whether the
+    /// columns exist is checked against the header while parsing.
+    pub fn apply(&self, options: &mut ParseOptions) -> Result<()> {
+        for (name, spec) in &self.columns {
+            if !spec.is_factor() {
+                if let Some(dtype) = spec.dtype {
+                    options.dtypes.insert(name.clone(), dtype);
+                }
+                continue;
+            }
+            if let Some(dtype) = spec.dtype.filter(|d| !matches!(d, Dtype::Factor | Dtype::FreeText)) {
+                bail!(InvalidArgument, "Spec of column '{}': levels, reference and labels need a factor, not dtype {}", name, dtype);
+            }
+            let factor_spec = FactorSpec {
+                ordered: spec.ordered,
+                levels: spec.levels.clone().unwrap_or_default(),
+                other: spec.other.clone(),
+                reference: spec.reference.clone(),
+                ..options.factor_specs.get(name).cloned().unwrap_or_default()
+            };
+            options.dtypes.remove(name);
+            options.categorical.insert(name.clone());
+            options.factor_specs.insert(name.clone(), factor_spec);
+        }
+        Ok(())
+    }
+
+    /// The display labels of all columns - for `SurvivalData::relabel_levels()` after parsing.
+    pub fn labels(&self) -> LevelLabels {
+        self.columns
+            .iter()
+            .filter_map(|(name, spec)| {
+                let labels = spec.labels.as_ref()?;
+                Some((name.clone(), labels.iter().map(|(l, v)| (l.clone(), v.clone())).collect()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::SurvivalData;
+
+    fn spec(json: &str) -> TableSpec {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_apply_spec() -> Result<()> {
+        let spec = spec(r#"{"columns": {
+            "stage": {"levels": ["low", "high"], "ordered": true},
+            "condition": {"reference": "control"},
+            "cluster": {"categorical": true, "labels": {"0": "Naive CD4"}},
+            "age": {"dtype": "float"}
+        }}"#);
+        let mut options = ParseOptions::default();
+        spec.apply(&mut options)?;
+        assert_eq!(options.dtypes.get("age"), Some(&Dtype::Float));
+        assert!(options.categorical.contains("cluster"));
+        assert_eq!(options.factor_specs["stage"], FactorSpec::ordered(vec!["low".into(), "high".into()]));
+
+        let tsv = "cluster\tstage\tcondition\tage\n0\thigh\ttreated\t42\n1\tlow\tcontrol\t7\n";
+        let mut data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        data.relabel_levels(&spec.labels())?;
+        assert_eq!(data.column("age").unwrap().dtype, Dtype::Float, "declared, not inferred");
+        assert_eq!(data.factors["stage"].get_levels(), ["low", "high"]);
+        assert_eq!(data.factors["condition"].get_levels(), ["control", "treated"]);
+        assert_eq!(data.factors["cluster"].label("0"), "Naive CD4");
+        Ok(())
+    }
+
+    #[test]
+    fn test_spec_conflicts() {
+        let mut options = ParseOptions::default();
+        let err = spec(r#"{"columns": {"age": {"dtype": "integer", "reference": "1"}}}"#).apply(&mut options).unwrap_err();
+        assert!(matches!(err, DataTableError::InvalidArgument(_)), "{err}");
+
+        let mut options = ParseOptions::default();
+        spec(r#"{"columns": {"age": {"dtype": "integer"}, "sex": {"levels": ["m", "f"]}}}"#).apply(&mut options).unwrap();
+        let err = SurvivalData::from_reader_with_options(Cursor::new("age\tsex\n42\tm\nold\tf\n"), &options).unwrap_err();
+        assert!(err.to_string().contains("line 3: 'old' is no integer value"), "{err}");
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, "age\tsex\n42\tm\nold\tf\n").unwrap();
+        let err = SurvivalData::from_file_streaming(&input, &options).unwrap_err();
+        assert!(err.to_string().contains("line 3: 'old' is no integer value"), "streaming: {err}");
+        let err = SurvivalData::from_reader_with_options(Cursor::new("age\tsex\n42\tx\n"), &options).unwrap_err();
+        assert!(err.to_string().contains("Value 'x' in column 'sex'"), "{err}");
+        let err = SurvivalData::from_reader_with_options(Cursor::new("years\tsex\n42\tm\n"), &options).unwrap_err();
+        assert!(matches!(err, DataTableError::UnknownColumns { .. }), "validated against the header: {err}");
+
+        assert!(serde_json::from_str::<TableSpec>(r#"{"columns": {"age": {"type": "integer"}}}"#).is_err(), "typo");
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::column::{Column, Dtype};
+use crate::error::{DataTableError, Result};
+use crate::factor::FactorJson;
+use crate::factor_map::{FactorMap, JsonLayout, Provenance, SCHEMA_VERSION};
+
+/// The name of the index file `FactorMap::write_split()` writes next to the column files.
+pub const SPLIT_INDEX: &str = "index.json";
+
+/// One column file of a split output (see `SplitIndex`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitEntry {
+    /// The column name as in the table
+    pub column: String,
+    pub dtype: Dtype,
+    /// The file name in the output directory - the column name made safe for a file system
+    pub file: String,
+}
+
+/// The index of a split output: one entry per column, in column order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitIndex {
+    pub schema_version: String,
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Provenance>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n_rows: Option<usize>,
+    pub columns: Vec<SplitEntry>,
+}
+
+/// The file of one column: its type and summary and - for factors - the levels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnFile {
+    pub schema_version: String,
+    pub column: Column,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factor: Option<FactorJson>,
+}
+
+/// A file stem for a column name: ASCII letters, digits, `.`, `-` and `_` are kept, any other
+/// character becomes `_` (`CD4/CD8 ratio` -> `CD4_CD8_ratio`). Names without a safe character
+/// left (`..`, empty) become `_`.
+pub fn safe_file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    if stem.chars().all(|c| c == '.') { "_".to_string() } else { stem }
+}
+
+impl FactorMap {
+    /// The file name of every column for `write_split()`, in column order. Names that end up
+    /// the same (also when differing in case only, for case-insensitive file systems) get
+    /// a `_2`, `_3`, ... suffix in column order, so the result is the same on every run.
+    pub fn split_file_names(&self) -> Vec<(String, String)> {
+        let mut taken: HashSet<String> = [SPLIT_INDEX.to_ascii_lowercase()].into();
+        self.split_columns()
+            .into_iter()
+            .map(|(column, _)| {
+                let stem = safe_file_stem(&column.name);
+                let file = (1..)
+                    .map(|i| if i == 1 { format!("{stem}.json") } else { format!("{stem}_{i}.json") })
+                    .find(|file| taken.insert(file.to_ascii_lowercase()))
+                    .expect("an unused suffix exists");
+                (column.name, file)
+            })
+            .collect()
+    }
+
+    /// Write one small JSON per column (`ColumnFile`) into the directory `dir` - created if
+    /// needed - and the `SplitIndex` listing them as `index.json`, all in `layout`.
+    pub fn write_split<P: AsRef<Path>>(&self, dir: P, layout: JsonLayout) -> Result<SplitIndex> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(|e| DataTableError::io(dir, e))?;
+        let mut index = SplitIndex { schema_version: SCHEMA_VERSION.to_string(), meta: self.meta.clone(), n_rows: self.n_rows, columns: Vec::new() };
+        for ((column, factor), (_, file)) in self.split_columns().into_iter().zip(self.split_file_names()) {
+            index.columns.push(SplitEntry { column: column.name.clone(), dtype: column.dtype, file: file.clone() });
+            let content = ColumnFile { schema_version: SCHEMA_VERSION.to_string(), column, factor };
+            layout.write_file(dir.join(file), &content)?;
+        }
+        layout.write_file(dir.join(SPLIT_INDEX), &index)?;
+        Ok(index)
+    }
+
+    /// Every column with its factor - a factor without a column entry (a legacy file) as a factor column.
+    fn split_columns(&self) -> Vec<(Column, Option<FactorJson>)> {
+        let mut columns: Vec<(Column, Option<FactorJson>)> =
+            self.columns.iter().map(|c| (c.clone(), self.get(&c.name).cloned())).collect();
+        for factor in self.factors.iter().filter(|f| self.column(&f.column).is_none()) {
+            columns.push((Column::new(&factor.column, Dtype::Factor), Some(factor.clone())));
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use crate::SurvivalData;
+
+    #[test]
+    fn test_safe_file_stem() {
+        assert_eq!(safe_file_stem("CD4/CD8 ratio"), "CD4_CD8_ratio");
+        assert_eq!(safe_file_stem("percent.mt"), "percent.mt");
+        assert_eq!(safe_file_stem("Köln"), "K_ln");
+        assert_eq!(safe_file_stem(".."), "_");
+        assert_eq!(safe_file_stem(""), "_");
+    }
+
+    #[test]
+    fn test_write_split() -> Result<()> {
+        let tsv = "cell type\tcell/type\tCell_type\tindex\tnCount\nT\tx\ta\t1\t10\nB\ty\tb\t2\t20\n";
+        let map = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?.compute_factors();
+        let files: Vec<String> = map.split_file_names().into_iter().map(|(_, file)| file).collect();
+        assert_eq!(files, vec!["cell_type.json", "cell_type_2.json", "Cell_type_3.json", "index_2.json", "nCount.json"]);
+
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("split");
+        let index = map.write_split(&out, JsonLayout::Pretty)?;
+        let read: SplitIndex = serde_json::from_str(&fs::read_to_string(out.join(SPLIT_INDEX))?)?;
+        assert_eq!(read, index);
+        assert_eq!(read.columns[1], SplitEntry { column: "cell/type".into(), dtype: Dtype::Factor, file: "cell_type_2.json".into() });
+
+        let cell: ColumnFile = serde_json::from_str(&fs::read_to_string(out.join("cell_type.json"))?)?;
+        assert_eq!(cell.column.name, "cell type");
+        assert_eq!(cell.factor.unwrap().levels, vec!["T", "B"]);
+        let counts: ColumnFile = serde_json::from_str(&fs::read_to_string(out.join("nCount.json"))?)?;
+        assert!(counts.factor.is_none());
+        assert_eq!(counts.column.summary.unwrap().max, Some(20.0));
+        Ok(())
+    }
+}
@@ -0,0 +1,915 @@
+use std::collections::HashMap;
+use crate::error::{bail, Result};
+use serde::Serialize;
+use crate::report::DropReason;
+use crate::SurvivalData;
+
+/// The time and event (censoring) columns that make a table a survival table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurvivalColumns {
+    /// Numeric follow up time
+    pub time: String,
+    /// Binary event indicator - `1`/`TRUE` for an event, `0`/`FALSE` for a censored observation
+    pub event: String,
+    /// The value of `event` that means an event - all others are censored (see `with_survival_event()`)
+    pub event_token: Option<String>,
+    /// Rows without a time or an event value when the pair was declared
+    pub dropped: usize,
+}
+
+/// One step of a Kaplan-Meier curve - a distinct event time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KaplanMeierStep {
+    pub time: f64,
+    /// Observations still followed just before `time`
+    pub at_risk: usize,
+    /// Events at `time`
+    pub events: usize,
+    /// Estimated survival probability right after `time`
+    pub survival: f64,
+    /// Standard error of `survival` (Greenwood's formula)
+    pub std_err: f64,
+    /// Pointwise confidence interval of `survival` - see `KaplanMeier::level` and `transform`
+    pub lower: f64,
+    pub upper: f64,
+    /// The summed weight of the observations at risk - a weighted curve only (`KaplanMeier::fit_weighted()`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weighted_at_risk: Option<f64>,
+    /// The summed weight of the events at `time` - a weighted curve only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weighted_events: Option<f64>,
+}
+
+/// How the pointwise confidence intervals of a Kaplan-Meier curve are formed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CiTransform {
+    /// `survival ± z * std_err`, cut to [0, 1]
+    #[default]
+    Plain,
+    /// Symmetric on the log(-log(survival)) scale - within [0, 1] by construction and
+    /// closer to the nominal level for small risk sets (R's `conf.type = "log-log"`)
+    LogLog,
+}
+
+/// The Kaplan-Meier estimate of a survival function as step function.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KaplanMeier {
+    pub steps: Vec<KaplanMeierStep>,
+    /// The confidence level of the step intervals (0.95 unless set with `with_confidence()`)
+    pub level: f64,
+    pub transform: CiTransform,
+}
+
+impl Default for KaplanMeier {
+    fn default() -> Self {
+        KaplanMeier { steps: Vec::new(), level: 0.95, transform: CiTransform::Plain }
+    }
+}
+
+impl KaplanMeier {
+    /// Estimate the curve from (time, event) pairs, with 95% plain confidence intervals.
+    /// Censored observations (`false`) leave the risk set without lowering the curve.
+    pub fn fit(pairs: &[(f64, bool)]) -> Self {
+        let observations: Vec<(f64, bool, f64)> = pairs.iter().map(|&(time, event)| (time, event, 1.0)).collect();
+        Self::fit_observations(observations, false)
+    }
+
+    /// Estimate the curve from (time, event, weight) observations - e.g. the number of cells
+    /// of a pseudo-bulk sample or an inverse probability weight: the events and the risk set
+    /// are summed in weights, `at_risk` and `events` still count the observations. The
+    /// variance is Greenwood's formula on the weighted sums. Observations with a weight that
+    /// is missing, zero or negative are left out.
+    pub fn fit_weighted(observations: &[(f64, bool, f64)]) -> Self {
+        let observations = observations.iter().copied().filter(|&(_, _, weight)| weight > 0.0).collect();
+        Self::fit_observations(observations, true)
+    }
+
+    fn fit_observations(mut sorted: Vec<(f64, bool, f64)>, weighted: bool) -> Self {
+        sorted.retain(|(time, _, _)| !time.is_nan());
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut steps = Vec::new();
+        let mut at_risk = sorted.len();
+        let mut weight_at_risk: f64 = sorted.iter().map(|(_, _, weight)| weight).sum();
+        let mut survival = 1.0;
+        for group in sorted.chunk_by(|a, b| a.0 == b.0) {
+            let events = group.iter().filter(|(_, event, _)| *event).count();
+            if events > 0 {
+                let weight_events: f64 = group.iter().filter(|(_, event, _)| *event).map(|(_, _, weight)| weight).sum();
+                survival *= 1.0 - weight_events / weight_at_risk;
+                steps.push(KaplanMeierStep {
+                    time: group[0].0,
+                    at_risk,
+                    events,
+                    survival,
+                    std_err: 0.0,
+                    lower: 0.0,
+                    upper: 0.0,
+                    weighted_at_risk: weighted.then_some(weight_at_risk),
+                    weighted_events: weighted.then_some(weight_events),
+                });
+            }
+            at_risk -= group.len();
+            weight_at_risk -= group.iter().map(|(_, _, weight)| weight).sum::<f64>();
+        }
+        let mut km = KaplanMeier { steps, ..Default::default() };
+        km.set_intervals();
+        km
+    }
+
+    /// The curve with confidence intervals at `level` (e.g. 0.9) formed by `transform`.
+    pub fn with_confidence(mut self, level: f64, transform: CiTransform) -> Result<Self> {
+        if !(level > 0.0 && level < 1.0) {
+            bail!(InvalidArgument, "The confidence level must be between 0 and 1 (exclusive), not {}", level);
+        }
+        self.level = level;
+        self.transform = transform;
+        self.set_intervals();
+        Ok(self)
+    }
+
+    /// Greenwood's variance, Var(S(t)) = S(t)² Σ d / (n (n - d)) over the event times up to t,
+    /// and the bounds from it. Once the curve reaches 0 (all at risk had the event) the sum is
+    /// undefined; the estimate is then certain: standard error 0, bounds 0.
+    fn set_intervals(&mut self) {
+        let z = normal_quantile(0.5 + self.level / 2.0);
+        let mut greenwood = 0.0;
+        for step in &mut self.steps {
+            if step.survival <= 0.0 {
+                (step.std_err, step.lower, step.upper) = (0.0, 0.0, 0.0);
+                continue;
+            }
+            let n = step.weighted_at_risk.unwrap_or(step.at_risk as f64);
+            let d = step.weighted_events.unwrap_or(step.events as f64);
+            greenwood += d / (n * (n - d));
+            let s = step.survival;
+            step.std_err = s * greenwood.sqrt();
+            (step.lower, step.upper) = match self.transform {
+                CiTransform::Plain => ((s - z * step.std_err).max(0.0), (s + z * step.std_err).min(1.0)),
+                CiTransform::LogLog => {
+                    let spread = (z * greenwood.sqrt() / s.ln()).exp();
+                    (s.powf(1.0 / spread), s.powf(spread))
+                }
+            };
+        }
+    }
+
+    /// The estimated survival probability at `time` (1.0 before the first event).
+    pub fn survival_at(&self, time: f64) -> f64 {
+        self.steps
+            .iter()
+            .take_while(|step| step.time <= time)
+            .last()
+            .map_or(1.0, |step| step.survival)
+    }
+}
+
+/// Values of a competing risks event column meaning "censored" (compared ignoring ASCII case) -
+/// every other value is an event type.
+pub const CENSORED_VALUES: [&str; 3] = ["0", "censored", "FALSE"];
+
+/// One step of a cumulative incidence curve - a distinct time with an event of interest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CumulativeIncidenceStep {
+    pub time: f64,
+    /// Observations still followed just before `time`
+    pub at_risk: usize,
+    /// Events of interest at `time`
+    pub events: usize,
+    /// Competing events at `time`
+    pub competing: usize,
+    /// Estimated probability of the event of interest up to and including `time`
+    pub incidence: f64,
+}
+
+/// The cumulative incidence function of one event type in the presence of competing events
+/// (Aalen-Johansen estimator, see `SurvivalData::cumulative_incidence()`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CumulativeIncidence {
+    /// The event of interest
+    pub event: String,
+    pub steps: Vec<CumulativeIncidenceStep>,
+}
+
+impl CumulativeIncidence {
+    /// Estimate the curve from (time, event type) pairs - `None` is a censored observation.
+    ///
+    /// At every time the incidence grows by the events of interest over the risk set, weighted
+    /// with the probability to be free of any event just before (the all cause Kaplan-Meier).
+    /// Competing events and censored observations leave the risk set; only competing events
+    /// lower the event free probability.
+    pub fn fit<S: AsRef<str>>(observations: &[(f64, Option<S>)], event: &str) -> Self {
+        let mut sorted: Vec<(f64, Option<bool>)> = observations
+            .iter()
+            .filter(|(t, _)| !t.is_nan())
+            .map(|(t, cause)| (*t, cause.as_ref().map(|c| c.as_ref() == event)))
+            .collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut steps = Vec::new();
+        let mut at_risk = sorted.len();
+        let (mut event_free, mut incidence) = (1.0, 0.0);
+        for group in sorted.chunk_by(|a, b| a.0 == b.0) {
+            let events = group.iter().filter(|(_, cause)| *cause == Some(true)).count();
+            let competing = group.iter().filter(|(_, cause)| *cause == Some(false)).count();
+            let n = at_risk as f64;
+            if events > 0 {
+                incidence += event_free * events as f64 / n;
+                steps.push(CumulativeIncidenceStep { time: group[0].0, at_risk, events, competing, incidence });
+            }
+            event_free *= 1.0 - (events + competing) as f64 / n;
+            at_risk -= group.len();
+        }
+        CumulativeIncidence { event: event.to_string(), steps }
+    }
+
+    /// The estimated incidence at `time` (0.0 before the first event of interest).
+    pub fn incidence_at(&self, time: f64) -> f64 {
+        self.steps
+            .iter()
+            .take_while(|step| step.time <= time)
+            .last()
+            .map_or(0.0, |step| step.incidence)
+    }
+}
+
+/// The (time, event) pairs per level of a factor.
+type Strata = Vec<(String, Vec<(f64, bool)>)>;
+
+/// Observed and expected events of one group of a log-rank test.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogRankGroup {
+    pub level: String,
+    /// Observations of this group
+    pub n: usize,
+    /// Events of this group
+    pub observed: usize,
+    /// Events expected if all groups had the same survival
+    pub expected: f64,
+}
+
+/// The log-rank test comparing the survival of the levels of a factor (see `SurvivalData::log_rank_test()`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogRankResult {
+    pub chi_squared: f64,
+    /// Number of groups at risk at an event time minus one
+    pub df: usize,
+    pub p_value: f64,
+    /// In level order
+    pub groups: Vec<LogRankGroup>,
+}
+
+impl LogRankResult {
+    /// Compare the (time, event) pairs of the groups.
+    ///
+    /// Groups never at risk at an event time carry no information and do not count
+    /// towards `df`; with less than two informative groups the statistic is 0 and the p-value 1.
+    pub fn fit(groups: &[(String, Vec<(f64, bool)>)]) -> Self {
+        let k = groups.len();
+        let mut all: Vec<(f64, bool, usize)> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, (_, pairs))| pairs.iter().filter(|(t, _)| !t.is_nan()).map(move |&(t, e)| (t, e, g)))
+            .collect();
+        all.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut n = vec![0_usize; k];
+        for &(_, _, g) in &all {
+            n[g] += 1;
+        }
+        let mut at_risk = n.clone();
+        let mut observed = vec![0_usize; k];
+        let mut expected = vec![0.0; k];
+        let mut variance = vec![vec![0.0; k]; k];
+        for time in all.chunk_by(|a, b| a.0 == b.0) {
+            let n_t: usize = at_risk.iter().sum();
+            let mut events = vec![0_usize; k];
+            for &(_, event, g) in time {
+                if event {
+                    events[g] += 1;
+                }
+            }
+            let d_t: usize = events.iter().sum();
+            if d_t > 0 {
+                let (n_f, d_f) = (n_t as f64, d_t as f64);
+                // hypergeometric variance, 0 if everybody at risk had the event
+                let spread = if n_t > 1 { d_f * (n_f - d_f) / (n_f - 1.0) } else { 0.0 };
+                for g in 0..k {
+                    observed[g] += events[g];
+                    let share = at_risk[g] as f64 / n_f;
+                    expected[g] += d_f * share;
+                    for h in 0..k {
+                        let other = at_risk[h] as f64 / n_f;
+                        let same = if g == h { 1.0 } else { 0.0 };
+                        variance[g][h] += spread * share * (same - other);
+                    }
+                }
+            }
+            for &(_, _, g) in time {
+                at_risk[g] -= 1;
+            }
+        }
+
+        // the statistic uses all but one of the informative groups
+        let informative: Vec<usize> = (0..k).filter(|&g| variance[g][g] > 0.0).collect();
+        let (chi_squared, df) = if informative.len() < 2 {
+            (0.0, 0)
+        } else {
+            let used = &informative[..informative.len() - 1];
+            let diff: Vec<f64> = used.iter().map(|&g| observed[g] as f64 - expected[g]).collect();
+            let matrix: Vec<Vec<f64>> = used.iter().map(|&g| used.iter().map(|&h| variance[g][h]).collect()).collect();
+            match solve(matrix, diff.clone()) {
+                Some(x) => (diff.iter().zip(&x).map(|(d, x)| d * x).sum(), used.len()),
+                None => (0.0, 0),
+            }
+        };
+        LogRankResult {
+            chi_squared,
+            df,
+            p_value: chi_squared_sf(chi_squared, df),
+            groups: groups
+                .iter()
+                .enumerate()
+                .map(|(g, (level, _))| LogRankGroup { level: level.clone(), n: n[g], observed: observed[g], expected: expected[g] })
+                .collect(),
+        }
+    }
+}
+
+/// Solve `a x = b` by Gaussian elimination (`None` if `a` is singular).
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot) in a[row].iter_mut().zip(&pivot_row).skip(col) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|c| a[row][c] * x[c]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// The `p` quantile of the standard normal distribution for p in (0.5, 1) - by bisection on
+/// P(Z² > z²), the chi-squared tail with one degree of freedom.
+fn normal_quantile(p: f64) -> f64 {
+    let tail = 2.0 * (1.0 - p);
+    let (mut low, mut high) = (0.0, 40.0_f64);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if chi_squared_sf(mid * mid, 1) > tail {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// P(X > x) of a chi-squared distribution with `df` degrees of freedom (1.0 for `df` 0).
+fn chi_squared_sf(x: f64, df: usize) -> f64 {
+    if df == 0 || x <= 0.0 {
+        return 1.0;
+    }
+    upper_gamma(df as f64 / 2.0, x / 2.0)
+}
+
+/// The regularized upper incomplete gamma function Q(a, x)
+/// (series below `a + 1`, continued fraction above - as in Numerical Recipes).
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    let ln_front = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut ap) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..500 {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * ln_front.exp()
+    } else {
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        ln_front.exp() * h
+    }
+}
+
+/// ln Γ(x) for x > 0 (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COEF: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for (i, c) in COEF.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Read an event label: 0/1 or TRUE/FALSE (ignoring case).
+fn parse_event(label: &str) -> Option<bool> {
+    match label.trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        l if l.eq_ignore_ascii_case("true") => Some(true),
+        l if l.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+impl SurvivalData {
+    /// Declare the time and the event column of the table.
+    ///
+    /// `time_col` has to be numeric and `event_col` binary: the numbers 0/1 or a factor
+    /// with the levels 0/1 or TRUE/FALSE. Rows with a missing time or event are not part of
+    /// `survival_pairs()`; their number is stored in `SurvivalColumns::dropped`.
+    pub fn with_survival(self, time_col: &str, event_col: &str) -> Result<Self> {
+        self.declare_survival(time_col, event_col, None)
+    }
+
+    /// Declare the time and the event column of a table coding the event with a word like
+    /// `Dead` or `Deceased`: rows with `event_token` (exact match) had the event, any other
+    /// value is censored and missing values are dropped.
+    pub fn with_survival_event(self, time_col: &str, event_col: &str, event_token: &str) -> Result<Self> {
+        self.declare_survival(time_col, event_col, Some(event_token))
+    }
+
+    /// Is `time_col` a numeric column?
+    fn check_time_column(&self, time_col: &str) -> Result<()> {
+        match self.column(time_col) {
+            None => bail!(Column, "Survival time column '{}' not found", time_col),
+            Some(column) if !column.dtype.is_numeric() || self.factors.contains_key(time_col) => {
+                bail!(Column, "Survival time column '{}' must be numeric, but it is {:?}", time_col, column.dtype)
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn declare_survival(mut self, time_col: &str, event_col: &str, event_token: Option<&str>) -> Result<Self> {
+        self.check_time_column(time_col)?;
+        if self.column(event_col).is_none() {
+            bail!(Column, "Survival event column '{}' not found", event_col);
+        }
+        let mut values: Vec<String> = Vec::new();
+        for value in self.as_vec_f64(event_col) {
+            let label = self.event_label(event_col, value);
+            if !value.is_nan() && !values.contains(&label) {
+                values.push(label);
+            }
+        }
+        match event_token {
+            Some(token) if !values.iter().any(|v| v == token) => bail!(
+                InvalidData,
+                "Survival event token '{}' is none of the values of '{}': {}",
+                token,
+                event_col,
+                values.join(", ")
+            ),
+            Some(_) => {}
+            None => {
+                if let Some(value) = values.iter().find(|v| parse_event(v).is_none()) {
+                    bail!(
+                        InvalidData,
+                        "Survival event column '{}' must be binary (0/1 or TRUE/FALSE), found '{}' ({} distinct values) - use with_survival_event() to name the event value",
+                        event_col,
+                        value,
+                        values.len()
+                    );
+                }
+            }
+        }
+
+        self.survival = Some(SurvivalColumns {
+            time: time_col.to_string(),
+            event: event_col.to_string(),
+            event_token: event_token.map(str::to_string),
+            dropped: 0,
+        });
+        let dropped = self.numeric_data.nrows() - self.survival_pairs().len();
+        if dropped > 0 {
+            log::warn!("{dropped} rows without '{time_col}' or '{event_col}' are not part of the survival data");
+        }
+        if let Some(survival) = self.survival.as_mut() {
+            survival.dropped = dropped;
+        }
+        self.report.set(DropReason::MissingSurvival, dropped);
+        Ok(self)
+    }
+
+    /// The (time, event) pairs of all rows having both values - in row order.
+    /// `event` is `true` for an event and `false` for a censored observation.
+    /// Empty if no survival columns have been declared.
+    pub fn survival_pairs(&self) -> Vec<(f64, bool)> {
+        let Some(survival) = &self.survival else {
+            return Vec::new();
+        };
+        self.as_vec_f64(&survival.time)
+            .into_iter()
+            .zip(self.as_vec_f64(&survival.event))
+            .filter(|(time, _)| !time.is_nan())
+            .filter_map(|(time, event)| self.event_of(survival, event).map(|e| (time, e)))
+            .collect()
+    }
+
+    /// The Kaplan-Meier estimate of the declared time/event columns
+    /// (an empty curve if no survival columns have been declared).
+    pub fn kaplan_meier(&self) -> KaplanMeier {
+        KaplanMeier::fit(&self.survival_pairs())
+    }
+
+    /// The Kaplan-Meier estimate with every row weighted by the numeric column `weight_col`
+    /// (see `KaplanMeier::fit_weighted()`). The event column is read like in `with_survival()`
+    /// (with the event token of `with_survival_event()` if it declared `event_col`). Rows
+    /// without time or event and rows with a missing or zero weight are left out; a negative
+    /// or infinite weight is an error.
+    pub fn kaplan_meier_weighted(&self, time_col: &str, event_col: &str, weight_col: &str) -> Result<KaplanMeier> {
+        self.check_time_column(time_col)?;
+        if self.column(event_col).is_none() {
+            bail!(Column, "Survival event column '{}' not found", event_col);
+        }
+        match self.column(weight_col) {
+            None => bail!(Column, "Weight column '{}' not found", weight_col),
+            Some(column) if !column.dtype.is_numeric() || self.factors.contains_key(weight_col) => {
+                bail!(Column, "Weight column '{}' must be numeric, but it is {:?}", weight_col, column.dtype)
+            }
+            Some(_) => {}
+        }
+        let event_token = self.survival.as_ref().filter(|s| s.event == event_col).and_then(|s| s.event_token.clone());
+        let survival = SurvivalColumns { time: time_col.to_string(), event: event_col.to_string(), event_token, dropped: 0 };
+
+        let mut observations = Vec::new();
+        let rows = self.as_vec_f64(time_col).into_iter().zip(self.as_vec_f64(event_col)).zip(self.as_vec_f64(weight_col));
+        for (row, ((time, event), weight)) in rows.enumerate() {
+            if weight < 0.0 || weight.is_infinite() {
+                bail!(InvalidData, "Weight column '{}' has the weight {} in data row {} - weights must be finite and not negative", weight_col, weight, row + 1);
+            }
+            if time.is_nan() || weight.is_nan() || weight == 0.0 {
+                continue;
+            }
+            if let Some(event) = self.event_of(&survival, event) {
+                observations.push((time, event, weight));
+            }
+        }
+        let dropped = self.n_rows() - observations.len();
+        if dropped > 0 {
+            log::warn!("{dropped} rows without '{time_col}', '{event_col}' or a weight > 0 in '{weight_col}' are not part of the weighted curve");
+        }
+        Ok(KaplanMeier::fit_weighted(&observations))
+    }
+
+    /// One Kaplan-Meier curve per level of the categorical column `factor_col`.
+    /// Rows without a `factor_col` value are left out.
+    pub fn kaplan_meier_by(&self, factor_col: &str) -> Result<HashMap<String, KaplanMeier>> {
+        let groups = self.survival_groups(factor_col)?;
+        Ok(groups.into_iter().map(|(level, pairs)| (level, KaplanMeier::fit(&pairs))).collect())
+    }
+
+    /// The log-rank test of equal survival in all levels of the factor `by`:
+    /// observed and expected events per level, chi-squared statistic, degrees of freedom and p-value.
+    /// Rows without a `by` value are left out.
+    pub fn log_rank_test(&self, by: &str) -> Result<LogRankResult> {
+        Ok(LogRankResult::fit(&self.survival_groups(by)?))
+    }
+
+    /// The cumulative incidence of `event_of_interest` for a multi-valued event column like
+    /// `0` censored, `1` relapse, `2` death: the other event types compete with it.
+    /// Values in `CENSORED_VALUES` are censored; rows without time or event are left out.
+    ///
+    /// Unlike one minus a Kaplan-Meier curve treating competing events as censored, the
+    /// estimate does not exceed the real probability of the event.
+    pub fn cumulative_incidence(&self, time_col: &str, event_col: &str, event_of_interest: &str) -> Result<CumulativeIncidence> {
+        self.check_time_column(time_col)?;
+        if self.column(event_col).is_none() {
+            bail!(Column, "Survival event column '{}' not found", event_col);
+        }
+        let censored = |label: &str| CENSORED_VALUES.iter().any(|c| c.eq_ignore_ascii_case(label));
+        if censored(event_of_interest) {
+            bail!(InvalidArgument, "The event of interest '{}' means censored", event_of_interest);
+        }
+        let mut observations: Vec<(f64, Option<String>)> = Vec::new();
+        let mut types: Vec<String> = Vec::new();
+        for (time, value) in self.as_vec_f64(time_col).into_iter().zip(self.as_vec_f64(event_col)) {
+            if time.is_nan() || value.is_nan() {
+                continue;
+            }
+            let label = self.event_label(event_col, value);
+            if censored(&label) {
+                observations.push((time, None));
+                continue;
+            }
+            if !types.contains(&label) {
+                types.push(label.clone());
+            }
+            observations.push((time, Some(label)));
+        }
+        if !types.iter().any(|t| t == event_of_interest) {
+            bail!(
+                InvalidData,
+                "The event of interest '{}' is none of the event types of '{}': {}",
+                event_of_interest,
+                event_col,
+                types.join(", ")
+            );
+        }
+        Ok(CumulativeIncidence::fit(&observations, event_of_interest))
+    }
+
+    /// The (time, event) pairs per level of `factor_col` - in level order, levels without rows left out.
+    fn survival_groups(&self, factor_col: &str) -> Result<Strata> {
+        let Some(survival) = &self.survival else {
+            bail!(Column, "No survival columns declared - use with_survival() first");
+        };
+        let Some(factor) = self.factors.get(factor_col) else {
+            bail!(Column, "Stratification column '{}' is not a factor", factor_col);
+        };
+        let mut groups: HashMap<String, Vec<(f64, bool)>> = HashMap::new();
+        let times = self.as_vec_f64(&survival.time);
+        let events = self.as_vec_f64(&survival.event);
+        for ((time, event), group) in times.into_iter().zip(events).zip(self.as_vec_f64(factor_col)) {
+            if time.is_nan() || group.is_nan() {
+                continue;
+            }
+            if let Some(event) = self.event_of(survival, event) {
+                groups.entry(factor.get_string(group)).or_default().push((time, event));
+            }
+        }
+        Ok(factor
+            .get_levels()
+            .iter()
+            .filter_map(|level| groups.remove(level).map(|pairs| (level.clone(), pairs)))
+            .collect())
+    }
+
+    /// The text behind a value of the event column.
+    fn event_label(&self, event_col: &str, value: f64) -> String {
+        match self.factors.get(event_col) {
+            Some(factor) => factor.get_string(value),
+            None => value.to_string(),
+        }
+    }
+
+    /// Decode one value of the event column (`None` for missing or non binary values).
+    fn event_of(&self, survival: &SurvivalColumns, value: f64) -> Option<bool> {
+        if value.is_nan() {
+            return None;
+        }
+        let label = self.event_label(&survival.event, value);
+        match &survival.event_token {
+            Some(token) => Some(&label == token),
+            None => parse_event(&label),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_survival_pairs() -> Result<()> {
+        let tsv = "time\tstatus\tdead\tgroup\n5\t1\tTRUE\ta\n8.5\t0\tFALSE\tb\nNA\t1\tTRUE\ta\n3\tNA\tfalse\tb\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let numeric = data.clone().with_survival("time", "status")?;
+        assert_eq!(numeric.survival_pairs(), vec![(5.0, true), (8.5, false)]);
+        assert_eq!(numeric.survival.as_ref().unwrap().dropped, 2);
+
+        let labels = data.clone().with_survival("time", "dead")?;
+        assert_eq!(labels.survival_pairs(), vec![(5.0, true), (8.5, false), (3.0, false)]);
+        assert_eq!(labels.survival.as_ref().unwrap().dropped, 1);
+        assert!(data.parse_report().is_clean());
+        assert_eq!(numeric.parse_report().to_string(), "4 rows read, 2 kept, 2 dropped (2 no survival time or event)");
+        // declaring again replaces the count
+        let report = numeric.with_survival("time", "dead")?.parse_report().clone();
+        assert_eq!((report.rows_kept, report.dropped(DropReason::MissingSurvival)), (3, 1));
+
+        assert!(data.survival_pairs().is_empty(), "nothing declared");
+        let err = data.clone().with_survival("group", "status").unwrap_err();
+        assert!(err.to_string().contains("must be numeric"), "{err}");
+        let err = data.clone().with_survival("time", "group").unwrap_err();
+        assert!(err.to_string().contains("must be binary"), "{err}");
+        assert!(data.with_survival("time", "missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_survival_event_token() -> Result<()> {
+        let tsv = "time\tstatus\tcode\n5\tDead\t2\n8\tAlive\t1\n3\tNA\t1\n4\tLost\t2\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let err = data.clone().with_survival("time", "status").unwrap_err();
+        assert!(err.to_string().contains("found 'Dead' (3 distinct values) - use with_survival_event()"), "{err}");
+        let words = data.clone().with_survival_event("time", "status", "Dead")?;
+        assert_eq!(words.survival_pairs(), vec![(5.0, true), (8.0, false), (4.0, false)]);
+        assert_eq!(words.survival.as_ref().unwrap().dropped, 1);
+        assert_eq!(words.survival.unwrap().event_token.as_deref(), Some("Dead"));
+
+        // R's survival coding: 1 censored, 2 dead
+        let codes = data.clone().with_survival_event("time", "code", "2")?;
+        assert_eq!(codes.survival_pairs(), vec![(5.0, true), (8.0, false), (3.0, false), (4.0, true)]);
+
+        let err = data.with_survival_event("time", "status", "dead").unwrap_err();
+        assert_eq!(err.to_string(), "Survival event token 'dead' is none of the values of 'status': Dead, Alive, Lost");
+        Ok(())
+    }
+
+    #[test]
+    fn test_kaplan_meier() {
+        // two events at 2, a censored observation at 3, an event at 5
+        let km = KaplanMeier::fit(&[(2.0, true), (3.0, false), (2.0, true), (5.0, true), (6.0, false)]);
+        let times: Vec<f64> = km.steps.iter().map(|s| s.time).collect();
+        assert_eq!(times, vec![2.0, 5.0], "censoring does not cause a step");
+        assert_eq!((km.steps[0].at_risk, km.steps[0].events), (5, 2));
+        assert!((km.steps[0].survival - 0.6).abs() < 1e-12);
+        // the censored observation left the risk set
+        assert_eq!((km.steps[1].at_risk, km.steps[1].events), (2, 1));
+        assert!((km.steps[1].survival - 0.3).abs() < 1e-12);
+        assert_eq!(km.survival_at(1.0), 1.0);
+        assert!((km.survival_at(4.0) - 0.6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_kaplan_meier_confidence() -> Result<()> {
+        assert!((normal_quantile(0.975) - 1.959963985).abs() < 1e-8);
+        let pairs = [(1.0, true), (2.0, false), (3.0, true), (4.0, true), (5.0, false), (6.0, true), (6.0, true)];
+        // at 3: S = 6/7 * 4/5, Greenwood sum 1/(7*6) + 1/(5*4)
+        let km = KaplanMeier::fit(&pairs);
+        assert_eq!((km.level, km.transform), (0.95, CiTransform::Plain));
+        let step = &km.steps[1];
+        assert!((step.survival - 0.6857142857).abs() < 1e-9);
+        assert!((step.std_err - 0.1862942).abs() < 1e-6);
+        assert!((step.lower - 0.3205843).abs() < 1e-6 && (step.upper - 1.0).abs() < 1e-12, "{step:?}");
+        // everyone left had the event: no NaN
+        let last = km.steps.last().unwrap();
+        assert_eq!((last.survival, last.std_err, last.lower, last.upper), (0.0, 0.0, 0.0, 0.0));
+
+        // 90% on the log-log scale: S^exp(∓z * sqrt(sum) / ln S)
+        let km = km.with_confidence(0.9, CiTransform::LogLog)?;
+        let step = &km.steps[1];
+        assert!((step.lower - 0.2913332).abs() < 1e-6 && (step.upper - 0.8909883).abs() < 1e-6, "{step:?}");
+        assert!(km.steps.iter().all(|s| (0.0..=1.0).contains(&s.lower) && s.lower <= s.survival && s.survival <= s.upper));
+        let err = km.with_confidence(95.0, CiTransform::Plain).unwrap_err();
+        assert_eq!(err.to_string(), "The confidence level must be between 0 and 1 (exclusive), not 95");
+        Ok(())
+    }
+
+    #[test]
+    fn test_kaplan_meier_weighted() -> Result<()> {
+        // weight 1 everywhere is the plain curve
+        let pairs = [(1.0, true), (2.0, false), (3.0, true), (4.0, true), (5.0, false), (6.0, true), (6.0, true)];
+        let plain = KaplanMeier::fit(&pairs);
+        let ones: Vec<(f64, bool, f64)> = pairs.iter().map(|&(t, e)| (t, e, 1.0)).collect();
+        let weighted = KaplanMeier::fit_weighted(&ones);
+        for (a, b) in plain.steps.iter().zip(&weighted.steps) {
+            assert!((a.survival - b.survival).abs() < 1e-12 && (a.std_err - b.std_err).abs() < 1e-12);
+            assert_eq!((b.weighted_at_risk, b.weighted_events), (Some(b.at_risk as f64), Some(b.events as f64)));
+        }
+        assert_eq!(plain.steps[0].weighted_at_risk, None);
+
+        // a weight of 3 counts like three rows
+        let km = KaplanMeier::fit_weighted(&[(1.0, true, 3.0), (2.0, true, 1.0), (4.0, false, 2.0), (5.0, true, 0.0), (6.0, true, f64::NAN)]);
+        let repeated = KaplanMeier::fit(&[(1.0, true), (1.0, true), (1.0, true), (2.0, true), (4.0, false), (4.0, false)]);
+        assert_eq!(km.steps.len(), 2, "zero and missing weights are left out");
+        assert_eq!((km.steps[0].at_risk, km.steps[0].events, km.steps[0].weighted_at_risk), (3, 1, Some(6.0)));
+        for (a, b) in repeated.steps.iter().zip(&km.steps) {
+            assert!((a.survival - b.survival).abs() < 1e-12 && (a.std_err - b.std_err).abs() < 1e-12, "{b:?}");
+        }
+        assert!((km.survival_at(2.0) - 1.0 / 3.0).abs() < 1e-12, "1 - 3/6, then 1 - 1/3");
+
+        let tsv = "time\tstatus\tcells\n1\tDead\t3\n2\tDead\t1\n4\tAlive\t2\n5\tDead\t0\n6\tDead\tNA\nNA\tDead\t4\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?.with_survival_event("time", "status", "Dead")?;
+        let km = data.kaplan_meier_weighted("time", "status", "cells")?;
+        assert_eq!(km.steps.iter().map(|s| s.survival).collect::<Vec<_>>(), vec![0.5, 0.5 * (1.0 - 1.0 / 3.0)]);
+        assert_eq!(data.kaplan_meier_weighted("time", "status", "level").unwrap_err().to_string(), "Weight column 'level' not found");
+
+        let negative = SurvivalData::from_reader(Cursor::new("time\tstatus\tw\n1\t1\t2\n2\t0\t-1\n"), b'\t', HashSet::new())?;
+        let err = negative.kaplan_meier_weighted("time", "status", "w").unwrap_err();
+        assert_eq!(err.to_string(), "Weight column 'w' has the weight -1 in data row 2 - weights must be finite and not negative");
+        Ok(())
+    }
+
+    #[test]
+    fn test_kaplan_meier_by() -> Result<()> {
+        let tsv = "time\tstatus\tarm\n1\t1\tA\n2\t0\tA\n3\t1\tA\n1\t0\tB\n4\t1\tB\n5\t1\tNA\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?
+            .with_survival("time", "status")?;
+
+        assert_eq!(data.kaplan_meier().steps.len(), 4);
+        let by_arm = data.kaplan_meier_by("arm")?;
+        assert_eq!(by_arm.len(), 2, "missing groups are left out");
+        let a = &by_arm["A"];
+        assert_eq!(a.steps.iter().map(|s| s.time).collect::<Vec<_>>(), vec![1.0, 3.0]);
+        assert!((a.survival_at(3.0) - 0.0).abs() < 1e-12);
+        assert!((by_arm["B"].survival_at(4.0) - 0.0).abs() < 1e-12);
+        assert_eq!(by_arm["B"].steps[0].at_risk, 1);
+        assert!(data.kaplan_meier_by("time").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_rank_test() -> Result<()> {
+        // arm A dies at 1, 2 and 3, arm B at 4, 5 and 6
+        let tsv = "time\tstatus\tarm\n1\t1\tA\n2\t1\tA\n3\t1\tA\n4\t1\tB\n5\t1\tB\n6\t1\tB\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?
+            .with_survival("time", "status")?;
+        let test = data.log_rank_test("arm")?;
+        assert_eq!(test.df, 1);
+        assert_eq!(test.groups.iter().map(|g| (g.level.as_str(), g.n, g.observed)).collect::<Vec<_>>(), vec![("A", 3, 3), ("B", 3, 3)]);
+        assert!((test.groups[0].expected - 1.15).abs() < 1e-12);
+        // (3 - 1.15)^2 / 0.6775, p-value 1 - pchisq(chi, 1) from R
+        assert!((test.chi_squared - 5.051660516605166).abs() < 1e-9);
+        assert!((test.p_value - 0.024602349953641786).abs() < 1e-9, "{}", test.p_value);
+
+        // a group without events and a group never at risk at an event time
+        let tsv = "time\tstatus\tarm\n1\t1\tA\n2\t1\tA\n3\t0\tB\n4\t0\tB\n0.5\t0\tC\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?
+            .with_survival("time", "status")?;
+        let test = data.log_rank_test("arm")?;
+        assert_eq!(test.df, 1, "C carries no information");
+        assert_eq!((test.groups[1].observed, test.groups[2].expected), (0, 0.0));
+        assert!(test.chi_squared.is_finite() && test.p_value < 1.0);
+
+        let none = LogRankResult::fit(&[("A".into(), vec![(1.0, false)]), ("B".into(), vec![(2.0, false)])]);
+        assert_eq!((none.chi_squared, none.df, none.p_value), (0.0, 0, 1.0));
+        assert!((chi_squared_sf(6.0, 2) - (-3.0_f64).exp()).abs() < 1e-12);
+        assert!((chi_squared_sf(0.5, 4) - 0.9735009788392561).abs() < 1e-9);
+        assert!(data.log_rank_test("time").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cumulative_incidence() -> Result<()> {
+        // 1 relapse, 2 death (competing), 0 censored
+        let tsv = "time\tstatus\tcause\n1\t1\trelapse\n2\t2\tdeath\n3\t0\tCensored\n4\t1\trelapse\n5\t1\trelapse\nNA\t1\trelapse\n6\tNA\tNA\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        let relapse = data.cumulative_incidence("time", "status", "1")?;
+        let steps: Vec<(f64, usize, usize)> = relapse.steps.iter().map(|s| (s.time, s.at_risk, s.events)).collect();
+        assert_eq!(steps, vec![(1.0, 5, 1), (4.0, 2, 1), (5.0, 1, 1)]);
+        // 1/5, + 0.6 * 1/2 after the death at 2, + 0.3 * 1/1
+        let incidence: Vec<f64> = relapse.steps.iter().map(|s| s.incidence).collect();
+        for (got, want) in incidence.iter().zip([0.2, 0.5, 0.8]) {
+            assert!((got - want).abs() < 1e-12, "{incidence:?}");
+        }
+        assert_eq!((relapse.incidence_at(0.5), relapse.incidence_at(3.0), relapse.incidence_at(10.0)), (0.0, 0.2, relapse.steps[2].incidence));
+        let death = data.cumulative_incidence("time", "status", "2")?;
+        assert_eq!((death.steps.len(), death.steps[0].competing), (1, 0));
+        assert!((death.incidence_at(5.0) - 0.2).abs() < 1e-12, "the curves add up to 1 - S");
+
+        // word labels of a factor column give the same curve
+        let by_word = data.cumulative_incidence("time", "cause", "relapse")?;
+        assert_eq!((by_word.event.as_str(), by_word.steps.len()), ("relapse", 3));
+        assert!((by_word.incidence_at(5.0) - 0.8).abs() < 1e-12);
+
+        let err = data.cumulative_incidence("time", "cause", "progression").unwrap_err();
+        assert_eq!(err.to_string(), "The event of interest 'progression' is none of the event types of 'cause': relapse, death");
+        assert!(data.cumulative_incidence("time", "cause", "censored").is_err());
+        assert!(data.cumulative_incidence("cause", "status", "1").is_err());
+        assert!(data.cumulative_incidence("time", "outcome", "1").is_err());
+        Ok(())
+    }
+}
@@ -1,26 +1,113 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path};
 use csv::{ WriterBuilder};
 use ndarray::{Array2, Axis, s, concatenate};
-use anyhow::Result;
-use serde::{Serialize, Deserialize};
-use serde_json;
-use std::fs::File;
-use std::io::{BufWriter, BufReader};
+use crate::error::{bail, DataTableError, Result};
+use std::io::{BufRead, Read, Write};
 use std::fmt;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use crate::{Factor, factor::FactorJson};
+use rand::rng;
+use crate::{Factor, FactorMap};
+use crate::factor_map::LevelLabels;
+use crate::options::{JsonCells, ParseOptions, TrailingDelimiter, DATE_FORMATS, SPLIT_DELIMITER};
+use crate::input::{open_input, BOM};
+use crate::column::{Column, Dtype, NumericSummary};
+use crate::report::{DropReason, ParseReport};
+use crate::survival::SurvivalColumns;
+use crate::builder::SurvivalDataBuilder;
+use crate::infer::{
+    check_strict, date_format, declared_mismatch, fits_declared, flatten_json, format_date, infer_dtypes, is_json_column,
+    json_object, parse_date, parse_float, refine_dtype, report_coerced, MixedValues,
+};
+
+
+/// How `write_tsv_as()` writes factor columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactorFormat {
+    /// The level labels (`"B cell"`)
+    Labels,
+    /// The numeric codes - for tools that only read numbers
+    Codes,
+}
+
+/// The factor column `SurvivalData::from_files()` records the input file of each row in.
+pub const SOURCE_COLUMN: &str = "source_file";
+
+/// The fields of a record that are read (`ParseOptions::columns`).
+struct Selection {
+    /// Number of fields in the file
+    width: usize,
+    keep: Vec<usize>,
+}
+
+impl Selection {
+    fn fields<'r>(&'r self, record: &'r csv::StringRecord) -> impl Iterator<Item = &'r str> + 'r {
+        self.keep.iter().map(move |&i| &record[i])
+    }
+}
+
+/// What `complete_row()` has seen of the rows so far.
+#[derive(Debug, Default)]
+struct RowCheck {
+    /// Rows dropped with `skip_bad_rows`
+    skipped: usize,
+    /// Whether the first row ended with a delimiter, and its line
+    trailing: Option<(bool, u64)>,
+    /// Complete rows taken so far (see `at_limit()`)
+    rows: usize,
+    /// A complete row after `ParseOptions::max_rows` was found - the table was cut
+    limited: bool,
+    /// The first row, read by `file_column_names()` to see how the rows end (see `read()`)
+    pending: Option<csv::StringRecord>,
+}
+
+impl RowCheck {
+    /// Count a complete row - or, if `max_rows` rows are taken already, stop: the reader loops
+    /// break on `true`, so factors and summaries describe the first `max_rows` rows only.
+    fn at_limit(&mut self, options: &ParseOptions) -> bool {
+        if options.max_rows.is_some_and(|max| self.rows >= max) {
+            self.limited = true;
+            return true;
+        }
+        self.rows += 1;
+        false
+    }
 
+    /// `max_rows` if it cut the table.
+    fn limit(&self, options: &ParseOptions) -> Option<usize> {
+        options.max_rows.filter(|_| self.limited)
+    }
+
+    /// The next row of `rdr` into `record` - the `pending` one first.
+    fn read<R: Read>(&mut self, rdr: &mut csv::Reader<R>, record: &mut csv::StringRecord) -> Result<bool> {
+        if let Some(first) = self.pending.take() {
+            *record = first;
+            return Ok(true);
+        }
+        Ok(rdr.read_record(record)?)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SurvivalData {
     pub headers: Vec<String>,
     pub numeric_data: Array2<f64>,
     pub factors: HashMap<String, Factor>,
+    /// Per column metadata (detected type), aligned with `headers`
+    pub columns: Vec<Column>,
     pub exclude: HashSet<String>,
+    /// The declared time/event columns (see `with_survival()`)
+    pub survival: Option<SurvivalColumns>,
     header_lookup: HashMap<String, usize>,
     next_order: f64,
+    /// The number of rows read by `from_file_streaming()` - `numeric_data` stays empty there
+    pub(crate) streamed_rows: Option<usize>,
+    /// The delimiter the table was read with (see `delimiter()`)
+    pub(crate) delimiter: u8,
+    /// The column names of the file (see `header()`)
+    file_header: Vec<String>,
+    /// The rows read and dropped (see `parse_report()`)
+    pub(crate) report: ParseReport,
     //pub max_levels: usize,
 }
 
@@ -30,9 +117,15 @@ impl Default for SurvivalData {
             headers: Vec::new(),
             numeric_data: Array2::zeros((0, 0)),
             factors: HashMap::new(),
+            columns: Vec::new(),
             exclude: HashSet::new(),
+            survival: None,
             header_lookup: HashMap::new(),
             next_order: 0.0,
+            streamed_rows: None,
+            delimiter: b'\t',
+            file_header: Vec::new(),
+            report: ParseReport::default(),
         }
     }
 }
@@ -46,8 +139,8 @@ impl fmt::Display for SurvivalData {
             writeln!(f, "  {}: {}", idx, header)?;
         }
         writeln!(f, "Factors:")?;
-        for (_name, factor) in &self.factors {
-            writeln!(f,"{}",factor );
+        for factor in self.factors.values() {
+            writeln!(f,"{}",factor )?;
         }
         writeln!(f, "Excluded columns: {:?}", self.exclude)
     }
@@ -58,6 +151,7 @@ impl SurvivalData {
 
     /// ------------------------------------------------------------------------
     /// Core loader: reads TSV/CSV file, applies factors if available.
+    /// Gzip-compressed files (`.gz` or gzip magic bytes) are decoded on the fly.
     /// If the `.factors.json` exists, it will be used.
     /// If not, it loads data heuristically and continues silently.
     /// ------------------------------------------------------------------------
@@ -67,347 +161,1391 @@ impl SurvivalData {
         categorical_cols: HashSet<String>,
         factors_file: FF,
     ) -> Result<Self> {
+        Self::from_file_with_options(file_path, &ParseOptions::new(delimiter, categorical_cols), factors_file)
+    }
 
-        let mut ret = SurvivalData::default();
+    /// Like `from_tsv()`, but all parser settings come from `options`.
+    pub fn from_file_with_options<P: AsRef<Path> + std::fmt::Debug, FF: AsRef<Path> + std::fmt::Debug>(
+        file_path: P,
+        options: &ParseOptions,
+        factors_file: FF,
+    ) -> Result<Self> {
 
         // --- 1. Load existing factors if the file exists ---
         let factors_path = factors_file.as_ref();
-        if factors_path.exists() {
-            println!("🧬 Using existing factors file: {:?}", factors_path);
-            ret.load_factors(factors_path)?;
+        let factors = if factors_path.exists() {
+            log::info!("Using existing factors file: {:?}", factors_path);
+            FactorMap::read(factors_path)?
         } else {
-            println!("ℹ️ No factors file found, proceeding without it (new dataset?)");
-        }
+            log::info!("No factors file found, proceeding without it (new dataset?)");
+            FactorMap::default()
+        };
 
-        // --- 2. Open file and prepare CSV reader ---
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(delimiter)
-            .from_path(&file_path)?;
+        // --- 2. Open file (gzip aware) and parse it ---
+        Self::from_file_with_factors(file_path, options, &factors)
+    }
 
-        // --- 3. Expand headers based on existing factors (for one-hot) ---
-        let headers: Vec<String> = rdr
-            .headers()?
-            .iter()
-            .flat_map(|s| {
-                // 💡 handle R-style empty header (rownames column)
-                let header_name = if s.trim().is_empty() {
-                    println!("⚠️ Detected unlabeled first(?) column -> treating as 'barcode'");
-                    "barcode".to_string()
-                } else {
-                    s.to_string()
-                };
-                if let Some(fact) = ret.factors.get(s) {
-                    if fact.one_hot {
-                        let mut c = vec![s.to_string()];
-                        c.extend(fact.all_column_names());
-                        c
-                    } else {
-                        vec![s.to_string()]
-                    }
-                } else {
-                    vec![s.to_string()]
-                }
-            })
-            .collect();
+    /// Read a data file applying already known factor definitions,
+    /// e.g. a hand edited factors file loaded with `FactorMap::from_json()`.
+    /// The level order and numeric codes of these factors are kept as defined.
+    pub fn from_file_with_factors<P: AsRef<Path> + std::fmt::Debug>(
+        file_path: P,
+        options: &ParseOptions,
+        factors: &FactorMap,
+    ) -> Result<Self> {
+        let mut ret = SurvivalData {
+            factors: factors.to_factors(),
+            ..Default::default()
+        };
+        ret.read_table(open_input(&file_path)?, options)?;
+        Ok(ret)
+    }
 
-        ret.headers = headers.clone();
-        ret.header_lookup = headers
-            .iter()
-            .enumerate()
-            .map(|(id, name)| (name.clone(), id))
-            .collect();
+    /// Read several tables with the same columns (e.g. one `meta.tsv` per sample) as one:
+    /// the rows are concatenated in file order and the factor column `SOURCE_COLUMN`
+    /// records the file (path as given) each row came from.
+    ///
+    /// The files may list the columns in a different order. A column missing from some
+    /// files is an error naming the difference, unless `union_columns` is set - then
+    /// the absent cells are missing values. The types are inferred over all rows.
+    pub fn from_files<P: AsRef<Path> + std::fmt::Debug>(paths: &[P], options: &ParseOptions, union_columns: bool) -> Result<Self> {
+        let Some(first) = paths.first() else {
+            bail!(InvalidArgument, "No input files given");
+        };
+        if options.transpose {
+            bail!(InvalidArgument, "Transposed tables cannot be combined - read them one by one");
+        }
+        let resolved = options.resolve_renames();
+        let options = &*resolved;
+        let mut tables = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut rdr = Self::csv_reader(open_input(path)?, options);
+            let mut peek = RowCheck::default();
+            let names = Self::file_column_names(&mut rdr, options, &mut peek).map_err(|err| Self::in_file(path, err))?;
+            // every file has to have them, also with union_columns
+            options.check_required(&names).map_err(|err| match err {
+                DataTableError::MissingColumns { columns, message } => DataTableError::MissingColumns { columns, message: format!("{} in {:?}", message, path) },
+                err => err,
+            })?;
+            if names.iter().any(|n| n == SOURCE_COLUMN) {
+                bail!(InvalidData, "{:?} already has a '{}' column", path, SOURCE_COLUMN);
+            }
+            tables.push((path, rdr, names, peek.pending));
+        }
 
-        // --- 4. Mark forced categorical columns as factors ---
-        for header in &headers {
-            if categorical_cols.contains(header) {
-                println!("Forcing header {header} to be a factor");
-                ret.factors
-                    .entry(header.clone())
-                    .or_insert_with(|| Factor::new(header, false));
-            }
-        }
-
-        // --- 5. Parse rows (one-hot aware logic) ---
-        let n_cols = headers.len();
-        let mut raw_rows: Vec<Vec<f64>> = Vec::new();
-
-        for result in rdr.records() {
-            let record = result?;
-            let mut row: Vec<f64> = Vec::with_capacity(n_cols);
-            let mut expanded = 0;
-
-            for (i, value) in record.iter().enumerate() {
-                let trimmed = value.trim().trim_matches('"');
-                match trimmed.replace(',', ".").parse::<f64>() {
-                    Ok(num) => {
-                        if let Some(factor) = ret.factors.get_mut(&headers[i + expanded]) {
-                            let (idx, col_to_add, all_cols) = factor.push(&num.to_string());
-                            let alt = if idx.is_nan() { f64::NAN } else { 0.0 };
-                            match all_cols {
-                                Some(cols) => {
-                                    expanded += cols.len();
-                                    row.push(factor.get_f64(trimmed));
-                                    for cname in cols.iter().cloned() {
-                                        row.push(if cname == col_to_add { idx } else { alt });
-                                    }
-                                }
-                                None => row.push(idx),
-                            }
-                        } else {
-                            row.push(num);
-                        }
+        // all columns in order of first appearance
+        let mut columns: Vec<String> = Vec::new();
+        for (_, _, names, _) in &tables {
+            columns.extend(names.iter().filter(|n| !columns.contains(n)).cloned().collect::<Vec<_>>());
+        }
+        if !union_columns {
+            let expected = &tables[0].2;
+            for (path, _, names, _) in &tables[1..] {
+                let listed = |a: &[String], b: &[String]| a.iter().filter(|c| !b.contains(c)).map(|c| format!("'{}'", c)).collect::<Vec<_>>();
+                let (lacks, extra) = (listed(expected, names), listed(names, expected));
+                if !lacks.is_empty() || !extra.is_empty() {
+                    let mut diff = Vec::new();
+                    if !lacks.is_empty() {
+                        diff.push(format!("lacks {}", lacks.join(", ")));
                     }
-                    Err(_) => {
-                        if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("NA") {
-                            if !ret.factors.contains_key(&headers[i + expanded]) {
-                                row.push(f64::NAN);
-                                continue;
-                            }
-                        }
-                        let factor = ret
-                            .factors
-                            .entry(headers[i + expanded].clone())
-                            .or_insert_with(|| Factor::new(&headers[i + expanded], false));
-                        let (idx, col_to_add, all_cols) = factor.push(trimmed);
-                        let alt = if idx.is_nan() { f64::NAN } else { 0.0 };
-
-                        match all_cols {
-                            Some(cols) => {
-                                expanded += cols.len();
-                                row.push(factor.get_f64(trimmed));
-                                for cname in cols.iter() {
-                                    row.push(if *cname == col_to_add { idx } else { alt });
-                                }
-                            }
-                            None => row.push(if trimmed.is_empty() { f64::NAN } else { idx }),
-                        }
+                    if !extra.is_empty() {
+                        diff.push(format!("has the extra {}", extra.join(", ")));
                     }
+                    bail!(
+                        InvalidData,
+                        "The header of {:?} differs from {:?}: it {} (read with union_columns to fill absent columns with missing values)",
+                        path, first, diff.join(" and ")
+                    );
                 }
             }
-
-            raw_rows.push(row);
         }
+        let Some(absent) = options.missing_tokens.first() else {
+            bail!(InvalidArgument, "Combining files needs a missing token for the absent cells");
+        };
 
-        // --- 6. Build Array2 ---
-        let n_rows = raw_rows.len();
-        let mut numeric_data = Array2::<f64>::zeros((n_rows, n_cols));
-        for (i, row) in raw_rows.iter().enumerate() {
-            for (j, &val) in row.iter().enumerate() {
-                numeric_data[[i, j]] = val;
+        // one tab separated table in memory, read with the normal parser
+        let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_writer(Vec::new());
+        writer.write_record(columns.iter().map(String::as_str).chain([SOURCE_COLUMN]))?;
+        let mut check = RowCheck::default();
+        // max_rows counts the rows of all files together
+        let mut record = csv::StringRecord::new();
+        for (path, rdr, names, pending) in &mut tables {
+            if check.limited {
+                break;
+            }
+            let positions: Vec<Option<usize>> = columns.iter().map(|c| names.iter().position(|n| n == c)).collect();
+            let source = path.as_ref().display().to_string();
+            // every file may end its lines its own way
+            check.trailing = None;
+            check.pending = pending.take();
+            while check.read(rdr, &mut record).map_err(|err| Self::in_file(*path, err))? {
+                if !Self::complete_row(&mut record, names.len(), options, &mut check).map_err(|err| Self::in_file(*path, err))? {
+                    continue;
+                }
+                if check.at_limit(options) {
+                    break;
+                }
+                let fields = positions.iter().map(|p| p.map_or(absent.as_str(), |i| &record[i]));
+                writer.write_record(fields.chain([source.as_str()]))?;
             }
         }
-
-        ret.numeric_data = numeric_data;
+        let skipped = check.skipped;
+        Self::report_skipped(skipped);
+        let combined = writer.into_inner().map_err(|e| DataTableError::Stream(e.into_error()))?;
+
+        // the text is decoded, split and without comments already
+        let (options_delimiter, options_header_row) = (options.delimiter, options.header_row);
+        let mut options = options.clone();
+        options.delimiter = b'\t';
+        options.separator = None;
+        options.encoding = Default::default();
+        options.comment = None;
+        options.progress = None;
+        options.header_row = 0;
+        options.max_rows = None;
+        // the combined header has the new names already
+        options.renames.clear();
+        options.categorical.insert(SOURCE_COLUMN.to_string());
+        let mut ret = Self::from_reader_with_options(&combined[..], &options)?;
+        ret.mark_renamed(&resolved);
+        ret.delimiter = options_delimiter;
+        let titles = options_header_row * paths.len();
+        ret.report = ParseReport::new(ret.n_rows() + skipped + titles);
+        ret.report.set(DropReason::WrongFieldCount, skipped);
+        ret.report.set(DropReason::BeforeHeader, titles);
+        ret.report.limit = check.limit(&resolved);
         Ok(ret)
     }
 
-    /// High-level loader: handles factor file and performs cell-name consistency checks.
-    /// Reuses `from_tsv()` internally.
-    pub fn from_file<P: AsRef<Path> + std::fmt::Debug, FF: AsRef<Path> + std::fmt::Debug>(
-        file_path: P,
+    /// Parse a TSV/CSV table from any buffered reader - purely in memory.
+    ///
+    /// Nothing is read from or written to disk: factors are inferred from the data
+    /// (plus the forced `categorical_cols`). Use `save_factors()` afterwards if the
+    /// factor definitions should be persisted.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let tsv = "age\tsex\n42\tm\n37\tf\n";
+    /// let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+    /// assert_eq!(data.as_vec_f64("age"), vec![42.0, 37.0]);
+    /// ```
+    pub fn from_reader<R: BufRead>(
+        reader: R,
         delimiter: u8,
         categorical_cols: HashSet<String>,
-        factors_file: FF,
     ) -> Result<Self> {
+        Self::from_reader_with_options(reader, &ParseOptions::new(delimiter, categorical_cols))
+    }
 
-        let file_path_ref = file_path.as_ref();
-        let factors_file_ref = factors_file.as_ref();
-
-        // --- 1. Load the numeric/factor data from TSV ---
-        let mut ret = Self::from_tsv(file_path_ref, delimiter, categorical_cols, &factors_file)?;
-
-        // --- 2. Load or create factors file ---
-        if factors_file_ref.exists() {
-            println!("Factors are loaded from file");
-            ret.load_factors(&factors_file_ref)?;
-        } else {
-            println!("Saved a new factors file to fine tune the factors: '{:?}'", &factors_file_ref);
-            ret.save_factors(&factors_file_ref)?;
-            panic!("
-Please review and update the factors file so that it accurately reflects the logic in the data.
-
-The factors file is a JSON-formatted file, for example:
-
-[
-  {{
-    'column': 'status2',
-    'levels': [
-      '1',
-      '0'
-    ],
-    'numeric': [
-      0.0,
-      1.0
-    ],
-    'matching': null,
-    'one_hot': false
-  }}
-]
-In this example, there is an error: the numeric values do not match the actual data. They should be [1.0, 0.0]. Once corrected, the factor will work as expected.
-
-
-The one_hot option allows the factor to be expanded into multiple 0.0/1.0 columns—two in this case. This is particularly useful when the factor levels have no inherent numeric order or relationship:
-
-[
-  {{
-    'column': 'tp53_mutation_type',
-    'levels': [
-      'Missense',
-      'Nonsense',
-      'Frameshift',
-      'Splice_site',
-      'Silent',
-    ],
-    'numeric': [
-      0.0,
-      1.0,
-      2.0,
-      3.0,
-      4.0,
-    ],
-    'matching': null,
-    'one_hot': true
-  }}
-]
-
-This factor is categorical with no inherent order, so it’s a good candidate for one-hot encoding in a model. 
-Each level will be represented as a separate binary column (0/1) if one-hot encoding is used.
-
-                ");
-        }
+    /// Like `from_reader()`, but all parser settings come from `options`.
+    pub fn from_reader_with_options<R: BufRead>(reader: R, options: &ParseOptions) -> Result<Self> {
+        let mut ret = SurvivalData::default();
+        ret.read_table(reader, options)?;
+        Ok(ret)
+    }
 
+    /// Like `from_reader_with_options()`, applying already known factor definitions.
+    pub fn from_reader_with_factors<R: BufRead>(reader: R, options: &ParseOptions, factors: &FactorMap) -> Result<Self> {
+        let mut ret = SurvivalData {
+            factors: factors.to_factors(),
+            ..Default::default()
+        };
+        ret.read_table(reader, options)?;
         Ok(ret)
     }
 
-    /// Add a new data row (group) initialized with NaN values across all columns (cells).
-    ///
-    /// Each row represents a dataset or feature.
-    /// If this is the first row added, you must specify the expected number of columns via `size`.
-    /// If `is_factor` is true, a corresponding `Factor` is also created and registered.
+    /// Download the table at an `http(s)://` URL and parse it while it arrives (feature `net`,
+    /// see `input::open_url()`) - the body is not kept, so `options.delimiter` has to be the right one.
+    #[cfg(feature = "net")]
+    pub fn from_url_with_options(url: &str, options: &ParseOptions) -> Result<Self> {
+        Self::from_reader_with_options(crate::input::open_url(url)?, options)
+    }
+
+    /// Summarize a (possibly huge) table without keeping its rows in memory.
     ///
-    /// # Panics
-    /// Panics if the data table is empty and no `size` is provided.
+    /// The file is read twice: the first pass infers the column types, counts the missing
+    /// cells and keeps a `NumericSummary` (count, min, max, mean) per numeric column, the
+    /// second pass collects the levels of the factor columns in order of appearance.
+    /// Quartiles would need all values, so they stay `None` here.
+    /// Peak memory is proportional to the number of distinct factor levels, not to the rows.
     ///
-    /// # Example
-    /// ```
-    /// use rust_data_table::SurvivalData;
-    /// let mut data = SurvivalData::default();
-    /// data.add_dataset("age", false, Some(10));     // first row → needs size
-    /// data.add_dataset("treatment", true, None);    // second row → inferred columns
-    /// ```
-    pub fn add_dataset(&mut self, dataset_name: &str, is_factor: bool, size: Option<usize>) {
-        // Don't add the same dataset twice
-        if self.headers.contains(&dataset_name.to_string()) {
-            #[cfg(debug_assertions)]
-            println!("Row '{}' already exists, skipping.", dataset_name);
-            return;
+    /// The result has the same `headers`, `columns` and `factors` as the in-memory parser,
+    /// but no rows (`numeric_data` is empty) - use `stream_rows()` for the encoded values.
+    /// Free text columns keep no levels, so their `cardinality` is not reported.
+    /// JSON object columns (`json_cells`, `Json` dtypes) need the in-memory reader.
+    pub fn from_file_streaming<P: AsRef<Path> + std::fmt::Debug>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        if options.json_cells != JsonCells::Off || options.dtypes.values().any(|d| *d == Dtype::Json) {
+            bail!(InvalidArgument, "JSON object columns are not supported while streaming - read the table into memory");
         }
+        if options.transpose {
+            bail!(InvalidArgument, "A transposed table cannot be streamed - read it into memory");
+        }
+        let resolved = options.resolve_renames();
+        let options = &*resolved;
+        let mut ret = SurvivalData::default();
 
-        let n_rows = self.numeric_data.nrows();
-        let n_cols = self.numeric_data.ncols();
-
-        // Case 1: Empty table — initialize with one row and known number of columns
-        if n_rows == 0 {
-            let init_cols = size.unwrap_or_else(|| {
-                panic!("🛑 Must provide 'size' (number of cells) when adding the first dataset!");
-            });
-            self.numeric_data = Array2::<f64>::from_elem((1, init_cols), f64::NAN);
-        } else {
-            // Case 2: Append a new row of NaN across existing columns
-            let new_row = Array2::<f64>::from_elem((1, n_cols), f64::NAN);
-            self.numeric_data = concatenate(Axis(0), &[self.numeric_data.view(), new_row.view()])
-                .expect("❌ Failed to concatenate new row");
+        // --- 1. types, missing counts and numeric ranges ---
+        let mut rdr = Self::csv_reader(open_input(&file_path)?, options);
+        let mut check = RowCheck::default();
+        let (names, selection) = ret.read_header(&mut rdr, options, &mut check)?;
+        let declared: Vec<bool> = names.iter().map(|name| ret.factors.contains_key(name)).collect();
+        let mut dtypes: Vec<Option<Dtype>> = declared.iter().map(|&d| d.then_some(Dtype::Factor)).collect();
+        let mut missing = vec![0_usize; names.len()];
+        let mut empty = vec![0_usize; names.len()];
+        let mut summaries = vec![NumericSummary::default(); names.len()];
+        // as_numeric columns only look at the numbers - anything else is missing
+        let numeric: Vec<bool> = names.iter().map(|name| options.as_numeric.contains(name)).collect();
+        let mut coerced = vec![0_usize; names.len()];
+        let mut boolean: Vec<bool> = declared.iter().zip(&numeric).map(|(&d, &n)| !d && !n).collect();
+        let mut leading_zero = vec![false; names.len()];
+        // declared types are checked, not inferred
+        let forced: Vec<Option<Dtype>> = names
+            .iter()
+            .zip(&declared)
+            .map(|(name, &d)| if d { None } else { options.dtypes.get(name).copied() })
+            .collect();
+        // the date formats every value so far parses with
+        let mut date_formats: Vec<Vec<&str>> = declared
+            .iter()
+            .zip(&forced)
+            .zip(&numeric)
+            .map(|((&d, &f), &n)| match f {
+                Some(Dtype::Date) => DATE_FORMATS.to_vec(),
+                Some(_) => Vec::new(),
+                None if options.detect_dates && !d && !n => DATE_FORMATS.to_vec(),
+                None => Vec::new(),
+            })
+            .collect();
+        // the first value and whether any other differs from it (see `Column::constant`)
+        let mut first: Vec<Option<String>> = vec![None; names.len()];
+        let mut varies = vec![false; names.len()];
+        // strict mode: the numbers and texts of the inferred columns
+        let mut mixed = vec![MixedValues::default(); if options.strict { names.len() } else { 0 }];
+        let mut record = csv::StringRecord::new();
+        let mut n_rows = 0;
+        while check.read(&mut rdr, &mut record)? {
+            if !Self::complete_row(&mut record, selection.width, options, &mut check)? {
+                continue;
+            }
+            if check.at_limit(options) {
+                break;
+            }
+            n_rows += 1;
+            for (j, value) in selection.fields(&record).enumerate() {
+                let value = options.mask_na_value(&names[j], value.trim());
+                if options.is_missing(value) {
+                    missing[j] += 1;
+                    empty[j] += usize::from(value.is_empty());
+                    continue;
+                }
+                if numeric[j] && options.number_format.parse_float(value).is_none() {
+                    missing[j] += 1;
+                    coerced[j] += 1;
+                    continue;
+                }
+                let key = match options.parse_boolean(value) {
+                    Some(true) => "true",
+                    Some(false) => "false",
+                    None => value,
+                };
+                match &first[j] {
+                    None => first[j] = Some(key.to_string()),
+                    Some(first) => varies[j] |= first != key,
+                }
+                boolean[j] &= options.parse_boolean(value).is_some();
+                leading_zero[j] |= !numeric[j] && options.keeps_leading_zero(value);
+                if let Some(mixed) = mixed.get_mut(j) {
+                    mixed.push(value, options);
+                }
+                date_formats[j].retain(|format| parse_date(value, format).is_some());
+                if let Some(dtype) = forced[j] {
+                    let fit = match dtype {
+                        Dtype::Date => !date_formats[j].is_empty(),
+                        _ => fits_declared(dtype, value, options),
+                    };
+                    if !fit {
+                        let line = record.position().map_or(0, |pos| pos.line());
+                        return Err(declared_mismatch(&names[j], line, value, dtype));
+                    }
+                }
+                let dtype = refine_dtype(dtypes[j], value, options.number_format);
+                if dtype.is_numeric() && let Some(num) = options.number_format.parse_float(value) {
+                    summaries[j].push(num);
+                }
+                dtypes[j] = Some(dtype);
+            }
+        }
+        let skipped = check.skipped;
+        Self::report_skipped(skipped);
+        ret.streamed_rows = Some(n_rows);
+        ret.report = ParseReport::new(n_rows + skipped + options.header_row);
+        ret.report.set(DropReason::WrongFieldCount, skipped);
+        ret.report.set(DropReason::BeforeHeader, options.header_row);
+        ret.report.limit = check.limit(options);
+        for (name, &coerced) in names.iter().zip(&coerced) {
+            report_coerced(name, coerced);
+        }
+        // a column without any value is AllMissing (like infer_dtypes) - Float for as_numeric, declared types win
+        let dtypes: Vec<Dtype> = dtypes
+            .into_iter()
+            .zip(boolean)
+            .zip(&date_formats)
+            .zip(&leading_zero)
+            .zip(&forced)
+            .zip(&numeric)
+            .map(|(((((d, boolean), formats), &leading_zero), &forced), &numeric)| {
+                forced.unwrap_or(match d {
+                    Some(_) if boolean => Dtype::Boolean,
+                    Some(_) if !formats.is_empty() => Dtype::Date,
+                    Some(d) if d.is_numeric() && leading_zero => Dtype::Factor,
+                    None if !numeric => Dtype::AllMissing,
+                    d => d.unwrap_or(Dtype::Float),
+                })
+            })
+            .collect();
+        if options.strict {
+            let mixed: Vec<(&str, MixedValues)> = (0..names.len())
+                .filter(|&j| !declared[j] && !numeric[j] && forced[j].is_none() && dtypes[j] == Dtype::Factor)
+                .map(|j| (names[j].as_str(), std::mem::take(&mut mixed[j])))
+                .collect();
+            check_strict(&mixed)?;
         }
 
-        // Register dataset name (row name)
-        self.headers.push(dataset_name.to_string());
+        // --- 2. factor levels in order of appearance ---
+        // levels from a spec keep their order
+        let inferred: Vec<bool> = names
+            .iter()
+            .map(|name| ret.factors.get(name).is_none_or(|f| f.get_levels().is_empty()))
+            .collect();
+        let mut free_text: Vec<bool> = names.iter().map(|name| options.as_string.contains(name)).collect();
+        for name in &options.as_string {
+            ret.factors.remove(name);
+        }
+        let mut spellings = vec![BTreeMap::new(); names.len()];
+        if dtypes.contains(&Dtype::Factor) {
+            let mut rdr = Self::csv_reader(open_input(&file_path)?, options);
+            Self::skip_to_header(&mut rdr, options)?;
+            let mut check = RowCheck::default();
+            while rdr.read_record(&mut record)? {
+                if !Self::complete_row(&mut record, selection.width, options, &mut check)? {
+                    continue;
+                }
+                if check.at_limit(options) {
+                    break;
+                }
+                for (j, value) in selection.fields(&record).enumerate() {
+                    if dtypes[j] != Dtype::Factor || free_text[j] {
+                        continue;
+                    }
+                    let name = &names[j];
+                    let value = options.mask_na_value(name, value.trim());
+                    let key = Self::factor_key(name, value, options)?;
+                    let factor = ret.factors
+                        .entry(name.clone())
+                        .or_insert_with(|| Factor::new(name, false).starting_at(options.first_code()));
+                    factor.count(&key);
+                    Self::add_spelling(&mut spellings[j], &key, value, options);
+                    if let Some(max) = options.max_levels
+                        && !declared[j]
+                        && factor.get_levels().len() > max
+                    {
+                        log::warn!("Column '{name}' has more than {max} distinct values (max_levels) -> free text, not a factor");
+                        free_text[j] = true;
+                        ret.factors.remove(name);
+                    }
+                }
+            }
+        }
 
-        // Register mapping for fast lookup
-        let row_idx = self.numeric_data.nrows() - 1;
+        for ((name, spellings), &inferred) in names.iter().zip(&spellings).zip(&inferred) {
+            if let Some(factor) = ret.factors.get_mut(name) {
+                Self::note_spellings(factor, spellings);
+                if options.sort_levels && inferred {
+                    factor.sort_levels();
+                }
+            }
+        }
+        for (name, spec) in &options.factor_specs {
+            if let Some(factor) = ret.factors.get_mut(name) {
+                factor.apply_counted_spec(spec)?;
+            }
+        }
 
-        self.header_lookup.insert(dataset_name.to_string(), row_idx);
+        // --- 3. the encoded columns (one-hot aware) ---
+        let mut headers: Vec<String> = Vec::with_capacity(names.len());
+        let mut columns: Vec<Column> = Vec::with_capacity(names.len());
+        for (j, name) in names.iter().enumerate() {
+            let mut column = Column::new(name, dtypes[j]);
+            column.missing = missing[j];
+            column.missing_kinds = options.missing_kinds(missing[j], empty[j]);
+            column.constant = !varies[j];
+            let mut one_hot = Vec::new();
+            if free_text[j] {
+                column.dtype = Dtype::FreeText;
+            } else if let Some(factor) = ret.factors.get(name) {
+                column.cardinality = Some(factor.get_levels().len());
+                // levels may differ in spelling only
+                column.constant = factor.get_levels().iter().filter(|level| factor.count_of(level) > 0).count() <= 1;
+                if factor.one_hot {
+                    one_hot = factor.all_column_names();
+                }
+            } else if dtypes[j].is_numeric() {
+                // `1` and `1.0` are the same number
+                column.constant = summaries[j].min == summaries[j].max;
+                column.summary = Some(summaries[j].clone());
+            } else if dtypes[j] == Dtype::Date {
+                column.format = date_formats[j].first().map(|f| f.to_string());
+            } else if dtypes[j] == Dtype::AllMissing {
+                column.cardinality = Some(0);
+            }
+            headers.push(name.clone());
+            columns.push(column);
+            for header in one_hot {
+                let mut column = Column::new(&header, Dtype::Integer);
+                column.missing = missing[j];
+                column.missing_kinds = options.missing_kinds(missing[j], empty[j]);
+                headers.push(header);
+                columns.push(column);
+            }
+        }
+        ret.numeric_data = Array2::zeros((0, headers.len()));
+        ret.set_columns(headers, columns);
+        ret.mark_renamed(options);
+        Ok(ret)
+    }
 
-        // Optionally register a Factor
-        if is_factor {
-            self.factors
-                .insert(dataset_name.to_string(), Factor::new(dataset_name, false));
+    /// The optional second pass of `from_file_streaming()`: read the file once more and
+    /// hand every row, encoded like `numeric_data` (one value per entry in `headers`), to `emit`.
+    /// Free text columns are emitted as NaN. Returns the number of rows.
+    pub fn stream_rows<P, F>(&self, file_path: P, options: &ParseOptions, mut emit: F) -> Result<usize>
+    where
+        P: AsRef<Path> + std::fmt::Debug,
+        F: FnMut(&[f64]) -> Result<()>,
+    {
+        let mut rdr = Self::csv_reader(open_input(&file_path)?, options);
+        let mut check = RowCheck::default();
+        let names = Self::file_column_names(&mut rdr, options, &mut check)?;
+        let mut record = csv::StringRecord::new();
+        let mut row: Vec<f64> = Vec::with_capacity(self.headers.len());
+        let mut n_rows = 0;
+        while check.read(&mut rdr, &mut record)? {
+            if !Self::complete_row(&mut record, names.len(), options, &mut check)? {
+                continue;
+            }
+            if check.at_limit(options) {
+                break;
+            }
+            row.clear();
+            for (name, value) in names.iter().zip(record.iter()) {
+                let value = options.mask_na_value(name, value.trim());
+                match (self.factors.get(name), self.header_lookup.get(name).map(|&id| self.columns[id].dtype)) {
+                    (_, None) => {}
+                    (_, Some(Dtype::FreeText | Dtype::Json)) => row.push(f64::NAN),
+                    (_, Some(Dtype::Date)) => {
+                        let format = self.column(name).and_then(|c| c.format.as_deref()).unwrap_or(DATE_FORMATS[0]);
+                        row.push(parse_date(value, format).unwrap_or(f64::NAN));
+                    }
+                    (_, Some(Dtype::Boolean)) => row.push(match options.parse_boolean(value) {
+                        Some(true) => 1.0,
+                        Some(false) => 0.0,
+                        None => f64::NAN,
+                    }),
+                    (Some(factor), Some(Dtype::Factor)) => {
+                        let key = Self::factor_key(name, value, options)?;
+                        let code = factor.get_f64(&key);
+                        row.push(code);
+                        if factor.one_hot {
+                            for level in factor.get_levels() {
+                                row.push(if code.is_nan() { f64::NAN } else if level == factor.level_of(&key) { 1.0 } else { 0.0 });
+                            }
+                        }
+                    }
+                    _ if options.is_missing(value) => row.push(f64::NAN),
+                    _ => row.push(options.number_format.parse_float(value).unwrap_or(f64::NAN)),
+                }
+            }
+            emit(&row)?;
+            n_rows += 1;
         }
+        Ok(n_rows)
+    }
 
-        #[cfg(debug_assertions)]
-        println!(
-            "✅ Added dataset row '{}' (factor={}) → total rows: {}, columns: {}",
-            dataset_name,
-            is_factor,
-            self.numeric_data.nrows(),
-            self.numeric_data.ncols()
-        );
+    /// Hand every complete data row of `reader` to `visit` as it is in the file - the fields
+    /// neither trimmed nor masked - along with the column names (see `lint_levels()`).
+    pub(crate) fn visit_raw_rows<R, F>(reader: R, options: &ParseOptions, mut visit: F) -> Result<()>
+    where
+        R: Read,
+        F: FnMut(&[String], &csv::StringRecord),
+    {
+        if options.transpose {
+            bail!(InvalidArgument, "The raw rows of a transposed table cannot be read");
+        }
+        let mut rdr = Self::csv_reader(reader, options);
+        let mut check = RowCheck::default();
+        let names = Self::file_column_names(&mut rdr, options, &mut check)?;
+        let mut record = csv::StringRecord::new();
+        while check.read(&mut rdr, &mut record)? {
+            if !Self::complete_row(&mut record, names.len(), options, &mut check)? {
+                continue;
+            }
+            if check.at_limit(options) {
+                break;
+            }
+            visit(&names, &record);
+        }
+        Ok(())
     }
 
-    /// Update a single cell by dataset (row) name and sample (column) index.
-    /// Returns true if successful, false if the dataset or index is invalid.
-    pub fn update_value(&mut self, dataset_name: &str, col: usize, value: f64) -> bool {
-        // Look up the row index for this dataset
-        let row_idx = match self.header_lookup.get(dataset_name) {
-            Some(idx) => idx,
-            None => {
-                eprintln!(
-                    "⚠️ update_value(): dataset '{}' not found. Headers: {:?}",
-                    dataset_name, self.headers
-                );
-                return false;
+    /// Shared parser behind `from_tsv()` and `from_reader()`.
+    /// Already registered factors (e.g. loaded from a factors file) are applied.
+    fn read_table<R: Read>(&mut self, reader: R, options: &ParseOptions) -> Result<()> {
+        if options.transpose {
+            let transposed = Self::transpose_table(reader, options)?;
+            // the text is decoded, split and without comments already
+            let inner = ParseOptions {
+                delimiter: b'\t',
+                separator: None,
+                encoding: Default::default(),
+                comment: None,
+                progress: None,
+                transpose: false,
+                header_row: 0,
+                ..options.clone()
+            };
+            self.read_table(&transposed[..], &inner)?;
+            self.delimiter = options.delimiter;
+            return Ok(());
+        }
+        let resolved = options.resolve_renames();
+        let options = &*resolved;
+        // --- 2. Prepare CSV reader ---
+        let mut rdr = Self::csv_reader(reader, options);
+
+        // --- 3. Read the header and register the declared factors ---
+        let mut check = RowCheck::default();
+        let (mut names, selection) = self.read_header(&mut rdr, options, &mut check)?;
+
+        // --- 4. Collect the raw cells column by column ---
+        let mut raw: Vec<Vec<String>> = vec![Vec::new(); names.len()];
+        let mut record = csv::StringRecord::new();
+        while check.read(&mut rdr, &mut record)? {
+            if !Self::complete_row(&mut record, selection.width, options, &mut check)? {
+                continue;
+            }
+            if check.at_limit(options) {
+                break;
             }
+            for (cells, value) in raw.iter_mut().zip(selection.fields(&record)) {
+                // the csv reader already removed the quoting - remaining quotes are data
+                cells.push(value.trim().to_string());
+            }
+        }
+        let skipped = check.skipped;
+        Self::report_skipped(skipped);
+        Self::mask_na_values(&names, &mut raw, options);
+        let n_rows = raw.first().map_or(0, |cells| cells.len());
+        self.report = ParseReport::new(n_rows + skipped + options.header_row);
+        self.report.set(DropReason::WrongFieldCount, skipped);
+        self.report.set(DropReason::BeforeHeader, options.header_row);
+        self.report.limit = check.limit(options);
+        // JSON object columns are kept as written - like free text
+        let json = self.json_columns(&mut names, &mut raw, options)?;
+        let extended;
+        let options = if json.is_empty() {
+            options
+        } else {
+            extended = ParseOptions { as_string: options.as_string.iter().chain(&json).cloned().collect(), ..options.clone() };
+            &extended
         };
 
-        let (n_rows, n_cols) = self.numeric_data.dim();
-        if *row_idx >= n_rows {
-            eprintln!(
-                "🛑 Row index {} out of bounds (rows={}) for dataset '{}'",
-                row_idx, n_rows, dataset_name
-            );
-            return false;
+        // --- 5. Infer the column types and encode them (one-hot aware) ---
+        let mut headers: Vec<String> = Vec::with_capacity(names.len());
+        let mut columns: Vec<Column> = Vec::with_capacity(names.len());
+        let mut values: Vec<Vec<f64>> = Vec::with_capacity(names.len());
+
+        // declared factors (categorical, spec or factors file) are never re-inferred
+        let declared: Vec<bool> = names.iter().map(|name| self.factors.contains_key(name)).collect();
+        let dtypes = infer_dtypes(&names, &raw, &declared, options)?;
+        for (name, dtype) in names.iter().zip(&dtypes) {
+            log::debug!("Column '{name}': {dtype}");
         }
-        if col >= n_cols {
-            eprintln!(
-                "🛑 Column index {} out of bounds (cols={}) for dataset '{}'",
-                col, n_cols, dataset_name
-            );
-            return false;
+        if options.strict {
+            let mixed: Vec<(&str, MixedValues)> = (0..names.len())
+                .filter(|&j| !declared[j] && dtypes[j] == Dtype::Factor)
+                .map(|j| (names[j].as_str(), MixedValues::from_cells(&raw[j], options)))
+                .collect();
+            check_strict(&mixed)?;
         }
 
-        if self.numeric_data[[*row_idx, col]] == value{
-            false
-        }else {
-            self.numeric_data[[*row_idx, col]] = value;
-            true
+        // encoding stays sequential and in column order - factor levels do not depend on threads
+        for (((name, cells), dtype), declared) in names.iter().zip(raw.iter()).zip(dtypes).zip(declared) {
+            let mut missing = cells.iter().filter(|c| options.is_missing(c)).count();
+            let empty = cells.iter().filter(|c| c.is_empty()).count();
+            let first = columns.len();
+            match dtype {
+                Dtype::Factor => {
+                    let encoded = self.encode_factor(name, cells, options)?;
+                    let cardinality = self.factors[name].get_levels().len();
+                    let free_text = match options.max_levels {
+                        _ if options.as_string.contains(name) => true,
+                        Some(max) if !declared && cardinality > max => {
+                            log::warn!("Column '{name}' has {cardinality} distinct values (max_levels {max}) -> free text, not a factor");
+                            true
+                        }
+                        _ => false,
+                    };
+                    for (header, encoded) in encoded {
+                        let mut column = if header == *name {
+                            let dtype = match free_text {
+                                _ if json.contains(name) => Dtype::Json,
+                                true => Dtype::FreeText,
+                                false => Dtype::Factor,
+                            };
+                            let mut column = Column::new(&header, dtype);
+                            column.cardinality = Some(cardinality);
+                            column
+                        } else {
+                            Column::new(&header, Dtype::Integer)
+                        };
+                        column.missing = missing;
+                        columns.push(column);
+                        headers.push(header);
+                        values.push(encoded);
+                    }
+                }
+                Dtype::Date => {
+                    // a sampled column might not match one format completely - the first value decides then
+                    let format = date_format(cells, options)
+                        .or_else(|| {
+                            let first = cells.iter().find(|c| !options.is_missing(c))?;
+                            DATE_FORMATS.iter().find(|f| parse_date(first, f).is_some()).copied()
+                        })
+                        .unwrap_or(DATE_FORMATS[0]);
+                    let encoded = cells
+                        .iter()
+                        .map(|c| if options.is_missing(c) { f64::NAN } else { parse_date(c, format).unwrap_or(f64::NAN) })
+                        .collect();
+                    let mut column = Column::new(name, dtype);
+                    column.missing = missing;
+                    column.format = Some(format.to_string());
+                    columns.push(column);
+                    headers.push(name.clone());
+                    values.push(encoded);
+                }
+                Dtype::Boolean => {
+                    let encoded = cells
+                        .iter()
+                        .map(|c| match options.parse_boolean(c) {
+                            Some(true) => 1.0,
+                            Some(false) => 0.0,
+                            None => f64::NAN,
+                        })
+                        .collect();
+                    let mut column = Column::new(name, dtype);
+                    column.missing = missing;
+                    columns.push(column);
+                    headers.push(name.clone());
+                    values.push(encoded);
+                }
+                Dtype::AllMissing => {
+                    let mut column = Column::new(name, dtype);
+                    column.missing = missing;
+                    column.cardinality = Some(0);
+                    columns.push(column);
+                    headers.push(name.clone());
+                    values.push(vec![f64::NAN; cells.len()]);
+                }
+                _ => {
+                    let encoded: Vec<f64> = cells
+                        .iter()
+                        .map(|c| if options.is_missing(c) { f64::NAN } else { options.number_format.parse_float(c).unwrap_or(f64::NAN) })
+                        .collect();
+                    if options.as_numeric.contains(name) {
+                        // coerced values are missing now
+                        missing = encoded.iter().filter(|v| v.is_nan()).count();
+                    }
+                    let mut column = Column::new(name, dtype);
+                    column.missing = missing;
+                    column.summary = Some(NumericSummary::from_values(&encoded));
+                    columns.push(column);
+                    headers.push(name.clone());
+                    values.push(encoded);
+                }
+            }
+            for column in &mut columns[first..] {
+                column.missing_kinds = options.missing_kinds(column.missing, empty);
+            }
         }
+
+        // --- 6. Build Array2 ---
+        self.numeric_data = Array2::from_shape_fn((n_rows, values.len()), |(i, j)| values[j][i]);
+        self.set_columns(headers, columns);
+        self.mark_constant();
+        self.mark_renamed(options);
+        Ok(())
     }
 
-    /// Update a single cell by dataset (row) name and sample (column) index.
-    /// The value will represent the order in which this function is called.
-    /// reset the order with self.reset_order().
-    pub fn update_order(&mut self, dataset_name: &str, col: usize) -> bool {
-        // Look up the row index for this dataset
-        let row_idx = match self.header_lookup.get(dataset_name) {
-            Some(idx) => idx,
-            None => {
-                eprintln!(
-                    "⚠️ update_value(): dataset '{}' not found. Headers: {:?}",
-                    dataset_name, self.headers
+    /// The table with rows and columns swapped, as tab separated text: the first column of
+    /// the file is the new header, its header the first column. Every row needs as many fields as the header.
+    fn transpose_table<R: Read>(reader: R, options: &ParseOptions) -> Result<Vec<u8>> {
+        let mut rdr = Self::csv_reader(reader, options);
+        Self::skip_to_header(&mut rdr, options)?;
+        let header = rdr.headers()?.clone();
+        if header.iter().all(|s| s.trim_start_matches(BOM).trim().is_empty()) && header.len() <= 1 {
+            return Err(DataTableError::EmptyInput);
+        }
+        let mut rows: Vec<Vec<String>> = vec![header.iter().enumerate().map(|(i, s)| if i == 0 { s.trim_start_matches(BOM) } else { s }.to_string()).collect()];
+        for record in rdr.records() {
+            let record = record?;
+            if record.len() != header.len() {
+                bail!(
+                    InvalidData,
+                    "Cannot transpose the table: line {} has {} fields, the header {} - every variable needs a value (or a missing token) per column",
+                    record.position().map_or(0, |pos| pos.line()), record.len(), header.len()
                 );
-                return false;
             }
-        };
-        let (n_rows, n_cols) = self.numeric_data.dim();
+            rows.push(record.iter().map(str::to_string).collect());
+        }
+        let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_writer(Vec::new());
+        for j in 0..header.len() {
+            writer.write_record(rows.iter().map(|row| row[j].as_str()))?;
+        }
+        writer.into_inner().map_err(|e| DataTableError::Stream(e.into_error()))
+    }
+
+    /// Replace the `na_values` sentinels by a missing token before anything looks at the cells.
+    fn mask_na_values(names: &[String], raw: &mut [Vec<String>], options: &ParseOptions) {
+        let Some(token) = options.missing_tokens.first() else { return };
+        for (name, cells) in names.iter().zip(raw.iter_mut()) {
+            let mut masked = 0;
+            for cell in cells.iter_mut().filter(|cell| options.is_na_value(name, cell)) {
+                *cell = token.clone();
+                masked += 1;
+            }
+            if masked > 0 {
+                log::info!("Column '{name}': {masked} missing value sentinel(s) read as missing");
+            }
+        }
+    }
+
+    /// Record the header name of the renamed columns (`ParseOptions::renames`).
+    fn mark_renamed(&mut self, options: &ParseOptions) {
+        for (original, new) in &options.renames {
+            if let Some(&id) = self.header_lookup.get(new) {
+                self.columns[id].original_name = Some(original.clone());
+            }
+        }
+    }
+
+    /// Find the columns of JSON objects - the declared `Json` columns and, as `json_cells` says,
+    /// the inferred ones. Flattened columns are replaced by one column per key in `names` and `raw`.
+    /// Returns the names of the `Json` columns (registered as factors to keep their texts).
+    fn json_columns(&mut self, names: &mut Vec<String>, raw: &mut Vec<Vec<String>>, options: &ParseOptions) -> Result<HashSet<String>> {
+        let mut json = HashSet::new();
+        let original: HashSet<String> = names.iter().cloned().collect();
+        let mut kept_names = Vec::with_capacity(names.len());
+        let mut kept_raw = Vec::with_capacity(raw.len());
+        for (name, cells) in names.drain(..).zip(raw.drain(..)) {
+            let declared = options.dtypes.get(&name) == Some(&Dtype::Json);
+            if declared
+                && let Some((i, value)) = cells.iter().enumerate().find(|(_, c)| !options.is_missing(c) && json_object(c).is_none())
+            {
+                // header is line 1
+                return Err(declared_mismatch(&name, i as u64 + 2, value, Dtype::Json));
+            }
+            let detected = options.json_cells != JsonCells::Off
+                && !declared
+                && !self.factors.contains_key(&name)
+                && !options.dtypes.contains_key(&name)
+                && !options.as_numeric.contains(&name)
+                && is_json_column(&cells, options);
+            if detected && options.json_cells == JsonCells::Flatten {
+                let columns = flatten_json(&name, &cells, options)?;
+                let flat: Vec<&str> = columns.iter().map(|(flat, _)| flat.as_str()).collect();
+                if let Some(taken) = flat.iter().find(|flat| original.contains(**flat)) {
+                    bail!(InvalidData, "Flattening the JSON column '{}' gives '{}' - the table already has a column of that name", name, taken);
+                }
+                log::info!("Flattening the JSON column '{name}' into {}", flat.join(", "));
+                for (flat, values) in columns {
+                    kept_names.push(flat);
+                    kept_raw.push(values);
+                }
+                continue;
+            }
+            if declared || detected {
+                if detected {
+                    log::info!("Column '{name}' holds JSON objects -> json, not a factor");
+                }
+                self.factors.insert(name.clone(), Factor::new(&name, false));
+                json.insert(name.clone());
+            }
+            kept_names.push(name);
+            kept_raw.push(cells);
+        }
+        *names = kept_names;
+        *raw = kept_raw;
+        Ok(json)
+    }
+
+    /// Set `Column::constant` from `numeric_data`. One-hot indicator columns are never constant.
+    pub(crate) fn mark_constant(&mut self) {
+        let indicators: HashSet<String> = self.factors.values().filter(|f| f.one_hot).flat_map(|f| f.all_column_names()).collect();
+        for (column, values) in self.columns.iter_mut().zip(self.numeric_data.columns()) {
+            let mut values = values.iter().filter(|v| !v.is_nan());
+            column.constant = match column.dtype {
+                _ if indicators.contains(&column.name) => false,
+                Dtype::FreeText | Dtype::Json => column.cardinality.is_some_and(|c| c <= 1),
+                _ => values.next().is_none_or(|first| values.all(|v| v == first)),
+            };
+        }
+    }
+
+    /// Remove the constant columns (see `Column::constant`) with their factors and one-hot columns.
+    /// Returns the names of the removed columns.
+    pub fn drop_constant_columns(&mut self) -> Vec<String> {
+        let constant: Vec<String> = self.columns.iter().filter(|c| c.constant).map(|c| c.name.clone()).collect();
+        self.drop_columns(&constant);
+        constant
+    }
+
+    /// Remove the columns without a single value (`Dtype::AllMissing`) - they are kept in the
+    /// schema otherwise. Returns the names of the removed columns.
+    pub fn drop_all_missing_columns(&mut self) -> Vec<String> {
+        let empty: Vec<String> = self.columns.iter().filter(|c| c.dtype == Dtype::AllMissing).map(|c| c.name.clone()).collect();
+        self.drop_columns(&empty);
+        empty
+    }
+
+    /// The fraction of rows with a value per column (`1 - missing / rows`), in column order.
+    /// One-hot indicator columns are left out - they share the coverage of their factor.
+    /// A table without rows has no coverage.
+    pub fn coverage(&self) -> Vec<(String, f64)> {
+        let rows = self.streamed_rows.unwrap_or(self.n_rows());
+        if rows == 0 {
+            return Vec::new();
+        }
+        let indicators: HashSet<String> = self.factors.values().filter(|f| f.one_hot).flat_map(|f| f.all_column_names()).collect();
+        self.columns
+            .iter()
+            .filter(|c| !indicators.contains(&c.name))
+            .map(|c| (c.name.clone(), 1.0 - c.missing as f64 / rows as f64))
+            .collect()
+    }
+
+    /// Remove the columns with a `coverage()` below `min_coverage` (e.g. `0.1`: fewer than
+    /// one in ten rows has a value) with their factors and one-hot columns.
+    /// Returns the removed columns with their coverage.
+    pub fn drop_sparse_columns(&mut self, min_coverage: f64) -> Vec<(String, f64)> {
+        let sparse: Vec<(String, f64)> = self.coverage().into_iter().filter(|(_, coverage)| *coverage < min_coverage).collect();
+        self.drop_columns(&sparse.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>());
+        sparse
+    }
+
+    /// Remove the columns in `dropped` from the table (headers, metadata and values) - their
+    /// factors stay.
+    pub(crate) fn drop_headers(&mut self, dropped: &HashSet<String>) {
+        if dropped.is_empty() {
+            return;
+        }
+        let keep: Vec<usize> = (0..self.headers.len()).filter(|&j| !dropped.contains(&self.headers[j])).collect();
+        self.numeric_data = self.numeric_data.select(Axis(1), &keep);
+        let headers = keep.iter().map(|&j| self.headers[j].clone()).collect();
+        let columns = keep.iter().map(|&j| self.columns[j].clone()).collect();
+        self.set_columns(headers, columns);
+    }
+
+    /// Remove columns with their factors and one-hot columns.
+    fn drop_columns(&mut self, names: &[String]) {
+        let mut dropped: HashSet<String> = names.iter().cloned().collect();
+        for name in names {
+            if let Some(factor) = self.factors.remove(name)
+                && factor.one_hot
+            {
+                dropped.extend(factor.all_column_names());
+            }
+        }
+        self.drop_headers(&dropped);
+    }
+
+    /// Put the columns in the order of `order`, the columns not listed after them in their
+    /// order so far. The one-hot columns of a factor stay right behind it. Values, factors and
+    /// summaries move with their column; an unknown, repeated or one-hot column name is an error.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::io::Cursor;
+    /// use rust_data_table::SurvivalData;
+    /// let mut data = SurvivalData::from_reader(Cursor::new("a\tb\tc\td\n1\t2\t3\t4\n"), b'\t', HashSet::new()).unwrap();
+    /// data.reorder_columns(&["c", "a"]).unwrap();
+    /// assert_eq!(data.column_names(), ["c", "a", "b", "d"]);
+    /// assert_eq!(data.as_vec_f64("c"), vec![3.0]);
+    /// ```
+    pub fn reorder_columns(&mut self, order: &[&str]) -> Result<()> {
+        // a one-hot column belongs to its factor
+        let parent: HashMap<String, &str> = self
+            .factors
+            .values()
+            .filter(|f| f.one_hot)
+            .flat_map(|f| f.all_column_names().into_iter().map(move |c| (c, f.column_name.as_str())))
+            .collect();
+        let mut listed: Vec<usize> = Vec::with_capacity(self.headers.len());
+        for &name in order {
+            let Some(&index) = self.header_lookup.get(name) else {
+                bail!(Column, "Cannot reorder: no column '{}'", name);
+            };
+            if let Some(factor) = parent.get(name) {
+                bail!(InvalidArgument, "Cannot reorder '{}' on its own - it is a one-hot column of '{}'", name, factor);
+            }
+            if listed.contains(&index) {
+                bail!(InvalidArgument, "Cannot reorder: column '{}' is listed twice", name);
+            }
+            listed.push(index);
+        }
+        let rest = (0..self.headers.len()).filter(|j| !listed.contains(j) && !parent.contains_key(&self.headers[*j]));
+        let mut keep: Vec<usize> = Vec::with_capacity(self.headers.len());
+        for j in listed.clone().into_iter().chain(rest) {
+            keep.push(j);
+            if let Some(factor) = self.factors.get(&self.headers[j]).filter(|f| f.one_hot) {
+                keep.extend(factor.all_column_names().iter().filter_map(|c| self.header_lookup.get(c).copied()));
+            }
+        }
+        self.numeric_data = self.numeric_data.select(Axis(1), &keep);
+        let headers = keep.iter().map(|&j| self.headers[j].clone()).collect();
+        let columns = keep.iter().map(|&j| self.columns[j].clone()).collect();
+        self.set_columns(headers, columns);
+        Ok(())
+    }
+
+    /// Store the (encoded) column names and their metadata.
+    pub(crate) fn set_columns(&mut self, headers: Vec<String>, columns: Vec<Column>) {
+        self.header_lookup = headers
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id))
+            .collect();
+        self.headers = headers;
+        self.columns = columns;
+    }
+
+    /// RFC-4180 quoting: "T cell, CD8+" keeps its delimiter, "" is a literal quote and a quoted
+    /// line break belongs to the field.
+    /// The input is transcoded to UTF-8 as `options.encoding` says.
+    fn csv_reader<'a, R: Read + 'a>(reader: R, options: &ParseOptions) -> csv::Reader<Box<dyn Read + 'a>> {
+        let reader: Box<dyn Read + 'a> = match &options.progress {
+            Some(progress) => Box::new(progress.wrap(reader)),
+            None => Box::new(reader),
+        };
+        let reader = options.encoding.decode(reader);
+        let (reader, delimiter) = match &options.separator {
+            Some(separator) => (separator.split(reader), SPLIT_DELIMITER),
+            None => (reader, options.delimiter),
+        };
+        csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .quote(b'"')
+            .double_quote(true)
+            // a split line has no quoting
+            .quoting(options.separator.is_none())
+            .comment(options.comment)
+            // `\r\n`, `\n` and `\r` all end a row - also mixed in one file
+            .terminator(csv::Terminator::CRLF)
+            // the row lengths are checked by complete_row()
+            .flexible(true)
+            .from_reader(reader)
+    }
+
+    /// Does the row have one field per column? Without `skip_bad_rows` a short or long row
+    /// is an `InconsistentRow` error (with its 1-based line), otherwise it is counted in `check`.
+    ///
+    /// With `TrailingDelimiter::Strip` an empty field more than expected is a delimiter at the
+    /// end of the line and removed. The first row decides whether the lines end with one - a
+    /// row ending otherwise is an error (or skipped).
+    fn complete_row(record: &mut csv::StringRecord, expected: usize, options: &ParseOptions, check: &mut RowCheck) -> Result<bool> {
+        let line = record.position().map_or(0, |pos| pos.line());
+        let trailing = options.trailing_delimiter == TrailingDelimiter::Strip
+            && record.len() == expected + 1
+            && record.get(expected).is_some_and(|field| field.trim().is_empty());
+        if record.len() == expected || trailing {
+            let (first_trailing, first_line) = *check.trailing.get_or_insert((trailing, line));
+            if trailing == first_trailing {
+                record.truncate(expected);
+                return Ok(true);
+            }
+            if !options.skip_bad_rows {
+                let (with, without) = if trailing { (line, first_line) } else { (first_line, line) };
+                bail!(
+                    InvalidData,
+                    "Inconsistent trailing delimiters: line {} ends with a delimiter, line {} does not - fix the file or read it with skip_bad_rows",
+                    with, without
+                );
+            }
+        }
+        if options.skip_bad_rows {
+            check.skipped += 1;
+            return Ok(false);
+        }
+        Err(DataTableError::InconsistentRow { line, found: record.len(), expected })
+    }
+
+    /// `err` of one of the files `from_files()` combines, with its path.
+    fn in_file<P: AsRef<Path>>(path: P, err: DataTableError) -> DataTableError {
+        DataTableError::InFile { path: path.as_ref().to_path_buf(), source: Box::new(err) }
+    }
+
+    fn report_skipped(skipped: usize) {
+        if skipped > 0 {
+            log::warn!("Skipped {skipped} row(s) with the wrong number of fields (skip_bad_rows)");
+        }
+    }
+
+    /// The column names of the file. A header ending with a delimiter is settled by the first
+    /// row, which is left in `check` for the caller to read (`RowCheck::read()`).
+    fn file_column_names<R: Read>(rdr: &mut csv::Reader<R>, options: &ParseOptions, check: &mut RowCheck) -> Result<Vec<String>> {
+        Self::skip_to_header(rdr, options)?;
+        let header = rdr.headers()?;
+        // a blank file is read as one nameless column
+        if header.iter().all(|s| s.trim_start_matches(BOM).trim().is_empty()) && header.len() <= 1 {
+            return Err(DataTableError::EmptyInput);
+        }
+        let mut header = header.clone();
+        // a delimiter at the end of the header line
+        if header.len() > 1 && header.get(header.len() - 1).is_some_and(|name| name.trim().is_empty()) {
+            let n = header.len();
+            match options.trailing_delimiter {
+                TrailingDelimiter::Strip => {
+                    // a value below the empty name makes it a column of its own
+                    let mut first = csv::StringRecord::new();
+                    let has_row = rdr.read_record(&mut first)?;
+                    if !(has_row && first.get(n - 1).is_some_and(|field| !field.trim().is_empty())) {
+                        log::info!("The header ends with a delimiter - the empty last column is dropped");
+                        header.truncate(n - 1);
+                    }
+                    check.pending = has_row.then_some(first);
+                }
+                TrailingDelimiter::Keep => {
+                    header = header.iter().take(n - 1).chain([format!("V{n}").as_str()]).collect();
+                }
+            }
+        }
+        let names = header
+            .iter()
+            .enumerate()
+            // a byte order mark is no part of the first column name
+            .map(|(i, s)| if i == 0 { s.trim_start_matches(BOM) } else { s })
+            .map(|s| {
+                // 💡 handle R-style empty header (rownames column)
+                if s.trim().is_empty() {
+                    log::warn!("Detected unlabeled first(?) column -> treating as 'barcode'");
+                    "barcode".to_string()
+                } else {
+                    s.to_string()
+                }
+            })
+            .collect();
+        options.rename_header(options.unique_names(names)?)
+    }
+
+    /// Pass the `header_row` title lines: the reader took the first line as header, so the
+    /// line after the titles replaces it. Comment and blank lines are skipped by the reader.
+    fn skip_to_header<R: Read>(rdr: &mut csv::Reader<R>, options: &ParseOptions) -> Result<()> {
+        if options.header_row == 0 {
+            return Ok(());
+        }
+        rdr.headers()?;
+        let mut header = csv::StringRecord::new();
+        for _ in 0..options.header_row {
+            if !rdr.read_record(&mut header)? {
+                bail!(InvalidData, "The table ends before its header: it has less than {} lines above one (header_row)", options.header_row);
+            }
+        }
+        rdr.set_headers(header);
+        Ok(())
+    }
+
+    /// Read the column names and register the factors declared in `options`.
+    /// Returns the names of the selected columns (`ParseOptions::columns`) and where they are in a record.
+    fn read_header<R: Read>(&mut self, rdr: &mut csv::Reader<R>, options: &ParseOptions, check: &mut RowCheck) -> Result<(Vec<String>, Selection)> {
+        let all = Self::file_column_names(rdr, options, check)?;
+        options.check_columns(&all)?;
+        self.delimiter = options.delimiter;
+        self.file_header = all.clone();
+        let selection = Selection {
+            width: all.len(),
+            keep: options.columns.keep_indices(&all),
+        };
+        let names: Vec<String> = selection.keep.iter().map(|&i| all[i].clone()).collect();
+
+        // forced categorical columns are factors
+        for name in &names {
+            if let Some(spec) = options.factor_specs.get(name) {
+                // an explicit spec wins over a factors file
+                self.factors.insert(name.clone(), Factor::from_spec(name, spec).starting_at(options.first_code()));
+            } else if options.as_string.contains(name) {
+                log::debug!("Forcing header {name} to be free text");
+                self.factors.insert(name.clone(), Factor::new(name, false).declared());
+            } else if options.categorical.contains(name)
+                || matches!(options.dtypes.get(name), Some(Dtype::Factor | Dtype::FreeText))
+            {
+                log::debug!("Forcing header {name} to be a factor");
+                self.factors
+                    .entry(name.clone())
+                    .or_insert_with(|| Factor::new(name, false).declared().starting_at(options.first_code()));
+            }
+            if let Some(codes) = options.level_codes.get(name) {
+                self.factors
+                    .entry(name.clone())
+                    .or_insert_with(|| Factor::new(name, false).declared().starting_at(options.first_code()))
+                    .pin_codes(codes);
+            }
+        }
+        Ok((names, selection))
+    }
+
+    /// The level a factor cell belongs to.
+    /// Numbers are stored with their canonical text (`1.0` -> `1`), missing cells get
+    /// the empty key - the Factor maps it to NaN - and a `FactorSpec` may map the value.
+    fn factor_key(name: &str, cell: &str, options: &ParseOptions) -> Result<String> {
+        if options.is_missing(cell) {
+            return Ok(String::new());
+        }
+        if options.as_string.contains(name) {
+            return Ok(cell.to_string());
+        }
+        let cell = options.level_case.apply(cell);
+        if let Some(spec) = options.factor_specs.get(name) {
+            return Ok(spec.resolve(name, &cell)?.to_string());
+        }
+        // numbers are normalized (`1.0` = `1`) - identifiers like `007` keep their zeros
+        Ok(match parse_float(&cell) {
+            Some(num) if !options.keeps_leading_zero(&cell) => num.to_string(),
+            _ => cell,
+        })
+    }
+
+    /// Remember a raw `cell` behind a factor level - levels reached by several spellings are
+    /// reported with `Factor::note_spellings()` once a column is done.
+    fn add_spelling(spellings: &mut BTreeMap<String, BTreeSet<String>>, key: &str, cell: &str, options: &ParseOptions) {
+        if options.level_case.folds() && !key.is_empty() {
+            spellings.entry(key.to_string()).or_default().insert(cell.to_string());
+        }
+    }
+
+    /// Report the levels of `factor` several spellings were normalized to.
+    fn note_spellings(factor: &mut Factor, spellings: &BTreeMap<String, BTreeSet<String>>) {
+        for (level, raw) in spellings.iter().filter(|(_, raw)| raw.len() > 1) {
+            factor.note_spellings(level, &raw.iter().collect::<Vec<_>>());
+        }
+    }
+
+    /// Encode the cells of a factor column.
+    /// Returns the column itself plus - for one-hot factors - one 0/1 column per level.
+    /// Values not covered by a `FactorSpec` are an error (or go to its `other` level).
+    /// Missing cells never become a level.
+    fn encode_factor(
+        &mut self,
+        name: &str,
+        cells: &[String],
+        options: &ParseOptions,
+    ) -> Result<Vec<(String, Vec<f64>)>> {
+        let factor = self
+            .factors
+            .entry(name.to_string())
+            .or_insert_with(|| Factor::new(name, false).starting_at(options.first_code()));
+        // levels from a factors file or a spec keep their order
+        let inferred = factor.get_levels().is_empty();
+
+        let keys: Vec<String> = cells
+            .iter()
+            .map(|c| Self::factor_key(name, c, options))
+            .collect::<Result<_>>()?;
+        // register (and count) all levels first - the one-hot columns need the final level list
+        let mut spellings = BTreeMap::new();
+        for (key, cell) in keys.iter().zip(cells) {
+            factor.count(key);
+            Self::add_spelling(&mut spellings, key, cell, options);
+        }
+        Self::note_spellings(factor, &spellings);
+        if options.sort_levels && inferred {
+            factor.sort_levels();
+        }
+        if let Some(spec) = options.factor_specs.get(name) {
+            factor.apply_counted_spec(spec)?;
+        }
+        let codes: Vec<f64> = keys.iter().map(|k| factor.get_f64(k)).collect();
+
+        let mut ret = vec![(name.to_string(), codes.clone())];
+        if factor.one_hot {
+            for (level, header) in factor.get_levels().iter().zip(factor.all_column_names()) {
+                let indicator = keys
+                    .iter()
+                    .zip(codes.iter())
+                    .map(|(k, code)| if code.is_nan() { f64::NAN } else if factor.level_of(k) == level { 1.0 } else { 0.0 })
+                    .collect();
+                ret.push((header, indicator));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// High-level loader: reads the table and applies the factors file if it exists.
+    /// A thin wrapper around `SurvivalDataBuilder` - use the builder for more settings.
+    ///
+    /// The factors file is never written here - use `compute_factors()` to inspect
+    /// the inferred factors and `write_factors()` to store them.
+    pub fn from_file<P: AsRef<Path> + std::fmt::Debug, FF: AsRef<Path> + std::fmt::Debug>(
+        file_path: P,
+        delimiter: u8,
+        categorical_cols: HashSet<String>,
+        factors_file: FF,
+    ) -> Result<Self> {
+        SurvivalDataBuilder::new()
+            .delimiter(delimiter)
+            .categorical(categorical_cols)
+            .factors_file(factors_file)
+            .build_from_file(file_path)
+    }
+
+    /// Start configuring the parser - see `SurvivalDataBuilder`.
+    pub fn builder() -> SurvivalDataBuilder {
+        SurvivalDataBuilder::new()
+    }
+
+    /// Add a new data row (group) initialized with NaN values across all columns (cells).
+    ///
+    /// Each row represents a dataset or feature.
+    /// If this is the first row added, you must specify the expected number of columns via `size`.
+    /// If `is_factor` is true, a corresponding `Factor` is also created and registered.
+    ///
+    /// # Panics
+    /// Panics if the data table is empty and no `size` is provided.
+    ///
+    /// # Example
+    /// ```
+    /// use rust_data_table::SurvivalData;
+    /// let mut data = SurvivalData::default();
+    /// data.add_dataset("age", false, Some(10));     // first row → needs size
+    /// data.add_dataset("treatment", true, None);    // second row → inferred columns
+    /// ```
+    pub fn add_dataset(&mut self, dataset_name: &str, is_factor: bool, size: Option<usize>) {
+        // Don't add the same dataset twice
+        if self.headers.contains(&dataset_name.to_string()) {
+            log::debug!("Row '{}' already exists, skipping.", dataset_name);
+            return;
+        }
+
+        let n_rows = self.numeric_data.nrows();
+        let n_cols = self.numeric_data.ncols();
+
+        // Case 1: Empty table — initialize with one row and known number of columns
+        if n_rows == 0 {
+            let init_cols = size.unwrap_or_else(|| {
+                panic!("🛑 Must provide 'size' (number of cells) when adding the first dataset!");
+            });
+            self.numeric_data = Array2::<f64>::from_elem((1, init_cols), f64::NAN);
+        } else {
+            // Case 2: Append a new row of NaN across existing columns
+            let new_row = Array2::<f64>::from_elem((1, n_cols), f64::NAN);
+            self.numeric_data = concatenate(Axis(0), &[self.numeric_data.view(), new_row.view()])
+                .expect("❌ Failed to concatenate new row");
+        }
+
+        // Register dataset name (row name)
+        self.headers.push(dataset_name.to_string());
+        let dtype = if is_factor { Dtype::Factor } else { Dtype::Float };
+        self.columns.push(Column::new(dataset_name, dtype));
+
+        // Register mapping for fast lookup
+        let row_idx = self.numeric_data.nrows() - 1;
+
+        self.header_lookup.insert(dataset_name.to_string(), row_idx);
+
+        // Optionally register a Factor
+        if is_factor {
+            self.factors
+                .insert(dataset_name.to_string(), Factor::new(dataset_name, false));
+        }
+
+        log::debug!(
+            "Added dataset row '{}' (factor={}) → total rows: {}, columns: {}",
+            dataset_name,
+            is_factor,
+            self.numeric_data.nrows(),
+            self.numeric_data.ncols()
+        );
+    }
+
+    /// Update a single cell by dataset (row) name and sample (column) index.
+    /// Returns true if successful, false if the dataset or index is invalid.
+    pub fn update_value(&mut self, dataset_name: &str, col: usize, value: f64) -> bool {
+        // Look up the row index for this dataset
+        let row_idx = match self.header_lookup.get(dataset_name) {
+            Some(idx) => idx,
+            None => {
+                log::warn!(
+                    "update_value(): dataset '{}' not found. Headers: {:?}",
+                    dataset_name, self.headers
+                );
+                return false;
+            }
+        };
+
+        let (n_rows, n_cols) = self.numeric_data.dim();
+        if *row_idx >= n_rows {
+            log::warn!(
+                "Row index {} out of bounds (rows={}) for dataset '{}'",
+                row_idx, n_rows, dataset_name
+            );
+            return false;
+        }
+        if col >= n_cols {
+            log::warn!(
+                "Column index {} out of bounds (cols={}) for dataset '{}'",
+                col, n_cols, dataset_name
+            );
+            return false;
+        }
+
+        if self.numeric_data[[*row_idx, col]] == value{
+            false
+        }else {
+            self.numeric_data[[*row_idx, col]] = value;
+            true
+        }
+    }
+
+    /// Update a single cell by dataset (row) name and sample (column) index.
+    /// The value will represent the order in which this function is called.
+    /// reset the order with self.reset_order().
+    pub fn update_order(&mut self, dataset_name: &str, col: usize) -> bool {
+        // Look up the row index for this dataset
+        let row_idx = match self.header_lookup.get(dataset_name) {
+            Some(idx) => idx,
+            None => {
+                log::warn!(
+                    "update_value(): dataset '{}' not found. Headers: {:?}",
+                    dataset_name, self.headers
+                );
+                return false;
+            }
+        };
+        let (n_rows, n_cols) = self.numeric_data.dim();
         if *row_idx >= n_rows {
-            eprintln!(
-                "🛑 Row index {} out of bounds (rows={}) for dataset '{}'",
+            log::warn!(
+                "Row index {} out of bounds (rows={}) for dataset '{}'",
                 row_idx, n_rows, dataset_name
             );
             return false;
         }
         if col >= n_cols {
-            eprintln!(
-                "🛑 Column index {} out of bounds (cols={}) for dataset '{}'",
+            log::warn!(
+                "Column index {} out of bounds (cols={}) for dataset '{}'",
                 col, n_cols, dataset_name
             );
             return false;
@@ -432,7 +1570,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
             match value.trim().parse::<f64>() {
                 Ok(v) => self.update_value(dataset_name, col, v),
                 Err(_) => {
-                    eprintln!(
+                    log::warn!(
                         "Value '{}' not recognized for column '{}'",
                         value, dataset_name
                     );
@@ -450,7 +1588,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
 
         let n_rows = self.numeric_data.nrows();
         let mut indices: Vec<usize> = (0..n_rows).collect();
-        let mut rng = thread_rng();
+        let mut rng = rng();
         indices.shuffle(&mut rng);
 
         let train_size = (n_rows as f64 * train_fraction).round() as usize;
@@ -458,23 +1596,55 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         let train_idx = &indices[..train_size];
         let test_idx = &indices[train_size..];
 
-        let train_data = self.numeric_data.select(Axis(0), train_idx);
-        let test_data = self.numeric_data.select(Axis(0), test_idx);
-
+        (self.select_rows(train_idx), self.select_rows(test_idx))
+    }
 
-        let make_subset = |data: Array2<f64>| SurvivalData {
+    /// A table with only these `rows` (in this order) - columns and factors are kept as they are.
+    pub(crate) fn select_rows(&self, rows: &[usize]) -> SurvivalData {
+        SurvivalData {
             headers: self.headers.clone(),
-            numeric_data: data,
+            numeric_data: self.numeric_data.select(Axis(0), rows),
             factors: self.factors.clone(),
+            columns: self.columns.clone(),
             exclude: self.exclude.clone(),
+            survival: self.survival.clone(),
             header_lookup: self.header_lookup.clone(),
+            delimiter: self.delimiter,
+            file_header: self.file_header.clone(),
             ..Default::default()
-        };
+        }
+    }
 
-
-        (make_subset(train_data), make_subset(test_data))
+    /// Bring factors and column metadata in line with the current rows: factors keep only
+    /// the levels that still have rows (codes unchanged, see `Factor::subset()`), the one-hot
+    /// columns of dropped levels go and the missing counts, cardinalities and numeric summaries are recounted.
+    pub(crate) fn drop_unused_levels(&mut self) {
+        let mut dropped: HashSet<String> = HashSet::new();
+        for factor in self.factors.values_mut() {
+            let Some(&col_id) = self.header_lookup.get(&factor.column_name) else { continue };
+            let subset = factor.subset(&self.numeric_data, col_id);
+            if factor.one_hot {
+                let kept = subset.all_column_names();
+                dropped.extend(factor.all_column_names().into_iter().filter(|name| !kept.contains(name)));
+            }
+            *factor = subset;
+        }
+        self.drop_headers(&dropped);
+        for (column, values) in self.columns.iter_mut().zip(self.numeric_data.columns()) {
+            column.missing = values.iter().filter(|v| v.is_nan()).count();
+            // which of the kept rows were empty is not known any more
+            column.missing_kinds = None;
+            if let Some(factor) = self.factors.get(&column.name) {
+                column.cardinality = Some(factor.get_levels().len());
+            }
+            if column.summary.is_some() {
+                column.summary = Some(NumericSummary::from_values(&values.to_vec()));
+            }
+        }
+        self.mark_constant();
     }
 
+    #[allow(dead_code)]
     fn header_error( &self, i: usize, expanded: usize) -> String {
         let mut parts:Vec<String> = Vec::with_capacity( self.factors.len() + 1 );
         for factor in self.factors.values() {
@@ -487,6 +1657,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         parts.join("\n")
     }
 
+    #[allow(unreachable_code)]
     pub fn data_summary( &self ) {
         return ;
         println!("Shape: {} rows x {} columns", self.numeric_data.nrows(), self.numeric_data.ncols());
@@ -510,6 +1681,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         }
     }
 
+    #[allow(dead_code)]
     fn factors_extra_columns( &self ) -> usize {
         let mut ret = 0;
         for factor in &self.factors {
@@ -519,19 +1691,19 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
     }
 
     /// Remove all rows that contain any NaN in numeric_data
-    pub fn filter_all_na_rows(&mut self, usable:&Vec<String>) {
+    pub fn filter_all_na_rows(&mut self, usable:&[String]) {
         let n_rows = self.numeric_data.nrows();
         let n_cols = self.numeric_data.ncols();
-        println!("filter_all_na_rows got {} rows and {} columns and checks {} of these columns for na's", 
+        log::debug!("filter_all_na_rows got {} rows and {} columns and checks {} of these columns for na's", 
             n_rows, n_cols, usable.len() );
 
         // Build a lookup of usable column indices
         let usable_indices: Vec<usize> = usable
             .iter()
-            .filter_map(|col| self.headers.iter().position(|h| h == col))
+            .filter_map(|col| self.column_index(col))
             .collect();
         if usable_indices.len() == n_rows {
-            println!("No rows containing NA values found");
+            log::info!("No rows containing NA values found");
             return
         }
         // Determine which rows to keep
@@ -543,7 +1715,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
             })
             .collect();
         if keep_rows.len() == n_rows {
-            println!("No na's found in the {} columns", usable.len() );
+            log::info!("No na's found in the {} columns", usable.len() );
             return
         }
 
@@ -560,7 +1732,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         let mut new_factors = HashMap::<String,Factor>::new();
 
         for factor in self.factors.values() {
-            if let Some(col_id) = self.headers.iter().position(|h| h == &factor.column_name) {
+            if let Some(col_id) = self.column_index(&factor.column_name) {
                 new_factors.insert( 
                     factor.column_name.clone(), 
                     factor.subset( &self.numeric_data, col_id ) 
@@ -571,7 +1743,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         }
         self.factors = new_factors;
 
-        println!(
+        log::info!(
             "Filtered out {} rows containing NaNs. Remaining rows: {}",
             n_rows - keep_rows.len(),
             keep_rows.len()
@@ -582,9 +1754,9 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
     ///
     /// * `k` – number of neighbours to use (e.g. 3 for “mean of 3 closest”).
     /// * `min_common` – minimum number of shared non-NA features required
-    ///                  between two rows to consider them neighbours.
+    ///   between two rows to consider them neighbours.
     /// * `weighted` – if `true`, use distance-weighted mean;
-    ///                if `false`, simple mean of the k neighbours.
+    ///   if `false`, simple mean of the k neighbours.
     ///
     /// Missing values are represented as `f64::NAN`.
     pub fn impute_knn(&mut self, k: usize, min_common: usize, weighted: bool) {
@@ -605,18 +1777,12 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
             let std  = var.sqrt().max(eps);
             col_means[j] = mean;
             col_stds[j]  = std;
-            for j in 0..n_cols {
-                if self.factors.contains_key(&self.headers[j]) {
-                    // factor column → use mode of non-NA values
-                    let mut counts = std::collections::HashMap::new();
-                    for &v in &vals { *counts.entry(v as usize).or_insert(0) += 1; }
-                    let &mode = counts.iter().max_by_key(|(_, c)| *c).unwrap().0;
-                    col_means[j] = mode as f64;  // for later distance scaling, can still normalize if needed
-                } else {
-                    // numeric → mean
-                    let mean = vals.iter().sum::<f64>() / vals.len() as f64;
-                    col_means[j] = mean;
-                }
+            if self.factors.contains_key(&self.headers[j]) {
+                // factor column → use mode of non-NA values
+                let mut counts = std::collections::HashMap::new();
+                for &v in &vals { *counts.entry(v as usize).or_insert(0) += 1; }
+                let &mode = counts.iter().max_by_key(|(_, c)| *c).unwrap().0;
+                col_means[j] = mode as f64;  // for later distance scaling, can still normalize if needed
             }
         }
 
@@ -721,6 +1887,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
     }
 
     /// Remove columns with variance below `threshold`
+    #[allow(unreachable_code, unused_variables)]
     pub fn filter_low_var(&mut self, threshold: f64) -> usize{
         return 0;
         let mut keep_cols = Vec::new();
@@ -730,7 +1897,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
             let vals: Vec<f64> = col.iter().copied().filter(|v| !v.is_nan()).collect();
             if vals.is_empty() {
                 filtered+=1;
-                println!("Dropping empty column: {}", self.headers[j]);
+                log::info!("Dropping empty column: {}", self.headers[j]);
                 continue; 
             }
 
@@ -741,21 +1908,24 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
                 keep_cols.push(j);
             } else {
                 filtered+=1;
-                println!("Dropping low-variance column (mean {mean:2e}, var {var:2e}): {}", self.headers[j]);
+                log::info!("Dropping low-variance column (mean {mean:2e}, var {var:2e}): {}", self.headers[j]);
             }
         }
 
         // Rebuild numeric_data and headers
         let mut filtered_data = Array2::<f64>::zeros((self.numeric_data.nrows(), keep_cols.len()));
         let mut new_headers = Vec::with_capacity(keep_cols.len());
+        let mut new_columns = Vec::with_capacity(keep_cols.len());
 
         for (new_j, &old_j) in keep_cols.iter().enumerate() {
             filtered_data.column_mut(new_j).assign(&self.numeric_data.column(old_j));
             new_headers.push(self.headers[old_j].clone());
+            new_columns.push(self.columns[old_j].clone());
         }
 
         self.numeric_data = filtered_data;
         self.headers = new_headers;
+        self.columns = new_columns;
 
         // Remove factors whose columns were dropped
         self.factors.retain(|name, _| self.headers.contains(name));
@@ -811,12 +1981,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
             Some(cols) => {
                 let indices: Vec<usize> = cols
                 .iter()
-                .map(|c| {
-                    self.headers
-                    .iter()
-                    .position(|h| h == c)
-                    .expect("Column not found")
-                })
+                .map(|c| self.column_index(c).expect("Column not found"))
                 .collect();
                 let mut arr = Array2::<f64>::zeros((self.numeric_data.nrows(), indices.len()));
                 for (j, &col_idx) in indices.iter().enumerate() {
@@ -831,12 +1996,76 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
 
     /// Return a single column as Vec<f64>
     pub fn as_vec_f64(&self, column: &str) -> Vec<f64> {
-        let idx = self.headers.iter().position(|h| h == column)
+        let idx = self.column_index(column)
             .unwrap_or_else(|| panic!("Column '{}' not found in the dataset; all columns: \n{}", 
                 column, self.headers(20).join("\n")));
         self.numeric_data.column(idx).to_vec()
     }
 
+    /// The metadata (detected type etc.) of one column.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.header_lookup.get(name).and_then(|&id| self.columns.get(id))
+    }
+
+    /// The position of a column in `column_names()` (and in `numeric_data`).
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.header_lookup.get(name).copied()
+    }
+
+    /// The code `level` is stored as in the factor column `column` (see `Factor::code_of()`) -
+    /// `None` if `column` is no factor or has no such level.
+    pub fn code_of(&self, column: &str, level: &str) -> Option<u32> {
+        self.factor_column(column)?.code_of(level)
+    }
+
+    /// The level behind `code` in the factor column `column` - the reverse of `code_of()`.
+    pub fn level_of(&self, column: &str, code: u32) -> Option<&str> {
+        self.factor_column(column)?.level_of_code(code)
+    }
+
+    /// The factor of a `Dtype::Factor` column - free text has no codes to look up.
+    fn factor_column(&self, column: &str) -> Option<&Factor> {
+        self.factors.get(column).filter(|_| self.column(column).is_some_and(|c| c.dtype == Dtype::Factor))
+    }
+
+    /// The metadata of the column at position `index` (in `column_names()` order).
+    pub fn column_at(&self, index: usize) -> Option<&Column> {
+        self.columns.get(index)
+    }
+
+    /// All (encoded) column names in order - one-hot columns follow their factor column.
+    pub fn column_names(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// The field delimiter the table was read with - the detected one if the builder or the
+    /// command line auto-detected it. Pass it to `write_tsv()` to write the table back the same way.
+    /// A multi-character `ParseOptions::separator` is not reflected.
+    pub fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    /// The rows read and the rows dropped with their reasons: rows with the wrong number of
+    /// fields (`skip_bad_rows`) and - once survival columns are declared - the rows without
+    /// survival time or event. Empty if the table was not parsed from a file or reader.
+    pub fn parse_report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    /// The column names of the file header as read: all columns (before `ParseOptions::columns`),
+    /// without one-hot columns. Empty if the table was not parsed from a file or reader.
+    pub fn header(&self) -> &[String] {
+        &self.file_header
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.numeric_data.nrows()
+    }
+
+    pub fn n_cols(&self) -> usize {
+        self.numeric_data.ncols()
+    }
+
     /// Return a single column as Vec<u8>
     pub fn as_vec_u8(&self, column: &str) -> Vec<u8> {
         let idx = self.headers.iter().position(|h| h == column)
@@ -886,7 +2115,7 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         for i in 0..self.numeric_data.nrows() {
             let mut record: Vec<String> = Vec::with_capacity(self.headers.len());
 
-            for (j, col_name) in self.headers.iter().enumerate() {
+            for j in 0..self.headers.len() {
                 let val = self.numeric_data[[i, j]];
                 if val.is_nan() {
                     record.push("NA".to_string());
@@ -902,25 +2131,192 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
         Ok(())
     }
 
-    /// Save all factors to a JSON file
-    pub fn save_factors<P: AsRef<Path>>(&self, path: P) -> Result<()>  {
-        let defs: Vec<FactorJson> = self.factors.iter()
-        .map(|(col, factor)| factor.as_json( col ) )
-        .collect();
-        let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &defs)?;
+    /// Apply display labels to factor levels (see `Factor::relabel()`).
+    /// The data and the numeric codes are untouched; `compute_factors()` reports the labels.
+    pub fn relabel_levels(&mut self, labels: &LevelLabels) -> Result<()> {
+        for (column, map) in labels {
+            match self.factors.get_mut(column) {
+                Some(factor) => factor.relabel(map)?,
+                None => bail!(Column, "Cannot relabel '{}': it is not a factor column", column),
+            }
+        }
         Ok(())
     }
 
-    /// Load factors from a JSON file
-    pub fn load_factors<P: AsRef<Path>>(&mut self, path: P) -> Result<()>  {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let defs: Vec<FactorJson> = serde_json::from_reader(reader)?;
-        for def in defs {
-            self.factors.insert(def.column.clone(), Factor::from_def(&def));
+    /// Write the table as delimited text: factor columns with their labels,
+    /// missing values as `NA`. Fields containing the delimiter are quoted.
+    pub fn write_tsv<P: AsRef<Path>>(&self, path: P, delimiter: u8) -> Result<()> {
+        self.write_tsv_as(path, delimiter, FactorFormat::Labels)
+    }
+
+    /// Like `write_tsv()`, but `format` decides whether factor columns hold
+    /// their labels or their numeric codes (as defined in the factors JSON).
+    pub fn write_tsv_as<P: AsRef<Path>>(&self, path: P, delimiter: u8, format: FactorFormat) -> Result<()> {
+        let mut wtr = WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_path(path)?;
+        wtr.write_record(&self.headers)?;
+
+        let factors: Vec<Option<&Factor>> = match format {
+            FactorFormat::Labels => self.headers.iter().map(|h| self.factors.get(h)).collect(),
+            FactorFormat::Codes => vec![None; self.headers.len()],
+        };
+        // dates are written as ISO-8601 whatever format they were read in
+        let date_formats: Vec<Option<&str>> = self.headers
+            .iter()
+            .map(|h| self.column(h).filter(|c| c.dtype == Dtype::Date).map(|c| c.format.as_deref().unwrap_or(DATE_FORMATS[0])))
+            .collect();
+        for row in self.numeric_data.rows() {
+            let record = row.iter().zip(&factors).zip(&date_formats).map(|((&value, factor), date)| match (factor, date) {
+                _ if value.is_nan() => "NA".to_string(),
+                (Some(factor), _) => factor.get_string(value),
+                (None, Some(format)) => format_date(value, format),
+                (None, None) => value.to_string(),
+            });
+            wtr.write_record(record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Write the table as newline delimited JSON - one object per row, keys in column order.
+    ///
+    /// Values follow the column type: integers and floats are JSON numbers, booleans `true`/`false`,
+    /// factors their level labels and dates ISO-8601 strings. Missing values are `null`.
+    pub fn write_ndjson<W: Write>(&self, mut writer: W) -> Result<()> {
+        for row in self.numeric_data.rows() {
+            self.write_ndjson_row(&mut writer, &row.to_vec())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write one encoded row (one value per entry in `headers`) as a line of `write_ndjson()` -
+    /// e.g. from `stream_rows()` for a table that does not fit into memory.
+    pub fn write_ndjson_row<W: Write>(&self, writer: &mut W, row: &[f64]) -> Result<()> {
+        writer.write_all(b"{")?;
+        for (j, (name, &value)) in self.headers.iter().zip(row).enumerate() {
+            if j > 0 {
+                writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut *writer, name)?;
+            writer.write_all(b":")?;
+            serde_json::to_writer(&mut *writer, &self.json_value(j, value))?;
+        }
+        writer.write_all(b"}\n")?;
+        Ok(())
+    }
+
+    /// A JSON Schema (draft-07) of the rows `write_ndjson()` writes: numbers are `number` or
+    /// `integer`, factors `string` with their levels as `enum`, booleans `boolean`, dates
+    /// `string` with format `date`/`date-time`, free text `string`, JSON cells `object` and
+    /// columns without any value `null`. Columns with missing values also allow `null`.
+    pub fn json_schema(&self) -> serde_json::Value {
+        use serde_json::{json, Map, Value};
+        let mut properties = Map::new();
+        for (j, name) in self.headers.iter().enumerate() {
+            let Some(column) = self.columns.get(j) else { continue };
+            let (kind, mut property) = match (column.dtype, self.factors.get(name)) {
+                (Dtype::Factor, Some(factor)) => {
+                    let levels: Vec<Value> = factor.get_levels().iter().map(|level| Value::from(level.as_str())).collect();
+                    ("string", json!({ "enum": levels }))
+                }
+                (Dtype::FreeText, _) => ("string", json!({})),
+                (Dtype::Json, _) => ("object", json!({})),
+                (Dtype::Boolean, _) => ("boolean", json!({})),
+                (Dtype::Date, _) => {
+                    let format = if column.format.as_deref().is_some_and(|f| f.contains("%H")) { "date-time" } else { "date" };
+                    ("string", json!({ "format": format }))
+                }
+                (Dtype::Integer, _) => ("integer", json!({})),
+                (Dtype::AllMissing, _) => ("null", json!({})),
+                _ => ("number", json!({})),
+            };
+            property["type"] = if column.missing > 0 && kind != "null" { json!([kind, "null"]) } else { json!(kind) };
+            if column.missing > 0 && let Some(Value::Array(levels)) = property.get_mut("enum") {
+                levels.push(Value::Null);
+            }
+            properties.insert(name.clone(), property);
+        }
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": properties,
+            "required": self.headers,
+            "additionalProperties": false,
+        })
+    }
+
+    /// The JSON value of an encoded cell of column `j`.
+    fn json_value(&self, j: usize, value: f64) -> serde_json::Value {
+        use serde_json::Value;
+        if value.is_nan() {
+            return Value::Null;
+        }
+        if let Some(factor) = self.factors.get(&self.headers[j]) {
+            let text = factor.get_string(value);
+            // a JSON cell is written as the object itself
+            if self.columns.get(j).is_some_and(|c| c.dtype == Dtype::Json)
+                && let Some(object) = json_object(&text)
+            {
+                return Value::Object(object);
+            }
+            return Value::String(text);
+        }
+        match self.columns.get(j) {
+            Some(column) if column.dtype == Dtype::Boolean => Value::Bool(value != 0.0),
+            Some(column) if column.dtype == Dtype::Date => {
+                Value::String(format_date(value, column.format.as_deref().unwrap_or(DATE_FORMATS[0])))
+            }
+            Some(column) if column.dtype == Dtype::Integer => Value::from(value as i64),
+            _ => Value::from(value),
+        }
+    }
+
+    /// The column types and factor definitions of this table as a value - in column order.
+    /// Factors without a matching column follow sorted by name; free text columns have no entry.
+    pub fn compute_factors(&self) -> FactorMap {
+        // free text columns are stored as factors, but their levels are not worth saving
+        let free_text: HashSet<&String> = self.columns
+            .iter()
+            .filter(|c| matches!(c.dtype, Dtype::FreeText | Dtype::Json))
+            .map(|c| &c.name)
+            .collect();
+        let mut names: Vec<&String> = self.headers
+            .iter()
+            .filter(|h| self.factors.contains_key(*h) && !free_text.contains(h))
+            .collect();
+        let mut orphans: Vec<&String> = self.factors
+            .keys()
+            .filter(|name| !self.header_lookup.contains_key(*name))
+            .collect();
+        orphans.sort();
+        names.extend(orphans);
+
+        FactorMap {
+            columns: self.columns.clone(),
+            factors: names.into_iter()
+                .map(|name| self.factors[name].as_json(name))
+                .collect(),
+            n_rows: Some(self.streamed_rows.unwrap_or(self.n_rows())),
+            ..Default::default()
         }
+    }
+
+    /// Write the factor definitions (see `compute_factors()`) to a JSON file - or a table
+    /// if `path` ends in `.tsv` (see `FactorMap::write()`)
+    pub fn write_factors<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.compute_factors().write(path)
+    }
+
+    /// Save all factors to a JSON file - same as `write_factors()`
+    pub fn save_factors<P: AsRef<Path>>(&self, path: P) -> Result<()>  {
+        self.write_factors(path)
+    }
+
+    /// Load factors from a JSON (or `.tsv`) file
+    pub fn load_factors<P: AsRef<Path>>(&mut self, path: P) -> Result<()>  {
+        self.factors.extend(FactorMap::read(path)?.to_factors());
         Ok(())
     }
 
@@ -929,8 +2325,6 @@ Each level will be represented as a separate binary column (0/1) if one-hot enco
 #[cfg(test)]
 mod tests_one_hot_factors {
     use super::*;
-    use std::fs;
-    use std::collections::HashSet;
 
     #[test]
     fn test_save_and_load_factors() -> Result<(), Box<dyn std::error::Error>> {
@@ -963,227 +2357,1662 @@ mod tests_one_hot_factors {
         data.load_factors(&factors_path)?;
         let loaded_factors = &data.factors;
 
-        // Check factors exist
-        assert!(loaded_factors.contains_key("Color"));
-        assert!(loaded_factors.contains_key("Number"));
+        // Check factors exist
+        assert!(loaded_factors.contains_key("Color"));
+        assert!(loaded_factors.contains_key("Number"));
+
+        // Check levels
+        let color = loaded_factors.get("Color").unwrap();
+        assert_eq!(color.get_levels(), vec!["Red", "Blue", "Green"]);
+        assert!(color.one_hot);
+
+        let number = loaded_factors.get("Number").unwrap();
+        assert_eq!(number.get_levels(), vec!["1", "2", "3"]);
+        assert!(number.one_hot);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests_survival_data_from_file_one_hot_factors {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_survivaldata_one_hot_factors() -> Result<(), Box<dyn std::error::Error>> {
+        // --- Prepare temporary directory ---
+        let dir =  PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let csv_path = dir.as_path().join("test_factors.csv");
+        let factors_path = dir.as_path().join("test_factors_factors.json");
+
+        // --- Write CSV file ---
+        // Two categorical columns: Color, Number
+        // Values: Color = Red Blue Blue Green Blue Red Na Na
+        //         Number = 1 2 1 3 2 1 Na Na
+        let csv_content = "\
+Color,Number
+Red,1
+Blue,2
+Blue,1
+Green,3
+Blue,2
+Red,1
+Na,Na
+Na,Na
+";
+        let mut file = File::create(&csv_path)?;
+        file.write_all(csv_content.as_bytes())?;
+
+        let mut color = Factor::new("Color", false);
+        let _ = color.push("Red");
+        let _ = color.push("Blue");
+        let _ = color.push("Green");
+        color.one_hot = true;
+        
+        let mut number = Factor::new("Number", false);
+        let _ = number.push("1");
+        let _ = number.push("2");
+        let _ = number.push("3");
+        number.one_hot = true;
+        let mut temp = SurvivalData::default();
+        temp.factors.insert( "Color".to_string(), color);
+        temp.factors.insert( "Number".to_string(), number);
+
+        temp.save_factors(&factors_path)?; //default location
+        let categorical_cols= HashSet::<String>::new();
+        // --- Load SurvivalData ---
+        let data = SurvivalData::from_file(&csv_path, b',', categorical_cols, &factors_path)?;
+
+        // --- Assertions ---
+
+        // 1️⃣ Check original factor columns contain indices / NaN
+        println!("The data object: {data}");
+        let color_idx_col = data.headers.iter().position(|h| h == "Color").unwrap();
+        let number_idx_col = data.headers.iter().position(|h| h == "Number").unwrap();
+        assert_eq!(color_idx_col, 0, "expected color_idx_col 0 - have these cols: {:?}", &data.headers);
+        assert_eq!(number_idx_col, 4, "expected number_idx_col 4 - have these cols: {:?}", &data.headers);
+
+        assert_eq!(data.numeric_data[[0, color_idx_col]], 0.0); // Red first index 0
+        assert_eq!(data.numeric_data[[1, color_idx_col]], 1.0); // Blue index 1
+        assert!(data.numeric_data[[6, color_idx_col]].is_nan()); // Na
+
+        assert_eq!(data.numeric_data[[0, number_idx_col]], 0.0); // 1 -> index 0
+        assert_eq!(data.numeric_data[[1, number_idx_col]], 1.0); // 2 -> index 1
+        assert!(data.numeric_data[[6, number_idx_col]].is_nan()); // Na
+
+        assert_eq!(data.headers[1], "Color_Red");
+
+
+        // Find the indices of the one-hot columns
+        let color_red_idx = data.headers.iter().position(|h| h == "Color_Red").unwrap();
+        let color_blue_idx = data.headers.iter().position(|h| h == "Color_Blue").unwrap();
+        let color_green_idx = data.headers.iter().position(|h| h == "Color_Green").unwrap();
+
+        let number_1_idx = data.headers.iter().position(|h| h == "Number_1").unwrap();
+        let number_2_idx = data.headers.iter().position(|h| h == "Number_2").unwrap();
+        let number_3_idx = data.headers.iter().position(|h| h == "Number_3").unwrap();
+
+        // --- Expected one-hot values per row ---
+        // Rows: Color = Red, Blue, Blue, Green, Blue, Red, NA, NA
+        //       Number = 1,2,1,3,2,1,NA,NA
+        let expected_color = [
+            (1.0, 0.0, 0.0), // Red
+            (0.0, 1.0, 0.0), // Blue
+            (0.0, 1.0, 0.0), // Blue
+            (0.0, 0.0, 1.0), // Green
+            (0.0, 1.0, 0.0), // Blue
+            (1.0, 0.0, 0.0), // Red
+            (f64::NAN, f64::NAN, f64::NAN), // NA
+            (f64::NAN, f64::NAN, f64::NAN), // NA
+        ];
+
+        let expected_number = [
+            (1.0, 0.0, 0.0), // 1
+            (0.0, 1.0, 0.0), // 2
+            (1.0, 0.0, 0.0), // 1
+            (0.0, 0.0, 1.0), // 3
+            (0.0, 1.0, 0.0), // 2
+            (1.0, 0.0, 0.0), // 1
+            (f64::NAN, f64::NAN, f64::NAN), // NA
+            (f64::NAN, f64::NAN, f64::NAN), // NA
+        ];
+
+        for row in 0..data.numeric_data.nrows() {
+            // Color
+            let val_red = data.numeric_data[[row, color_red_idx]];
+            let val_blue = data.numeric_data[[row, color_blue_idx]];
+            let val_green = data.numeric_data[[row, color_green_idx]];
+
+            let (exp_red, exp_blue, exp_green) = expected_color[row];
+            if exp_red.is_nan() {
+                assert!(val_red.is_nan());
+                assert!(val_blue.is_nan());
+                assert!(val_green.is_nan());
+            } else {
+                assert_eq!(val_red, exp_red);
+                assert_eq!(val_blue, exp_blue);
+                assert_eq!(val_green, exp_green);
+            }
+
+            // Number
+            let val_1 = data.numeric_data[[row, number_1_idx]];
+            let val_2 = data.numeric_data[[row, number_2_idx]];
+            let val_3 = data.numeric_data[[row, number_3_idx]];
+
+            let (exp_1, exp_2, exp_3) = expected_number[row];
+            if exp_1.is_nan() {
+                assert!(val_1.is_nan(), "expected NA value for Number_1 at row {row}; all data {}",data.numeric_data);
+                assert!(val_2.is_nan(), "expected NA value for Number_2 at row {row}; all data {}",data.numeric_data);
+                assert!(val_3.is_nan(), "expected NA value for Number_3 at row {row}; all data {}",data.numeric_data);
+            } else {
+                assert_eq!(val_1, exp_1, "Number_1 mismatch at row {}/{}: {} != {} all data {}", number_1_idx, row, val_1, exp_1,data.numeric_data);
+                assert_eq!(val_2, exp_2, "Number_2 mismatch at row {}/{}: {} != {} all data {}", number_2_idx, row, val_2, exp_2,data.numeric_data);
+                assert_eq!(val_3, exp_3, "Number_3 mismatch at row {}/{}: {} != {} all data {}", number_3_idx, row, val_3, exp_3,data.numeric_data);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_survivaldata_train_test_split_unique() -> Result<(), Box<dyn std::error::Error>> {
+        use std::fs::File;
+        use std::io::Write;
+        use tempfile::tempdir;
+
+        // --- Prepare temporary directory and files ---
+        let dir = tempdir()?;
+        let csv_path = dir.path().join("unique_data.csv");
+        let factors_path = dir.path().join("unique_factors.json");
+
+        // --- Generate CSV data with 10 rows, 10 columns, unique values 1..100 ---
+        let nrows = 10;
+        let ncols = 10;
+        let mut csv_content = String::new();
+        // header
+        csv_content.push_str(&(0..ncols).map(|i| format!("Col{}", i)).collect::<Vec<_>>().join(","));
+        csv_content.push('\n');
+        // data
+        let mut val = 1;
+        for _ in 0..nrows {
+            let row = (0..ncols).map(|_| {
+                let s = val.to_string();
+                val += 1;
+                s
+            }).collect::<Vec<_>>().join(",");
+            csv_content.push_str(&row);
+            csv_content.push('\n');
+        }
+
+        // write CSV
+        let mut f = File::create(&csv_path)?;
+        f.write_all(csv_content.as_bytes())?;
+
+        // --- Create empty factors JSON ---
+        let mut f_factors = File::create(&factors_path)?;
+        f_factors.write_all(b"[]")?;
+
+        // --- Load data ---
+        let categorical_cols = HashSet::<String>::new();
+        let data = SurvivalData::from_file(&csv_path, b',', categorical_cols, &factors_path)?;
+
+        // --- Split ---
+        let (train, test) = data.train_test_split(0.7);
+
+        // --- Assertions ---
+        assert_eq!(train.numeric_data.nrows() + test.numeric_data.nrows(), nrows, "Row counts must add up");
+        assert_eq!(train.headers, data.headers);
+        assert_eq!(test.headers, data.headers);
+
+        // Check that all rows are unique across train and test
+        let train_rows: Vec<Vec<f64>> = train.numeric_data.rows().into_iter().map(|r| r.to_vec()).collect();
+        let test_rows: Vec<Vec<f64>> = test.numeric_data.rows().into_iter().map(|r| r.to_vec()).collect();
+
+        for row in &train_rows {
+            assert!(!test_rows.contains(row), "Row appeared in both train and test!");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file_gzip_matches_plain() -> Result<(), Box<dyn std::error::Error>> {
+        use flate2::{write::GzEncoder, Compression};
+        use tempfile::tempdir;
+
+        let dir = tempdir()?;
+        let content = "age\tsex\n42\tm\n37\tf\nNA\tf\n";
+        let plain_path = dir.path().join("meta.tsv");
+        let gz_path = dir.path().join("meta.tsv.gz");
+        let factors_path = dir.path().join("factors.json");
+        std::fs::write(&plain_path, content)?;
+        std::fs::write(&factors_path, b"[]")?;
+
+        let mut enc = GzEncoder::new(File::create(&gz_path)?, Compression::default());
+        enc.write_all(content.as_bytes())?;
+        enc.finish()?;
+
+        let plain = SurvivalData::from_file(&plain_path, b'\t', HashSet::new(), &factors_path)?;
+        let gz = SurvivalData::from_file(&gz_path, b'\t', HashSet::new(), &factors_path)?;
+
+        assert_eq!(plain.headers, gz.headers);
+        assert_eq!(plain.numeric_data.dim(), gz.numeric_data.dim());
+        assert_eq!(gz.as_vec_string("sex"), Some(vec!["m".to_string(), "f".to_string(), "f".to_string()]));
+        assert_eq!(gz.as_vec_f64("age")[..2], [42.0, 37.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_reader_in_memory() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let csv = "id,cluster,score\nc1,T cell,0.5\nc2,B cell,1.5\nc3,T cell,NA\n";
+        let categorical: HashSet<String> = ["id".to_string()].into();
+        let data = SurvivalData::from_reader(Cursor::new(csv.as_bytes().to_vec()), b',', categorical)?;
+
+        assert_eq!(data.headers, vec!["id", "cluster", "score"]);
+        assert_eq!(data.numeric_data.dim(), (3, 3));
+        assert_eq!(data.factors.get("cluster").unwrap().get_levels(), ["T cell", "B cell"]);
+        assert!(data.as_vec_f64("score")[2].is_nan());
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_access() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "cluster\tage\n1\t42\n2\t37\nx\t51\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        assert_eq!(data.column_names(), ["cluster", "age"]);
+        assert_eq!((data.n_rows(), data.n_cols()), (3, 2));
+        assert_eq!(data.column("cluster").map(|c| c.dtype), Some(Dtype::Factor));
+        assert_eq!(data.column_at(1).map(|c| c.name.as_str()), Some("age"));
+        assert!(data.column("clustr").is_none());
+        assert!(data.column_at(2).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compute_and_write_factors() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "zone\tage\tcolor\nA\t1\tred\nB\t2\tblue\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let map = data.compute_factors();
+        let names: Vec<&str> = map.iter().map(|f| f.column.as_str()).collect();
+        assert_eq!(names, vec!["zone", "color"], "factors follow the column order");
+
+        let dir = tempdir()?;
+        let path = dir.path().join("factors.json");
+        assert!(!path.exists(), "computing factors must not touch the disk");
+        data.write_factors(&path)?;
+        assert_eq!(FactorMap::from_json(&path)?, map);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_and_float_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "nCount_RNA\tpercent.mt\tcluster\n1200\t0.5\t1\n800\t2\tx\nNA\t1.25\t2\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let dtypes: Vec<Dtype> = data.columns.iter().map(|c| c.dtype).collect();
+        assert_eq!(dtypes, vec![Dtype::Integer, Dtype::Float, Dtype::Factor]);
+        assert_eq!(data.as_vec_string("cluster"), Some(vec!["1".to_string(), "x".to_string(), "2".to_string()]));
+
+        let map = data.compute_factors();
+        assert_eq!(map.column("percent.mt").unwrap().dtype, Dtype::Float);
+        let json = serde_json::to_string(&map)?;
+        assert!(json.contains(r#""dtype":"integer""#), "dtype is exposed in the JSON: {json}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_column_summary() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "nCount_RNA\tempty\tcluster\n1200\tNA\tx\n800\tNA\ty\nNA\tNA\tx\n1000\tNA\ty\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let counts = data.columns[0].summary.as_ref().unwrap();
+        assert_eq!(counts.count, 3, "missing values are excluded");
+        assert_eq!((counts.min, counts.median, counts.max), (Some(800.0), Some(1000.0), Some(1200.0)));
+        assert!(data.columns[2].summary.is_none(), "factors have no numeric summary");
+
+        let map = data.compute_factors();
+        let json = serde_json::to_value(&map)?;
+        assert_eq!(json["columns"][1]["dtype"], "all_missing");
+        assert!(json["columns"][1].get("summary").is_none(), "no summary without values");
+
+        let options = ParseOptions { dtypes: [("empty".to_string(), Dtype::Float)].into(), ..Default::default() };
+        let declared = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let json = serde_json::to_value(declared.compute_factors())?;
+        let empty = &json["columns"][1]["summary"];
+        assert_eq!(empty["count"], 0);
+        assert!(empty["min"].is_null() && empty["q3"].is_null(), "a declared numeric column without values has nulls: {empty}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_level() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use crate::factor::FactorSpec;
+
+        let tsv = "condition\tdose\ntreated\t1\ncontrol\t2\nsham\t1\ncontrol\t3\n";
+        let mut options = ParseOptions::default();
+        options.factor_specs.insert("condition".to_string(), FactorSpec::reference("control"));
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.factors["condition"].get_levels(), ["control", "treated", "sham"]);
+        assert_eq!(data.as_vec_f64("condition"), vec![1.0, 0.0, 2.0, 0.0]);
+        assert_eq!(data.as_vec_string("condition").unwrap(), vec!["treated", "control", "sham", "control"]);
+        let json = data.compute_factors();
+        assert_eq!(json.get("condition").unwrap().counts, Some(vec![2, 1, 1]), "counted in the final level order");
+        let with_missing = SurvivalData::from_reader(Cursor::new("x\na\nNA\na\n"), b'\t', HashSet::new())?;
+        assert_eq!(with_missing.compute_factors().get("x").unwrap().missing, Some(1));
+
+        options.factor_specs.insert("condition".to_string(), FactorSpec::reference("placebo"));
+        let err = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).unwrap_err();
+        assert!(err.to_string().contains("Reference level 'placebo'"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_rare_levels() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use crate::factor::FactorSpec;
+
+        let tsv = "cluster\nT\nB\nT\nNK\nB\nDC\nT\n";
+        let mut options = ParseOptions::default();
+        options.factor_specs.insert("cluster".to_string(), FactorSpec { reference: Some("B".into()), ..FactorSpec::merge_rare(2) });
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.factors["cluster"].get_levels(), ["B", "T", "Other"]);
+        assert_eq!(data.as_vec_string("cluster").unwrap(), vec!["T", "B", "T", "Other", "B", "Other", "T"]);
+        let json = data.compute_factors();
+        let cluster = json.get("cluster").unwrap();
+        assert_eq!(cluster.counts, Some(vec![2, 3, 2]));
+        assert_eq!(cluster.merged.as_ref().unwrap().keys().collect::<Vec<_>>(), ["DC", "NK"]);
+
+        // by rank: the 2 largest clusters, most frequent first
+        let mut options = ParseOptions::default();
+        options.factor_specs.insert("cluster".to_string(), FactorSpec::top_k(2));
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.factors["cluster"].get_levels(), ["T", "B", "Other"]);
+        assert_eq!(data.as_vec_string("cluster").unwrap(), vec!["T", "B", "T", "Other", "B", "Other", "T"]);
+        assert_eq!(data.compute_factors().get("cluster").unwrap().counts, Some(vec![3, 2, 2]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_column_names() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use crate::options::DuplicateColumns;
+
+        let tsv = "barcode\torig.ident\tnCount\torig.ident\nAAAC\tpbmc1\t10\tA\nAAAG\tpbmc2\t20\tB\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        assert_eq!(data.column_names(), ["barcode", "orig.ident", "nCount", "orig.ident.1"]);
+        assert_eq!(data.as_vec_string("orig.ident").unwrap(), vec!["pbmc1", "pbmc2"]);
+        assert_eq!(data.as_vec_string("orig.ident.1").unwrap(), vec!["A", "B"], "the later column is kept");
+
+        let options = ParseOptions { duplicate_columns: DuplicateColumns::Error, ..Default::default() };
+        let err = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).unwrap_err();
+        assert!(err.to_string().contains("'orig.ident'"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_inconsistent_rows() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "barcode\tcluster\tnCount\nAAAC\tT\t10\nAAAG\tB\nAAAT\tB\t30\t7\nAACA\tNK\t40\n";
+        let err = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap_err();
+        assert!(matches!(err, DataTableError::InconsistentRow { line: 3, found: 2, expected: 3 }), "{err:?}");
+        assert_eq!(err.to_string(), "line 3 has 2 fields, expected 3");
+
+        let options = ParseOptions { skip_bad_rows: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.as_vec_string("barcode").unwrap(), vec!["AAAC", "AACA"]);
+        assert_eq!(data.as_vec_f64("nCount"), vec![10.0, 40.0]);
+        let report = data.parse_report();
+        assert_eq!((report.rows_read, report.rows_kept, report.rows_dropped), (4, 2, 2));
+        assert_eq!(report.dropped(DropReason::WrongFieldCount), 2);
+        assert_eq!(report.to_string(), "4 rows read, 2 kept, 2 dropped (2 wrong number of fields)");
+
+        let dir = tempdir()?;
+        let input = dir.path().join("bad_rows.tsv");
+        std::fs::write(&input, tsv)?;
+        let err = SurvivalData::from_file_streaming(&input, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(err, DataTableError::InconsistentRow { line: 3, .. }), "{err:?}");
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.factors["cluster"].get_levels(), ["T", "NK"]);
+        assert_eq!(streamed.stream_rows(&input, &options, |_| Ok(()))?, 2);
+        assert_eq!(streamed.parse_report(), data.parse_report());
+        let combined = SurvivalData::from_files(&[&input, &input], &options, false)?;
+        assert_eq!((combined.parse_report().rows_read, combined.parse_report().rows_dropped), (8, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_delimiters() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use crate::options::TrailingDelimiter;
+
+        let parse = |tsv: &str, options: &ParseOptions| SurvivalData::from_reader_with_options(Cursor::new(tsv.to_string()), options);
+        // on every line, only on the rows, or only on the header
+        for tsv in ["cell\tn\t\nA\t1\t\nB\t2\t\n", "cell\tn\nA\t1\t\nB\t2\t\n", "cell\tn\t\nA\t1\nB\t2\n"] {
+            let data = parse(tsv, &ParseOptions::default())?;
+            assert_eq!(data.column_names(), ["cell", "n"], "{tsv:?}");
+            assert_eq!(data.as_vec_f64("n"), vec![1.0, 2.0]);
+            assert!(data.parse_report().is_clean());
+        }
+
+        // kept: a real, empty column
+        let keep = ParseOptions { trailing_delimiter: TrailingDelimiter::Keep, ..Default::default() };
+        let data = parse("cell\tn\t\nA\t1\t\nB\t2\t\n", &keep)?;
+        assert_eq!(data.column_names(), ["cell", "n", "V3"]);
+        assert_eq!(data.column("V3").unwrap().missing, 2);
+        let err = parse("cell\tn\nA\t1\t\nB\t2\t\n", &keep).unwrap_err();
+        assert!(matches!(err, DataTableError::InconsistentRow { line: 2, found: 3, expected: 2 }), "{err:?}");
+
+        // only some rows: an error naming both lines, or skipped
+        let ragged = "cell\tn\nA\t1\t\nB\t2\nC\t3\t\n";
+        let err = parse(ragged, &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("line 2 ends with a delimiter, line 3 does not"), "{err}");
+        let err = parse("cell\tn\nA\t1\nB\t2\t\n", &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("line 3 ends with a delimiter, line 2 does not"), "{err}");
+        let skip = ParseOptions { skip_bad_rows: true, ..Default::default() };
+        let data = parse(ragged, &skip)?;
+        assert_eq!(data.as_vec_string("cell").unwrap(), vec!["A", "C"]);
+        assert_eq!(data.parse_report().dropped(DropReason::WrongFieldCount), 1);
+
+        // a value in the extra field is no trailing delimiter
+        let err = parse("cell\tn\nA\t1\t9\n", &ParseOptions::default()).unwrap_err();
+        assert!(matches!(err, DataTableError::InconsistentRow { found: 3, .. }), "{err:?}");
+        // an unnamed last column holding data is a column as before
+        let unnamed = "a\tb\t\n1\t2\t3\n";
+        let data = parse(unnamed, &ParseOptions::default())?;
+        assert_eq!(data.column_names(), ["a", "b", "barcode"]);
+        assert_eq!(data.as_vec_f64("barcode"), vec![3.0]);
+
+        // the streaming reader strips the same way
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("trailing.tsv");
+        std::fs::write(&input, "cell\tn\t\nA\t1\t\nB\t2\t\n")?;
+        let streamed = SurvivalData::from_file_streaming(&input, &ParseOptions::default())?;
+        assert_eq!(streamed.column_names(), ["cell", "n"]);
+        assert_eq!(streamed.stream_rows(&input, &ParseOptions::default(), |row| {
+            assert_eq!(row.len(), 2);
+            Ok(())
+        })?, 2);
+        std::fs::write(&input, unnamed)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &ParseOptions::default())?;
+        assert_eq!(streamed.column_names(), ["a", "b", "barcode"]);
+        assert_eq!(streamed.stream_rows(&input, &ParseOptions::default(), |row| {
+            assert_eq!(row, [1.0, 2.0, 3.0]);
+            Ok(())
+        })?, 1);
+        let combined = SurvivalData::from_files(&[&input, &input], &ParseOptions::default(), false)?;
+        assert_eq!(combined.as_vec_f64("barcode"), vec![3.0, 3.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_format() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use crate::options::NumberFormat;
+
+        let csv = "id;creatinine;count;mixed\n1;1.234,5;1.200;1.234,5\n2;0,75;15;1,234.5\n3;NA;2.000.000;7\n";
+        let options = ParseOptions {
+            delimiter: b';',
+            number_format: NumberFormat::new(b',', Some(b'.'))?,
+            ..Default::default()
+        };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(csv), &options)?;
+        assert_eq!(data.column("creatinine").unwrap().dtype, Dtype::Float);
+        assert_eq!(data.as_vec_f64("creatinine")[..2], [1234.5, 0.75]);
+        assert_eq!(data.column("count").unwrap().dtype, Dtype::Integer);
+        assert_eq!(data.as_vec_f64("count"), vec![1200.0, 15.0, 2_000_000.0]);
+        assert_eq!(data.column("mixed").unwrap().dtype, Dtype::Factor, "two formats in one column");
+        assert_eq!(data.factors["mixed"].get_levels(), ["1.234,5", "1,234.5", "7"]);
+
+        let default = ParseOptions { delimiter: b';', ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(csv), &default)?;
+        assert_eq!(data.column("creatinine").unwrap().dtype, Dtype::Factor);
+        Ok(())
+    }
+
+    #[test]
+    fn test_separator() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+        use crate::options::Separator;
+
+        let text = "# run 7\ngene::count::note\nCD3E::12::\"a\"\nMS4A1::7::b\n";
+        let options = ParseOptions { separator: Some(Separator::text("::")?), comment: Some(b'#'), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(text), &options)?;
+        assert_eq!(data.column_names(), ["gene", "count", "note"]);
+        assert_eq!(data.as_vec_f64("count"), vec![12.0, 7.0]);
+        assert_eq!(data.as_vec_string("note").unwrap(), vec!["\"a\"", "b"], "no quoting");
+
+        let dir = tempdir()?;
+        let input = dir.path().join("fit.txt");
+        std::fs::write(&input, "   id    chi2  ok\r\n    1   0.53  yes\r\n   12  11.20  no\r\n")?;
+        let data = SurvivalData::builder().separator(Separator::regex(r"\s+")?).build_from_file(&input)?;
+        assert_eq!(data.column_names(), ["id", "chi2", "ok"]);
+        assert_eq!(data.as_vec_f64("chi2"), vec![0.53, 11.2]);
+        assert_eq!(data.column("ok").unwrap().dtype, Dtype::Boolean);
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_selection() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+        use crate::options::ColumnSelection;
+
+        let tsv = "barcode\tcluster\tnCount\tsample\nAAAC\tT\t10\ts1\nAAAG\tB\t20\ts2\n";
+        let keep = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let options = ParseOptions { columns: ColumnSelection::Include(keep(&["nCount", "cluster"])), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column_names(), ["cluster", "nCount"], "file order");
+        assert_eq!(data.as_vec_f64("nCount"), vec![10.0, 20.0]);
+        assert!(data.compute_factors().get("barcode").is_none());
+
+        let options = ParseOptions { columns: ColumnSelection::Exclude(keep(&["barcode", "sample"])), ..Default::default() };
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.column_names(), ["cluster", "nCount"]);
+        let mut rows = Vec::new();
+        streamed.stream_rows(&input, &options, |row| { rows.push(row.to_vec()); Ok(()) })?;
+        assert_eq!(rows, vec![vec![0.0, 10.0], vec![1.0, 20.0]]);
+
+        let options = ParseOptions { columns: ColumnSelection::Include(keep(&["nCont"])), ..Default::default() };
+        let err = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).unwrap_err();
+        assert!(err.to_string().contains("'nCont' (did you mean 'nCount'?)"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_mode() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "cell\tdose\tcluster\tage\tflag\nA\t1.5\tT\t40\tyes\nB\t<0.5\tB\tunknown\tno\nC\t2\t3\t52\tyes\n";
+        let lenient = SurvivalData::from_reader_with_options(Cursor::new(tsv), &ParseOptions::default())?;
+        assert_eq!(lenient.column("dose").unwrap().dtype, Dtype::Factor, "the lenient default guesses");
+
+        let strict = ParseOptions { strict: true, ..Default::default() };
+        let expected = "Strict mode: 3 column(s) mix numbers and text - declare them (categorical, as_numeric, as_string) \
+                        or clean the input: 'dose' ('<0.5'), 'cluster' ('3'), 'age' ('unknown')";
+        let err = SurvivalData::from_reader_with_options(Cursor::new(tsv), &strict).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        assert_eq!(SurvivalData::from_file_streaming(&input, &strict).unwrap_err().to_string(), expected);
+
+        // declared columns are no guess
+        let declared = ParseOptions {
+            categorical: ["cluster".to_string()].into(),
+            as_numeric: ["dose".to_string()].into(),
+            as_string: ["age".to_string()].into(),
+            ..strict
+        };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &declared)?;
+        assert_eq!(data.column("dose").unwrap().dtype, Dtype::Float);
+        assert_eq!(data.column("flag").unwrap().dtype, Dtype::Boolean);
+        assert!(SurvivalData::from_file_streaming(&input, &declared).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+        use crate::options::{parse_renames, ColumnSelection};
+
+        let tsv = "barcode\tpercent.mt\tnCount_RNA\tcluster\nAAAC\t1.5\t10\t1\nAAAG\t2.5\t20\t2\n";
+        let renames = parse_renames("percent.mt=mito_pct, nCount_RNA=counts,cluster=group")?;
+        // settings may use the old or the new name
+        let options = ParseOptions {
+            renames: renames.clone(),
+            categorical: ["cluster".to_string()].into(),
+            as_string: ["barcode".to_string()].into(),
+            columns: ColumnSelection::Exclude(vec!["counts".into()]),
+            ..Default::default()
+        };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column_names(), ["barcode", "mito_pct", "group"]);
+        assert_eq!((data.header(), data.as_vec_f64("mito_pct")), (&["barcode", "mito_pct", "counts", "group"].map(String::from)[..], vec![1.5, 2.5]));
+        assert!(data.factors.contains_key("group"), "categorical by its old name");
+        let map = data.compute_factors();
+        let column = |name: &str| map.columns.iter().find(|c| c.name == name).unwrap().clone();
+        assert_eq!((column("mito_pct").original_name, column("barcode").original_name), (Some("percent.mt".into()), None));
+        let json = serde_json::to_string(&column("group"))?;
+        assert!(json.contains(r#""name":"group""#) && json.contains(r#""original_name":"cluster""#), "{json}");
+
+        // streaming and several files rename too
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let options = ParseOptions { renames: renames.clone(), categorical: ["group".to_string()].into(), ..Default::default() };
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.column_names(), ["barcode", "mito_pct", "counts", "group"]);
+        assert_eq!(streamed.column("counts").unwrap().original_name.as_deref(), Some("nCount_RNA"));
+        let mut rows = 0;
+        streamed.stream_rows(&input, &options, |_| { rows += 1; Ok(()) })?;
+        assert_eq!(rows, 2);
+        let combined = SurvivalData::from_files(&[&input, &input], &options, false)?;
+        assert_eq!((combined.n_rows(), combined.column("group").unwrap().original_name.as_deref()), (4, Some("cluster")));
+
+        // a taken name, one name twice and an unknown column
+        let read = |renames: &str| {
+            let options = ParseOptions { renames: parse_renames(renames).unwrap(), ..Default::default() };
+            SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).map(|d| d.column_names().to_vec())
+        };
+        assert_eq!(read("cluster=barcode").unwrap_err().to_string(), "Cannot rename 'cluster' to 'barcode' - the header has a column 'barcode' already");
+        assert_eq!(read("cluster=x,barcode=x").unwrap_err().to_string(), "Cannot rename both 'barcode' and 'cluster' to 'x'");
+        assert!(read("clustr=group").unwrap_err().to_string().contains("'clustr' (did you mean 'cluster'?)"));
+        assert_eq!(read("cluster=barcode,barcode=cluster")?, ["cluster", "percent.mt", "nCount_RNA", "barcode"], "a swap");
+        assert!(parse_renames("cluster").is_err() && parse_renames("cluster=").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_latin1_input() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+        use crate::options::TextEncoding;
+
+        let latin1: &[u8] = b"probe\tm\xfcde\nK\xf6ln\tja\nM\xfcnchen\tnein\nK\xf6ln\tja\n";
+        let options = ParseOptions { encoding: TextEncoding::Windows1252, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(latin1), &options)?;
+        assert_eq!(data.column_names(), ["probe", "müde"]);
+        assert_eq!(data.factors["probe"].get_levels(), ["Köln", "München"]);
+
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, latin1)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.factors["probe"].get_levels(), ["Köln", "München"]);
+        assert!(SurvivalData::from_reader(Cursor::new(latin1), b'\t', HashSet::new()).is_err(), "no valid UTF-8");
+        Ok(())
+    }
+
+    #[test]
+    fn test_byte_order_mark() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "\u{feff}orig.ident\tnCount\npbmc1\t1\npbmc2\t2\n";
+        let options = ParseOptions::new(b'\t', ["orig.ident".to_string()].into());
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column_names(), ["orig.ident", "nCount"]);
+        assert!(data.factors.contains_key("orig.ident"), "--categorical matches the name without BOM");
+
+        let commented = ParseOptions { comment: Some(b'#'), ..options.clone() };
+        let with_comment = format!("\u{feff}# exported by Excel\n{}", &tsv[3..]);
+        let data = SurvivalData::from_reader_with_options(Cursor::new(with_comment), &commented)?;
+        assert_eq!(data.column_names(), ["orig.ident", "nCount"]);
+
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.column_names(), ["orig.ident", "nCount"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_level_order() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+        use crate::factor::FactorSpec;
+
+        let tsv = "cell_type\tstage\tn\nT\tlow\t1\nNK\thigh\t2\nB\tlow\t3\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        assert_eq!(data.factors["cell_type"].get_levels(), ["T", "NK", "B"], "first appearance");
+        let again = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        assert_eq!(serde_json::to_string(&again.compute_factors())?, serde_json::to_string(&data.compute_factors())?);
+
+        let mut options = ParseOptions { sort_levels: true, ..Default::default() };
+        options.factor_specs.insert("stage".into(), FactorSpec::ordered(vec!["low".into(), "high".into()]));
+        let sorted = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(sorted.factors["cell_type"].get_levels(), ["B", "NK", "T"]);
+        assert_eq!(sorted.factors["stage"].get_levels(), ["low", "high"], "declared levels are not sorted");
+        assert_eq!(sorted.as_vec_string("cell_type").unwrap(), vec!["T", "NK", "B"]);
+
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.factors["cell_type"].get_levels(), ["B", "NK", "T"]);
+        assert_eq!(streamed.factors["stage"].get_levels(), ["low", "high"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_ndjson() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "barcode\tnCount\tscore\tdoublet\tcluster\nAAAC\t10\t0.5\tTRUE\tT cell\nAAAG\tNA\t1.25\tno\tNA\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        let mut out = Vec::new();
+        data.write_ndjson(&mut out)?;
+        let text = String::from_utf8(out)?;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], r#"{"barcode":"AAAC","nCount":10,"score":0.5,"doublet":true,"cluster":"T cell"}"#);
+        assert_eq!(lines[1], r#"{"barcode":"AAAG","nCount":null,"score":1.25,"doublet":false,"cluster":null}"#);
+
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let options = ParseOptions::default();
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        let mut out = Vec::new();
+        streamed.stream_rows(&input, &options, |row| streamed.write_ndjson_row(&mut out, row))?;
+        assert_eq!(String::from_utf8(out)?, text, "streaming gives the same lines");
+        Ok(())
+    }
+
+    #[test]
+    fn test_transpose() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let csv = "\u{feff}variable,S1,S2,S3\nage,54,61,NA\nsex,m,f,f\nbmi,22.5,30.1,27\n";
+        let options = ParseOptions { delimiter: b',', transpose: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(csv), &options)?;
+        assert_eq!(data.headers, vec!["variable", "age", "sex", "bmi"]);
+        assert_eq!(data.n_rows(), 3);
+        let dtypes: Vec<Dtype> = data.columns.iter().map(|c| c.dtype).collect();
+        assert_eq!(dtypes, vec![Dtype::Factor, Dtype::Integer, Dtype::Factor, Dtype::Float]);
+        assert_eq!(data.column("age").unwrap().missing, 1);
+        assert_eq!(data.delimiter(), b',');
+        assert_eq!(data.factors["sex"].get_levels(), ["m", "f"]);
+
+        let err = SurvivalData::from_reader_with_options(Cursor::new("variable,S1,S2\nage,54\n"), &options).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot transpose the table: line 2 has 2 fields, the header 3"), "{err}");
+
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("wide.csv");
+        std::fs::write(&input, csv)?;
+        assert!(SurvivalData::from_file_streaming(&input, &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_schema() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "nCount\tscore\tdoublet\tcluster\tday\n10\t0.5\tTRUE\tT cell\t2023-05-14\nNA\t1.25\tno\tNA\t2023-05-15\n";
+        let options = ParseOptions { detect_dates: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let schema = data.json_schema();
+        assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+        assert_eq!(schema["required"], serde_json::json!(["nCount", "score", "doublet", "cluster", "day"]));
+        let properties = &schema["properties"];
+        assert_eq!(properties["nCount"], serde_json::json!({ "type": ["integer", "null"] }));
+        assert_eq!(properties["score"], serde_json::json!({ "type": "number" }));
+        assert_eq!(properties["doublet"], serde_json::json!({ "type": "boolean" }));
+        assert_eq!(properties["cluster"], serde_json::json!({ "type": ["string", "null"], "enum": ["T cell", null] }));
+        assert_eq!(properties["day"], serde_json::json!({ "type": "string", "format": "date" }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_comment_lines() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "# exported by seurat 5.0\n#date: 2024-05-14\nbarcode\tlabel\tnCount\nAAAC\tT #1\t10\n# filtered\nAAAG\tB\t20\n";
+        let options = ParseOptions { comment: Some(b'#'), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column_names(), ["barcode", "label", "nCount"]);
+        assert_eq!(data.as_vec_string("label").unwrap(), vec!["T #1", "B"], "a # inside a field is data");
+        assert_eq!(data.as_vec_f64("nCount"), vec![10.0, 20.0]);
+
+        let truncated = "# exported by seurat 5.0\nbarcode\tlabel\tnCount\nAAAC\tT\n";
+        let err = SurvivalData::from_reader_with_options(Cursor::new(truncated), &options).unwrap_err();
+        assert!(matches!(err, DataTableError::InconsistentRow { line: 3, .. }), "the comment is line 1: {err:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_broken_input_never_panics() {
+        use std::io::Cursor;
+        use rand::{Rng, SeedableRng};
+
+        // the quick in-tree version of fuzz/fuzz_targets/from_reader.rs: broken copies of valid tables
+        let tables = [
+            "barcode\tgroup\tn\tscore\nAAAC\tT\t1\t0.5\nAAAG\tB\t2\tNA\n",
+            "a,b,c\n1,\"x,y\",3\n2,\"\"\"q\",4\n",
+            "\u{feff}x\ty\n007\t1e400\n-9223372036854775809\t{\"a\":[1]}\n",
+            "d\tt\n2024-01-02\t2024-01-02T10:00:00\n03.04.2020\tNA\n",
+        ];
+        let bytes = b"\t,\n\r\"\\ 0189.-eNA{}[]:#\xff\xef\xbb\xbf\x00";
+        let mut rng = rand::rngs::StdRng::seed_from_u64(102);
+        for round in 0..2000 {
+            let mut table = tables[round % tables.len()].as_bytes().to_vec();
+            for _ in 0..rng.random_range(1..6) {
+                let at = rng.random_range(0..=table.len());
+                match rng.random_range(0..3) {
+                    0 => table.insert(at, bytes[rng.random_range(0..bytes.len())]),
+                    1 if at < table.len() => drop(table.remove(at)),
+                    _ => table.truncate(at),
+                }
+            }
+            let options = ParseOptions {
+                delimiter: if round % 2 == 0 { b'\t' } else { b',' },
+                detect_dates: round % 3 == 0,
+                max_levels: (round % 4 == 0).then_some(1),
+                comment: (round % 5 == 0).then_some(b'#'),
+                skip_bad_rows: round % 7 == 0,
+                transpose: round % 11 == 0,
+                header_row: usize::from(round % 13 == 0),
+                ..Default::default()
+            };
+            if let Ok(data) = SurvivalData::from_reader_with_options(Cursor::new(&table), &options) {
+                let _ = data.compute_factors();
+                let _ = data.write_ndjson(std::io::sink());
+                let _ = data.json_schema();
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_row() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "Sample overview\t\t\n# run 7\n\nexported 2024-05-14\nbarcode\tgroup\tnCount\nAAAC\tT\t10\nAAAG\tB\t20\n";
+        let options = ParseOptions { comment: Some(b'#'), header_row: 2, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column_names(), ["barcode", "group", "nCount"], "comment and blank lines are no title lines");
+        assert_eq!(data.as_vec_f64("nCount"), vec![10.0, 20.0]);
+        let report = data.parse_report();
+        assert_eq!((report.rows_read, report.rows_kept, report.dropped(DropReason::BeforeHeader)), (4, 2, 2));
+        assert_eq!(report.to_string(), "4 rows read, 2 kept, 2 dropped (2 line before the header)");
+
+        let dir = tempdir()?;
+        let path = dir.path().join("titled.tsv");
+        std::fs::write(&path, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&path, &options)?;
+        let dtypes = |d: &SurvivalData| d.columns.iter().map(|c| (c.name.clone(), c.dtype)).collect::<Vec<_>>();
+        assert_eq!(dtypes(&streamed), dtypes(&data));
+        assert_eq!(streamed.parse_report(), report);
+
+        // the lines keep their numbers in errors
+        let err = SurvivalData::from_reader_with_options(Cursor::new(format!("{tsv}AAAT\tT\n")), &options).unwrap_err();
+        assert!(matches!(err, DataTableError::InconsistentRow { line: 8, .. }), "{err:?}");
+        let short = ParseOptions { header_row: 9, ..options };
+        let err = SurvivalData::from_reader_with_options(Cursor::new(tsv), &short).unwrap_err();
+        assert_eq!(err.to_string(), "The table ends before its header: it has less than 9 lines above one (header_row)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_of() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "cell\tcluster\tn\nA\tT\t1\nB\tB\t2\nC\tT\t3\n";
+        let options = ParseOptions { max_levels: Some(2), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!((data.code_of("cluster", "T"), data.code_of("cluster", "B")), (Some(0), Some(1)));
+        assert_eq!(data.level_of("cluster", 1), Some("B"));
+        assert_eq!((data.code_of("cluster", "NK"), data.level_of("cluster", 2)), (None, None));
+        assert_eq!(data.code_of("cell", "A"), None, "free text has no codes");
+        assert_eq!((data.code_of("n", "1"), data.level_of("missing", 0)), (None, None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reorder_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let mut color = Factor::new("color", false);
+        let _ = color.push("red");
+        let _ = color.push("blue");
+        color.one_hot = true;
+        let factors = FactorMap::new(vec![color.as_json("color")]);
+        let tsv = "cell\tcolor\tscore\tgroup\nA\tred\t1.5\tx\nB\tblue\tNA\ty\nC\tred\t3.5\tx\n";
+        let options = ParseOptions { categorical: ["group".to_string()].into(), ..Default::default() };
+        let mut data = SurvivalData::from_reader_with_factors(Cursor::new(tsv), &options, &factors)?;
+        assert_eq!(data.column_names(), ["cell", "color", "color_red", "color_blue", "score", "group"]);
+        let (score, group) = (data.as_vec_f64("score"), data.as_vec_string("group"));
+
+        data.reorder_columns(&["group", "score", "color"])?;
+        assert_eq!(data.column_names(), ["group", "score", "color", "color_red", "color_blue", "cell"], "the one-hot columns follow their factor");
+        assert_eq!(data.as_vec_string("group"), group);
+        assert_eq!(data.as_vec_f64("score").iter().map(|v| v.to_string()).collect::<Vec<_>>(), score.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+        assert_eq!(data.as_vec_f64("color_blue"), vec![0.0, 1.0, 0.0]);
+        let column = data.column("score").unwrap();
+        assert_eq!((column.missing, column.summary.as_ref().and_then(|s| s.max)), (1, Some(3.5)));
+        let map = data.compute_factors();
+        assert_eq!(map.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>()[..3], ["group", "score", "color"]);
+
+        let before = data.column_names().to_vec();
+        assert_eq!(data.reorder_columns(&["score", "nope"]).unwrap_err().to_string(), "Cannot reorder: no column 'nope'");
+        assert_eq!(data.reorder_columns(&["cell", "cell"]).unwrap_err().to_string(), "Cannot reorder: column 'cell' is listed twice");
+        let err = data.reorder_columns(&["color_red"]).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot reorder 'color_red' on its own - it is a one-hot column of 'color'");
+        assert_eq!(data.column_names(), before, "an error changes nothing");
+        data.reorder_columns(&[])?;
+        assert_eq!(data.column_names(), before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_rows() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "Sample overview\nbarcode\tgroup\tnCount\n# run 7\nAAAC\tT\t10\nAAAG\tB\nAAAT\tB\t20\nAACA\tNK\t400\nAACC\tDC\t5\n";
+        let options = ParseOptions { comment: Some(b'#'), header_row: 1, skip_bad_rows: true, max_rows: Some(2), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.as_vec_string("barcode").unwrap(), vec!["AAAC", "AAAT"], "titles, comments and bad rows do not count");
+        assert_eq!(data.factors["group"].get_levels(), ["T", "B"]);
+        assert_eq!(data.column("nCount").unwrap().summary.as_ref().and_then(|s| s.max), Some(20.0));
+        let report = data.parse_report();
+        assert_eq!((report.rows_read, report.rows_kept, report.limit), (4, 2, Some(2)));
+        assert_eq!(report.to_string(), "4 rows read, 2 kept, 2 dropped (1 wrong number of fields, 1 line before the header) - stopped after 2 rows (max_rows)");
+
+        // the streaming reader stops at the same row in every pass
+        let dir = tempdir()?;
+        let path = dir.path().join("long.tsv");
+        std::fs::write(&path, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&path, &options)?;
+        assert_eq!(streamed.factors["group"].get_levels(), ["T", "B"]);
+        assert_eq!(streamed.parse_report(), report);
+        assert_eq!(streamed.stream_rows(&path, &options, |_| Ok(()))?, 2);
+
+        // the files together
+        let combined = SurvivalData::from_files(&[&path, &path], &ParseOptions { max_rows: Some(5), ..options.clone() }, false)?;
+        assert_eq!(combined.as_vec_string("barcode").unwrap(), vec!["AAAC", "AAAT", "AACA", "AACC", "AAAC"]);
+        assert_eq!(combined.parse_report().limit, Some(5));
+
+        // no note if the table has no more rows
+        let all = ParseOptions { max_rows: Some(4), ..options };
+        assert_eq!(SurvivalData::from_reader_with_options(Cursor::new(tsv), &all)?.parse_report().limit, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_leading_zeros() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "barcode\tzip\tnCount\nAAAC\t01067\t010\nAAAG\t80331\t0\n";
+        let options = ParseOptions { preserve_leading_zeros: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column("zip").unwrap().dtype, Dtype::Factor);
+        assert_eq!(data.as_vec_string("zip").unwrap(), vec!["01067", "80331"], "the zeros are kept");
+        assert_eq!(data.column("nCount").unwrap().dtype, Dtype::Factor);
+
+        let default = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        assert_eq!(default.column("zip").unwrap().dtype, Dtype::Integer);
+
+        let dir = tempdir()?;
+        let input = dir.path().join("zip.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.columns, data.columns.iter().map(|c| Column { summary: None, ..c.clone() }).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_boolean_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "doublet\tflag\tsex\tcount\nTRUE\tyes\tm\t1\n1\tno\tf\t0\nFALSE\tNA\tm\t2\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        let dtypes: Vec<Dtype> = data.columns.iter().map(|c| c.dtype).collect();
+        assert_eq!(dtypes, vec![Dtype::Boolean, Dtype::Boolean, Dtype::Factor, Dtype::Integer]);
+        assert_eq!(data.as_vec_f64("doublet"), vec![1.0, 1.0, 0.0], "mixed TRUE/1 tokens are still boolean");
+        assert!(data.as_vec_f64("flag")[2].is_nan());
+        assert!(!data.factors.contains_key("doublet"), "booleans are no factors");
+        let json = serde_json::to_string(&data.compute_factors())?;
+        assert!(json.contains(r#""dtype":"boolean""#), "{json}");
+
+        // configurable vocabulary
+        let options = ParseOptions {
+            true_tokens: vec!["m".to_string()],
+            false_tokens: vec!["f".to_string()],
+            ..Default::default()
+        };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.column("sex").unwrap().dtype, Dtype::Boolean);
+        assert_eq!(data.column("doublet").unwrap().dtype, Dtype::Factor);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_categorical_column() {
+        use std::io::Cursor;
+
+        let tsv = "cluster\tage\n1\t42\n";
+        let err = SurvivalData::from_reader(Cursor::new(tsv), b'\t', ["clustr".to_string()].into()).unwrap_err();
+        assert!(err.to_string().contains("did you mean 'cluster'?"), "{err}");
+    }
+
+    #[test]
+    fn test_ordered_factor_spec() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "stage\nhigh\nlow\nNA\nmedium\nlow\n";
+        let mut options = ParseOptions::default();
+        options.factor_specs.insert("stage".into(), crate::FactorSpec::ordered(vec!["low".into(), "medium".into(), "high".into()]));
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+
+        let stage = data.compute_factors();
+        let stage = stage.get("stage").unwrap();
+        assert!(stage.ordered);
+        assert_eq!(stage.levels, vec!["low", "medium", "high"], "declared order - not first seen");
+        assert_eq!(data.as_vec_f64("stage")[..2], [2.0, 0.0]);
+
+        // unknown values are an error ...
+        let tsv = "stage\nlow\nextreme\n";
+        assert!(SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).is_err());
+
+        // ... unless a trailing level is configured
+        options.factor_specs.get_mut("stage").unwrap().other = Some("other".into());
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.factors["stage"].get_levels(), ["low", "medium", "high", "other"]);
+        assert_eq!(data.as_vec_string("stage"), Some(vec!["low".to_string(), "other".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_tokens() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "group\tscore\nA\t1.5\nNA\tNaN\nNULL\tNone\n\t2\nB\tNULL\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+
+        assert_eq!(data.factors["group"].get_levels(), ["A", "B"], "no missing token becomes a level");
+        let missing: Vec<(Dtype, usize)> = data.columns.iter().map(|c| (c.dtype, c.missing)).collect();
+        assert_eq!(missing, vec![(Dtype::Factor, 3), (Dtype::Float, 3)]);
+
+        // a custom token list
+        let options = ParseOptions { missing_tokens: vec!["-".to_string()], ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new("score\n1\n-\n3\n"), &options)?;
+        assert_eq!(data.columns[0].dtype, Dtype::Integer);
+        assert_eq!(data.columns[0].missing, 1);
+
+        let json = serde_json::to_string(&data.compute_factors())?;
+        assert!(json.contains(r#""missing":1"#), "{json}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_na_values() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let tsv = "age\tbmi\tstage\n54\t-999\tII\n-1\t22.5\t-999\n61\t-999.0\tIII\n";
+        let (na_values, column_na_values) = crate::options::parse_na_values("-999,age:-1")?;
+        let options = ParseOptions { na_values, column_na_values, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let age = data.column("age").unwrap();
+        assert_eq!((age.dtype, age.missing), (Dtype::Integer, 1));
+        assert_eq!(age.summary.as_ref().unwrap().min, Some(54.0), "no -1 in the range");
+        let bmi = data.column("bmi").unwrap();
+        assert_eq!((bmi.dtype, bmi.missing, bmi.summary.as_ref().unwrap().count), (Dtype::Float, 2, 1));
+        assert_eq!(data.factors["stage"].get_levels(), ["II", "III"], "not a level");
+
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("clinical.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.column("age").unwrap().missing, 1);
+        assert_eq!(streamed.column("bmi").unwrap().summary.as_ref().unwrap().max, Some(22.5));
+        assert_eq!(streamed.factors["stage"].get_levels(), ["II", "III"]);
+
+        let options = ParseOptions { column_na_values: [("agee".to_string(), vec![-1.0])].into(), ..Default::default() };
+        let err = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).unwrap_err();
+        assert!(err.to_string().contains("did you mean 'age'"), "{err}");
+        let options = ParseOptions { na_values: vec![-999.0], missing_tokens: Vec::new(), ..Default::default() };
+        assert!(SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quoted_fields() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let csv = "\"cell, type\",note,n\n\"T cell, CD8+\",\"say \"\"hi\"\"\",1\nB cell,plain,2\n";
+        let data = SurvivalData::from_reader(Cursor::new(csv), b',', HashSet::new())?;
+
+        assert_eq!(data.headers, vec!["cell, type", "note", "n"]);
+        assert_eq!(data.numeric_data.dim(), (2, 3));
+        assert_eq!(data.factors["cell, type"].get_levels(), ["T cell, CD8+", "B cell"]);
+        assert_eq!(data.factors["note"].get_levels(), ["say \"hi\"", "plain"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_levels_free_text() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+
+        let mut tsv = String::from("barcode\tcluster\tsample\n");
+        for i in 0..10 {
+            tsv.push_str(&format!("cell{i}\tc{}\ts{i}\n", i % 2));
+        }
+        let options = ParseOptions {
+            max_levels: Some(5),
+            categorical: ["sample".to_string()].into(),
+            ..Default::default()
+        };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+
+        let barcode = &data.columns[0];
+        assert_eq!(barcode.dtype, Dtype::FreeText);
+        assert_eq!(barcode.cardinality, Some(10));
+        assert_eq!(data.columns[1].dtype, Dtype::Factor);
+        assert_eq!(data.columns[1].cardinality, Some(2));
+        assert_eq!(data.columns[2].dtype, Dtype::Factor, "declared categorical columns are kept");
+
+        let map = data.compute_factors();
+        assert!(map.get("barcode").is_none(), "free text levels are not written");
+        assert_eq!(map.column("barcode").unwrap().cardinality, Some(10));
+        assert!(map.get("cluster").is_some());
+        // the values are still available in memory
+        assert_eq!(data.as_vec_string("barcode").unwrap()[3], "cell3");
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_numeric_as_string() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
+
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        std::fs::write(&path, "dose\tid\tflag\n1\t007\tyes\n<0.5\t1.0\tno\nNA\t1\tyes\n2\tx\tno\n")?;
+        let options = ParseOptions {
+            as_numeric: ["dose".to_string(), "flag".to_string()].into(),
+            as_string: ["id".to_string()].into(),
+            ..Default::default()
+        };
+        let data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        assert_eq!(data.columns[0].dtype, Dtype::Integer, "only the numbers decide");
+        assert_eq!(data.columns[0].missing, 2, "'<0.5' is coerced to missing");
+        let dose = data.as_vec_f64("dose");
+        assert_eq!((dose[0], dose[3]), (1.0, 2.0));
+        assert!(dose[1].is_nan());
+        assert_eq!((data.columns[2].dtype, data.columns[2].missing), (Dtype::Float, 4), "no boolean and no value left");
+        assert_eq!(data.columns[1].dtype, Dtype::FreeText);
+        assert_eq!(data.as_vec_string("id").unwrap(), ["007", "1.0", "1", "x"], "kept as written");
+        assert!(data.compute_factors().get("id").is_none());
+
+        let streamed = SurvivalData::from_file_streaming(&path, &options)?;
+        for (s, d) in streamed.columns.iter().zip(&data.columns) {
+            assert_eq!((&s.name, s.dtype, s.missing), (&d.name, d.dtype, d.missing));
+        }
+        assert!(streamed.factors.is_empty());
+
+        let conflict = ParseOptions { categorical: ["dose".to_string()].into(), ..options };
+        let err = SurvivalData::from_file_with_factors(&path, &conflict, &FactorMap::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Column 'dose' has conflicting type settings: as_numeric and categorical");
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_cells() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
 
-        // Check levels
-        let color = loaded_factors.get("Color").unwrap();
-        assert_eq!(color.get_levels(), vec!["Red", "Blue", "Green"]);
-        assert!(color.one_hot);
+        let tsv = "cell\tqc\tgroup\nA\t{\"x\":1,\"y\":\"low\"}\ta\nB\tNA\tb\nC\t{\"y\":\"high\", \"x\":2.5}\ta\n";
+        let read = |json_cells| {
+            let options = ParseOptions { json_cells, ..Default::default() };
+            SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)
+        };
 
-        let number = loaded_factors.get("Number").unwrap();
-        assert_eq!(number.get_levels(), vec!["1", "2", "3"]);
-        assert!(number.one_hot);
+        // off: every object is a level
+        assert_eq!(read(JsonCells::Off)?.column("qc").unwrap().dtype, Dtype::Factor);
+
+        let kept = read(JsonCells::Keep)?;
+        let qc = kept.column("qc").unwrap();
+        assert_eq!((qc.dtype, qc.missing, qc.cardinality), (Dtype::Json, 1, Some(2)));
+        assert_eq!(kept.as_vec_string("qc").unwrap()[2], "{\"y\":\"high\", \"x\":2.5}", "kept as written");
+        assert!(kept.compute_factors().get("qc").is_none());
+        assert!(kept.compute_factors().get("group").is_some());
+        let mut ndjson = Vec::new();
+        kept.write_ndjson(&mut ndjson)?;
+        let first: serde_json::Value = serde_json::from_slice(ndjson.split(|&b| b == b'\n').next().unwrap())?;
+        assert_eq!(first["qc"]["y"], "low", "a nested object");
+
+        let flat = read(JsonCells::Flatten)?;
+        assert_eq!(flat.column_names(), ["cell", "qc.x", "qc.y", "group"]);
+        assert_eq!(flat.column("qc.x").unwrap().dtype, Dtype::Float);
+        assert_eq!(flat.column("qc.x").unwrap().missing, 1);
+        assert_eq!(flat.as_vec_string("qc.y").unwrap(), ["low", "NA", "high"]);
+        assert_eq!(flat.header(), ["cell", "qc", "group"], "the file header is unchanged");
+
+        let uneven = "cell\tqc\nA\t{\"x\":1}\nB\t{\"x\":2,\"z\":3}\n";
+        let options = ParseOptions { json_cells: JsonCells::Flatten, ..Default::default() };
+        let err = SurvivalData::from_reader_with_options(Cursor::new(uneven), &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Column 'qc' line 3: the JSON keys x, z differ from x - flattening needs the same keys in every row"
+        );
 
+        // a declared json column has to hold objects only
+        let options = ParseOptions { dtypes: [("cell".to_string(), Dtype::Json)].into(), ..Default::default() };
+        assert!(SurvivalData::from_reader_with_options(Cursor::new(tsv), &options).is_err());
+        let options = ParseOptions { dtypes: [("qc".to_string(), Dtype::Json)].into(), ..Default::default() };
+        assert_eq!(SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?.column("qc").unwrap().dtype, Dtype::Json);
         Ok(())
     }
-}
 
-#[cfg(test)]
-use std::path::PathBuf;
+    #[test]
+    fn test_from_files() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-mod tests_survival_data_from_file_one_hot_factors {
-    use super::*;
-    use std::fs::{self, File};
-    use std::io::Write;
-    use std::collections::HashSet;
-    use tempfile::tempdir;
+        let dir = tempdir()?;
+        let (a, b, c) = (dir.path().join("a.tsv"), dir.path().join("b.tsv"), dir.path().join("c.tsv"));
+        std::fs::write(&a, "cell\tcluster\tnCount\nA1\t0\t10\nA2\t1\t20\n")?;
+        std::fs::write(&b, "nCount\tcell\tcluster\n30\tB1\t2\n")?;
+        std::fs::write(&c, "cell\tcluster\tbatch\nC1\t0\tx\n")?;
+        let options = ParseOptions::default();
+
+        let data = SurvivalData::from_files(&[&a, &b], &options, false)?;
+        assert_eq!(data.column_names(), ["cell", "cluster", "nCount", SOURCE_COLUMN]);
+        assert_eq!(data.as_vec_f64("nCount"), vec![10.0, 20.0, 30.0], "matched by name");
+        let sources = data.as_vec_string(SOURCE_COLUMN).unwrap();
+        assert_eq!(sources, [a.display().to_string(), a.display().to_string(), b.display().to_string()]);
+        assert_eq!(data.column(SOURCE_COLUMN).unwrap().dtype, Dtype::Factor);
+
+        let err = SurvivalData::from_files(&[&a, &c], &options, false).unwrap_err();
+        assert!(err.to_string().contains("it lacks 'nCount' and has the extra 'batch'"), "{err}");
+        assert!(err.to_string().starts_with(&format!("The header of {:?} differs", c)), "{err}");
+        let ragged = dir.path().join("ragged.tsv");
+        std::fs::write(&ragged, "cell\tcluster\tnCount\nR1\t0\n")?;
+        let err = SurvivalData::from_files(&[&a, &ragged], &options, false).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("{:?}: line 2 has 2 fields", ragged)), "{err}");
+        match err {
+            DataTableError::InFile { path, source } => {
+                assert_eq!(path, ragged);
+                assert!(matches!(*source, DataTableError::InconsistentRow { line: 2, found: 2, expected: 3 }), "{source:?}");
+            }
+            err => panic!("expected InFile, got {err:?}"),
+        }
+        let union = SurvivalData::from_files(&[&a, &c], &options, true)?;
+        assert_eq!(union.column_names(), ["cell", "cluster", "nCount", "batch", SOURCE_COLUMN]);
+        assert_eq!(union.column("nCount").unwrap().missing, 1);
+        assert_eq!(union.column("batch").unwrap().missing, 2);
+        Ok(())
+    }
 
     #[test]
-    fn test_survivaldata_one_hot_factors() -> Result<(), Box<dyn std::error::Error>> {
-        // --- Prepare temporary directory ---
-        let dir =  PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let csv_path = dir.as_path().join("test_factors.csv");
-        let factors_path = dir.as_path().join("test_factors_factors.json");
+    fn test_empty_input() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-        // --- Write CSV file ---
-        // Two categorical columns: Color, Number
-        // Values: Color = Red Blue Blue Green Blue Red Na Na
-        //         Number = 1 2 1 3 2 1 Na Na
-        let csv_content = "\
-Color,Number
-Red,1
-Blue,2
-Blue,1
-Green,3
-Blue,2
-Red,1
-Na,Na
-Na,Na
-";
-        let mut file = File::create(&csv_path)?;
-        file.write_all(csv_content.as_bytes())?;
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        let options = ParseOptions::default();
+        for blank in ["", "  \n\n", "\u{feff}\n"] {
+            std::fs::write(&path, blank)?;
+            let err = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default()).unwrap_err();
+            assert!(matches!(err, DataTableError::EmptyInput), "{blank:?}: {err:?}");
+            assert_eq!(err.to_string(), "input is empty");
+            assert!(matches!(SurvivalData::from_file_streaming(&path, &options), Err(DataTableError::EmptyInput)));
+        }
 
-        let mut color = Factor::new("Color", false);
-        let _ = color.push("Red");
-        let _ = color.push("Blue");
-        let _ = color.push("Green");
-        color.one_hot = true;
-        
-        let mut number = Factor::new("Number", false);
-        let _ = number.push("1");
-        let _ = number.push("2");
-        let _ = number.push("3");
-        number.one_hot = true;
-        let mut temp = SurvivalData::default();
-        temp.factors.insert( "Color".to_string(), color);
-        temp.factors.insert( "Number".to_string(), number);
+        std::fs::write(&path, "cell\tage\n")?;
+        let data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        assert_eq!(data.column_names(), ["cell", "age"]);
+        let map = data.compute_factors();
+        assert_eq!(map.n_rows, Some(0));
+        assert_eq!(map.columns.len(), 2);
+        assert_eq!(SurvivalData::from_file_streaming(&path, &options)?.compute_factors().n_rows, Some(0));
 
-        temp.save_factors(&factors_path); //default location
-        let categorical_cols= HashSet::<String>::new();
-        // --- Load SurvivalData ---
-        let data = SurvivalData::from_file(&csv_path, b',', categorical_cols, &factors_path)?;
+        std::fs::write(&path, "cell\tage\nA\t1\nB\t2\n")?;
+        assert_eq!(SurvivalData::from_file_streaming(&path, &options)?.compute_factors().n_rows, Some(2));
+        Ok(())
+    }
 
-        // --- Assertions ---
+    #[test]
+    fn test_constant_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-        // 1️⃣ Check original factor columns contain indices / NaN
-        println!("The data object: {data}");
-        let color_idx_col = data.headers.iter().position(|h| h == "Color").unwrap();
-        let number_idx_col = data.headers.iter().position(|h| h == "Number").unwrap();
-        assert_eq!(color_idx_col, 0, "expected color_idx_col 0 - have these cols: {:?}", &data.headers);
-        assert_eq!(number_idx_col, 4, "expected number_idx_col 4 - have these cols: {:?}", &data.headers);
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        std::fs::write(&path, "cell\tbatch\tdose\tempty\tflag\tscore\nA\tb1\t1\tNA\tTRUE\t1.5\nB\tb1\t1.0\tNA\tyes\t2\nC\tNA\t1\t\tTRUE\t2\n")?;
+        let options = ParseOptions::default();
+        let constant = |data: &SurvivalData| -> Vec<String> {
+            data.columns.iter().filter(|c| c.constant).map(|c| c.name.clone()).collect()
+        };
 
-        assert_eq!(data.numeric_data[[0, color_idx_col]], 0.0); // Red first index 0
-        assert_eq!(data.numeric_data[[1, color_idx_col]], 1.0); // Blue index 1
-        assert!(data.numeric_data[[6, color_idx_col]].is_nan()); // Na
+        let mut data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        assert_eq!(constant(&data), ["batch", "dose", "empty", "flag"]);
+        assert_eq!(constant(&SurvivalData::from_file_streaming(&path, &options)?), ["batch", "dose", "empty", "flag"]);
+        let json = serde_json::to_value(data.compute_factors())?;
+        assert_eq!(json["columns"][1]["constant"], true);
+        assert!(json["columns"][0].get("constant").is_none(), "only constant columns are marked");
+
+        assert_eq!(data.drop_constant_columns(), ["batch", "dose", "empty", "flag"]);
+        assert_eq!(data.column_names(), ["cell", "score"]);
+        assert_eq!(data.as_vec_f64("score"), vec![1.5, 2.0, 2.0]);
+        assert!(!data.factors.contains_key("batch"));
+
+        // a filter can leave a single level
+        let subset = data.filter_eq("score", "2")?;
+        assert!(!subset.column("cell").unwrap().constant);
+        assert!(subset.column("score").unwrap().constant);
+        Ok(())
+    }
 
-        assert_eq!(data.numeric_data[[0, number_idx_col]], 0.0); // 1 -> index 0
-        assert_eq!(data.numeric_data[[1, number_idx_col]], 1.0); // 2 -> index 1
-        assert!(data.numeric_data[[6, number_idx_col]].is_nan()); // Na
+    #[test]
+    fn test_sparse_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-        assert_eq!(data.headers[1], "Color_Red");
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        std::fs::write(&path, "cell\tnote\tgroup\tscore\nA\tNA\tx\t1\nB\tseen\tNA\t2\nC\t-\tNA\t3\nD\tNA\ty\t\n")?;
+        let options = ParseOptions::default();
+        let mut group = Factor::new("group", true);
+        group.level_to_index("x");
+        group.level_to_index("y");
+
+        let mut data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::new(vec![group.as_json("group")]))?;
+        assert_eq!(data.n_cols(), 6);
+        assert_eq!(data.coverage(), [("cell".to_string(), 1.0), ("note".into(), 0.5), ("group".into(), 0.5), ("score".into(), 0.75)]);
+        assert!(data.drop_sparse_columns(0.5).is_empty(), "the threshold itself is enough");
+        assert_eq!(data.drop_sparse_columns(0.6), [("note".to_string(), 0.5), ("group".into(), 0.5)]);
+        assert_eq!(data.column_names(), ["cell", "score"], "with the one-hot columns");
+        assert!(!data.factors.contains_key("group"));
+
+        // the missing tokens decide what counts as a value
+        let dash = ParseOptions { missing_tokens: vec!["NA".into(), "-".into(), "".into()], ..Default::default() };
+        let mut streamed = SurvivalData::from_file_streaming(&path, &dash)?;
+        assert_eq!(streamed.drop_sparse_columns(0.5), [("note".to_string(), 0.25)]);
+        Ok(())
+    }
 
+    #[test]
+    fn test_mixed_line_endings() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-        // Find the indices of the one-hot columns
-        let color_red_idx = data.headers.iter().position(|h| h == "Color_Red").unwrap();
-        let color_blue_idx = data.headers.iter().position(|h| h == "Color_Blue").unwrap();
-        let color_green_idx = data.headers.iter().position(|h| h == "Color_Green").unwrap();
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        // concatenated from a Windows and a Unix export
+        std::fs::write(&path, "cell\tdose\tgroup\tnote\r\nA\t1\ttreated\tx y\r\nB\t2\tcontrol\tz\nC\t3\ttreated\tx y\r\nD\t4\tcontrol\tz\n")?;
+        let options = ParseOptions { as_string: ["note".to_string()].into(), ..Default::default() };
+        let clean = |data: &SurvivalData| {
+            assert_eq!(data.header(), ["cell", "dose", "group", "note"]);
+            assert_eq!(data.factors["group"].get_levels(), ["treated", "control"]);
+            assert_eq!(data.column("dose").unwrap().dtype, Dtype::Integer);
+        };
 
-        let number_1_idx = data.headers.iter().position(|h| h == "Number_1").unwrap();
-        let number_2_idx = data.headers.iter().position(|h| h == "Number_2").unwrap();
-        let number_3_idx = data.headers.iter().position(|h| h == "Number_3").unwrap();
+        let data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        clean(&data);
+        assert_eq!(data.as_vec_f64("dose"), vec![1.0, 2.0, 3.0, 4.0]);
+        clean(&SurvivalData::from_file_streaming(&path, &options)?);
+        let split = ParseOptions { separator: Some(crate::options::Separator::text("\t")?), ..options.clone() };
+        clean(&SurvivalData::from_file_with_factors(&path, &split, &FactorMap::default())?);
+        let mut ndjson = Vec::new();
+        data.write_ndjson(&mut ndjson)?;
+        assert!(!String::from_utf8(ndjson)?.contains("\\r"), "no carriage return in any value");
+        Ok(())
+    }
 
-        // --- Expected one-hot values per row ---
-        // Rows: Color = Red, Blue, Blue, Green, Blue, Red, NA, NA
-        //       Number = 1,2,1,3,2,1,NA,NA
-        let expected_color = vec![
-            (1.0, 0.0, 0.0), // Red
-            (0.0, 1.0, 0.0), // Blue
-            (0.0, 1.0, 0.0), // Blue
-            (0.0, 0.0, 1.0), // Green
-            (0.0, 1.0, 0.0), // Blue
-            (1.0, 0.0, 0.0), // Red
-            (f64::NAN, f64::NAN, f64::NAN), // NA
-            (f64::NAN, f64::NAN, f64::NAN), // NA
-        ];
+    #[test]
+    fn test_multi_line_fields() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-        let expected_number = vec![
-            (1.0, 0.0, 0.0), // 1
-            (0.0, 1.0, 0.0), // 2
-            (1.0, 0.0, 0.0), // 1
-            (0.0, 0.0, 1.0), // 3
-            (0.0, 1.0, 0.0), // 2
-            (1.0, 0.0, 0.0), // 1
-            (f64::NAN, f64::NAN, f64::NAN), // NA
-            (f64::NAN, f64::NAN, f64::NAN), // NA
-        ];
+        let dir = tempdir()?;
+        let path = dir.path().join("notes.csv");
+        // clinical notes that wrap - a quoted line break is part of the field, not a new record
+        std::fs::write(&path, "patient,note,age\nP1,\"line1\nline2\",40\nP2,\"short\",52\nP3,\"a \"\"quoted\"\"\r\nword\",61\n")?;
+        let options = ParseOptions { delimiter: b',', as_string: ["note".to_string()].into(), ..Default::default() };
+
+        let data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        assert_eq!(data.n_rows(), 3);
+        assert_eq!(data.as_vec_string("note").unwrap(), ["line1\nline2", "short", "a \"quoted\"\r\nword"]);
+        assert_eq!(data.as_vec_f64("age"), vec![40.0, 52.0, 61.0]);
+        assert_eq!(data.column("age").unwrap().dtype, Dtype::Integer);
+        assert_eq!(data.parse_report().rows_read, 3);
+
+        let streamed = SurvivalData::from_file_streaming(&path, &options)?;
+        assert_eq!(streamed.compute_factors().n_rows, Some(3));
+        assert_eq!(streamed.stream_rows(&path, &options, |_| Ok(()))?, 3);
+        assert_eq!(SurvivalData::from_files(&[&path, &path], &options, false)?.n_rows(), 6);
+
+        // written back quoted, so it reads back the same
+        let out = dir.path().join("out.csv");
+        data.write_tsv(&out, b',')?;
+        let back = SurvivalData::from_file_with_factors(&out, &options, &FactorMap::default())?;
+        assert_eq!(back.as_vec_string("note"), data.as_vec_string("note"));
+
+        // a short row after a wrapped field reports its line in the file
+        std::fs::write(&path, "patient,note,age\nP1,\"line1\nline2\",40\nP2,52\n")?;
+        let err = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default()).unwrap_err();
+        assert_eq!(err.to_string(), "line 4 has 2 fields, expected 3");
+        Ok(())
+    }
 
-        for row in 0..data.numeric_data.nrows() {
-            // Color
-            let val_red = data.numeric_data[[row, color_red_idx]];
-            let val_blue = data.numeric_data[[row, color_blue_idx]];
-            let val_green = data.numeric_data[[row, color_green_idx]];
+    #[test]
+    fn test_one_based_codes() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-            let (exp_red, exp_blue, exp_green) = expected_color[row];
-            if exp_red.is_nan() {
-                assert!(val_red.is_nan());
-                assert!(val_blue.is_nan());
-                assert!(val_green.is_nan());
-            } else {
-                assert_eq!(val_red, exp_red);
-                assert_eq!(val_blue, exp_blue);
-                assert_eq!(val_green, exp_green);
-            }
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        std::fs::write(&path, "group\tstage\tn\nb\tlow\t1\na\thigh\t2\nb\tNA\t3\n")?;
+        let mut options = ParseOptions { one_based_codes: true, ..Default::default() };
+        options.factor_specs.insert("stage".into(), crate::FactorSpec::ordered(vec!["low".into(), "high".into()]));
+
+        let data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        assert_eq!(data.as_vec_f64("group"), vec![1.0, 2.0, 1.0]);
+        let stage = data.as_vec_f64("stage");
+        assert_eq!((stage[0], stage[1]), (1.0, 2.0));
+        let map = data.compute_factors();
+        assert_eq!(map.get("group").unwrap().numeric, Some(vec![1.0, 2.0]));
+        assert_eq!(map.get("stage").unwrap().numeric, Some(vec![1.0, 2.0]));
+
+        // the streaming output pass uses the same codes
+        let streamed = SurvivalData::from_file_streaming(&path, &options)?;
+        assert_eq!(streamed.compute_factors().factors, map.factors);
+        let mut first = Vec::new();
+        streamed.stream_rows(&path, &options, |row| {
+            first.push(row[0]);
+            Ok(())
+        })?;
+        assert_eq!(first, vec![1.0, 2.0, 1.0]);
+
+        let codes = dir.path().join("codes.tsv");
+        data.write_tsv_as(&codes, b'\t', FactorFormat::Codes)?;
+        assert!(std::fs::read_to_string(&codes)?.contains("\n1\t1\t1\n"));
+        Ok(())
+    }
 
-            // Number
-            let val_1 = data.numeric_data[[row, number_1_idx]];
-            let val_2 = data.numeric_data[[row, number_2_idx]];
-            let val_3 = data.numeric_data[[row, number_3_idx]];
+    #[test]
+    fn test_from_file_with_edited_factors() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
 
-            let (exp_1, exp_2, exp_3) = expected_number[row];
-            if exp_1.is_nan() {
-                assert!(val_1.is_nan(), "expected NA value for Number_1 at row {row}; all data {}",data.numeric_data);
-                assert!(val_2.is_nan(), "expected NA value for Number_2 at row {row}; all data {}",data.numeric_data);
-                assert!(val_3.is_nan(), "expected NA value for Number_3 at row {row}; all data {}",data.numeric_data);
-            } else {
-                assert_eq!(val_1, exp_1, "Number_1 mismatch at row {}/{}: {} != {} all data {}", number_1_idx, row, val_1, exp_1,data.numeric_data);
-                assert_eq!(val_2, exp_2, "Number_2 mismatch at row {}/{}: {} != {} all data {}", number_2_idx, row, val_2, exp_2,data.numeric_data);
-                assert_eq!(val_3, exp_3, "Number_3 mismatch at row {}/{}: {} != {} all data {}", number_3_idx, row, val_3, exp_3,data.numeric_data);
+        let dir = tempdir()?;
+        let data_path = dir.path().join("meta.tsv");
+        let factors_path = dir.path().join("meta.factors.json");
+        std::fs::write(&data_path, "cluster\tn\n0\t5\n1\t6\n2\t7\n0\t8\n")?;
+
+        let first = SurvivalData::from_file_with_options(&data_path, &ParseOptions::new(b'\t', ["cluster".to_string()].into()), &factors_path)?;
+        first.write_factors(&factors_path)?;
+
+        // hand edit: reverse the codes
+        let mut map = FactorMap::from_json(&factors_path)?;
+        map.factors[0].numeric = Some(vec![2.0, 1.0, 0.0]);
+        map.write_json(&factors_path)?;
+
+        let edited = FactorMap::from_json(&factors_path)?;
+        let data = SurvivalData::from_file_with_factors(&data_path, &ParseOptions::default(), &edited)?;
+        assert_eq!(data.as_vec_f64("cluster"), vec![2.0, 1.0, 0.0, 2.0]);
+        assert_eq!(data.compute_factors().get("cluster"), edited.get("cluster"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file_streaming() -> Result<(), Box<dyn std::error::Error>> {
+        use tempfile::tempdir;
+
+        let dir = tempdir()?;
+        let path = dir.path().join("meta.tsv");
+        let mut tsv = String::from("barcode\tage\tscore\tcluster\n");
+        for i in 0..10 {
+            let age = if i == 4 { "NA".to_string() } else { (20 + i).to_string() };
+            tsv.push_str(&format!("cell{i}\t{age}\t{}.5\tc{}\n", i, (i * 7) % 3));
+        }
+        std::fs::write(&path, tsv)?;
+        let options = ParseOptions { max_levels: Some(5), ..Default::default() };
+
+        let streamed = SurvivalData::from_file_streaming(&path, &options)?;
+        let data = SurvivalData::from_file_with_factors(&path, &options, &FactorMap::default())?;
+        assert_eq!(streamed.numeric_data.nrows(), 0, "no rows are kept");
+        assert_eq!(streamed.headers, data.headers);
+        for (s, d) in streamed.columns.iter().zip(&data.columns) {
+            assert_eq!((&s.name, s.dtype, s.missing), (&d.name, d.dtype, d.missing));
+        }
+        assert_eq!(streamed.columns[0].dtype, Dtype::FreeText);
+        assert_eq!(streamed.compute_factors().factors, data.compute_factors().factors);
+
+        let age = streamed.columns[1].summary.as_ref().unwrap();
+        assert_eq!(age.count, 9);
+        assert_eq!((age.min, age.max), (Some(20.0), Some(29.0)));
+        assert!((age.mean.unwrap() - 221.0 / 9.0).abs() < 1e-9);
+        assert!(streamed.columns[3].summary.is_none(), "factors have no numeric summary");
+
+        // the optional output pass gives the same values as the in-memory parser
+        let mut rows = Vec::new();
+        let n = streamed.stream_rows(&path, &options, |row| {
+            rows.push(row.to_vec());
+            Ok(())
+        })?;
+        assert_eq!(n, 10);
+        for (i, row) in rows.iter().enumerate() {
+            assert!(row[0].is_nan(), "free text is not emitted");
+            for (j, value) in row.iter().enumerate().skip(1) {
+                let expected = data.numeric_data[[i, j]];
+                assert!(*value == expected || (value.is_nan() && expected.is_nan()), "row {i} column {j}");
             }
         }
+        Ok(())
+    }
 
+    #[test]
+    fn test_relabel_levels() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+
+        let tsv = "cluster\tn\n0\t1\n1\t2\n2\t3\n";
+        let mut data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', ["cluster".to_string()].into())?;
+        let dir = tempdir()?;
+        let labels_path = dir.path().join("labels.json");
+        std::fs::write(&labels_path, r#"{"cluster": {"0": "Naive CD4", "1": "Memory CD4"}}"#)?;
+        data.relabel_levels(&crate::factor_map::read_level_labels(&labels_path)?)?;
+
+        let map = data.compute_factors();
+        let cluster = map.get("cluster").unwrap();
+        assert_eq!(cluster.levels, vec!["0", "1", "2"], "the levels still match the data");
+        assert_eq!(cluster.numeric, Some(vec![0.0, 1.0, 2.0]));
+        assert_eq!(cluster.labels, Some(vec!["Naive CD4".to_string(), "Memory CD4".to_string(), "2".to_string()]));
+
+        // the labels survive the factors file
+        let factors = dir.path().join("factors.json");
+        map.write_json(&factors)?;
+        let back = FactorMap::from_json(&factors)?.to_factors();
+        assert_eq!(back["cluster"].label("1"), "Memory CD4");
+
+        let collision: LevelLabels = [("cluster".to_string(), [("0".to_string(), "2".to_string())].into())].into();
+        let err = data.relabel_levels(&collision).unwrap_err();
+        assert!(err.to_string().contains("would both be labeled '2'"), "{err}");
+        let unknown: LevelLabels = [("n".to_string(), HashMap::new())].into();
+        assert!(data.relabel_levels(&unknown).is_err());
         Ok(())
     }
 
     #[test]
-    fn test_survivaldata_train_test_split_unique() -> Result<(), Box<dyn std::error::Error>> {
-        use std::fs::File;
-        use std::io::Write;
+    fn test_write_tsv() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
         use tempfile::tempdir;
 
-        // --- Prepare temporary directory and files ---
+        let tsv = "cell type\tn\tscore\n\"T cell, CD8+\"\t3\tNA\nB cell\t4\t0.5\nNA\t5\t1\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
         let dir = tempdir()?;
-        let csv_path = dir.path().join("unique_data.csv");
-        let factors_path = dir.path().join("unique_factors.json");
 
-        // --- Generate CSV data with 10 rows, 10 columns, unique values 1..100 ---
-        let nrows = 10;
-        let ncols = 10;
-        let mut csv_content = String::new();
-        // header
-        csv_content.push_str(&(0..ncols).map(|i| format!("Col{}", i)).collect::<Vec<_>>().join(","));
-        csv_content.push('\n');
-        // data
-        let mut val = 1;
-        for _ in 0..nrows {
-            let row = (0..ncols).map(|_| {
-                let s = val.to_string();
-                val += 1;
-                s
-            }).collect::<Vec<_>>().join(",");
-            csv_content.push_str(&row);
-            csv_content.push('\n');
-        }
+        let labels = dir.path().join("labels.csv");
+        data.write_tsv(&labels, b',')?;
+        assert_eq!(
+            std::fs::read_to_string(&labels)?,
+            "cell type,n,score\n\"T cell, CD8+\",3,NA\nB cell,4,0.5\nNA,5,1\n"
+        );
+        let back = SurvivalData::from_tsv(&labels, b',', HashSet::new(), dir.path().join("none.json"))?;
+        assert_eq!(back.as_vec_string("cell type"), data.as_vec_string("cell type"));
 
-        // write CSV
-        let mut f = File::create(&csv_path)?;
-        f.write_all(csv_content.as_bytes())?;
+        let codes = dir.path().join("codes.tsv");
+        data.write_tsv_as(&codes, b'\t', FactorFormat::Codes)?;
+        assert_eq!(std::fs::read_to_string(&codes)?, "cell type\tn\tscore\n0\t3\tNA\n1\t4\t0.5\nNA\t5\t1\n");
+        Ok(())
+    }
 
-        // --- Create empty factors JSON ---
-        let mut f_factors = File::create(&factors_path)?;
-        f_factors.write_all(b"[]")?;
+    #[test]
+    fn test_level_case() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
+        use crate::options::LevelCase;
 
-        // --- Load data ---
-        let categorical_cols = HashSet::<String>::new();
-        let data = SurvivalData::from_file(&csv_path, b',', categorical_cols, &factors_path)?;
+        let tsv = "cell\nCD8 \ncd8\nCD4\nCD8\n";
+        let kept = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new())?;
+        assert_eq!(kept.factors["cell"].get_levels(), ["CD8", "cd8", "CD4"], "trimmed, case kept");
 
-        // --- Split ---
-        let (train, test) = data.train_test_split(0.7);
+        let options = ParseOptions { level_case: LevelCase::Upper, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        assert_eq!(data.factors["cell"].get_levels(), ["CD8", "CD4"]);
+        let json = data.compute_factors();
+        let cell = json.get("cell").unwrap();
+        assert_eq!(cell.counts, Some(vec![3, 1]), "collapsed spellings are counted together");
+        assert_eq!(cell.merged.as_ref().unwrap()["cd8"], "CD8");
 
-        // --- Assertions ---
-        assert_eq!(train.numeric_data.nrows() + test.numeric_data.nrows(), nrows, "Row counts must add up");
-        assert_eq!(train.headers, data.headers);
-        assert_eq!(test.headers, data.headers);
+        let dir = tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.compute_factors().factors, json.factors);
+        Ok(())
+    }
 
-        // Check that all rows are unique across train and test
-        let train_rows: Vec<Vec<f64>> = train.numeric_data.rows().into_iter().map(|r| r.to_vec()).collect();
-        let test_rows: Vec<Vec<f64>> = test.numeric_data.rows().into_iter().map(|r| r.to_vec()).collect();
+    #[test]
+    fn test_date_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        use tempfile::tempdir;
 
-        for row in &train_rows {
-            assert!(!test_rows.contains(row), "Row appeared in both train and test!");
-        }
+        let tsv = "sample\tacquired\tmixed\nA\t14.05.2023\t2023-05-14\nB\tNA\t15.05.2023\nC\t02.01.2024\t2023-05-16\n";
+        let options = ParseOptions { detect_dates: true, ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let acquired = data.column("acquired").unwrap();
+        assert_eq!((acquired.dtype, acquired.format.as_deref(), acquired.missing), (Dtype::Date, Some("%d.%m.%Y"), 1));
+        assert_eq!(data.column("mixed").unwrap().dtype, Dtype::Factor, "one format per column");
+        assert!(serde_json::to_string(&data.compute_factors())?.contains(r#""format":"%d.%m.%Y""#));
 
+        let dir = tempdir()?;
+        let out = dir.path().join("out.tsv");
+        data.write_tsv(&out, b'\t')?;
+        let written = std::fs::read_to_string(&out)?;
+        assert!(written.contains("A\t2023-05-14\t2023-05-14\n"), "normalized to ISO-8601: {written}");
+
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.column("acquired"), Some(acquired));
+        let mut rows = Vec::new();
+        streamed.stream_rows(&input, &options, |row| { rows.push(row.to_vec()); Ok(()) })?;
+        assert_eq!(rows[0][1], data.as_vec_f64("acquired")[0]);
         Ok(())
     }
 
@@ -1251,7 +4080,6 @@ Na,Na
         // 1️⃣ create a new dataset column with 10 entries
         sd.add_dataset("group_000", true, Some(10));
         assert!(sd.factors.contains_key("group_000"));
-        let f = sd.factors.get("group_000").unwrap();
         assert!(
             sd.numeric_data[[0, 3]].is_nan(),
             "initially we have NaN here"
@@ -1278,4 +4106,89 @@ Na,Na
         sd.reset_order();
         assert_eq!(sd.next_order, 0.0, "reset resets to zero");
     }
+
+    #[test]
+    fn test_factor_source() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::factor::FactorSource;
+        let tsv = "cell\tcluster\tgroup\tn\nA\t1\tx\t5\nB\t2\ty\t6\nC\t1\tx\t7\n";
+        let options = ParseOptions { categorical: ["cluster".to_string()].into(), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(std::io::Cursor::new(tsv), &options)?;
+        let map = data.compute_factors();
+        let source = |column: &str| map.get(column).and_then(|f| f.source);
+        assert_eq!(source("cluster"), Some(FactorSource::Declared), "--categorical");
+        assert_eq!(source("group"), Some(FactorSource::Inferred));
+        let json = serde_json::to_string(map.get("group").unwrap())?;
+        assert!(json.contains("\"source\":\"inferred\""), "{json}");
+
+        // a factors file keeps what it says - one without `source` declares its factors
+        let again = SurvivalData::from_reader_with_factors(std::io::Cursor::new(tsv), &ParseOptions::default(), &map)?;
+        assert_eq!(again.factors["group"].source, FactorSource::Inferred);
+        let mut legacy = map.clone();
+        legacy.factors.iter_mut().for_each(|f| f.source = None);
+        let again = SurvivalData::from_reader_with_factors(std::io::Cursor::new(tsv), &ParseOptions::default(), &legacy)?;
+        assert_eq!(again.factors["group"].source, FactorSource::Declared);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_kinds() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::column::MissingKinds;
+        use std::io::Cursor;
+        let tsv = "cell\tdose\tgroup\tnote\nA\t\tx\ta\nB\tNA\t\tb\nC\t-999\tNA\tc\nD\t<0.5\t\td\nE\t2\ty\te\n";
+        let options = ParseOptions { na_values: vec![-999.0], as_numeric: ["dose".to_string()].into(), ..Default::default() };
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let kinds: Vec<Option<MissingKinds>> = data.columns.iter().map(|c| c.missing_kinds).collect();
+        assert_eq!(kinds, [
+            None,
+            Some(MissingKinds { empty: 1, na: 3 }),
+            Some(MissingKinds { empty: 2, na: 1 }),
+            None,
+        ], "sentinels and coerced values are na");
+
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("kinds.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.columns.iter().map(|c| c.missing_kinds).collect::<Vec<_>>(), kinds);
+
+        let json = serde_json::to_value(&data.columns[2])?;
+        assert_eq!(json["missing_kinds"], serde_json::json!({ "empty": 2, "na": 1 }));
+        let collapsed = SurvivalData::from_reader_with_options(Cursor::new(tsv), &ParseOptions { collapse_missing: true, ..options })?;
+        assert!(collapsed.columns.iter().all(|c| c.missing_kinds.is_none()));
+        assert_eq!(collapsed.columns[2].missing, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_missing_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Cursor;
+        let tsv = "cell\tempty\tnotes\tgroup\nA\tNA\t\tx\nB\t\tNA\tNA\nC\tNA\tNULL\t\n";
+        let options = ParseOptions { categorical: ["group".to_string()].into(), ..Default::default() };
+        let mut data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &options)?;
+        let dtypes: Vec<(Dtype, Option<usize>)> = data.columns.iter().map(|c| (c.dtype, c.cardinality)).collect();
+        assert_eq!(dtypes, [(Dtype::Factor, Some(3)), (Dtype::AllMissing, Some(0)), (Dtype::AllMissing, Some(0)), (Dtype::Factor, Some(1))]);
+        assert!(data.columns[1].summary.is_none() && data.columns[1].missing == 3);
+        assert!(!data.factors.contains_key("empty"), "no factor, no NA level");
+        assert_eq!(data.json_schema()["properties"]["empty"]["type"], "null");
+
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("meta.tsv");
+        std::fs::write(&input, tsv)?;
+        let streamed = SurvivalData::from_file_streaming(&input, &options)?;
+        assert_eq!(streamed.compute_factors().columns, data.compute_factors().columns);
+
+        let json = serde_json::to_value(data.compute_factors())?;
+        assert_eq!(json["columns"][1]["dtype"], "all_missing");
+        assert_eq!(json["factors"].as_array().map(|f| f.len()), Some(2));
+
+        assert_eq!(data.drop_all_missing_columns(), ["empty", "notes"]);
+        assert_eq!(data.column_names(), ["cell", "group"]);
+        let mut header_only = SurvivalData::from_reader_with_options(Cursor::new("cell\tscore\n"), &ParseOptions::default())?;
+        assert!(header_only.columns.iter().all(|c| c.dtype == Dtype::AllMissing), "no rows - no value");
+        std::fs::write(&input, "cell\tscore\n")?;
+        let streamed = SurvivalData::from_file_streaming(&input, &ParseOptions::default())?;
+        assert_eq!(streamed.compute_factors().columns, header_only.compute_factors().columns);
+        assert_eq!(header_only.drop_all_missing_columns(), ["cell", "score"]);
+        Ok(())
+    }
 }
\ No newline at end of file
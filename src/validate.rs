@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use serde::Serialize;
+use crate::column::Dtype;
+use crate::error::Result;
+use crate::factor_map::FactorMap;
+use crate::options::ParseOptions;
+use crate::SurvivalData;
+
+/// One difference between a table and the factors file it is expected to match
+/// (see `FactorMap::validate()`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Drift {
+    /// A column of the factors file is not in the table
+    MissingColumn { column: String },
+    /// A column of the table is not in the factors file
+    NewColumn { column: String },
+    /// Values of a factor that are none of its defined levels
+    NewLevels { column: String, levels: Vec<String> },
+    /// The column no longer has the type recorded in the factors file
+    TypeMismatch { column: String, expected: Dtype, found: Dtype },
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::MissingColumn { column } => write!(f, "column '{}' is missing", column),
+            Drift::NewColumn { column } => write!(f, "column '{}' is not in the factors file", column),
+            Drift::NewLevels { column, levels } => {
+                write!(f, "factor '{}' has new level(s): {}", column, levels.join(", "))
+            }
+            Drift::TypeMismatch { column, expected, found } => {
+                write!(f, "column '{}' is {}, expected {}", column, found, expected)
+            }
+        }
+    }
+}
+
+/// Integers are valid floats, a text column may cross the `max_levels` limit and a column
+/// without any value contradicts no type.
+fn compatible(expected: Dtype, found: Dtype) -> bool {
+    expected == found
+        || found == Dtype::AllMissing
+        || (expected == Dtype::Float && found == Dtype::Integer)
+        || matches!((expected, found), (Dtype::Factor, Dtype::FreeText) | (Dtype::FreeText, Dtype::Factor))
+}
+
+impl FactorMap {
+    /// Compare a table with this map: missing and new columns, factor values that are
+    /// none of the defined levels and changed column types. An empty list means no drift.
+    ///
+    /// `data` has to be read with this map applied (`SurvivalData::from_file_with_factors()`),
+    /// so that unknown values show up as additional factor levels.
+    pub fn validate(&self, data: &SurvivalData) -> Vec<Drift> {
+        let mut drift = Vec::new();
+        let mut known: HashSet<&str> = data.columns.iter().map(|c| c.name.as_str()).collect();
+        let one_hot: Vec<String> = data.factors.values().filter(|f| f.one_hot).flat_map(|f| f.all_column_names()).collect();
+        known.extend(one_hot.iter().map(|c| c.as_str()));
+
+        let expected = self.columns.iter().map(|c| c.name.as_str()).chain(self.factors.iter().map(|f| f.column.as_str()));
+        let mut seen = HashSet::new();
+        for column in expected {
+            if seen.insert(column) && !known.contains(column) {
+                drift.push(Drift::MissingColumn { column: column.to_string() });
+            }
+        }
+        if !self.columns.is_empty() {
+            for column in &data.columns {
+                if !seen.contains(column.name.as_str()) {
+                    drift.push(Drift::NewColumn { column: column.name.clone() });
+                }
+            }
+        }
+
+        for column in &data.columns {
+            if let Some(expected) = self.column(&column.name)
+                && !compatible(expected.dtype, column.dtype)
+            {
+                drift.push(Drift::TypeMismatch {
+                    column: column.name.clone(),
+                    expected: expected.dtype,
+                    found: column.dtype,
+                });
+            }
+            if let (Some(def), Some(factor)) = (self.get(&column.name), data.factors.get(&column.name)) {
+                let levels: Vec<String> = factor
+                    .get_levels()
+                    .iter()
+                    .filter(|level| !def.levels.contains(level))
+                    .cloned()
+                    .collect();
+                if !levels.is_empty() {
+                    drift.push(Drift::NewLevels { column: column.name.clone(), levels });
+                }
+            }
+        }
+        drift
+    }
+
+    /// Read `path` with this map applied and `validate()` it.
+    ///
+    /// Dates are only detected if the map has a date column.
+    pub fn validate_file<P: AsRef<Path> + fmt::Debug>(&self, path: P, options: &ParseOptions) -> Result<Vec<Drift>> {
+        let mut options = options.clone();
+        options.detect_dates |= self.columns.iter().any(|c| c.dtype == Dtype::Date);
+        let data = SurvivalData::from_file_with_factors(path, &options, self)?;
+        Ok(self.validate(&data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_validate() {
+        let tsv = "cell\tcluster\tage\tscore\nA\tx\t40\t1.5\nB\ty\t52\t2.5\n";
+        let data = SurvivalData::from_reader(Cursor::new(tsv), b'\t', HashSet::new()).unwrap();
+        let map = data.compute_factors();
+        assert_eq!(map.validate(&data), vec![]);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("new.tsv");
+        std::fs::write(&path, "cluster\tage\tscore\tbatch\nx\t40\t1\tb1\nz\tNA\t2\tb1\nx\told\t3\tb2\n").unwrap();
+        let options = ParseOptions::new(b'\t', HashSet::new());
+        let drift = map.validate_file(&path, &options).unwrap();
+        assert_eq!(drift, vec![
+            Drift::MissingColumn { column: "cell".into() },
+            Drift::NewColumn { column: "batch".into() },
+            Drift::NewLevels { column: "cluster".into(), levels: vec!["z".into()] },
+            Drift::TypeMismatch { column: "age".into(), expected: Dtype::Integer, found: Dtype::Factor },
+        ]);
+        assert_eq!(drift[2].to_string(), "factor 'cluster' has new level(s): z");
+        assert_eq!(drift[3].to_string(), "column 'age' is factor, expected integer");
+    }
+}
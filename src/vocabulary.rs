@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::path::Path;
+use crate::error::{DataTableError, Result};
+use crate::input::open_input;
+use crate::SurvivalData;
+
+/// Read a controlled vocabulary: one allowed value per line (e.g. ontology terms), surrounding
+/// whitespace trimmed. Blank lines and lines starting with `#` are skipped.
+pub fn read_vocabulary<P: AsRef<Path>>(path: P) -> Result<HashSet<String>> {
+    let path = path.as_ref();
+    let mut allowed = HashSet::new();
+    for line in open_input(path)?.lines() {
+        let line = line.map_err(|e| DataTableError::io(path, e))?;
+        let term = line.trim();
+        if !term.is_empty() && !term.starts_with('#') {
+            allowed.insert(term.to_string());
+        }
+    }
+    Ok(allowed)
+}
+
+impl SurvivalData {
+    /// The levels of the factor (or free text) column `col` that occur in the data but are not
+    /// in `allowed`, in level order - empty if the column conforms. The levels are compared as
+    /// they are in the data, not their display labels; a column that is no factor has no levels.
+    pub fn check_vocabulary(&self, col: &str, allowed: &HashSet<String>) -> Vec<String> {
+        let (Some(index), Some(factor)) = (self.column_index(col), self.factors.get(col)) else {
+            return Vec::new();
+        };
+        let observed: HashSet<u64> = self.numeric_data.column(index).iter().filter(|v| !v.is_nan()).map(|v| v.to_bits()).collect();
+        factor
+            .get_levels()
+            .iter()
+            .filter(|level| !allowed.contains(*level) && observed.contains(&factor.get_f64(level).to_bits()))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::options::ParseOptions;
+
+    #[test]
+    fn test_check_vocabulary() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cell_types.txt");
+        std::fs::write(&path, "# Cell Ontology labels\nT cell\n  B cell  \n\nmonocyte\n")?;
+        let allowed = read_vocabulary(&path)?;
+        assert_eq!(allowed, ["T cell", "B cell", "monocyte"].map(String::from).into());
+
+        let tsv = "cell\tcell_type\tn\nA\tT cell\t1\nB\tT-cell\t2\nC\tB cell\t3\nD\tNA\t4\nE\tNK\t5\nF\tT-cell\t6\n";
+        let data = SurvivalData::from_reader_with_options(Cursor::new(tsv), &ParseOptions::default())?;
+        assert_eq!(data.check_vocabulary("cell_type", &allowed), ["T-cell", "NK"], "missing values are no violation");
+
+        let mut filtered = data.select_rows(&[0, 2]);
+        filtered.factors = data.factors.clone();
+        assert!(filtered.check_vocabulary("cell_type", &allowed).is_empty(), "only observed levels count");
+        assert!(data.check_vocabulary("n", &allowed).is_empty());
+        Ok(())
+    }
+}